@@ -6,14 +6,17 @@ import tomlkit
 import subprocess
 import re
 import csv
+import json
 import sys
 import os
 from concurrent import futures
 import glob
+import time
 
 __version__ = '1.0.0'
 
 ARGS_RE = r'ARGS: (.*)'
+PROJECT_CONFIG = 'bril.toml'
 
 
 def run_pipe(cmds, input, timeout):
@@ -55,16 +58,20 @@ def compare_output(o1, o2, ε=0.0):
     return all(my_compare(x, y) for x, y in zip(o1.split(), o2.split()))
 
 
-def run_bench(pipeline, fn, timeout):
+def run_bench(pipeline, fn, timeout, args=None):
     """Run a single benchmark pipeline.
+
+    The arguments come from the benchmark's `ARGS:` line unless `args`
+    overrides them.
     """
     # Load the benchmark.
     with open(fn) as f:
         in_data = f.read()
 
     # Extract arguments.
-    match = re.search(ARGS_RE, in_data)
-    args = match.group(1) if match else ''
+    if args is None:
+        match = re.search(ARGS_RE, in_data)
+        args = match.group(1) if match else ''
 
     # Run pipeline.
     cmds = [
@@ -84,35 +91,180 @@ def get_result(strings, extract_re):
     return None
 
 
+def find_project_config():
+    """Find the nearest `bril.toml` in the current directory or a parent.
+    """
+    path = os.getcwd()
+    while True:
+        candidate = os.path.join(path, PROJECT_CONFIG)
+        if os.path.isfile(candidate):
+            return candidate
+        parent = os.path.dirname(path)
+        if parent == path:
+            return None
+        path = parent
+
+
+def load_config(config_path, files):
+    """Load the benchmark configuration and the list of files to run.
+
+    A brench configuration is used as is. Without one, the `[bench]` table
+    of the project's `bril.toml` is used instead, and everything runs from
+    the directory containing it so its relative paths work from anywhere in
+    the project.
+    """
+    if config_path and not config_path.endswith('.toml'):
+        # Not a configuration at all, but the first benchmark.
+        files = (config_path,) + files
+        config_path = None
+    if config_path and os.path.basename(config_path) != PROJECT_CONFIG:
+        with open(config_path) as f:
+            return tomlkit.loads(f.read()), files
+
+    config_path = config_path or find_project_config()
+    if not config_path:
+        raise click.UsageError(
+            'no CONFIG given and no {} found'.format(PROJECT_CONFIG)
+        )
+    with open(config_path) as f:
+        project = tomlkit.loads(f.read())
+    if 'bench' not in project:
+        raise click.UsageError(
+            '{} has no [bench] table'.format(config_path)
+        )
+    files = tuple(os.path.abspath(fn) for fn in files)
+    os.chdir(os.path.dirname(os.path.abspath(config_path)))
+    return project['bench'], files
+
+
+def write_results(results, fmt):
+    """Print (benchmark, run, result) rows as CSV or as a JSON list.
+    """
+    if fmt == 'json':
+        json.dump([
+            {'benchmark': bench, 'run': name, 'result': result}
+            for bench, name, result in results
+        ], sys.stdout, indent=2)
+        sys.stdout.write('\n')
+    else:
+        writer = csv.writer(sys.stdout)
+        writer.writerow(['benchmark', 'run', 'result'])
+        writer.writerows(results)
+
+
+def as_number(result):
+    """The figure of merit as a number, or None for a status or text.
+    """
+    try:
+        value = float(result)
+    except (TypeError, ValueError):
+        return None
+    return int(value) if value.is_integer() else value
+
+
+def write_metrics(path, results, baseline):
+    """Write the results and their change from the `baseline` run to `path`.
+
+    The file is in the Prometheus text format if its name ends in `.prom` or
+    `.txt` and JSON otherwise, so dashboards can collect one per invocation.
+    """
+    baselines = {
+        bench: as_number(result)
+        for bench, name, result in results if name == baseline
+    }
+    rows = []
+    for bench, name, result in results:
+        value = as_number(result)
+        base = baselines.get(bench)
+        rows.append({
+            'benchmark': bench,
+            'run': name,
+            'result': value,
+            'status': None if value is not None else result,
+            'delta': None if value is None or base is None else value - base,
+        })
+    timestamp = int(time.time())
+
+    with open(path, 'w') as f:
+        if not (path.endswith('.prom') or path.endswith('.txt')):
+            json.dump({'timestamp': timestamp, 'results': rows}, f, indent=2)
+            f.write('\n')
+            return
+
+        def gauge(name, help, samples):
+            f.write('# HELP bril_bench_{} {}\n'.format(name, help))
+            f.write('# TYPE bril_bench_{} gauge\n'.format(name))
+            for labels, value in samples:
+                # JSON strings are escaped the way Prometheus label values are.
+                labels = ','.join('{}={}'.format(k, json.dumps(v))
+                                  for k, v in labels.items())
+                f.write('bril_bench_{}{{{}}} {}\n'.format(name, labels, value))
+
+        f.write('# HELP bril_bench_timestamp_seconds When brench ran.\n')
+        f.write('# TYPE bril_bench_timestamp_seconds gauge\n')
+        f.write('bril_bench_timestamp_seconds {}\n'.format(timestamp))
+        key = lambda row: {'benchmark': row['benchmark'], 'run': row['run']}
+        gauge('result', 'The figure of merit of a run.', [
+            (key(row), row['result']) for row in rows
+            if row['result'] is not None
+        ])
+        gauge('delta', 'The change in the figure of merit from the baseline.', [
+            (key(row), row['delta']) for row in rows
+            if row['delta'] is not None
+        ])
+        gauge('status', 'Runs without a figure of merit, by status.', [
+            (dict(key(row), status=row['status']), 1) for row in rows
+            if row['status'] is not None
+        ])
+
+
 @click.command()
 @click.option('-j', '--jobs', default=None, type=int,
               help='parallel threads to use (default: suitable for machine)')
-@click.argument('config_path', metavar='CONFIG', type=click.Path(exists=True))
+@click.option('--format', 'fmt', default=None,
+              type=click.Choice(['csv', 'json']),
+              help='output format (default: csv)')
+@click.option('--metrics', 'metrics_path', default=None,
+              help='also write metrics to this JSON or .prom file')
+@click.argument('config_path', metavar='[CONFIG]', required=False,
+                type=click.Path(exists=True))
 @click.argument('files', nargs=-1, type=click.Path(exists=True))
-def brench(config_path, files, jobs):
+def brench(config_path, files, jobs, fmt, metrics_path):
     """Run a batch of benchmarks and emit a CSV of results.
+
+    Without a CONFIG, the [bench] table of the nearest bril.toml is used.
     """
-    with open(config_path) as f:
-        config = tomlkit.loads(f.read())
+    # The metrics file is relative to where brench was run.
+    if metrics_path:
+        metrics_path = os.path.abspath(metrics_path)
+    config, files = load_config(config_path, files)
 
-    # Use configured file list, if none is specified via the CLI.
+    # Use configured file list, if none is specified via the CLI. The
+    # benchmarks may be one glob or a list of them.
     if not files and 'benchmarks' in config:
-        files = glob.glob(config['benchmarks'])
+        patterns = config['benchmarks']
+        if isinstance(patterns, str):
+            patterns = [patterns]
+        files = [fn for pattern in patterns for fn in sorted(glob.glob(pattern))]
 
     timeout = config.get('timeout', 5)
     ε = config.get('epsilon', 0.0)
+    bench_args = config.get('args', {})
+    fmt = fmt or config.get('format', 'csv')
+    metrics_path = metrics_path or config.get('metrics')
 
     with futures.ThreadPoolExecutor(max_workers=jobs) as pool:
         # Submit jobs.
         futs = {}
         for fn in files:
+            bench, _ = os.path.splitext(os.path.basename(fn))
+            args = bench_args.get(bench)
             for name, run in config['runs'].items():
                 futs[(fn, name)] = pool.submit(run_bench, run['pipeline'], fn,
-                                               timeout)
+                                               timeout, args)
 
-        # Collect results and print CSV.
-        writer = csv.writer(sys.stdout)
-        writer.writerow(['benchmark', 'run', 'result'])
+        # Collect results and print them.
+        results = []
         for fn in files:
             first_out = None
             for name in config['runs']:
@@ -137,11 +289,10 @@ def brench(config_path, files, jobs):
 
                 # Report the result.
                 bench, _ = os.path.splitext(os.path.basename(fn))
-                writer.writerow([
-                    bench,
-                    name,
-                    status if status else result,
-                ])
+                results.append((bench, name, status if status else result))
+        write_results(results, fmt)
+        if metrics_path:
+            write_metrics(metrics_path, results, next(iter(config['runs'])))
 
 
 if __name__ == '__main__':
@@ -9,4 +9,7 @@ pub struct Cli {
     /// Flag for whether position information should be included
     #[arg(short, action)]
     pub position: bool,
+    /// The number to start numbering generated names from in each function
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
 }
@@ -23,6 +23,7 @@ use syn::{
 
 use proc_macro2::Span;
 
+use bril_rs::fresh::FreshNames;
 use std::collections::HashMap;
 
 // References, Dereference, Mutability, and Visibility are all silently ignored
@@ -31,38 +32,34 @@ use std::collections::HashMap;
 struct State {
     is_pos: bool,
     src: Option<String>,
-    temp_var_count: u64,
+    fresh: FreshNames,
     ident_type_map: HashMap<String, Type>,
     func_context_map: HashMap<String, (HashMap<String, Type>, Option<Type>)>,
 }
 
 impl State {
-    fn new(is_pos: bool, src: Option<String>) -> Self {
+    fn new(is_pos: bool, src: Option<String>, seed: u64) -> Self {
         Self {
             is_pos,
             src,
-            temp_var_count: 0,
+            fresh: FreshNames::new(seed),
             ident_type_map: HashMap::new(),
             func_context_map: HashMap::new(),
         }
     }
 
     fn fresh_var(&mut self, ty: Type) -> String {
-        let num = self.temp_var_count;
-        self.temp_var_count += 1;
-        let name = format!("tmp{num}");
+        let name = format!("tmp{}", self.fresh.number());
         self.add_type_for_ident(name.clone(), ty);
         name
     }
 
     fn fresh_label(&mut self) -> String {
-        let num = self.temp_var_count;
-        self.temp_var_count += 1;
-        let name = format!("label{num}");
-        name
+        format!("label{}", self.fresh.number())
     }
 
     fn starting_new_function(&mut self, name: &String) {
+        self.fresh.reset();
         self.ident_type_map = self.func_context_map.get(name).unwrap().0.clone();
     }
 
@@ -1139,12 +1136,13 @@ pub fn from_file_to_program(
     }: File,
     is_pos: bool,
     src: Option<String>,
+    seed: u64,
 ) -> Program {
     assert!(shebang.is_none(), "can't handle shebang items in Rust file");
 
     assert!(attrs.is_empty(), "can't handle attributes in Rust file");
 
-    let mut state = State::new(is_pos, src);
+    let mut state = State::new(is_pos, src, seed);
 
     // The processing of Functions is separated into two parts to get global information like type signatures for functions before processing function bodies
     let sigs_processed: Vec<(Function, Block)> = items
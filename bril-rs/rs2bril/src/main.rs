@@ -22,5 +22,10 @@ fn main() {
 
     let syntax = syn::parse_file(&src).unwrap();
 
-    output_program(&from_file_to_program(syntax, args.position, source_name));
+    output_program(&from_file_to_program(
+        syntax,
+        args.position,
+        source_name,
+        args.seed,
+    ));
 }
@@ -0,0 +1,186 @@
+use crate::fresh::FreshNames;
+use crate::{Argument, Code, ConstOps, EffectOps, Function, Instruction, Literal, Type, ValueOps};
+
+/// Appends instructions to a [Function] one at a time, handing out fresh
+/// variable and label names along the way.
+///
+/// Fresh names contain a `.`, so a frontend whose identifiers cannot will
+/// never see them collide with its own names. They are numbered per function
+/// by [`FreshNames`], starting from the seed given to [`Builder::with_seed`].
+#[derive(Debug, Clone)]
+pub struct Builder {
+    function: Function,
+    fresh: FreshNames,
+}
+
+impl Builder {
+    /// Starts an empty function called `name` without arguments or a return type
+    #[must_use]
+    pub const fn new(name: String) -> Self {
+        Self::with_seed(name, 0)
+    }
+
+    /// Like [`Builder::new`], numbering fresh names from `seed`
+    #[must_use]
+    pub const fn with_seed(name: String, seed: u64) -> Self {
+        Self {
+            function: Function {
+                args: Vec::new(),
+                instrs: Vec::new(),
+                name,
+                #[cfg(feature = "position")]
+                pos: None,
+                return_type: None,
+            },
+            fresh: FreshNames::new(seed),
+        }
+    }
+
+    /// Adds an argument to the function
+    pub fn arg(&mut self, name: String, arg_type: Type) {
+        self.function.args.push(Argument { name, arg_type });
+    }
+
+    /// Sets the type of the value the function returns
+    // Only const without the memory extension, whose pointer types need dropping
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn return_type(&mut self, return_type: Type) {
+        self.function.return_type = Some(return_type);
+    }
+
+    /// A variable name that has not been handed out before
+    pub fn fresh_var(&mut self) -> String {
+        self.fresh.name("v")
+    }
+
+    /// A label name starting with `hint` that has not been handed out before
+    pub fn fresh_label(&mut self, hint: &str) -> String {
+        self.fresh.name(hint)
+    }
+
+    /// Places `label` at the current end of the function
+    pub fn label(&mut self, label: String) {
+        self.function.instrs.push(Code::Label {
+            label,
+            #[cfg(feature = "position")]
+            pos: None,
+        });
+    }
+
+    /// Appends an instruction built by hand
+    pub fn instr(&mut self, instr: Instruction) {
+        self.function.instrs.push(Code::Instruction(instr));
+    }
+
+    /// Stores `value` in `dest`
+    pub fn constant_into(&mut self, dest: String, value: Literal) {
+        self.instr(Instruction::Constant {
+            dest,
+            op: ConstOps::Const,
+            #[cfg(feature = "position")]
+            pos: None,
+            const_type: value.get_type(),
+            value,
+        });
+    }
+
+    /// Stores `value` in a fresh variable and returns its name
+    pub fn constant(&mut self, value: Literal) -> String {
+        let dest = self.fresh_var();
+        self.constant_into(dest.clone(), value);
+        dest
+    }
+
+    /// Stores the result of `op` on `args` in `dest`
+    pub fn value_into(&mut self, dest: String, op: ValueOps, op_type: Type, args: Vec<String>) {
+        self.instr(Instruction::Value {
+            args,
+            dest,
+            funcs: Vec::new(),
+            labels: Vec::new(),
+            op,
+            #[cfg(feature = "position")]
+            pos: None,
+            op_type,
+        });
+    }
+
+    /// Stores the result of `op` on `args` in a fresh variable and returns its name
+    pub fn value(&mut self, op: ValueOps, op_type: Type, args: Vec<String>) -> String {
+        let dest = self.fresh_var();
+        self.value_into(dest.clone(), op, op_type, args);
+        dest
+    }
+
+    /// Calls `func` with `args`, returning the name of the variable holding the
+    /// result if `return_type` says there is one
+    pub fn call(
+        &mut self,
+        func: String,
+        args: Vec<String>,
+        return_type: Option<Type>,
+    ) -> Option<String> {
+        if let Some(op_type) = return_type {
+            let dest = self.fresh_var();
+            self.instr(Instruction::Value {
+                args,
+                dest: dest.clone(),
+                funcs: vec![func],
+                labels: Vec::new(),
+                op: ValueOps::Call,
+                #[cfg(feature = "position")]
+                pos: None,
+                op_type,
+            });
+            Some(dest)
+        } else {
+            self.instr(Instruction::Effect {
+                args,
+                funcs: vec![func],
+                labels: Vec::new(),
+                op: EffectOps::Call,
+                #[cfg(feature = "position")]
+                pos: None,
+            });
+            None
+        }
+    }
+
+    /// Appends an effect operation `op` on `args` and `labels`
+    pub fn effect(&mut self, op: EffectOps, args: Vec<String>, labels: Vec<String>) {
+        self.instr(Instruction::Effect {
+            args,
+            funcs: Vec::new(),
+            labels,
+            op,
+            #[cfg(feature = "position")]
+            pos: None,
+        });
+    }
+
+    /// Jumps to `label`
+    pub fn jump(&mut self, label: String) {
+        self.effect(EffectOps::Jump, Vec::new(), vec![label]);
+    }
+
+    /// Branches on `cond` to `then_label` or `else_label`
+    pub fn branch(&mut self, cond: String, then_label: String, else_label: String) {
+        self.effect(EffectOps::Branch, vec![cond], vec![then_label, else_label]);
+    }
+
+    /// Returns from the function, with `value` if there is one
+    pub fn ret(&mut self, value: Option<String>) {
+        self.effect(EffectOps::Return, value.into_iter().collect(), Vec::new());
+    }
+
+    /// Prints `args` on one line
+    pub fn print(&mut self, args: Vec<String>) {
+        self.effect(EffectOps::Print, args, Vec::new());
+    }
+
+    /// The function built so far
+    #[must_use]
+    pub fn finish(self) -> Function {
+        self.function
+    }
+}
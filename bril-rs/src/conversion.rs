@@ -227,6 +227,8 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "sub" => ValueOps::Sub,
                     #[cfg(feature = "ssa")]
                     "phi" => ValueOps::Phi,
+                    #[cfg(feature = "ssa")]
+                    "get" => ValueOps::Get,
                     #[cfg(feature = "float")]
                     "fadd" => ValueOps::Fadd,
                     #[cfg(feature = "float")]
@@ -287,6 +289,8 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "commit" => EffectOps::Commit,
                     #[cfg(feature = "speculate")]
                     "guard" => EffectOps::Guard,
+                    #[cfg(feature = "ssa")]
+                    "set" => EffectOps::Set,
                     e => {
                         return Err(ConversionError::InvalidEffectOps(e.to_string()))
                             .map_err(|e| e.add_pos(pos))
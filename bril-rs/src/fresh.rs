@@ -0,0 +1,42 @@
+/// Hands out generated names like `v.0`, `v.1` and `then.2` from a counter.
+///
+/// The counter starts at a seed and belongs to one function, so the names a
+/// function gets depend only on the seed and on what was generated for it
+/// before, not on other functions or on other runs. Giving a tool the same
+/// seed reproduces its output exactly, which golden tests rely on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreshNames {
+    seed: u64,
+    next: u64,
+}
+
+impl FreshNames {
+    /// Starts counting at `seed`
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self { seed, next: seed }
+    }
+
+    /// Starts counting from the seed again, for the next function
+    pub const fn reset(&mut self) {
+        self.next = self.seed;
+    }
+
+    /// The next number of the counter
+    pub const fn number(&mut self) -> u64 {
+        let num = self.next;
+        self.next += 1;
+        num
+    }
+
+    /// `prefix` followed by the next number, as in `prefix.3`
+    pub fn name(&mut self, prefix: &str) -> String {
+        format!("{prefix}.{}", self.number())
+    }
+}
+
+impl Default for FreshNames {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
@@ -9,8 +9,12 @@
 
 /// Provides the unstructured representation of Bril programs
 pub mod abstract_program;
+/// Provides [Builder](builder::Builder) for generating Bril functions instruction by instruction
+pub mod builder;
 /// Provides the Error handling and conversion between [`AbstractProgram`] and [Program]
 pub mod conversion;
+/// Provides [`FreshNames`](fresh::FreshNames) for generating names deterministically
+pub mod fresh;
 /// Provides the structured representation of Bril programs
 pub mod program;
 pub use abstract_program::*;
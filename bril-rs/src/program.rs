@@ -357,6 +357,9 @@ pub enum EffectOps {
     /// <https://capra.cs.cornell.edu/bril/lang/spec.html#operations>
     #[cfg(feature = "speculate")]
     Guard,
+    /// <https://capra.cs.cornell.edu/bril/lang/ssa.html#operations>
+    #[cfg(feature = "ssa")]
+    Set,
 }
 
 impl Display for EffectOps {
@@ -378,6 +381,8 @@ impl Display for EffectOps {
             Self::Commit => write!(f, "commit"),
             #[cfg(feature = "speculate")]
             Self::Guard => write!(f, "guard"),
+            #[cfg(feature = "ssa")]
+            Self::Set => write!(f, "set"),
         }
     }
 }
@@ -417,6 +422,9 @@ pub enum ValueOps {
     /// <https://capra.cs.cornell.edu/bril/lang/ssa.html#operations>
     #[cfg(feature = "ssa")]
     Phi,
+    /// <https://capra.cs.cornell.edu/bril/lang/ssa.html#operations>
+    #[cfg(feature = "ssa")]
+    Get,
     /// <https://capra.cs.cornell.edu/bril/lang/float.html#operations>
     #[cfg(feature = "float")]
     Fadd,
@@ -474,6 +482,8 @@ impl Display for ValueOps {
             Self::Id => write!(f, "id"),
             #[cfg(feature = "ssa")]
             Self::Phi => write!(f, "phi"),
+            #[cfg(feature = "ssa")]
+            Self::Get => write!(f, "get"),
             #[cfg(feature = "float")]
             Self::Fadd => write!(f, "fadd"),
             #[cfg(feature = "float")]
@@ -0,0 +1,17 @@
+use bril_rs::output_program;
+
+use tiny2bril::cli::ExprCli;
+use tiny2bril::compile_body;
+
+use clap::Parser;
+
+fn main() {
+    let args = ExprCli::parse();
+    match compile_body(&args.body, args.args, args.seed) {
+        Ok(program) => output_program(&program),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use clap::Parser;
+
+#[derive(Parser)]
+#[command(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The program to compile. stdin is assumed if file is not provided.
+    #[arg(short, long, action)]
+    pub file: Option<String>,
+    /// The number to start numbering generated names from in each function
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+#[derive(Parser)]
+#[command(
+    about = "Compiles a one-line body of a main function to Bril",
+    version,
+    author
+)]
+pub struct ExprCli {
+    /// The statements to compile, such as "print((a + b) * (a + b))"
+    pub body: String,
+    /// The arguments of main, written as name:type
+    #[arg(long, num_args = 1.., value_parser = parse_arg)]
+    pub args: Vec<(String, bril_rs::Type)>,
+    /// The number to start numbering generated names from in each function
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+fn parse_arg(arg: &str) -> Result<(String, bril_rs::Type), String> {
+    let (name, ty) = arg
+        .split_once(':')
+        .ok_or_else(|| format!("expected name:type, found {arg}"))?;
+    let ty = match ty {
+        "int" => bril_rs::Type::Int,
+        "bool" => bril_rs::Type::Bool,
+        _ => return Err(format!("unknown type {ty}")),
+    };
+    Ok((name.to_string(), ty))
+}
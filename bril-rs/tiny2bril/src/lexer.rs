@@ -0,0 +1,57 @@
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Int(i64),
+    // Keywords are identifiers too; the parser tells them apart.
+    Ident(String),
+    Symbol(&'static str),
+}
+
+// Longer symbols come first so that `<=` is not read as `<` then `=`.
+const SYMBOLS: [&str; 22] = [
+    "->", "==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", ",", ";", ":", "=", "<", ">",
+    "+", "-", "*", "/", "!",
+];
+
+/// Splits `src` into tokens, each with the line it starts on
+pub fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let value = rest[..end].parse().map_err(|_| Error {
+                line,
+                message: format!("integer literal {} is too large", &rest[..end]),
+            })?;
+            tokens.push((Token::Int(value), line));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push((Token::Ident(rest[..end].to_string()), line));
+            rest = &rest[end..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            tokens.push((Token::Symbol(symbol), line));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(Error {
+                line,
+                message: format!("unexpected character {c:?}"),
+            });
+        }
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,449 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+#[doc(hidden)]
+pub mod cli;
+mod lexer;
+mod parser;
+
+use bril_rs::builder::Builder;
+use bril_rs::{Literal, Program, Type, ValueOps};
+
+use parser::{BinOp, Expr, Func, Spanned, Stmt, UnOp};
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// A syntax or type error in the source program
+#[derive(Debug)]
+pub struct Error {
+    /// The line of the source program the error was found on
+    pub line: usize,
+    /// What went wrong
+    pub message: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+const fn error<T>(line: usize, message: String) -> Result<T, Error> {
+    Err(Error { line, message })
+}
+
+// Parameter and return types of every function, so calls can be checked
+// before or after the function they call.
+type Signatures = HashMap<String, (Vec<Type>, Option<Type>)>;
+
+struct State<'a> {
+    builder: Builder,
+    signatures: &'a Signatures,
+    return_type: Option<Type>,
+    // Variables live for the rest of the function once declared, like they
+    // do in Bril.
+    vars: HashMap<String, Type>,
+}
+
+impl State<'_> {
+    fn var_type(&self, name: &str, line: usize) -> Result<Type, Error> {
+        self.vars.get(name).cloned().ok_or_else(|| Error {
+            line,
+            message: format!("undeclared variable `{name}`"),
+        })
+    }
+
+    // The value of `expr` as a variable, which is `dest` if one is given.
+    fn expr(
+        &mut self,
+        expr: &Spanned<Expr>,
+        dest: Option<String>,
+    ) -> Result<(String, Type), Error> {
+        let line = expr.line;
+        match &expr.node {
+            Expr::Int(value) => {
+                let dest = self.dest(dest);
+                self.builder
+                    .constant_into(dest.clone(), Literal::Int(*value));
+                Ok((dest, Type::Int))
+            }
+            Expr::Bool(value) => {
+                let dest = self.dest(dest);
+                self.builder
+                    .constant_into(dest.clone(), Literal::Bool(*value));
+                Ok((dest, Type::Bool))
+            }
+            Expr::Var(name) => {
+                let ty = self.var_type(name, line)?;
+                Ok(self.expr_or_copy(name.clone(), ty, dest))
+            }
+            Expr::Call(func, args) => {
+                let result = self.call(func, args, line)?;
+                let Some((value, ty)) = result else {
+                    return error(line, format!("`{func}` does not return a value"));
+                };
+                Ok(self.expr_or_copy(value, ty, dest))
+            }
+            Expr::Unary(UnOp::Neg, operand) => {
+                if let Expr::Int(value) = operand.node {
+                    let dest = self.dest(dest);
+                    self.builder
+                        .constant_into(dest.clone(), Literal::Int(value.wrapping_neg()));
+                    return Ok((dest, Type::Int));
+                }
+                let (operand, ty) = self.expr(operand, None)?;
+                expect_type(&ty, &Type::Int, "-", line)?;
+                let zero = self.builder.constant(Literal::Int(0));
+                let dest = self.dest(dest);
+                self.builder.value_into(
+                    dest.clone(),
+                    ValueOps::Sub,
+                    Type::Int,
+                    vec![zero, operand],
+                );
+                Ok((dest, Type::Int))
+            }
+            Expr::Unary(UnOp::Not, operand) => {
+                let (operand, ty) = self.expr(operand, None)?;
+                expect_type(&ty, &Type::Bool, "!", line)?;
+                let dest = self.dest(dest);
+                self.builder
+                    .value_into(dest.clone(), ValueOps::Not, Type::Bool, vec![operand]);
+                Ok((dest, Type::Bool))
+            }
+            Expr::Binary(op @ (BinOp::And | BinOp::Or), lhs, rhs) => {
+                let dest = self.dest(dest);
+                self.short_circuit(*op, lhs, rhs, dest.clone())?;
+                Ok((dest, Type::Bool))
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, lhs_type) = self.expr(lhs, None)?;
+                let (rhs, rhs_type) = self.expr(rhs, None)?;
+                let symbol = symbol(*op);
+                expect_type(&lhs_type, &Type::Int, symbol, line)?;
+                expect_type(&rhs_type, &Type::Int, symbol, line)?;
+                let (value_op, ty) = match op {
+                    BinOp::Add => (ValueOps::Add, Type::Int),
+                    BinOp::Sub => (ValueOps::Sub, Type::Int),
+                    BinOp::Mul => (ValueOps::Mul, Type::Int),
+                    BinOp::Div => (ValueOps::Div, Type::Int),
+                    BinOp::Eq | BinOp::Ne => (ValueOps::Eq, Type::Bool),
+                    BinOp::Lt => (ValueOps::Lt, Type::Bool),
+                    BinOp::Le => (ValueOps::Le, Type::Bool),
+                    BinOp::Gt => (ValueOps::Gt, Type::Bool),
+                    BinOp::Ge => (ValueOps::Ge, Type::Bool),
+                    BinOp::And | BinOp::Or => unreachable!(),
+                };
+                if *op == BinOp::Ne {
+                    let equal = self.builder.value(value_op, ty, vec![lhs, rhs]);
+                    let dest = self.dest(dest);
+                    self.builder
+                        .value_into(dest.clone(), ValueOps::Not, Type::Bool, vec![equal]);
+                    return Ok((dest, Type::Bool));
+                }
+                let dest = self.dest(dest);
+                self.builder
+                    .value_into(dest.clone(), value_op, ty.clone(), vec![lhs, rhs]);
+                Ok((dest, ty))
+            }
+        }
+    }
+
+    fn dest(&mut self, dest: Option<String>) -> String {
+        dest.unwrap_or_else(|| self.builder.fresh_var())
+    }
+
+    // `value` itself, or a copy of it in `dest` if one is given.
+    fn expr_or_copy(&mut self, value: String, ty: Type, dest: Option<String>) -> (String, Type) {
+        match dest {
+            Some(dest) => {
+                self.builder
+                    .value_into(dest.clone(), ValueOps::Id, ty.clone(), vec![value]);
+                (dest, ty)
+            }
+            None => (value, ty),
+        }
+    }
+
+    // `&&` and `||` only evaluate their right-hand side when the left-hand
+    // one does not decide the result already.
+    fn short_circuit(
+        &mut self,
+        op: BinOp,
+        lhs: &Spanned<Expr>,
+        rhs: &Spanned<Expr>,
+        dest: String,
+    ) -> Result<(), Error> {
+        let symbol = symbol(op);
+        let (_, lhs_type) = self.expr(lhs, Some(dest.clone()))?;
+        expect_type(&lhs_type, &Type::Bool, symbol, lhs.line)?;
+        let rhs_label = self.builder.fresh_label("rhs");
+        let end_label = self.builder.fresh_label("end");
+        if op == BinOp::And {
+            self.builder
+                .branch(dest.clone(), rhs_label.clone(), end_label.clone());
+        } else {
+            self.builder
+                .branch(dest.clone(), end_label.clone(), rhs_label.clone());
+        }
+        self.builder.label(rhs_label);
+        let (_, rhs_type) = self.expr(rhs, Some(dest))?;
+        expect_type(&rhs_type, &Type::Bool, symbol, rhs.line)?;
+        self.builder.label(end_label);
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        func: &str,
+        args: &[Spanned<Expr>],
+        line: usize,
+    ) -> Result<Option<(String, Type)>, Error> {
+        let Some((params, return_type)) = self.signatures.get(func) else {
+            return error(line, format!("undefined function `{func}`"));
+        };
+        if params.len() != args.len() {
+            return error(
+                line,
+                format!(
+                    "`{func}` takes {} arguments but {} were given",
+                    params.len(),
+                    args.len()
+                ),
+            );
+        }
+        let mut values = Vec::new();
+        for (arg, param) in args.iter().zip(params) {
+            let (value, ty) = self.expr(arg, None)?;
+            if &ty != param {
+                return error(
+                    arg.line,
+                    format!("argument of `{func}` should be {param} but is {ty}"),
+                );
+            }
+            values.push(value);
+        }
+        let result = self
+            .builder
+            .call(func.to_string(), values, return_type.clone());
+        Ok(result.zip(return_type.clone()))
+    }
+
+    fn block(&mut self, stmts: &[Spanned<Stmt>]) -> Result<(), Error> {
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Spanned<Stmt>) -> Result<(), Error> {
+        let line = stmt.line;
+        match &stmt.node {
+            Stmt::Let(name, value) if !self.vars.contains_key(name) => {
+                let (_, ty) = self.expr(value, Some(name.clone()))?;
+                self.vars.insert(name.clone(), ty);
+            }
+            // Redeclaring a variable is assigning to it.
+            Stmt::Let(name, value) | Stmt::Assign(name, value) => {
+                let expected = self.var_type(name, line)?;
+                // The old value may still be needed while computing the new
+                // one, so it is only overwritten at the end.
+                let (value, ty) = self.expr(value, None)?;
+                if ty != expected {
+                    return error(line, format!("`{name}` is {expected} but is assigned {ty}"));
+                }
+                self.builder
+                    .value_into(name.clone(), ValueOps::Id, ty, vec![value]);
+            }
+            Stmt::If(cond, then_branch, else_branch) => {
+                let cond = self.condition(cond, "if")?;
+                let then_label = self.builder.fresh_label("then");
+                let else_label = self.builder.fresh_label("else");
+                let end_label = self.builder.fresh_label("endif");
+                self.builder
+                    .branch(cond, then_label.clone(), else_label.clone());
+                self.builder.label(then_label);
+                self.block(then_branch)?;
+                self.builder.jump(end_label.clone());
+                self.builder.label(else_label);
+                self.block(else_branch)?;
+                self.builder.label(end_label);
+            }
+            Stmt::While(cond, body) => {
+                let cond_label = self.builder.fresh_label("while");
+                let body_label = self.builder.fresh_label("body");
+                let end_label = self.builder.fresh_label("endwhile");
+                self.builder.label(cond_label.clone());
+                let cond = self.condition(cond, "while")?;
+                self.builder
+                    .branch(cond, body_label.clone(), end_label.clone());
+                self.builder.label(body_label);
+                self.block(body)?;
+                self.builder.jump(cond_label);
+                self.builder.label(end_label);
+            }
+            Stmt::Return(value) => {
+                let value = match (value, self.return_type.clone()) {
+                    (None, None) => None,
+                    (Some(value), Some(expected)) => {
+                        let (value, ty) = self.expr(value, None)?;
+                        if ty != expected {
+                            return error(line, format!("returning {ty} instead of {expected}"));
+                        }
+                        Some(value)
+                    }
+                    (None, Some(expected)) => {
+                        return error(line, format!("missing a return value of type {expected}"))
+                    }
+                    (Some(_), None) => {
+                        return error(
+                            line,
+                            String::from("returning a value from a function without a return type"),
+                        )
+                    }
+                };
+                self.builder.ret(value);
+            }
+            Stmt::Print(args) => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.expr(arg, None)?.0);
+                }
+                self.builder.print(values);
+            }
+            Stmt::Expr(Spanned {
+                node: Expr::Call(func, args),
+                line,
+            }) => {
+                self.call(func, args, *line)?;
+            }
+            Stmt::Expr(_) => {
+                return error(line, String::from("only calls can be used as statements"));
+            }
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self, cond: &Spanned<Expr>, keyword: &str) -> Result<String, Error> {
+        let (cond_var, ty) = self.expr(cond, None)?;
+        if ty != Type::Bool {
+            return error(
+                cond.line,
+                format!("`{keyword}` condition is {ty}, not bool"),
+            );
+        }
+        Ok(cond_var)
+    }
+}
+
+const fn symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        BinOp::Ge => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+    }
+}
+
+fn expect_type(ty: &Type, expected: &Type, symbol: &str, line: usize) -> Result<(), Error> {
+    if ty == expected {
+        Ok(())
+    } else {
+        error(line, format!("`{symbol}` expects {expected} but got {ty}"))
+    }
+}
+
+fn compile_function(
+    func: &Func,
+    signatures: &Signatures,
+    seed: u64,
+) -> Result<bril_rs::Function, Error> {
+    let mut state = State {
+        builder: Builder::with_seed(func.name.clone(), seed),
+        signatures,
+        return_type: func.return_type.clone(),
+        vars: HashMap::new(),
+    };
+    for (name, ty) in &func.params {
+        if state.vars.insert(name.clone(), ty.clone()).is_some() {
+            return error(func.line, format!("parameter `{name}` is declared twice"));
+        }
+        state.builder.arg(name.clone(), ty.clone());
+    }
+    if let Some(ty) = &func.return_type {
+        state.builder.return_type(ty.clone());
+    }
+    state.block(&func.body)?;
+    Ok(state.builder.finish())
+}
+
+/// Compiles the source of a program in the tiny language to Bril, numbering
+/// the names it makes up in each function from `seed`
+/// # Errors
+/// Returns the first syntax or type error in `src`
+pub fn compile(src: &str, seed: u64) -> Result<Program, Error> {
+    let funcs = parser::parse(lexer::tokenize(src)?)?;
+    let mut signatures = Signatures::new();
+    for func in &funcs {
+        let params = func.params.iter().map(|(_, ty)| ty.clone()).collect();
+        let signature = (params, func.return_type.clone());
+        if signatures.insert(func.name.clone(), signature).is_some() {
+            return error(
+                func.line,
+                format!("function `{}` is defined twice", func.name),
+            );
+        }
+    }
+    let functions = funcs
+        .iter()
+        .map(|func| compile_function(func, &signatures, seed))
+        .collect::<Result<_, _>>()?;
+    Ok(Program { functions })
+}
+
+/// Compiles the body of a `main` function taking `params` to Bril. The last
+/// statement may leave off its `;`, and is printed if it is a bare expression
+/// other than a call. Made-up names are numbered from `seed`.
+/// # Errors
+/// Returns the first syntax or type error in `src`
+pub fn compile_body(src: &str, params: Vec<(String, Type)>, seed: u64) -> Result<Program, Error> {
+    let mut body = parser::parse_body(lexer::tokenize(src)?)?;
+    if let Some(last) = body.pop() {
+        body.push(match last.node {
+            Stmt::Expr(value) if !matches!(value.node, Expr::Call(..)) => Spanned {
+                node: Stmt::Print(vec![value]),
+                line: last.line,
+            },
+            node => Spanned {
+                node,
+                line: last.line,
+            },
+        });
+    }
+    let main = Func {
+        name: String::from("main"),
+        params,
+        return_type: None,
+        body,
+        line: 1,
+    };
+    let signatures = Signatures::from([(
+        main.name.clone(),
+        (main.params.iter().map(|(_, ty)| ty.clone()).collect(), None),
+    )]);
+    Ok(Program {
+        functions: vec![compile_function(&main, &signatures, seed)?],
+    })
+}
@@ -0,0 +1,27 @@
+use std::io::Read;
+
+use bril_rs::output_program;
+
+use tiny2bril::cli::Cli;
+use tiny2bril::compile;
+
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let mut src = String::new();
+    if let Some(f) = args.file {
+        let mut file = std::fs::File::open(f).unwrap();
+        file.read_to_string(&mut src).unwrap();
+    } else {
+        std::io::stdin().read_to_string(&mut src).unwrap();
+    }
+
+    match compile(&src, args.seed) {
+        Ok(program) => output_program(&program),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use crate::lexer::Token;
+use crate::Error;
+use bril_rs::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Int(i64),
+    Bool(bool),
+    Var(String),
+    Call(String, Vec<Spanned<Self>>),
+    Unary(UnOp, Box<Spanned<Self>>),
+    Binary(BinOp, Box<Spanned<Self>>, Box<Spanned<Self>>),
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Let(String, Spanned<Expr>),
+    Assign(String, Spanned<Expr>),
+    If(Spanned<Expr>, Vec<Spanned<Self>>, Vec<Spanned<Self>>),
+    While(Spanned<Expr>, Vec<Spanned<Self>>),
+    Return(Option<Spanned<Expr>>),
+    Print(Vec<Spanned<Expr>>),
+    Expr(Spanned<Expr>),
+}
+
+/// A node of the syntax tree and the line it starts on
+#[derive(Debug)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct Func {
+    pub name: String,
+    pub params: Vec<(String, Type)>,
+    pub return_type: Option<Type>,
+    pub body: Vec<Spanned<Stmt>>,
+    pub line: usize,
+}
+
+const KEYWORDS: [&str; 11] = [
+    "fn", "let", "if", "else", "while", "return", "print", "true", "false", "int", "bool",
+];
+
+// Binary operators from the loosest binding to the tightest.
+const PRECEDENCE: [&[(&str, BinOp)]; 5] = [
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[
+        ("==", BinOp::Eq),
+        ("!=", BinOp::Ne),
+        ("<", BinOp::Lt),
+        ("<=", BinOp::Le),
+        (">", BinOp::Gt),
+        (">=", BinOp::Ge),
+    ],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div)],
+];
+
+// Comparisons do not chain, so `a < b < c` is an error.
+const COMPARISON_LEVEL: usize = 2;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // Whether the last statement of the input may leave off its `;`.
+    one_line: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |&(_, line)| line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, Error> {
+        Err(Error {
+            line: self.line(),
+            message,
+        })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, Error> {
+        match self.peek() {
+            Some(Token::Int(value)) => self.error(format!("expected {expected}, found {value}")),
+            Some(Token::Ident(name)) => self.error(format!("expected {expected}, found `{name}`")),
+            Some(Token::Symbol(symbol)) => {
+                self.error(format!("expected {expected}, found `{symbol}`"))
+            }
+            None => self.error(format!("expected {expected}, found the end of the file")),
+        }
+    }
+
+    fn at_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(name)) if name == keyword)
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.at_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), Error> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{symbol}`"))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{keyword}`"))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    fn type_(&mut self) -> Result<Type, Error> {
+        if self.eat_keyword("int") {
+            Ok(Type::Int)
+        } else if self.eat_keyword("bool") {
+            Ok(Type::Bool)
+        } else {
+            self.unexpected("a type")
+        }
+    }
+
+    fn func(&mut self) -> Result<Func, Error> {
+        let line = self.line();
+        self.expect_keyword("fn")?;
+        let name = self.ident()?;
+        self.expect_symbol("(")?;
+        let mut params = Vec::new();
+        if !self.at_symbol(")") {
+            loop {
+                let param = self.ident()?;
+                self.expect_symbol(":")?;
+                params.push((param, self.type_()?));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_symbol(")")?;
+        let return_type = if self.eat_symbol("->") {
+            Some(self.type_()?)
+        } else {
+            None
+        };
+        let body = self.block()?;
+        Ok(Func {
+            name,
+            params,
+            return_type,
+            body,
+            line,
+        })
+    }
+
+    fn end_stmt(&mut self) -> Result<(), Error> {
+        if self.one_line && self.peek().is_none() {
+            Ok(())
+        } else {
+            self.expect_symbol(";")
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Spanned<Stmt>>, Error> {
+        self.expect_symbol("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat_symbol("}") {
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Spanned<Stmt>, Error> {
+        let line = self.line();
+        let node = if self.eat_keyword("let") {
+            let name = self.ident()?;
+            self.expect_symbol("=")?;
+            let value = self.expr()?;
+            self.end_stmt()?;
+            Stmt::Let(name, value)
+        } else if self.at_keyword("if") {
+            return self.if_stmt();
+        } else if self.eat_keyword("while") {
+            let cond = self.expr()?;
+            Stmt::While(cond, self.block()?)
+        } else if self.eat_keyword("return") {
+            let value = if self.at_symbol(";") {
+                None
+            } else {
+                Some(self.expr()?)
+            };
+            self.end_stmt()?;
+            Stmt::Return(value)
+        } else if self.eat_keyword("print") {
+            self.expect_symbol("(")?;
+            let args = self.args()?;
+            self.end_stmt()?;
+            Stmt::Print(args)
+        } else if matches!(self.tokens.get(self.pos + 1), Some((Token::Symbol("="), _))) {
+            let name = self.ident()?;
+            self.expect_symbol("=")?;
+            let value = self.expr()?;
+            self.end_stmt()?;
+            Stmt::Assign(name, value)
+        } else {
+            let value = self.expr()?;
+            self.end_stmt()?;
+            Stmt::Expr(value)
+        };
+        Ok(Spanned { node, line })
+    }
+
+    fn if_stmt(&mut self) -> Result<Spanned<Stmt>, Error> {
+        let line = self.line();
+        self.expect_keyword("if")?;
+        let cond = self.expr()?;
+        let then_branch = self.block()?;
+        let else_branch = if !self.eat_keyword("else") {
+            Vec::new()
+        } else if self.at_keyword("if") {
+            vec![self.if_stmt()?]
+        } else {
+            self.block()?
+        };
+        Ok(Spanned {
+            node: Stmt::If(cond, then_branch, else_branch),
+            line,
+        })
+    }
+
+    // The arguments of a call, after its opening parenthesis.
+    fn args(&mut self) -> Result<Vec<Spanned<Expr>>, Error> {
+        let mut args = Vec::new();
+        if !self.eat_symbol(")") {
+            loop {
+                args.push(self.expr()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol(")")?;
+        }
+        Ok(args)
+    }
+
+    fn expr(&mut self) -> Result<Spanned<Expr>, Error> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Spanned<Expr>, Error> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = PRECEDENCE[level]
+            .iter()
+            .find(|&&(symbol, _)| self.at_symbol(symbol))
+        {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            let line = lhs.line;
+            lhs = Spanned {
+                node: Expr::Binary(op, Box::new(lhs), Box::new(rhs)),
+                line,
+            };
+            if level == COMPARISON_LEVEL {
+                break;
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Spanned<Expr>, Error> {
+        let line = self.line();
+        let op = if self.eat_symbol("-") {
+            UnOp::Neg
+        } else if self.eat_symbol("!") {
+            UnOp::Not
+        } else {
+            return self.primary();
+        };
+        let operand = self.unary()?;
+        Ok(Spanned {
+            node: Expr::Unary(op, Box::new(operand)),
+            line,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Spanned<Expr>, Error> {
+        let line = self.line();
+        let node = match self.peek().cloned() {
+            Some(Token::Int(value)) => {
+                self.pos += 1;
+                Expr::Int(value)
+            }
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                self.expect_symbol(")")?;
+                return Ok(inner);
+            }
+            _ if self.eat_keyword("true") => Expr::Bool(true),
+            _ if self.eat_keyword("false") => Expr::Bool(false),
+            _ => {
+                let name = self.ident()?;
+                if self.eat_symbol("(") {
+                    Expr::Call(name, self.args()?)
+                } else {
+                    Expr::Var(name)
+                }
+            }
+        };
+        Ok(Spanned { node, line })
+    }
+}
+
+/// Parses a whole program of functions
+pub fn parse(tokens: Vec<(Token, usize)>) -> Result<Vec<Func>, Error> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        one_line: false,
+    };
+    let mut funcs = Vec::new();
+    while parser.peek().is_some() {
+        funcs.push(parser.func()?);
+    }
+    Ok(funcs)
+}
+
+/// Parses the statements of a function body without the braces around it
+pub fn parse_body(tokens: Vec<(Token, usize)>) -> Result<Vec<Spanned<Stmt>>, Error> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        one_line: true,
+    };
+    let mut stmts = Vec::new();
+    while parser.peek().is_some() {
+        stmts.push(parser.stmt()?);
+    }
+    Ok(stmts)
+}
@@ -43,7 +43,8 @@ interface Op {
 export interface EffectOperation extends Op {
   op: "br" | "jmp" | "print" | "ret" | "call" |
     "store" | "free" |
-    "speculate" | "guard" | "commit";
+    "speculate" | "guard" | "commit" |
+    "set";
 }
 
 /**
@@ -58,7 +59,7 @@ export interface ValueOperation extends Op {
       "load" | "ptradd" | "alloc" |
       "fadd" | "fmul" | "fsub" | "fdiv" |
       "feq" | "flt" | "fle" | "fgt" | "fge" |
-      "phi";
+      "phi" | "get";
   dest: Ident;
   type: Type;
 }
@@ -111,4 +111,8 @@ export const OP_SIGS: {[key: string]: Signature | PolySignature} = {
   'speculate': {args: []},
   'commit': {args: []},
   'guard': {args: ['bool'], labels: 1},
+
+  // SSA.
+  'get': {tvar: {tv: 'T'}, sig: {args: [], dest: {tv: 'T'}}},
+  'set': {tvar: {tv: 'T'}, sig: {args: [{tv: 'T'}, {tv: 'T'}]}},
 };
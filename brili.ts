@@ -307,6 +307,9 @@ type State = {
   curlabel: string | null,
   lastlabel: string | null,
 
+  // For SSA (get/set) execution: the shadow variables written by `set`.
+  ssaEnv: Env,
+
   // For speculation: the state at the point where speculation began.
   specparent: State | null,
 }
@@ -352,6 +355,7 @@ function evalCall(instr: bril.Operation, state: State): Action {
     icount: state.icount,
     lastlabel: null,
     curlabel: null,
+    ssaEnv: new Map(),
     specparent: null,  // Speculation not allowed.
   }
   let retVal = evalFunc(func, newState);
@@ -688,6 +692,24 @@ function evalInstr(instr: bril.Instruction, state: State): Action {
     return NEXT;
   }
 
+  case "set": {
+    let args = instr.args || [];
+    if (args.length !== 2) {
+      throw error(`set needs 2 arguments, got ${args.length}`);
+    }
+    state.ssaEnv.set(args[0], get(state.env, args[1]));
+    return NEXT;
+  }
+
+  case "get": {
+    let val = state.ssaEnv.get(instr.dest);
+    if (val === undefined) {
+      throw error(`get of ${instr.dest} without a preceding set`);
+    }
+    state.env.set(instr.dest, val);
+    return NEXT;
+  }
+
   // Begin speculation.
   case "speculate": {
     return {"action": "speculate"};
@@ -729,6 +751,7 @@ function evalFunc(func: bril.Function, state: State): Value | null {
         // Begin speculation.
         state.specparent = {...state};
         state.env = new Map(state.env);
+        state.ssaEnv = new Map(state.ssaEnv);
         break;
       }
       case 'commit': {
@@ -750,6 +773,7 @@ function evalFunc(func: bril.Function, state: State): Value | null {
           env: state.specparent.env,
           lastlabel: state.specparent.lastlabel,
           curlabel: state.specparent.curlabel,
+          ssaEnv: state.specparent.ssaEnv,
           specparent: state.specparent.specparent,
         });
         break;
@@ -863,6 +887,7 @@ function evalProg(prog: bril.Program) {
     icount: BigInt(0),
     lastlabel: null,
     curlabel: null,
+    ssaEnv: new Map(),
     specparent: null,
   }
   evalFunc(main, state);
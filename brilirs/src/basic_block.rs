@@ -181,6 +181,8 @@ pub struct BBFunction {
   // These replacements are found for function args and for code in the BasicBlocks
   pub num_of_vars: usize,
   pub args_as_nums: Vec<usize>,
+  // The name of each variable, indexed by its number
+  pub var_names: Vec<String>,
   pub pos: Option<Position>,
 }
 
@@ -228,7 +230,9 @@ impl BBFunction {
           pos,
         }) if op == bril_rs::EffectOps::Jump
           || op == bril_rs::EffectOps::Branch
-          || op == bril_rs::EffectOps::Return =>
+          || op == bril_rs::EffectOps::Return
+          // A guard may jump away when it aborts, so the rest of its block would not run
+          || op == bril_rs::EffectOps::Guard =>
         {
           let i = bril_rs::Instruction::Effect {
             op,
@@ -262,13 +266,28 @@ impl BBFunction {
       }
     }
 
-    if !curr_block.instrs.is_empty() || curr_block.label.is_some() {
+    // A guard at the end of the function still needs a block to fall through to, which returns
+    let ends_in_guard = blocks.last().is_some_and(|b: &BasicBlock| {
+      matches!(
+        b.instrs.last(),
+        Some(bril_rs::Instruction::Effect {
+          op: bril_rs::EffectOps::Guard,
+          ..
+        })
+      )
+    });
+    if !curr_block.instrs.is_empty() || curr_block.label.is_some() || ends_in_guard {
       if let Some(l) = curr_block.label.as_ref() {
         label_map.insert(l.to_string(), blocks.len());
       }
       blocks.push(curr_block);
     }
 
+    let mut var_names = vec![String::new(); num_of_vars];
+    for (name, num) in num_var_map {
+      var_names[num] = name;
+    }
+
     Ok((
       Self {
         name: func.name,
@@ -277,6 +296,7 @@ impl BBFunction {
         blocks,
         args_as_nums,
         num_of_vars,
+        var_names,
         pos: func.pos,
       },
       label_map,
@@ -289,30 +309,42 @@ impl BBFunction {
     }
     let last_idx = self.blocks.len() - 1;
     for (i, block) in self.blocks.iter_mut().enumerate() {
-      // If we're before the last block
-      if i < last_idx {
-        // Get the last instruction
-        let last_instr = block.instrs.last().cloned();
-        if let Some(bril_rs::Instruction::Effect {
-          op: bril_rs::EffectOps::Jump | bril_rs::EffectOps::Branch,
-          labels,
-          ..
-        }) = last_instr
-        {
-          for l in labels {
-            block
-              .exit
-              .push(*label_map.get(&l).ok_or(InterpError::MissingLabel(l))?);
-          }
-        } else if let Some(bril_rs::Instruction::Effect {
-          op: bril_rs::EffectOps::Return,
-          ..
-        }) = last_instr
-        {
-          // We are done, there is no exit from this block
-        } else {
-          block.exit.push(i + 1);
+      // Get the last instruction
+      let last_instr = block.instrs.last().cloned();
+      if let Some(bril_rs::Instruction::Effect {
+        op: bril_rs::EffectOps::Jump | bril_rs::EffectOps::Branch,
+        labels,
+        ..
+      }) = last_instr
+      {
+        for l in labels {
+          block
+            .exit
+            .push(*label_map.get(&l).ok_or(InterpError::MissingLabel(l))?);
+        }
+      } else if let Some(bril_rs::Instruction::Effect {
+        op: bril_rs::EffectOps::Return,
+        ..
+      }) = last_instr
+      {
+        // We are done, there is no exit from this block
+      } else if let Some(bril_rs::Instruction::Effect {
+        op: bril_rs::EffectOps::Guard,
+        labels,
+        ..
+      }) = last_instr
+      {
+        // A guard falls through when it holds and jumps to its label when it aborts. There is
+        // always a next block, see `find_basic_blocks`
+        block.exit.push(i + 1);
+        for l in labels {
+          block
+            .exit
+            .push(*label_map.get(&l).ok_or(InterpError::MissingLabel(l))?);
         }
+      } else if i < last_idx {
+        // Fall through to the next block, unless this is the last one
+        block.exit.push(i + 1);
       }
     }
     Ok(())
@@ -263,6 +263,20 @@ fn type_check_instruction<'a>(
 
       update_env(env, dest, op_type)
     }
+    Instruction::Value {
+      op: ValueOps::Get,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(0, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      update_env(env, dest, op_type)
+    }
     Instruction::Value {
       op: ValueOps::Alloc,
       dest,
@@ -377,7 +391,7 @@ fn type_check_instruction<'a>(
       })
     }
     Instruction::Effect {
-      op: EffectOps::Nop,
+      op: EffectOps::Nop | EffectOps::Speculate | EffectOps::Commit,
       args,
       funcs,
       labels,
@@ -447,13 +461,34 @@ fn type_check_instruction<'a>(
       Ok(())
     }
     Instruction::Effect {
-      op: EffectOps::Speculate | EffectOps::Guard | EffectOps::Commit,
-      args: _,
-      funcs: _,
-      labels: _,
+      op: EffectOps::Set,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(2, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      // The shadow variable is named by a `get` that may not have been checked yet, so like the
+      // arguments of a phi node it takes the type of the value and any later `get` must agree.
+      let ty = *env
+        .get(&args[1] as &str)
+        .ok_or_else(|| InterpError::VarUndefined(args[1].clone()))?;
+      update_env(env, &args[0], ty)
+    }
+    Instruction::Effect {
+      op: EffectOps::Guard,
+      args,
+      funcs,
+      labels,
       pos: _,
     } => {
-      unimplemented!()
+      check_num_args(1, args)?;
+      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(1, labels)?;
+      Ok(())
     }
   }
 }
@@ -465,7 +500,12 @@ fn type_check_func(bbfunc: &BBFunction, bbprog: &BBProgram) -> Result<(), Positi
     env.insert(&a.name, &a.arg_type);
   });
 
-  let mut work_list = vec![0];
+  // A function without instructions has no blocks to check
+  let mut work_list = if bbfunc.blocks.is_empty() {
+    Vec::new()
+  } else {
+    vec![0]
+  };
   let mut done_list = Vec::new();
 
   while let Some(b) = work_list.pop() {
@@ -1,13 +1,39 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+/// How errors are reported
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Diagnostics {
+  /// A message and the offending source lines on stderr
+  Human,
+  /// A JSON document on stdout, with an empty list when there are no errors
+  Json,
+}
 
 #[derive(Parser)]
 #[command(about, version, author)] // keeps the cli synced with Cargo.toml
 #[command(allow_hyphen_values(true))]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
   /// Flag to output the total number of dynamic instructions
   #[arg(short, long, action)]
   pub profile: bool,
 
+  /// Flag to output a breakdown of dynamic instructions by category per function and per loop
+  #[arg(long, action)]
+  pub op_mix: bool,
+
+  /// Flag to output how many times each opcode was executed
+  #[arg(long, action)]
+  pub op_counts: bool,
+
+  /// Write a Graphviz rendering of the heap at its largest to this file
+  #[arg(long, action)]
+  pub heap_dot: Option<String>,
+
+  /// Run the program in a step debugger that reads its commands from stdin
+  #[arg(long, action, requires = "file")]
+  pub debug: bool,
+
   /// The bril file to run. stdin is assumed if file is not provided
   #[arg(short, long, action)]
   pub file: Option<String>,
@@ -16,10 +42,18 @@ pub struct Cli {
   #[arg(short, long, action)]
   pub check: bool,
 
+  /// The format to report errors in
+  #[arg(long, value_enum, default_value_t = Diagnostics::Human)]
+  pub diagnostics: Diagnostics,
+
   /// Flag for when the bril program is in text form
   #[arg(short, long, action)]
   pub text: bool,
 
+  /// Directories to look for the files the program imports in, after the one it is in
+  #[arg(long, value_name = "DIR")]
+  pub libs: Vec<std::path::PathBuf>,
+
   /// Arguments for the main function
   #[arg(action)]
   pub args: Vec<String>,
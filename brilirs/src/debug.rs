@@ -0,0 +1,219 @@
+use crate::basic_block::{BBFunction, BasicBlock};
+use std::io::{BufRead, Write};
+
+const HELP: &str = "\
+break @func | break .label   stop when a function is called or a label is reached
+delete @func | delete .label remove a breakpoint
+step                         run one instruction, stepping into calls
+next                         run one instruction, stepping over calls
+continue                     run until the next breakpoint
+print var...                 show the value of variables in the current function
+vars                         show every variable defined in the current function
+where                        show the call stack and the next instruction
+heap [file]                  draw the heap in Graphviz, to the file if one is given
+help                         show this message
+Ending the input lets the program run to completion.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Breakpoint {
+  Func(String),
+  Label(String),
+}
+
+impl Breakpoint {
+  fn parse(target: &str) -> Option<Self> {
+    target
+      .strip_prefix('@')
+      .map(|name| Self::Func(name.to_string()))
+      .or_else(|| {
+        target
+          .strip_prefix('.')
+          .map(|label| Self::Label(label.to_string()))
+      })
+  }
+}
+
+impl std::fmt::Display for Breakpoint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Func(name) => write!(f, "@{name}"),
+      Self::Label(label) => write!(f, ".{label}"),
+    }
+  }
+}
+
+// How far to run before stopping again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+  // Until the next instruction
+  Step,
+  // Until the next instruction in a call at most this deep
+  Next(usize),
+  // Until a breakpoint
+  Continue,
+  // Never again, once the commands have run out
+  Detached,
+}
+
+/// A debugger that pauses the interpreter at breakpoints or after each instruction and reads commands like `break`, `step` and `print` from its input. Everything it prints goes to the same output as the program, so the two are interleaved in the order they happen.
+pub struct Debugger {
+  input: Box<dyn BufRead>,
+  breakpoints: Vec<Breakpoint>,
+  mode: Mode,
+  // The functions being run, innermost last
+  frames: Vec<String>,
+  // Set when a breakpoint was hit on entering a block, so the next instruction stops
+  pending: bool,
+}
+
+impl Debugger {
+  /// Reads commands from `input`, stopping before the first instruction of `@main`
+  #[must_use]
+  pub fn new(input: Box<dyn BufRead>) -> Self {
+    Self {
+      input,
+      breakpoints: Vec::new(),
+      mode: Mode::Step,
+      frames: Vec::new(),
+      pending: false,
+    }
+  }
+
+  pub(crate) fn enter_func(&mut self, func: &BBFunction) {
+    self.frames.push(func.name.clone());
+    if self
+      .breakpoints
+      .contains(&Breakpoint::Func(func.name.clone()))
+    {
+      self.pending = true;
+    }
+  }
+
+  pub(crate) fn leave_func(&mut self) {
+    self.frames.pop();
+  }
+
+  pub(crate) fn enter_block(&mut self, block: &BasicBlock) {
+    if let Some(label) = &block.label {
+      if self.breakpoints.contains(&Breakpoint::Label(label.clone())) {
+        self.pending = true;
+      }
+    }
+  }
+
+  /// Called before running instruction `instr` of `block`. `lookup` gives the value of a variable of `func` by its number, or `None` when it has not been assigned yet, and `heap` renders the heap as it is
+  pub(crate) fn before_instr<W: Write>(
+    &mut self,
+    func: &BBFunction,
+    block: &BasicBlock,
+    instr: usize,
+    lookup: &dyn Fn(usize) -> Option<String>,
+    heap: &dyn Fn() -> String,
+    out: &mut W,
+  ) -> std::io::Result<()> {
+    let stop = match self.mode {
+      Mode::Step => true,
+      Mode::Next(depth) => self.pending || self.frames.len() <= depth,
+      Mode::Continue => self.pending,
+      Mode::Detached => false,
+    };
+    self.pending = false;
+    if !stop {
+      return Ok(());
+    }
+
+    Self::write_location(func, block, instr, out)?;
+    loop {
+      write!(out, "(brilirs) ")?;
+      out.flush()?;
+      let mut line = String::new();
+      if self.input.read_line(&mut line)? == 0 {
+        writeln!(out)?;
+        self.mode = Mode::Detached;
+        return Ok(());
+      }
+      let mut words = line.split_whitespace();
+      let Some(command) = words.next() else {
+        continue;
+      };
+      let rest: Vec<&str> = words.collect();
+      match command {
+        "s" | "step" => {
+          self.mode = Mode::Step;
+          return Ok(());
+        }
+        "n" | "next" => {
+          self.mode = Mode::Next(self.frames.len());
+          return Ok(());
+        }
+        "c" | "continue" => {
+          self.mode = Mode::Continue;
+          return Ok(());
+        }
+        "b" | "break" | "d" | "delete" => {
+          let Some(bp) = rest.first().and_then(|target| Breakpoint::parse(target)) else {
+            writeln!(out, "expected `@func` or `.label` after `{command}`")?;
+            continue;
+          };
+          if command.starts_with('b') {
+            if !self.breakpoints.contains(&bp) {
+              self.breakpoints.push(bp.clone());
+            }
+            writeln!(out, "breakpoint at {bp}")?;
+          } else if let Some(i) = self.breakpoints.iter().position(|b| *b == bp) {
+            self.breakpoints.remove(i);
+            writeln!(out, "deleted breakpoint at {bp}")?;
+          } else {
+            writeln!(out, "no breakpoint at {bp}")?;
+          }
+        }
+        "p" | "print" => {
+          for name in rest {
+            match func.var_names.iter().position(|v| v == name) {
+              None => writeln!(out, "no variable `{name}` in @{}", func.name)?,
+              Some(i) => match lookup(i) {
+                None => writeln!(out, "{name} is undefined")?,
+                Some(val) => writeln!(out, "{name} = {val}")?,
+              },
+            }
+          }
+        }
+        "vars" => {
+          for (i, name) in func.var_names.iter().enumerate() {
+            if let Some(val) = lookup(i) {
+              writeln!(out, "{name} = {val}")?;
+            }
+          }
+        }
+        "w" | "where" => {
+          for (depth, name) in self.frames.iter().enumerate().rev() {
+            writeln!(out, "#{depth} @{name}")?;
+          }
+          Self::write_location(func, block, instr, out)?;
+        }
+        "heap" => match rest.first() {
+          None => write!(out, "{}", heap())?,
+          Some(path) => match std::fs::write(path, heap()) {
+            Ok(()) => writeln!(out, "wrote the heap to {path}")?,
+            Err(e) => writeln!(out, "cannot write {path}: {e}")?,
+          },
+        },
+        "h" | "help" => writeln!(out, "{HELP}")?,
+        _ => writeln!(out, "unknown command `{command}`, try `help`")?,
+      }
+    }
+  }
+
+  fn write_location<W: Write>(
+    func: &BBFunction,
+    block: &BasicBlock,
+    instr: usize,
+    out: &mut W,
+  ) -> std::io::Result<()> {
+    write!(out, "@{}", func.name)?;
+    if let Some(label) = &block.label {
+      write!(out, " .{label}")?;
+    }
+    writeln!(out, ": {}", block.instrs[instr])
+  }
+}
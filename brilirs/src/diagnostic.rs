@@ -0,0 +1,117 @@
+use crate::error::{InterpError, PositionalInterpError};
+use bril_rs::conversion::ConversionError;
+use bril_rs::{ColRow, Position};
+use serde_json::{json, Value};
+
+/// The version of the schema written by [`to_json`]. It changes only when a
+/// field is removed or changes meaning; new fields may be added without it.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// One finding about a program, in a form that does not depend on how it is
+/// printed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  /// The source file, if the program was read from one
+  pub file: Option<String>,
+  /// Where in `file` the finding is, if the program has source positions
+  pub span: Option<Position>,
+  /// `error` for everything brilirs reports so far
+  pub severity: &'static str,
+  /// A stable name for the kind of finding, see [`InterpError::code`]
+  pub code: &'static str,
+  /// The human-readable message
+  pub message: String,
+  /// A suggestion for fixing the program, when there is an obvious one
+  pub fix: Option<String>,
+}
+
+impl Diagnostic {
+  /// Describes `e`, falling back to `file` when its position has no source
+  #[must_use]
+  pub fn from_error(e: &PositionalInterpError, file: Option<String>) -> Self {
+    let (code, fix) = match (
+      e.e.downcast_ref::<InterpError>(),
+      e.e.downcast_ref::<ConversionError>(),
+    ) {
+      (Some(e), _) => (e.code(), interp_fix(e)),
+      (None, Some(e)) => conversion_code(e),
+      (None, None) => ("error", None),
+    };
+    Self {
+      file: e.pos.as_ref().and_then(|pos| pos.src.clone()).or(file),
+      span: e.pos.clone(),
+      severity: "error",
+      code,
+      message: e.e.to_string(),
+      fix,
+    }
+  }
+}
+
+fn interp_fix(e: &InterpError) -> Option<String> {
+  match e {
+    InterpError::NoMainFunction => Some("add a `@main` function".to_string()),
+    InterpError::MemLeak => Some("`free` every allocation before the program ends".to_string()),
+    InterpError::MissingLabel(label) | InterpError::PhiMissingLabel(label) => {
+      Some(format!("add a label `.{label}` to the function"))
+    }
+    InterpError::FuncNotFound(name) => Some(format!("define a function `@{name}`")),
+    InterpError::VarUndefined(name) => Some(format!("assign to `{name}` before this instruction")),
+    InterpError::NonEmptyRetForFunc(name) => Some(format!(
+      "give `@{name}` a return type or return without a value"
+    )),
+    InterpError::BadNumArgs(expected, _) | InterpError::BadNumFuncArgs(expected, _) => {
+      Some(format!("pass {expected} argument(s)"))
+    }
+    InterpError::BadAsmtType(expected, _) => Some(format!("use a value of type `{expected}`")),
+    _ => None,
+  }
+}
+
+fn conversion_code(e: &ConversionError) -> (&'static str, Option<String>) {
+  match e {
+    ConversionError::InvalidPrimitive(_) | ConversionError::InvalidParameterized(..) => {
+      ("invalid-type", None)
+    }
+    ConversionError::InvalidValueOps(_) | ConversionError::InvalidEffectOps(_) => {
+      ("invalid-op", None)
+    }
+    ConversionError::MissingType => ("missing-type", Some("add a type annotation".to_string())),
+  }
+}
+
+fn location(pos: &ColRow) -> Value {
+  json!({ "line": pos.row, "column": pos.col })
+}
+
+/// Renders `diagnostics` as a JSON document for editors and graders:
+///
+/// ```json
+/// {"version": 1, "diagnostics": [{"file": "a.bril",
+///   "span": {"start": {"line": 2, "column": 3}, "end": null},
+///   "severity": "error", "code": "undefined-variable",
+///   "message": "undefined variable `b`",
+///   "fix": {"message": "assign to `b` before this instruction"}}]}
+/// ```
+///
+/// `file`, `span`, `span.end` and `fix` are `null` when they are not known.
+#[must_use]
+pub fn to_json(diagnostics: &[Diagnostic]) -> Value {
+  let diagnostics: Vec<Value> = diagnostics
+    .iter()
+    .map(|d| {
+      json!({
+        "file": d.file,
+        "span": d.span.as_ref().map(|span| json!({
+          "start": location(&span.pos),
+          "end": span.pos_end.as_ref().map(location),
+        })),
+        "severity": d.severity,
+        "code": d.code,
+        "message": d.message,
+        "fix": d.fix.as_ref().map(|fix| json!({ "message": fix })),
+      })
+    })
+    .collect();
+  json!({ "version": SCHEMA_VERSION, "diagnostics": diagnostics })
+}
@@ -45,12 +45,26 @@ pub enum InterpError {
   VarUndefined(String),
   #[error("Label `{0}` for phi node not found")]
   PhiMissingLabel(String),
+  #[error("get executed before a set of its variable")]
+  GetWithoutSet,
+  #[error("commit executed outside of speculation")]
+  CommitWithoutSpeculate,
+  #[error("guard failed outside of speculation")]
+  AbortWithoutSpeculate,
+  #[error("function calls are not allowed during speculation")]
+  CallDuringSpeculation,
+  #[error("returning from a function is not allowed during speculation")]
+  ReturnDuringSpeculation,
   #[error("unspecified pointer type `{0:?}`")]
   ExpectedPointerType(bril_rs::Type), // found type
   #[error("Expected type `{0:?}` for function argument, found `{1:?}`")]
   BadFuncArgType(bril_rs::Type, String), // (expected, actual)
   #[error("Expected type `{0:?}` for assignment, found `{1:?}`")]
   BadAsmtType(bril_rs::Type, bril_rs::Type), // (expected, actual). For when the LHS type of an instruction is bad
+  #[error(transparent)]
+  LinkError(#[from] brild::error::BrildError),
+  #[error("cannot create `{0}`: {1}")]
+  CannotCreateFile(String, std::io::Error),
   #[error("There has been an io error: `{0:?}`")]
   IoError(#[from] std::io::Error),
   #[error("You probably shouldn't see this error, this is here to handle conversions between InterpError and PositionalError")]
@@ -58,6 +72,45 @@ pub enum InterpError {
 }
 
 impl InterpError {
+  /// A short name for the kind of error that stays the same when the wording
+  /// of its message changes, for tools that match on it
+  #[must_use]
+  pub const fn code(&self) -> &'static str {
+    match self {
+      Self::DivisionByZero => "division-by-zero",
+      Self::MemLeak => "memory-leak",
+      Self::UsingUninitializedMemory => "uninitialized-memory",
+      Self::NoLastLabel => "phi-without-predecessor",
+      Self::MissingLabel(_) => "missing-label",
+      Self::NoMainFunction => "no-main-function",
+      Self::UnequalPhiNode => "unequal-phi",
+      Self::DuplicateFunction => "duplicate-function",
+      Self::NonEmptyRetForFunc(_) => "non-empty-return",
+      Self::CannotAllocSize(_) => "bad-alloc-size",
+      Self::IllegalFree(..) => "illegal-free",
+      Self::InvalidMemoryAccess(..) => "invalid-memory-access",
+      Self::BadNumFuncArgs(..) => "wrong-function-arg-count",
+      Self::BadNumArgs(..) => "wrong-arg-count",
+      Self::BadNumLabels(..) => "wrong-label-count",
+      Self::BadNumFuncs(..) => "wrong-function-count",
+      Self::FuncNotFound(_) => "undefined-function",
+      Self::VarUndefined(_) => "undefined-variable",
+      Self::PhiMissingLabel(_) => "phi-missing-label",
+      Self::GetWithoutSet => "get-without-set",
+      Self::CommitWithoutSpeculate => "commit-without-speculate",
+      Self::AbortWithoutSpeculate => "abort-without-speculate",
+      Self::CallDuringSpeculation => "call-during-speculation",
+      Self::ReturnDuringSpeculation => "return-during-speculation",
+      Self::ExpectedPointerType(_) => "expected-pointer",
+      Self::BadFuncArgType(..) => "bad-function-arg-type",
+      Self::BadAsmtType(..) => "bad-assignment-type",
+      Self::LinkError(_) => "link",
+      Self::CannotCreateFile(..) | Self::IoError(_) => "io",
+      // Only a wrapper, which `add_pos` and `into` unwrap.
+      Self::PositionalInterpErrorConversion(_) => "error",
+    }
+  }
+
   #[must_use]
   pub fn add_pos(self, pos: Option<Position>) -> PositionalInterpError {
     match self {
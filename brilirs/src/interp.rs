@@ -1,5 +1,7 @@
 use crate::basic_block::{BBFunction, BBProgram, BasicBlock};
+use crate::debug::Debugger;
 use crate::error::{InterpError, PositionalInterpError};
+use crate::op_mix;
 use bril_rs::Instruction;
 
 use fxhash::FxHashMap;
@@ -31,6 +33,10 @@ struct Environment {
   stack_pointers: Vec<(usize, usize)>,
   // env is used like a stack. Assume it only grows
   env: Vec<Value>,
+  // The shadow variables written by `set` and read by `get`, laid out like env. It stays empty until the first `set` so that programs not using them pay nothing.
+  shadow: Vec<Value>,
+  // Whether each new frame starts out uninitialized, so that the debugger does not show values left behind by an earlier call
+  fresh_frames: bool,
 }
 
 impl Environment {
@@ -41,6 +47,8 @@ impl Environment {
       stack_pointers: Vec::new(),
       // Allocate a larger stack size so the interpreter needs to allocate less often
       env: vec![Value::default(); max(size, 50)],
+      shadow: Vec::new(),
+      fresh_frames: false,
     }
   }
 
@@ -59,6 +67,21 @@ impl Environment {
   pub fn set(&mut self, ident: usize, val: Value) {
     self.env[self.current_pointer + ident] = val;
   }
+  pub fn get_shadow(&self, ident: usize) -> Value {
+    self
+      .shadow
+      .get(self.current_pointer + ident)
+      .copied()
+      .unwrap_or_default()
+  }
+
+  pub fn set_shadow(&mut self, ident: usize, val: Value) {
+    if self.shadow.len() < self.env.len() {
+      self.shadow.resize(self.env.len(), Value::default());
+    }
+    self.shadow[self.current_pointer + ident] = val;
+  }
+
   // Push a new frame onto the stack
   pub fn push_frame(&mut self, size: usize) {
     self
@@ -78,18 +101,76 @@ impl Environment {
         Value::default(),
       );
     }
+
+    if self.fresh_frames {
+      self.env[self.current_pointer..self.current_pointer + self.current_frame_size]
+        .fill(Value::default());
+    }
+
+    // A new call starts without any shadow variables set, whatever an earlier frame left here
+    if !self.shadow.is_empty() {
+      self.shadow.resize(self.env.len(), Value::default());
+      self.shadow[self.current_pointer..self.current_pointer + self.current_frame_size]
+        .fill(Value::default());
+    }
   }
 
   // Remove a frame from the stack
   pub fn pop_frame(&mut self) {
     (self.current_pointer, self.current_frame_size) = self.stack_pointers.pop().unwrap();
   }
+
+  const fn frame_range(&self) -> std::ops::Range<usize> {
+    self.current_pointer..self.current_pointer + self.current_frame_size
+  }
+
+  // A copy of the variables of the current frame, to roll back to if speculation aborts
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      values: self.env[self.frame_range()].to_vec(),
+      shadow: self
+        .shadow
+        .get(self.frame_range())
+        .map(<[Value]>::to_vec)
+        .unwrap_or_default(),
+    }
+  }
+
+  pub fn restore(&mut self, snapshot: &Snapshot) {
+    let range = self.frame_range();
+    self.env[range.clone()].copy_from_slice(&snapshot.values);
+    if snapshot.shadow.is_empty() {
+      // Nothing had been set when the snapshot was taken
+      if let Some(shadow) = self.shadow.get_mut(range) {
+        shadow.fill(Value::default());
+      }
+    } else {
+      self.shadow[range].copy_from_slice(&snapshot.shadow);
+    }
+  }
+}
+
+// The variables of a frame as they were when speculation began
+struct Snapshot {
+  values: Vec<Value>,
+  shadow: Vec<Value>,
 }
 
 // todo: This is basically a copy of the heap implement in brili and we could probably do something smarter. This currently isn't that worth it to optimize because most benchmarks do not use the memory extension nor do they run for very long. You (the reader in the future) may be working with bril programs that you would like to speed up that extensively use the bril memory extension. In that case, it would be worth seeing how to implement Heap without a map based memory. Maybe try to re-implement malloc for a large Vec<Value>?
 struct Heap {
   memory: FxHashMap<usize, Vec<Value>>,
   base_num_counter: usize,
+  // A description of the instruction that created each allocation, by base. Only kept when the heap may be drawn, for `--heap-dot` or the debugger
+  sites: Option<FxHashMap<usize, String>>,
+  // Only present when a heap visualization of the peak has been requested
+  peak: Option<Peak>,
+}
+
+// We keep a rendering of the heap as it looked just before the first `free` once the number of live allocations reaches its peak, since that is when the heap is at its largest and most likely to be fully initialized.
+#[derive(Default)]
+struct Peak {
+  live: usize,
+  snapshot: Option<String>,
 }
 
 impl Default for Heap {
@@ -97,15 +178,93 @@ impl Default for Heap {
     Self {
       memory: FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default()),
       base_num_counter: 0,
+      sites: None,
+      peak: None,
     }
   }
 }
 
+// Allocations with more cells than this only have their first cells drawn
+const MAX_DRAWN_CELLS: usize = 32;
+
 impl Heap {
   fn is_empty(&self) -> bool {
     self.memory.is_empty()
   }
 
+  fn record_site(&mut self, site: String) {
+    if let Some(sites) = self.sites.as_mut() {
+      sites.insert(self.base_num_counter - 1, site);
+    }
+  }
+
+  // Only a new peak is rendered, so the heap is not drawn again on every `free` while it stays at its largest
+  fn take_snapshot_if_peak(&mut self) {
+    let live = self.memory.len();
+    if self.peak.as_ref().is_some_and(|p| live > p.live) {
+      let snapshot = Some(self.to_dot());
+      self.peak = Some(Peak { live, snapshot });
+    }
+  }
+
+  // Renders the live allocations as a Graphviz graph with one record node per allocation and an edge for every pointer stored in the heap
+  fn to_dot(&self) -> String {
+    use std::fmt::Write;
+
+    let mut bases: Vec<&usize> = self.memory.keys().collect();
+    bases.sort_unstable();
+
+    let mut dot = String::from("digraph heap {\n  node [shape=record];\n");
+    let mut edges = String::new();
+    for base in bases {
+      let cells = &self.memory[base];
+      let site = self
+        .sites
+        .as_ref()
+        .and_then(|sites| sites.get(base))
+        .map_or("", String::as_str);
+      let mut fields: Vec<String> = cells
+        .iter()
+        .take(MAX_DRAWN_CELLS)
+        .enumerate()
+        .map(|(i, v)| {
+          let text = match v {
+            Value::Pointer(p) => {
+              let port = usize::try_from(p.offset)
+                .ok()
+                .filter(|o| *o < MAX_DRAWN_CELLS)
+                .map_or_else(String::new, |o| format!(":c{o}"));
+              writeln!(
+                edges,
+                "  alloc{base}:c{i} -> alloc{}{port} [label=\"+{}\"];",
+                p.base, p.offset
+              )
+              .unwrap();
+              "ptr".to_string()
+            }
+            Value::Uninitialized => "?".to_string(),
+            v => v.to_string(),
+          };
+          format!("<c{i}> {}", escape_record(&text))
+        })
+        .collect();
+      if cells.len() > MAX_DRAWN_CELLS {
+        fields.push(format!("… {} more", cells.len() - MAX_DRAWN_CELLS));
+      }
+      writeln!(
+        dot,
+        "  alloc{base} [label=\"{{alloc {base}, size {}\\n{}|{{{}}}}}\"];",
+        cells.len(),
+        escape_record(site),
+        fields.join("|")
+      )
+      .unwrap();
+    }
+    dot.push_str(&edges);
+    dot.push_str("}\n");
+    dot
+  }
+
   fn alloc(&mut self, amount: i64) -> Result<Value, InterpError> {
     let amount: usize = amount
       .try_into()
@@ -117,6 +276,7 @@ impl Heap {
   }
 
   fn free(&mut self, key: &Pointer) -> Result<(), InterpError> {
+    self.take_snapshot_if_peak();
     if self.memory.remove(&key.base).is_some() && key.offset == 0 {
       Ok(())
     } else {
@@ -157,6 +317,18 @@ impl Heap {
   }
 }
 
+// Record labels give `{}|<>` and a few other characters a special meaning
+fn escape_record(s: &str) -> String {
+  s.chars()
+    .fold(String::with_capacity(s.len()), |mut acc, c| {
+      if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+        acc.push('\\');
+      }
+      acc.push(c);
+      acc
+    })
+}
+
 // A getter function for when you know what constructor of the Value enum you have and
 // you just want the underlying value(like a f64).
 // Or can just be used to get a owned version of the Value
@@ -203,6 +375,14 @@ impl fmt::Display for Value {
   }
 }
 
+// The value of a variable as the debugger shows it
+fn describe_var(env: &Environment, ident: usize) -> Option<String> {
+  match env.get(ident) {
+    Value::Uninitialized => None,
+    val => Some(val.to_string()),
+  }
+}
+
 fn optimized_val_output<T: std::io::Write>(out: &mut T, val: &Value) -> Result<(), std::io::Error> {
   match val {
     Value::Int(i) => out.write_all(itoa::Buffer::new().format(*i).as_bytes()),
@@ -305,8 +485,8 @@ fn execute_value_op<'a, T: std::io::Write>(
   last_label: Option<&String>,
 ) -> Result<(), InterpError> {
   use bril_rs::ValueOps::{
-    Add, Alloc, And, Call, Div, Eq, Fadd, Fdiv, Feq, Fge, Fgt, Fle, Flt, Fmul, Fsub, Ge, Gt, Id,
-    Le, Load, Lt, Mul, Not, Or, Phi, PtrAdd, Sub,
+    Add, Alloc, And, Call, Div, Eq, Fadd, Fdiv, Feq, Fge, Fgt, Fle, Flt, Fmul, Fsub, Ge, Get, Gt,
+    Id, Le, Load, Lt, Mul, Not, Or, Phi, PtrAdd, Sub,
   };
   match op {
     Add => {
@@ -421,11 +601,14 @@ fn execute_value_op<'a, T: std::io::Write>(
       state.env.set(dest, Value::Bool(arg0 >= arg1));
     }
     Call => {
+      if !state.speculation.is_empty() {
+        return Err(InterpError::CallDuringSpeculation);
+      }
       let callee_func = state.prog.get(funcs[0]).unwrap();
 
       make_func_args(callee_func, args, &mut state.env);
 
-      let result = execute(state, callee_func)?.unwrap();
+      let result = execute(state, callee_func, funcs[0])?.unwrap();
 
       state.env.pop_frame();
 
@@ -442,6 +625,10 @@ fn execute_value_op<'a, T: std::io::Write>(
         state.env.set(dest, arg);
       }
     },
+    Get => match state.env.get_shadow(dest) {
+      Value::Uninitialized => return Err(InterpError::GetWithoutSet),
+      val => state.env.set(dest, val),
+    },
     Alloc => {
       let arg0 = get_arg::<i64>(&state.env, 0, args);
       let res = state.heap.alloc(arg0)?;
@@ -463,17 +650,19 @@ fn execute_value_op<'a, T: std::io::Write>(
 }
 
 fn execute_effect_op<'a, T: std::io::Write>(
-  state: &'a mut State<T>,
+  state: &mut State<'a, T>,
   op: bril_rs::EffectOps,
   args: &[usize],
   funcs: &[usize],
   curr_block: &BasicBlock,
+  // The label of the block being run, which speculation saves and restores for the `phi` nodes
+  current_label: &mut Option<&'a String>,
   // There are two output variables where values are stored to effect the loop execution.
   next_block_idx: &mut Option<usize>,
   result: &mut Option<Value>,
 ) -> Result<(), InterpError> {
   use bril_rs::EffectOps::{
-    Branch, Call, Commit, Free, Guard, Jump, Nop, Print, Return, Speculate, Store,
+    Branch, Call, Commit, Free, Guard, Jump, Nop, Print, Return, Set, Speculate, Store,
   };
   match op {
     Jump => {
@@ -485,6 +674,9 @@ fn execute_effect_op<'a, T: std::io::Write>(
       *next_block_idx = Some(curr_block.exit[exit_idx]);
     }
     Return => {
+      if !state.speculation.is_empty() {
+        return Err(InterpError::ReturnDuringSpeculation);
+      }
       if !args.is_empty() {
         *result = Some(get_arg::<Value>(&state.env, 0, args));
       }
@@ -510,11 +702,14 @@ fn execute_effect_op<'a, T: std::io::Write>(
     }
     Nop => {}
     Call => {
+      if !state.speculation.is_empty() {
+        return Err(InterpError::CallDuringSpeculation);
+      }
       let callee_func = state.prog.get(funcs[0]).unwrap();
 
       make_func_args(callee_func, args, &mut state.env);
 
-      execute(state, callee_func)?;
+      execute(state, callee_func, funcs[0])?;
       state.env.pop_frame();
     }
     Store => {
@@ -526,7 +721,34 @@ fn execute_effect_op<'a, T: std::io::Write>(
       let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
       state.heap.free(arg0)?;
     }
-    Speculate | Commit | Guard => unimplemented!(),
+    Set => {
+      let arg1 = get_arg::<Value>(&state.env, 1, args);
+      state.env.set_shadow(args[0], arg1);
+    }
+    Speculate => {
+      let snapshot = state.env.snapshot();
+      state.speculation.push((snapshot, *current_label));
+    }
+    Commit => {
+      // The speculative values are already in place, so committing only forgets how to undo them
+      if state.speculation.pop().is_none() {
+        return Err(InterpError::CommitWithoutSpeculate);
+      }
+    }
+    Guard => {
+      if get_arg::<bool>(&state.env, 0, args) {
+        *next_block_idx = Some(curr_block.exit[0]);
+      } else {
+        // The heap is not rolled back, only the variables
+        let (snapshot, label) = state
+          .speculation
+          .pop()
+          .ok_or(InterpError::AbortWithoutSpeculate)?;
+        state.env.restore(&snapshot);
+        *current_label = label;
+        *next_block_idx = Some(curr_block.exit[1]);
+      }
+    }
   }
   Ok(())
 }
@@ -534,26 +756,58 @@ fn execute_effect_op<'a, T: std::io::Write>(
 fn execute<'a, T: std::io::Write>(
   state: &mut State<'a, T>,
   func: &'a BBFunction,
+  func_idx: usize,
 ) -> Result<Option<Value>, PositionalInterpError> {
-  let mut last_label;
+  let mut last_label = None;
   let mut current_label = None;
   let mut curr_block_idx = 0;
   // A possible return value
   let mut result = None;
 
+  if let Some(debugger) = state.debugger.as_mut() {
+    debugger.enter_func(func);
+  }
+
   loop {
     let curr_block = &func.blocks[curr_block_idx];
     let curr_instrs = &curr_block.instrs;
     let curr_numified_instrs = &curr_block.numified_instrs;
-    // WARNING!!! We can add the # of instructions at once because you can only jump to a new block at the end. This may need to be changed if speculation is implemented
+    // We can add the # of instructions at once because you can only jump to a new block at the end. A guard that aborts ends its block too, and the instructions rolled back still count
     state.instruction_count += curr_instrs.len();
-    last_label = current_label;
-    current_label = curr_block.label.as_ref();
+    if let Some(block_counts) = state.block_counts.as_mut() {
+      block_counts[func_idx][curr_block_idx] += 1;
+    }
+    // A block without a label is the rest of one split after a guard, which is still the same block to a phi
+    if let Some(label) = curr_block.label.as_ref() {
+      last_label = current_label;
+      current_label = Some(label);
+    }
 
     // A place to store the next block that will be jumped to if specified by an instruction
     let mut next_block_idx = None;
 
-    for (code, numified_code) in curr_instrs.iter().zip(curr_numified_instrs.iter()) {
+    if let Some(debugger) = state.debugger.as_mut() {
+      debugger.enter_block(curr_block);
+    }
+
+    for (instr_idx, (code, numified_code)) in curr_instrs
+      .iter()
+      .zip(curr_numified_instrs.iter())
+      .enumerate()
+    {
+      if let Some(debugger) = state.debugger.as_mut() {
+        let (env, heap) = (&state.env, &state.heap);
+        debugger
+          .before_instr(
+            func,
+            curr_block,
+            instr_idx,
+            &|ident| describe_var(env, ident),
+            &|| heap.to_dot(),
+            &mut state.out,
+          )
+          .map_err(InterpError::IoError)?;
+      }
       match code {
         Instruction::Constant {
           op: bril_rs::ConstOps::Const,
@@ -601,6 +855,12 @@ fn execute<'a, T: std::io::Write>(
             last_label,
           )
           .map_err(|e| e.add_pos(pos.clone()))?;
+          if *op == bril_rs::ValueOps::Alloc && state.heap.sites.is_some() {
+            state.heap.record_site(pos.as_ref().map_or_else(
+              || format!("@{}", func.name),
+              |p| format!("@{} {}:{}", func.name, p.pos.row, p.pos.col),
+            ));
+          }
         }
         Instruction::Effect {
           op,
@@ -615,6 +875,7 @@ fn execute<'a, T: std::io::Write>(
             &numified_code.args,
             &numified_code.funcs,
             curr_block,
+            &mut current_label,
             &mut next_block_idx,
             &mut result,
           )
@@ -629,6 +890,12 @@ fn execute<'a, T: std::io::Write>(
     } else if curr_block.exit.len() == 1 {
       curr_block_idx = curr_block.exit[0];
     } else {
+      if !state.speculation.is_empty() {
+        return Err(InterpError::ReturnDuringSpeculation).map_err(|e| e.add_pos(func.pos.clone()));
+      }
+      if let Some(debugger) = state.debugger.as_mut() {
+        debugger.leave_func();
+      }
       return Ok(result);
     }
   }
@@ -699,6 +966,11 @@ struct State<'a, T: std::io::Write> {
   heap: Heap,
   out: T,
   instruction_count: usize,
+  // How many times each block of each function has run, only tracked for the operation-mix and opcode reports
+  block_counts: Option<Vec<Vec<u64>>>,
+  debugger: Option<Debugger>,
+  // What to roll back to for each speculation begun and not yet committed or aborted, innermost last, with the label of the block it began in. Calls and returns are not allowed while speculating, so these are all from the current frame
+  speculation: Vec<(Snapshot, Option<&'a String>)>,
 }
 
 impl<'a, T: std::io::Write> State<'a, T> {
@@ -709,11 +981,29 @@ impl<'a, T: std::io::Write> State<'a, T> {
       heap,
       out,
       instruction_count: 0,
+      block_counts: None,
+      debugger: None,
+      speculation: Vec::new(),
     }
   }
 }
 
-/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [`std::io::Write`]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [`std::io::stderr`]
+/// What the interpreter does besides running the program, for [`execute_main`]. The default only runs it
+#[derive(Default)]
+pub struct Options<'a> {
+  /// Count the number of instructions run
+  pub profiling: bool,
+  /// Break the instructions run down by category per function and per loop
+  pub op_mix: bool,
+  /// Count the instructions run by opcode
+  pub op_counts: bool,
+  /// Where to write a Graphviz rendering of the heap at its largest
+  pub heap_dot: Option<&'a mut dyn std::io::Write>,
+  /// A debugger to run the program under the control of
+  pub debugger: Option<Debugger>,
+}
+
+/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [`std::io::Write`]. What the [`Options`] ask for besides, like counts of the instructions run, is outputted to ```profiling_out```
 /// # Panics
 /// This should not panic with normal use except if there is a bug or if you are using an unimplemented feature
 /// # Errors
@@ -722,13 +1012,18 @@ pub fn execute_main<T: std::io::Write, U: std::io::Write>(
   prog: &BBProgram,
   out: T,
   input_args: &[String],
-  profiling: bool,
   mut profiling_out: U,
+  options: Options<'_>,
 ) -> Result<(), PositionalInterpError> {
-  let main_func = prog
-    .index_of_main
-    .map(|i| prog.get(i).unwrap())
-    .ok_or(InterpError::NoMainFunction)?;
+  let Options {
+    profiling,
+    op_mix,
+    op_counts,
+    heap_dot,
+    debugger,
+  } = options;
+  let main_idx = prog.index_of_main.ok_or(InterpError::NoMainFunction)?;
+  let main_func = prog.get(main_idx).unwrap();
 
   if main_func.return_type.is_some() {
     return Err(InterpError::NonEmptyRetForFunc(main_func.name.clone()))
@@ -736,14 +1031,46 @@ pub fn execute_main<T: std::io::Write, U: std::io::Write>(
   }
 
   let mut env = Environment::new(main_func.num_of_vars);
-  let heap = Heap::default();
+  let mut heap = Heap::default();
+  if heap_dot.is_some() || debugger.is_some() {
+    heap.sites = Some(FxHashMap::default());
+  }
+  if heap_dot.is_some() {
+    heap.peak = Some(Peak::default());
+  }
 
   env = parse_args(env, &main_func.args, &main_func.args_as_nums, input_args)
     .map_err(|e| e.add_pos(main_func.pos.clone()))?;
 
+  env.fresh_frames = debugger.is_some();
   let mut state = State::new(prog, env, heap, out);
+  state.debugger = debugger;
+  if op_mix || op_counts {
+    state.block_counts = Some(
+      prog
+        .func_index
+        .iter()
+        .map(|f| vec![0; f.blocks.len()])
+        .collect(),
+    );
+  }
 
-  execute(&mut state, main_func)?;
+  execute(&mut state, main_func, main_idx)?;
+
+  if let Some(heap_out) = heap_dot {
+    // Leaked allocations are still interesting to look at
+    state.heap.take_snapshot_if_peak();
+    let snapshot = state.heap.peak.take().and_then(|p| p.snapshot);
+    heap_out
+      .write_all(
+        snapshot
+          .as_deref()
+          .unwrap_or("digraph heap {\n}\n")
+          .as_bytes(),
+      )
+      .and_then(|()| heap_out.flush())
+      .map_err(InterpError::IoError)?;
+  }
 
   if !state.heap.is_empty() {
     return Err(InterpError::MemLeak).map_err(|e| e.add_pos(main_func.pos.clone()));
@@ -759,5 +1086,16 @@ pub fn execute_main<T: std::io::Write, U: std::io::Write>(
       .map_err(InterpError::IoError)?;
   }
 
+  if let Some(block_counts) = state.block_counts {
+    if op_mix {
+      op_mix::write_report(prog, &block_counts, &mut profiling_out)
+        .map_err(InterpError::IoError)?;
+    }
+    if op_counts {
+      op_mix::write_op_counts(prog, &block_counts, &mut profiling_out)
+        .map_err(InterpError::IoError)?;
+    }
+  }
+
   Ok(())
 }
@@ -8,8 +8,10 @@
 #![doc = include_str!("../README.md")]
 
 use basic_block::BBProgram;
-use bril_rs::Program;
-use error::PositionalInterpError;
+use bril_rs::{AbstractProgram, Program};
+use error::{InterpError, PositionalInterpError};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// The internal representation of brilirs, provided a ```TryFrom<Program>``` conversion
 pub mod basic_block;
@@ -17,35 +19,97 @@ pub mod basic_block;
 pub mod check;
 #[doc(hidden)]
 pub mod cli;
+/// Provides ```debug::Debugger``` to step through a program as it runs
+pub mod debug;
+/// Provides ```diagnostic::to_json``` to report errors to editors and graders
+pub mod diagnostic;
 #[doc(hidden)]
 pub mod error;
 /// Provides ```interp::execute_main``` to execute [Program] that have been converted into [`BBProgram`]
 pub mod interp;
+/// Provides ```op_mix::write_report``` to break down dynamic instructions by category and ```op_mix::write_op_counts``` to count them by opcode
+pub mod op_mix;
+
+/// Links `program` with the files it imports, as brild does.
+///
+/// They are looked for in the directory of `src_name`, or the current one
+/// for stdin, then in `libs`. Every function but `main` is renamed after the
+/// file it is from.
+/// # Errors
+/// When an imported file or function cannot be found, or a name is defined twice
+pub fn link(
+  program: AbstractProgram,
+  src_name: Option<String>,
+  libs: &[PathBuf],
+) -> Result<AbstractProgram, InterpError> {
+  let (path, dir) = match src_name {
+    Some(src) => {
+      let path = std::fs::canonicalize(src)?;
+      let dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+      (path, dir)
+    }
+    None => (PathBuf::new(), PathBuf::from(".")),
+  };
+  let libs: Vec<PathBuf> = std::iter::once(dir).chain(libs.iter().cloned()).collect();
+  let mut map = HashMap::new();
+  brild::handle_program(&mut map, program, &path, &libs, true)?;
+  let mut linked = AbstractProgram {
+    functions: Vec::new(),
+    imports: Vec::new(),
+  };
+  for program in map.into_values().flatten() {
+    linked.functions.extend(program.functions);
+  }
+  Ok(linked)
+}
+
+/// How [`run_input`] reads the program and what it does with it. The default reads JSON and runs it
+#[derive(Default)]
+pub struct RunOptions<'a> {
+  /// What the interpreter does besides running the program
+  pub interp: interp::Options<'a>,
+  /// Only type check the program
+  pub check: bool,
+  /// The program is in the text format
+  pub text: bool,
+  /// The file the program is from, for positions and to find what it imports
+  pub src_name: Option<String>,
+  /// Where else to look for what the program imports
+  pub libs: &'a [PathBuf],
+}
 
 #[doc(hidden)]
 pub fn run_input<T: std::io::Write, U: std::io::Write>(
   input: impl std::io::Read,
   out: T,
   input_args: &[String],
-  profiling: bool,
   profiling_out: U,
-  check: bool,
-  text: bool,
-  src_name: Option<String>,
+  options: RunOptions<'_>,
 ) -> Result<(), PositionalInterpError> {
+  let RunOptions {
+    interp: interp_options,
+    check,
+    text,
+    src_name,
+    libs,
+  } = options;
   // It's a little confusing because of the naming conventions.
   //      - bril_rs takes file.json as input
   //      - bril2json takes file.bril as input
-  let prog: Program = if text {
-    bril2json::parse_abstract_program_from_read(input, true, true, src_name).try_into()?
+  let mut abstract_prog = if text {
+    bril2json::parse_abstract_program_from_read(input, true, true, src_name.clone())
   } else {
-    bril_rs::load_abstract_program_from_read(input).try_into()?
+    bril_rs::load_abstract_program_from_read(input)
   };
+  if !abstract_prog.imports.is_empty() {
+    abstract_prog = link(abstract_prog, src_name, libs)?;
+  }
+  let prog: Program = abstract_prog.try_into()?;
   let bbprog: BBProgram = prog.try_into()?;
   check::type_check(&bbprog)?;
 
   if !check {
-    interp::execute_main(&bbprog, out, input_args, profiling, profiling_out)?;
+    interp::execute_main(&bbprog, out, input_args, profiling_out, interp_options)?;
   }
 
   Ok(())
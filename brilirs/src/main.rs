@@ -1,6 +1,9 @@
 use bril_rs::Position;
-use brilirs::cli::Cli;
-use brilirs::error::PositionalInterpError;
+use brilirs::cli::{Cli, Diagnostics};
+use brilirs::debug::Debugger;
+use brilirs::diagnostic::{self, Diagnostic};
+use brilirs::error::{InterpError, PositionalInterpError};
+use brilirs::{interp, RunOptions};
 use clap::Parser;
 use std::fs::File;
 use std::io::Read;
@@ -19,16 +22,55 @@ fn main() {
   Instead of builtin std::io::stdout()/std::io::stderr()
   */
 
-  if let Err(e) = brilirs::run_input(
-    input,
-    std::io::BufWriter::new(std::io::stdout()),
-    &args.args,
-    args.profile,
-    std::io::stderr(),
-    args.check,
-    args.text,
-    args.file,
-  ) {
+  let heap_dot = args
+    .heap_dot
+    .as_ref()
+    .map(|path| File::create(path).map_err(|e| InterpError::CannotCreateFile(path.clone(), e)))
+    .transpose();
+
+  let result = heap_dot
+    .map_err(PositionalInterpError::from)
+    .and_then(|mut heap_dot| {
+      let interp = interp::Options {
+        profiling: args.profile,
+        op_mix: args.op_mix,
+        op_counts: args.op_counts,
+        heap_dot: heap_dot.as_mut().map(|f| f as &mut dyn std::io::Write),
+        debugger: args
+          .debug
+          .then(|| Debugger::new(Box::new(std::io::stdin().lock()))),
+      };
+      let options = RunOptions {
+        interp,
+        check: args.check,
+        text: args.text,
+        src_name: args.file.clone(),
+        libs: &args.libs,
+      };
+      brilirs::run_input(
+        input,
+        std::io::BufWriter::new(std::io::stdout()),
+        &args.args,
+        std::io::stderr(),
+        options,
+      )
+    });
+
+  if args.diagnostics == Diagnostics::Json {
+    let diagnostics: Vec<Diagnostic> = result
+      .as_ref()
+      .err()
+      .map(|e| Diagnostic::from_error(e, args.file.clone()))
+      .into_iter()
+      .collect();
+    println!("{}", diagnostic::to_json(&diagnostics));
+    if result.is_err() {
+      std::process::exit(2)
+    }
+    return;
+  }
+
+  if let Err(e) = result {
     eprintln!("error: {e}");
     if let PositionalInterpError {
       pos: Some(Position {
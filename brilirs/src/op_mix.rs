@@ -0,0 +1,262 @@
+use crate::basic_block::{BBFunction, BBProgram, BasicBlock};
+use bril_rs::{EffectOps, Instruction, ValueOps};
+use fxhash::FxHashMap;
+
+/// The coarse buckets that dynamic instructions are sorted into by the operation-mix report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCategory {
+  /// Integer, floating point, comparison, and logic operations
+  Arithmetic,
+  /// `alloc`, `free`, `load`, `store`, and `ptradd`
+  Memory,
+  /// `jmp`, `br`, and `ret`
+  Control,
+  /// `call` in both its value and effect forms
+  Call,
+  /// `print`
+  Print,
+  /// Everything else: `const`, `id`, `nop`, `phi`, `get`, `set`, and the speculation operations
+  Other,
+}
+
+impl OpCategory {
+  const ALL: [Self; 6] = [
+    Self::Arithmetic,
+    Self::Memory,
+    Self::Control,
+    Self::Call,
+    Self::Print,
+    Self::Other,
+  ];
+
+  const fn name(self) -> &'static str {
+    match self {
+      Self::Arithmetic => "arithmetic",
+      Self::Memory => "memory",
+      Self::Control => "control",
+      Self::Call => "call",
+      Self::Print => "print",
+      Self::Other => "other",
+    }
+  }
+
+  /// Classifies a single instruction
+  #[must_use]
+  pub const fn of(instr: &Instruction) -> Self {
+    match instr {
+      Instruction::Constant { .. } => Self::Other,
+      Instruction::Value { op, .. } => match op {
+        ValueOps::Call => Self::Call,
+        ValueOps::Id | ValueOps::Phi | ValueOps::Get => Self::Other,
+        ValueOps::Alloc | ValueOps::Load | ValueOps::PtrAdd => Self::Memory,
+        _ => Self::Arithmetic,
+      },
+      Instruction::Effect { op, .. } => match op {
+        EffectOps::Jump | EffectOps::Branch | EffectOps::Return => Self::Control,
+        EffectOps::Call => Self::Call,
+        EffectOps::Print => Self::Print,
+        EffectOps::Store | EffectOps::Free => Self::Memory,
+        EffectOps::Nop
+        | EffectOps::Speculate
+        | EffectOps::Commit
+        | EffectOps::Guard
+        | EffectOps::Set => Self::Other,
+      },
+    }
+  }
+}
+
+/// Dynamic instruction counts, indexed in the same order as `OpCategory::ALL`
+#[derive(Debug, Default, Clone, Copy)]
+struct Mix([u64; 6]);
+
+impl Mix {
+  fn of_block(block: &BasicBlock) -> Self {
+    let mut mix = Self::default();
+    for instr in &block.instrs {
+      let idx = OpCategory::ALL
+        .iter()
+        .position(|c| *c == OpCategory::of(instr))
+        .unwrap();
+      mix.0[idx] += 1;
+    }
+    mix
+  }
+
+  fn add_scaled(&mut self, other: &Self, times: u64) {
+    self
+      .0
+      .iter_mut()
+      .zip(other.0.iter())
+      .for_each(|(a, b)| *a += b * times);
+  }
+
+  fn total(&self) -> u64 {
+    self.0.iter().sum()
+  }
+
+  fn write<U: std::io::Write>(&self, out: &mut U) -> std::io::Result<()> {
+    let total = self.total();
+    write!(out, "total: {total}")?;
+    for (category, count) in OpCategory::ALL.iter().zip(self.0.iter()) {
+      // A percentage is only for display so the precision loss is irrelevant
+      #[allow(clippy::cast_precision_loss)]
+      let percent = if total == 0 {
+        0.0
+      } else {
+        *count as f64 * 100.0 / total as f64
+      };
+      write!(out, ", {}: {count} ({percent:.1}%)", category.name())?;
+    }
+    writeln!(out)
+  }
+}
+
+/// A natural loop of a function, identified by the block index of its header
+#[derive(Debug)]
+struct NaturalLoop {
+  header: usize,
+  body: Vec<usize>,
+}
+
+// Finds the natural loops of `func` by running a depth-first search from the entry block and treating every edge back to a block that is still on the search stack as a back edge. This agrees with the dominator-based definition on reducible control flow, which is all that the Bril frontends produce.
+fn find_loops(func: &BBFunction) -> Vec<NaturalLoop> {
+  let num_blocks = func.blocks.len();
+  if num_blocks == 0 {
+    return Vec::new();
+  }
+
+  let mut preds = vec![Vec::new(); num_blocks];
+  for (i, block) in func.blocks.iter().enumerate() {
+    for &succ in &block.exit {
+      preds[succ].push(i);
+    }
+  }
+
+  // (tail, header) pairs
+  let mut back_edges = Vec::new();
+  let mut on_stack = vec![false; num_blocks];
+  let mut visited = vec![false; num_blocks];
+  // Each entry is a block and the index of the next successor to look at
+  let mut stack = vec![(0, 0)];
+  visited[0] = true;
+  on_stack[0] = true;
+  while let Some((block, next)) = stack.last_mut() {
+    let block = *block;
+    if let Some(&succ) = func.blocks[block].exit.get(*next) {
+      *next += 1;
+      if on_stack[succ] {
+        back_edges.push((block, succ));
+      } else if !visited[succ] {
+        visited[succ] = true;
+        on_stack[succ] = true;
+        stack.push((succ, 0));
+      }
+    } else {
+      on_stack[block] = false;
+      stack.pop();
+    }
+  }
+
+  // Loops that share a header are merged into one
+  let mut loops: Vec<NaturalLoop> = Vec::new();
+  for (tail, header) in back_edges {
+    let mut in_body = vec![false; num_blocks];
+    in_body[header] = true;
+    let mut worklist = vec![tail];
+    while let Some(b) = worklist.pop() {
+      if !in_body[b] {
+        in_body[b] = true;
+        worklist.extend(preds[b].iter().copied());
+      }
+    }
+    let body: Vec<usize> = (0..num_blocks).filter(|b| in_body[*b]).collect();
+    if let Some(l) = loops.iter_mut().find(|l| l.header == header) {
+      l.body.extend(body);
+      l.body.sort_unstable();
+      l.body.dedup();
+    } else {
+      loops.push(NaturalLoop { header, body });
+    }
+  }
+  loops.sort_by_key(|l| l.header);
+  loops
+}
+
+/// Writes the operation-mix report for `prog` to `out`, given how many times each block of each function was executed
+/// # Errors
+/// Will error if writing to `out` fails
+pub fn write_report<U: std::io::Write>(
+  prog: &BBProgram,
+  block_counts: &[Vec<u64>],
+  out: &mut U,
+) -> std::io::Result<()> {
+  let mut program_mix = Mix::default();
+  let mut lines = Vec::new();
+
+  for (func, counts) in prog.func_index.iter().zip(block_counts.iter()) {
+    if counts.iter().all(|c| *c == 0) {
+      continue;
+    }
+    let block_mixes: Vec<Mix> = func.blocks.iter().map(Mix::of_block).collect();
+
+    let mut func_mix = Mix::default();
+    for (mix, count) in block_mixes.iter().zip(counts.iter()) {
+      func_mix.add_scaled(mix, *count);
+    }
+    program_mix.add_scaled(&func_mix, 1);
+    lines.push((format!("@{}", func.name), func_mix));
+
+    for l in find_loops(func) {
+      let mut loop_mix = Mix::default();
+      for b in &l.body {
+        loop_mix.add_scaled(&block_mixes[*b], counts[*b]);
+      }
+      let header = func.blocks[l.header].label.as_ref().map_or_else(
+        || format!("block {}", l.header),
+        |label| format!(".{label}"),
+      );
+      lines.push((format!("  loop {header}"), loop_mix));
+    }
+  }
+
+  write!(out, "op_mix ")?;
+  program_mix.write(out)?;
+  for (name, mix) in lines {
+    write!(out, "{name} ")?;
+    mix.write(out)?;
+  }
+  out.flush()
+}
+
+fn op_name(instr: &Instruction) -> String {
+  match instr {
+    Instruction::Constant { op, .. } => op.to_string(),
+    Instruction::Value { op, .. } => op.to_string(),
+    Instruction::Effect { op, .. } => op.to_string(),
+  }
+}
+
+/// Writes how many times each opcode was executed in `prog` to `out`, most frequent first, given how many times each block of each function was executed
+/// # Errors
+/// Will error if writing to `out` fails
+pub fn write_op_counts<U: std::io::Write>(
+  prog: &BBProgram,
+  block_counts: &[Vec<u64>],
+  out: &mut U,
+) -> std::io::Result<()> {
+  let mut counts: FxHashMap<String, u64> = FxHashMap::default();
+  for (func, func_counts) in prog.func_index.iter().zip(block_counts.iter()) {
+    for (block, count) in func.blocks.iter().zip(func_counts.iter()) {
+      for instr in &block.instrs {
+        *counts.entry(op_name(instr)).or_default() += count;
+      }
+    }
+  }
+  let mut counts: Vec<(String, u64)> = counts.into_iter().filter(|(_, c)| *c > 0).collect();
+  counts.sort_unstable_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+  for (op, count) in counts {
+    writeln!(out, "op_count {op}: {count}")?;
+  }
+  out.flush()
+}
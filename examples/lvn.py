@@ -111,9 +111,11 @@ def lvn_block(block, lookup, canonicalize, fold):
         # Non-call value operations are candidates for replacement. (We
         # could conceivably include calls to pure functions as values,
         # but determining purity would require an interprocedural
-        # analysis.)
+        # analysis.) A `phi` depends on the incoming edge, not just on
+        # its arguments, so it is never replaced either.
         val = None
-        if 'dest' in instr and 'args' in instr and instr['op'] != 'call':
+        if 'dest' in instr and 'args' in instr and \
+           instr['op'] not in ('call', 'phi'):
             # Construct a Value for this computation.
             val = canonicalize(Value(instr['op'], argnums))
 
@@ -174,9 +176,15 @@ def lvn_block(block, lookup, canonicalize, fold):
                 # source for the newly computed value.
                 value2num[val] = newnum
 
-        # Update argument variable names to canonical variables.
-        if 'args' in instr:
-            instr['args'] = [num2var[n] for n in argnums]
+        # Update argument variable names to canonical variables. The
+        # arguments of a `phi` are read in predecessor blocks, and the
+        # first argument of `set` names a shadow variable, so those keep
+        # their names.
+        if 'args' in instr and instr['op'] != 'phi':
+            args = [num2var[n] for n in argnums]
+            if instr['op'] == 'set':
+                args[0] = argvars[0]
+            instr['args'] = args
 
 
 def _lookup(value2num, value):
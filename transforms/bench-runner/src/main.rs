@@ -0,0 +1,343 @@
+// Runs the benchmarks of the repository through an lvn pipeline and reports
+// how many instructions each one executes before and after, like a run of
+// brench with one treatment but without a configuration to write.
+//
+//     bench-runner [DIR...] [-p PASSES | --pipeline FILE] [--csv]
+//
+// Each `.bril` file under the directories, `benchmarks` unless some are
+// given, is converted with `bril2json`, run with `brilirs -p` on the
+// arguments of its `ARGS:` line, optimized with `lvn` and run again. The
+// dynamic instruction counts come from the `total_dyn_inst` line the
+// interpreter prints with `-p`.
+//
+// A benchmark is only counted when the optimized program prints what the
+// original does; otherwise it is reported as `incorrect`, or as `error` when
+// lvn fails, and bench-runner exits unsuccessfully. Runs over `--timeout`
+// seconds are stopped and reported as `timeout`. The summary gives the
+// instructions of all counted benchmarks together and the geometric mean of
+// how much each one keeps of its instructions. With `--csv` the results are
+// written in the CSV format of brench instead, a `baseline` and an `lvn` row
+// per benchmark.
+use bril_cli::exit_on_error;
+use bril_ir::error::BrilError;
+use clap::Parser;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "bench-runner",
+    about = "Measures how an lvn pipeline changes the instructions the benchmarks execute"
+)]
+struct Cli {
+    /// The directories to look for benchmarks in
+    #[arg(value_name = "DIR", default_value = "benchmarks")]
+    dirs: Vec<PathBuf>,
+
+    /// The passes to run, separated by commas, like `lvn,dce`
+    #[arg(short, long, value_name = "PASSES", conflicts_with = "pipeline")]
+    passes: Option<String>,
+
+    /// Run the pipeline described in this TOML or JSON file
+    #[arg(long, value_name = "FILE")]
+    pipeline: Option<PathBuf>,
+
+    /// The optimizer to run
+    #[arg(long, value_name = "CMD", default_value = "lvn")]
+    lvn: String,
+
+    /// The interpreter to run the programs with
+    #[arg(long, value_name = "CMD", default_value = "brilirs")]
+    interp: String,
+
+    /// How many seconds a run may take
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    timeout: u64,
+
+    /// Write the results as brench does
+    #[arg(long)]
+    csv: bool,
+
+    /// Report each benchmark as it is run on stderr
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+struct Output {
+    success: bool,
+    stdout: Vec<u8>,
+    stderr: String,
+}
+
+// What came of running one benchmark.
+enum Outcome {
+    Counted { baseline: u64, optimized: u64 },
+    Incorrect,
+    Error(String),
+    Timeout,
+}
+
+impl Outcome {
+    fn describe(&self) -> &'static str {
+        match self {
+            Outcome::Counted { .. } => "ok",
+            Outcome::Incorrect => "incorrect",
+            Outcome::Error(_) => "error",
+            Outcome::Timeout => "timeout",
+        }
+    }
+}
+
+fn split_command(command: &str) -> Vec<String> {
+    command.split_whitespace().map(String::from).collect()
+}
+
+// Runs `command` with `args` on `input`, or gives `None` when it takes over
+// `timeout`.
+fn run(command: &str, args: &[String], input: &[u8], timeout: Duration) -> Option<Output> {
+    let words = split_command(command);
+    let (program, extra) = words.split_first().expect("Empty command");
+    let mut child = Command::new(program)
+        .args(extra)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", program, e));
+    // The command may not read all of its input, so ignore broken pipes.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    // The output is read while the command runs so that it never waits for
+    // room in a pipe.
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().expect("Failed to wait for a run") {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    Some(Output {
+        success: status.success(),
+        stdout: stdout.join().unwrap(),
+        stderr: String::from_utf8_lossy(&stderr.join().unwrap()).into_owned(),
+    })
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+fn dyn_insts(interp: &Output) -> Option<u64> {
+    interp
+        .stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("total_dyn_inst: "))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+// The arguments on the first `ARGS:` line, as turnt reads them.
+fn program_args(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .find_map(|line| line.split_once("ARGS:"))
+        .map(|(_, args)| args.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn benchmarks(dir: &Path, found: &mut Vec<PathBuf>) -> Result<(), BrilError> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| BrilError::io(dir.display().to_string(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| BrilError::io(dir.display().to_string(), e))?
+            .path();
+        if path.is_dir() {
+            benchmarks(&path, found)?;
+        } else if path.extension().is_some_and(|ext| ext == "bril") {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn measure(cli: &Cli, lvn_args: &[String], path: &Path) -> Result<Outcome, BrilError> {
+    let timeout = Duration::from_secs(cli.timeout);
+    let source =
+        std::fs::read_to_string(path).map_err(|e| BrilError::io(path.display().to_string(), e))?;
+    let mut args = vec![String::from("-p")];
+    args.extend(program_args(&source));
+
+    let json = match run("bril2json", &[], source.as_bytes(), timeout) {
+        Some(json) if json.success => json.stdout,
+        Some(json) => return Ok(Outcome::Error(json.stderr)),
+        None => return Ok(Outcome::Timeout),
+    };
+    let baseline = match run(&cli.interp, &args, &json, timeout) {
+        Some(baseline) => baseline,
+        None => return Ok(Outcome::Timeout),
+    };
+    let optimized = match run(&cli.lvn, lvn_args, &json, timeout) {
+        Some(optimized) if optimized.success => optimized.stdout,
+        Some(optimized) => return Ok(Outcome::Error(optimized.stderr)),
+        None => return Ok(Outcome::Timeout),
+    };
+    let after = match run(&cli.interp, &args, &optimized, timeout) {
+        Some(after) => after,
+        None => return Ok(Outcome::Timeout),
+    };
+    if after.success != baseline.success || after.stdout != baseline.stdout {
+        return Ok(Outcome::Incorrect);
+    }
+    match (dyn_insts(&baseline), dyn_insts(&after)) {
+        (Some(baseline), Some(optimized)) => Ok(Outcome::Counted {
+            baseline,
+            optimized,
+        }),
+        _ => Ok(Outcome::Error(String::from("no instruction count"))),
+    }
+}
+
+fn name(path: &Path) -> String {
+    path.file_stem().unwrap().to_string_lossy().into_owned()
+}
+
+fn print_csv(results: &[(PathBuf, Outcome)]) {
+    println!("benchmark,run,result");
+    for (path, outcome) in results {
+        match outcome {
+            Outcome::Counted {
+                baseline,
+                optimized,
+            } => {
+                println!("{},baseline,{}", name(path), baseline);
+                println!("{},lvn,{}", name(path), optimized);
+            }
+            outcome => {
+                println!("{},baseline,", name(path));
+                println!("{},lvn,{}", name(path), outcome.describe());
+            }
+        }
+    }
+}
+
+fn print_table(results: &[(PathBuf, Outcome)]) {
+    let width = results
+        .iter()
+        .map(|(path, _)| name(path).len())
+        .chain(["benchmark".len()])
+        .max()
+        .unwrap();
+    println!(
+        "{:<width$}  {:>12}  {:>12}  {:>8}",
+        "benchmark",
+        "baseline",
+        "optimized",
+        "change",
+        width = width
+    );
+    let (mut before, mut after, mut ratios) = (0, 0, Vec::new());
+    for (path, outcome) in results {
+        match *outcome {
+            Outcome::Counted {
+                baseline,
+                optimized,
+            } => {
+                let ratio = optimized as f64 / baseline.max(1) as f64;
+                println!(
+                    "{:<width$}  {:>12}  {:>12}  {:>+7.1}%",
+                    name(path),
+                    baseline,
+                    optimized,
+                    (ratio - 1.0) * 100.0,
+                    width = width
+                );
+                before += baseline;
+                after += optimized;
+                ratios.push(ratio);
+            }
+            ref outcome => {
+                println!(
+                    "{:<width$}  {}",
+                    name(path),
+                    outcome.describe(),
+                    width = width
+                )
+            }
+        }
+    }
+    if ratios.is_empty() {
+        return;
+    }
+    let total = after as f64 / before.max(1) as f64;
+    let mean = (ratios.iter().map(|ratio| ratio.ln()).sum::<f64>() / ratios.len() as f64).exp();
+    println!(
+        "{:<width$}  {:>12}  {:>12}  {:>+7.1}%",
+        "total",
+        before,
+        after,
+        (total - 1.0) * 100.0,
+        width = width
+    );
+    println!(
+        "{} of {} benchmarks counted, geometric mean {:.3} of the instructions",
+        ratios.len(),
+        results.len(),
+        mean
+    );
+}
+
+fn run_all() -> Result<(), BrilError> {
+    let cli = Cli::parse();
+    let lvn_args = match (&cli.passes, &cli.pipeline) {
+        (Some(passes), _) => vec![String::from("--passes"), passes.clone()],
+        (_, Some(pipeline)) => vec![String::from("--pipeline"), pipeline.display().to_string()],
+        (None, None) => Vec::new(),
+    };
+    let mut paths = Vec::new();
+    for dir in &cli.dirs {
+        benchmarks(dir, &mut paths)?;
+    }
+    paths.sort();
+
+    let mut results = Vec::new();
+    for path in paths {
+        let outcome = measure(&cli, &lvn_args, &path)?;
+        if cli.verbose {
+            eprintln!("bench-runner: {} {}", path.display(), outcome.describe());
+            if let Outcome::Error(e) = &outcome {
+                eprint!("{}", e);
+            }
+        }
+        results.push((path, outcome));
+    }
+    if cli.csv {
+        print_csv(&results);
+    } else {
+        print_table(&results);
+    }
+    let failed = results
+        .iter()
+        .any(|(_, outcome)| matches!(outcome, Outcome::Incorrect | Outcome::Error(_)));
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error("bench-runner", run_all());
+}
@@ -0,0 +1,256 @@
+// Which pointers of a function may point into the same allocation.
+//
+// The analysis is flow-insensitive and based on allocation sites: every
+// `alloc` instruction is a site, standing for all the memory it ever
+// allocates, and each pointer variable may point into the sites that
+// assignments anywhere in the function can give it, whatever order they run
+// in. What the memory of a site holds is tracked the same way, so a pointer
+// loaded from it may point wherever the pointers stored into it do.
+//
+// Pointers from outside the function, its arguments and what calls return,
+// point to `Location::Unknown`, any memory the function did not allocate or
+// that other functions can reach. A site escapes when a pointer into it is
+// passed to a call, returned, stored into memory that escapes or that is
+// unknown, or used by an operation this analysis does not know; unknown
+// memory may hold pointers into escaped sites, and the pointers in escaped
+// memory may have been replaced by other functions with unknown ones.
+//
+// Offsets are not tracked, so two pointers into the same site may alias
+// whatever they add to it.
+use crate::ControlFlowGraph;
+use bril_ir::{Instruction, NamedArg, Type, PURE_OPS};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Location {
+    // The memory allocated by the site with this index in `sites`.
+    Site(usize),
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct AliasAnalysis {
+    // The block and the index in it of each `alloc`, in the order of the
+    // blocks.
+    pub sites: Vec<(usize, usize)>,
+    points_to: HashMap<String, BTreeSet<Location>>,
+    escaped: BTreeSet<usize>,
+    unknown: BTreeSet<Location>,
+}
+
+// Operations that use pointers without letting them escape.
+const CONTAINED_OPS: [&str; 10] = [
+    "const", "id", "ptradd", "phi", "load", "free", "print", "set", "get", "alloc",
+];
+
+fn is_pointer(instr: &Instruction) -> bool {
+    matches!(instr.type_, Some(Type::Ptr { .. }))
+}
+
+// The state the fixpoint grows until it stops changing.
+#[derive(Default)]
+struct State {
+    points_to: HashMap<String, BTreeSet<Location>>,
+    // The pointers the memory of each location may hold.
+    contents: HashMap<Location, BTreeSet<Location>>,
+    escaped: BTreeSet<usize>,
+    changed: bool,
+}
+
+impl State {
+    fn get(&self, var: &str) -> BTreeSet<Location> {
+        self.points_to.get(var).cloned().unwrap_or_default()
+    }
+
+    fn add(&mut self, var: &str, locations: impl IntoIterator<Item = Location>) {
+        let set = self.points_to.entry(var.to_string()).or_default();
+        for location in locations {
+            self.changed |= set.insert(location);
+        }
+    }
+
+    fn store(&mut self, location: Location, locations: &BTreeSet<Location>) {
+        let set = self.contents.entry(location).or_default();
+        for &stored in locations {
+            self.changed |= set.insert(stored);
+        }
+    }
+
+    fn escape(&mut self, locations: &BTreeSet<Location>) {
+        for &location in locations {
+            if let Location::Site(site) = location {
+                self.changed |= self.escaped.insert(site);
+            }
+        }
+    }
+
+    // Whether other functions may reach the memory of `location`.
+    fn reachable_outside(&self, location: Location) -> bool {
+        match location {
+            Location::Site(site) => self.escaped.contains(&site),
+            Location::Unknown => true,
+        }
+    }
+
+    // What a pointer loaded through a pointer to `locations` may point to.
+    fn loaded(&self, locations: &BTreeSet<Location>) -> BTreeSet<Location> {
+        let mut loaded = BTreeSet::new();
+        for &location in locations {
+            if let Some(contents) = self.contents.get(&location) {
+                loaded.extend(contents);
+            }
+            if self.reachable_outside(location) {
+                loaded.insert(Location::Unknown);
+            }
+        }
+        loaded
+    }
+
+    fn step(&mut self, instr: &Instruction, site: Option<usize>) {
+        let op = match instr.op.as_deref() {
+            Some(op) => op,
+            None => return,
+        };
+        let dest = instr.dest.as_deref().filter(|_| is_pointer(instr));
+        match op {
+            "alloc" => {
+                if let (Some(dest), Some(site)) = (dest, site) {
+                    self.add(dest, [Location::Site(site)]);
+                }
+            }
+            "id" | "ptradd" | "phi" => {
+                if let Some(dest) = dest {
+                    for arg in &instr.args {
+                        let locations = self.get(arg);
+                        self.add(dest, locations);
+                    }
+                }
+            }
+            // The variable `get` defines holds what some `set` of it wrote.
+            "set" => {
+                if let [shadow, value] = &instr.args[..] {
+                    let locations = self.get(value);
+                    self.add(shadow, locations);
+                }
+            }
+            "load" => {
+                if let Some(dest) = dest {
+                    let loaded = self.loaded(&self.get(&instr.args[0]));
+                    self.add(dest, loaded);
+                }
+            }
+            "store" => {
+                if let [pointer, value] = &instr.args[..] {
+                    let stored = self.get(value);
+                    for location in self.get(pointer) {
+                        self.store(location, &stored);
+                        if self.reachable_outside(location) {
+                            self.escape(&stored);
+                        }
+                    }
+                }
+            }
+            _ => {
+                if !CONTAINED_OPS.contains(&op) && !PURE_OPS.contains(&op) {
+                    for arg in &instr.args {
+                        let locations = self.get(arg);
+                        self.escape(&locations);
+                    }
+                    if let Some(dest) = dest {
+                        self.add(dest, [Location::Unknown]);
+                    }
+                }
+            }
+        }
+    }
+
+    // The pointers in escaped memory may escape in turn.
+    fn escape_contents(&mut self) {
+        for site in self.escaped.clone() {
+            if let Some(contents) = self.contents.get(&Location::Site(site)).cloned() {
+                self.escape(&contents);
+            }
+        }
+    }
+}
+
+impl AliasAnalysis {
+    pub fn new(args: &[NamedArg], cfg: &ControlFlowGraph) -> Self {
+        let mut sites = Vec::new();
+        for (b, block) in cfg.iter() {
+            for (i, instr) in block.instrs.iter().enumerate() {
+                if instr.op.as_deref() == Some("alloc") {
+                    sites.push((b, i));
+                }
+            }
+        }
+        let mut state = State::default();
+        for arg in args {
+            if matches!(arg.type_, Type::Ptr { .. }) {
+                state.add(&arg.name, [Location::Unknown]);
+            }
+        }
+        state.changed = true;
+        while state.changed {
+            state.changed = false;
+            let mut site = 0;
+            for (_, block) in cfg.iter() {
+                for instr in &block.instrs {
+                    let this_site = (instr.op.as_deref() == Some("alloc")).then_some(site);
+                    state.step(instr, this_site);
+                    site += this_site.is_some() as usize;
+                }
+            }
+            state.escape_contents();
+        }
+        // A pointer nothing is known of, like one loaded from memory no
+        // pointer was stored into, may point anywhere.
+        let unknown = BTreeSet::from([Location::Unknown]);
+        let mut points_to = state.points_to;
+        for locations in points_to.values_mut() {
+            if locations.is_empty() {
+                *locations = unknown.clone();
+            }
+        }
+        AliasAnalysis {
+            sites,
+            points_to,
+            escaped: state.escaped,
+            unknown,
+        }
+    }
+
+    // The locations `var` may point into; anywhere for a variable that is
+    // not given a pointer in the function.
+    pub fn points_to(&self, var: &str) -> &BTreeSet<Location> {
+        self.points_to.get(var).unwrap_or(&self.unknown)
+    }
+
+    // The variables the function gives pointers to, in order.
+    pub fn pointers(&self) -> BTreeSet<&str> {
+        self.points_to.keys().map(String::as_str).collect()
+    }
+
+    // Whether code outside the function may reach the memory of site `site`.
+    pub fn escapes(&self, site: usize) -> bool {
+        self.escaped.contains(&site)
+    }
+
+    // Whether the pointers in `a` and `b` may point into the same memory.
+    pub fn may_alias(&self, a: &str, b: &str) -> bool {
+        let (a, b) = (self.points_to(a), self.points_to(b));
+        // Unknown memory is any that other functions can reach, which
+        // includes the sites that escape.
+        let meets_unknown = |a: &BTreeSet<Location>, b: &BTreeSet<Location>| {
+            a.contains(&Location::Unknown)
+                && b.iter().any(|&location| match location {
+                    Location::Site(site) => self.escapes(site),
+                    Location::Unknown => true,
+                })
+        };
+        a.iter()
+            .any(|location| matches!(location, Location::Site(_)) && b.contains(location))
+            || meets_unknown(a, b)
+            || meets_unknown(b, a)
+    }
+}
@@ -0,0 +1,198 @@
+// The call graph of a program: which functions call which, and from where.
+//
+// There is a node for each function, by its index in the program, and an
+// edge for each `call` instruction, so a function calling another twice has
+// two edges to it. A call to a function the program does not define, as in a
+// library linked with others, is a call site with no callee. Functions that
+// can call each other, directly or through others, make up a strongly
+// connected component; a function is recursive when its component has other
+// functions in it or it calls itself.
+use bril_ir::Function;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug)]
+pub struct CallSite {
+    pub caller: usize,
+    // The index of the `call` in the instructions of the caller.
+    pub instr: usize,
+    // The name called, and the function it names, if the program has it.
+    pub name: String,
+    pub callee: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct CallGraph {
+    // The name of each function.
+    pub names: Vec<String>,
+    pub sites: Vec<CallSite>,
+    // The call sites in each function and those calling it, as indices into
+    // `sites`, in the order of the instructions.
+    pub calls: Vec<Vec<usize>>,
+    pub callers: Vec<Vec<usize>>,
+    // The strongly connected components, each in the order of the program.
+    // A component comes after every other component its functions call, so
+    // going through them in order visits callees before their callers.
+    pub components: Vec<Vec<usize>>,
+    // The component each function is in, as an index into `components`.
+    component: Vec<usize>,
+    index: HashMap<String, usize>,
+}
+
+impl CallGraph {
+    pub fn new(functions: &[Function]) -> Self {
+        let mut index = HashMap::new();
+        for (f, function) in functions.iter().enumerate() {
+            index.entry(function.name.clone()).or_insert(f);
+        }
+        let mut sites = Vec::new();
+        let mut calls = vec![Vec::new(); functions.len()];
+        let mut callers = vec![Vec::new(); functions.len()];
+        for (f, function) in functions.iter().enumerate() {
+            for (i, instr) in function.instrs.iter().enumerate() {
+                if instr.op.as_deref() != Some("call") {
+                    continue;
+                }
+                for name in &instr.funcs {
+                    let callee = index.get(name).copied();
+                    calls[f].push(sites.len());
+                    if let Some(callee) = callee {
+                        callers[callee].push(sites.len());
+                    }
+                    sites.push(CallSite {
+                        caller: f,
+                        instr: i,
+                        name: name.clone(),
+                        callee,
+                    });
+                }
+            }
+        }
+        let mut graph = CallGraph {
+            names: functions.iter().map(|f| f.name.clone()).collect(),
+            sites,
+            calls,
+            callers,
+            components: Vec::new(),
+            component: Vec::new(),
+            index,
+        };
+        graph.components = graph.strongly_connected_components();
+        graph.component = vec![0; functions.len()];
+        for (c, component) in graph.components.iter().enumerate() {
+            for &f in component {
+                graph.component[f] = c;
+            }
+        }
+        graph
+    }
+
+    // The function with this name.
+    pub fn function(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    // The functions of the program `f` calls, each once.
+    pub fn callees(&self, f: usize) -> BTreeSet<usize> {
+        self.calls[f]
+            .iter()
+            .filter_map(|&s| self.sites[s].callee)
+            .collect()
+    }
+
+    // The functions calling `f`, each once.
+    pub fn callers_of(&self, f: usize) -> BTreeSet<usize> {
+        self.callers[f]
+            .iter()
+            .map(|&s| self.sites[s].caller)
+            .collect()
+    }
+
+    pub fn component_of(&self, f: usize) -> usize {
+        self.component[f]
+    }
+
+    // Whether a call from `f` can lead back into `f`.
+    pub fn is_recursive(&self, f: usize) -> bool {
+        self.components[self.component[f]].len() > 1 || self.callees(f).contains(&f)
+    }
+
+    // The functions some chain of calls from `root` reaches, `root` included.
+    pub fn reachable_from(&self, root: usize) -> BTreeSet<usize> {
+        let mut reached = BTreeSet::from([root]);
+        let mut stack = vec![root];
+        while let Some(f) = stack.pop() {
+            for callee in self.callees(f) {
+                if reached.insert(callee) {
+                    stack.push(callee);
+                }
+            }
+        }
+        reached
+    }
+
+    // Tarjan's algorithm, which finishes each component only after those it
+    // reaches, with the depth-first search kept on a stack of its own so deep
+    // chains of calls do not overflow the real one.
+    fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let n = self.names.len();
+        let successors: Vec<Vec<usize>> = (0..n)
+            .map(|f| self.callees(f).into_iter().collect())
+            .collect();
+        let mut order: Vec<Option<usize>> = vec![None; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut components = Vec::new();
+        let mut visited = 0;
+        for root in 0..n {
+            if order[root].is_some() {
+                continue;
+            }
+            // Each function being searched, with how many of its successors
+            // it has gone through.
+            let mut search = vec![(root, 0)];
+            order[root] = Some(visited);
+            low[root] = visited;
+            visited += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            while let Some((f, next)) = search.last_mut() {
+                let f = *f;
+                if let Some(&g) = successors[f].get(*next) {
+                    *next += 1;
+                    match order[g] {
+                        None => {
+                            order[g] = Some(visited);
+                            low[g] = visited;
+                            visited += 1;
+                            stack.push(g);
+                            on_stack[g] = true;
+                            search.push((g, 0));
+                        }
+                        Some(seen) if on_stack[g] => low[f] = low[f].min(seen),
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+                search.pop();
+                if let Some(&(parent, _)) = search.last() {
+                    low[parent] = low[parent].min(low[f]);
+                }
+                if Some(low[f]) == order[f] {
+                    let mut component = Vec::new();
+                    loop {
+                        let g = stack.pop().unwrap();
+                        on_stack[g] = false;
+                        component.push(g);
+                        if g == f {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+}
@@ -0,0 +1,111 @@
+// A worklist solver for dataflow problems over the CFG.
+//
+// An analysis says which way facts flow, how facts from several edges are
+// combined, and how a block changes the fact going through it. The solver
+// starts every block from `initial` and applies the transfer functions until
+// nothing changes, which terminates as long as merging only ever moves facts
+// one way in a lattice of finite height.
+use crate::{Block, ControlFlowGraph};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+pub trait Analysis {
+    type Fact: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+
+    // What holds where control enters the function, or for a backward
+    // analysis where it leaves.
+    fn boundary(&self) -> Self::Fact;
+
+    // What holds before anything is known, which merging with any other
+    // fact gives that fact back.
+    fn initial(&self) -> Self::Fact;
+
+    // Combines `other` into the fact flowing into a block along another edge.
+    fn merge(&self, into: &mut Self::Fact, other: &Self::Fact);
+
+    // The fact on the far side of `block` given the one flowing into it:
+    // after it for a forward analysis, before it for a backward one.
+    fn transfer(&self, block: &Block, fact: &Self::Fact) -> Self::Fact;
+}
+
+// The facts before and after each block, in program order whichever way the
+// analysis runs.
+#[derive(Debug)]
+pub struct Solution<F> {
+    pub before: Vec<F>,
+    pub after: Vec<F>,
+}
+
+pub fn solve<A: Analysis>(cfg: &ControlFlowGraph, analysis: &A) -> Solution<A::Fact> {
+    let n = cfg.len();
+    let forward = analysis.direction() == Direction::Forward;
+    let mut before = vec![analysis.initial(); n];
+    let mut after = vec![analysis.initial(); n];
+
+    // Visiting blocks in the order facts flow through them settles most
+    // graphs in one pass; unreachable blocks come last.
+    let mut order = cfg.reverse_postorder();
+    let mut reached = vec![false; n];
+    for &b in &order {
+        reached[b] = true;
+    }
+    order.extend((0..n).filter(|&b| !reached[b]));
+    if !forward {
+        order.reverse();
+    }
+    let mut queued = vec![true; n];
+    let mut worklist: VecDeque<usize> = order.into_iter().collect();
+
+    while let Some(b) = worklist.pop_front() {
+        queued[b] = false;
+        let block = &cfg.blocks[b];
+        let (incoming, outgoing) = if forward {
+            (&block.prev_blocks, &block.next_blocks)
+        } else {
+            (&block.next_blocks, &block.prev_blocks)
+        };
+        let at_boundary = if forward {
+            cfg.entry() == Some(b)
+        } else {
+            block.next_blocks.is_empty()
+        };
+
+        let mut fact = if at_boundary {
+            analysis.boundary()
+        } else {
+            analysis.initial()
+        };
+        for &other in incoming {
+            let other = if forward {
+                &after[other]
+            } else {
+                &before[other]
+            };
+            analysis.merge(&mut fact, other);
+        }
+        let result = analysis.transfer(block, &fact);
+        let (input, output) = if forward {
+            (&mut before[b], &mut after[b])
+        } else {
+            (&mut after[b], &mut before[b])
+        };
+        *input = fact;
+        if *output != result {
+            *output = result;
+            for &next in outgoing {
+                if !queued[next] {
+                    queued[next] = true;
+                    worklist.push_back(next);
+                }
+            }
+        }
+    }
+    Solution { before, after }
+}
@@ -0,0 +1,203 @@
+// The dominator tree of a CFG, computed with the iterative algorithm of
+// Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm".
+//
+// A block dominates another when every path from the entry to the other goes
+// through it. Blocks that cannot be reached from the entry are left out of
+// the tree: they have no immediate dominator and dominate only themselves.
+//
+// Post-dominators are the dominators of the reverse CFG, whose entry is its
+// synthetic exit node.
+use crate::reverse::ReverseCfg;
+use crate::{reverse_postorder, ControlFlowGraph};
+
+#[derive(Debug)]
+pub struct DominatorTree {
+    root: Option<usize>,
+    idom: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    // When each block is entered and left in a walk of the tree, so that
+    // dominance is a check that one interval contains the other.
+    enter: Vec<usize>,
+    leave: Vec<usize>,
+}
+
+impl DominatorTree {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        Self::of_graph(
+            cfg.len(),
+            cfg.entry(),
+            |b| &cfg.blocks[b].next_blocks,
+            |b| &cfg.blocks[b].prev_blocks,
+        )
+    }
+
+    // The post-dominator tree of `cfg`, with the blocks numbered as in `cfg`
+    // and the synthetic exit, `cfg.len()`, at the root. Blocks that cannot
+    // reach a `ret` or the end of the function are left out.
+    pub fn postdominators(cfg: &ControlFlowGraph) -> Self {
+        let reverse = ReverseCfg::new(cfg);
+        Self::of_graph(
+            reverse.num_nodes(),
+            Some(reverse.exit),
+            |b| &reverse.next_nodes[b],
+            |b| &reverse.prev_nodes[b],
+        )
+    }
+
+    // The dominator tree of any graph with nodes `0..n`, given the
+    // successors and predecessors of each node.
+    pub fn of_graph<'a>(
+        n: usize,
+        entry: Option<usize>,
+        next: impl Fn(usize) -> &'a [usize],
+        prev: impl Fn(usize) -> &'a [usize],
+    ) -> Self {
+        let rpo = reverse_postorder(n, entry, next);
+        let mut order = vec![None; n];
+        for (i, &b) in rpo.iter().enumerate() {
+            order[b] = Some(i);
+        }
+
+        // During the iteration the entry is its own immediate dominator, which
+        // stops the walks up the tree in `intersect`.
+        let mut idom: Vec<Option<usize>> = vec![None; n];
+        if let Some(&entry) = rpo.first() {
+            idom[entry] = Some(entry);
+        }
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while order[a] > order[b] {
+                    a = idom[a].unwrap();
+                }
+                while order[b] > order[a] {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in rpo.iter().skip(1) {
+                let new_idom = prev(b)
+                    .iter()
+                    .copied()
+                    .filter(|&p| idom[p].is_some())
+                    .reduce(|a, p| intersect(&idom, a, p));
+                if idom[b] != new_idom {
+                    idom[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        if let Some(&entry) = rpo.first() {
+            idom[entry] = None;
+        }
+
+        let mut children = vec![Vec::new(); n];
+        for (b, parent) in idom.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(b);
+            }
+        }
+
+        let mut tree = DominatorTree {
+            root: rpo.first().copied(),
+            idom,
+            children,
+            enter: vec![0; n],
+            leave: vec![0; n],
+        };
+        let mut clock = 0;
+        let mut stack: Vec<(usize, usize)> =
+            rpo.first().map(|&entry| (entry, 0)).into_iter().collect();
+        while let Some((b, i)) = stack.pop() {
+            if i == 0 {
+                tree.enter[b] = clock;
+                clock += 1;
+            }
+            match tree.children[b].get(i) {
+                Some(&child) => {
+                    stack.push((b, i + 1));
+                    stack.push((child, 0));
+                }
+                None => {
+                    tree.leave[b] = clock;
+                    clock += 1;
+                }
+            }
+        }
+        // Unreachable blocks get an empty interval of their own.
+        for b in (0..n).filter(|&b| order[b].is_none()) {
+            tree.enter[b] = clock;
+            tree.leave[b] = clock;
+            clock += 1;
+        }
+        tree
+    }
+
+    // The closest block other than `b` that dominates it, or `None` for the
+    // entry and for unreachable blocks.
+    pub fn idom(&self, b: usize) -> Option<usize> {
+        self.idom[b]
+    }
+
+    // Whether every path from the entry to `b` goes through `a`. A block
+    // dominates itself.
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        a == b || (self.enter[a] < self.enter[b] && self.leave[b] < self.leave[a])
+    }
+
+    pub fn strictly_dominates(&self, a: usize, b: usize) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    // The blocks whose immediate dominator is `b`, in program order.
+    pub fn children(&self, b: usize) -> &[usize] {
+        &self.children[b]
+    }
+
+    // The dominance frontier of each block of `cfg`: the blocks where its
+    // dominance ends, which are reached from a block it dominates but are
+    // not strictly dominated by it themselves.
+    pub fn frontiers(&self, cfg: &ControlFlowGraph) -> Vec<Vec<usize>> {
+        let mut frontiers = vec![Vec::new(); cfg.len()];
+        for (b, block) in cfg.iter() {
+            if block.prev_blocks.len() < 2 {
+                continue;
+            }
+            for &p in &block.prev_blocks {
+                // Walk up from the predecessor to the immediate dominator of
+                // the join, skipping predecessors that are never reached.
+                let mut runner = Some(p).filter(|&p| self.is_reachable(p));
+                while let Some(r) = runner {
+                    if Some(r) == self.idom[b] {
+                        break;
+                    }
+                    if !frontiers[r].contains(&b) {
+                        frontiers[r].push(b);
+                    }
+                    runner = self.idom[r];
+                }
+            }
+        }
+        frontiers
+    }
+
+    // Whether `b` is in the tree, which it is unless no path from the entry
+    // leads to it.
+    pub fn is_reachable(&self, b: usize) -> bool {
+        self.idom[b].is_some() || self.root == Some(b)
+    }
+
+    // `b` and the blocks dominating it, from `b` up to the entry.
+    pub fn dominators(&self, b: usize) -> Vec<usize> {
+        let mut result = vec![b];
+        let mut cur = b;
+        while let Some(parent) = self.idom[cur] {
+            result.push(parent);
+            cur = parent;
+        }
+        result
+    }
+}
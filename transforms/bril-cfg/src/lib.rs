@@ -0,0 +1,320 @@
+// Control flow graphs of Bril functions, shared by the Rust passes.
+//
+// A function is split into basic blocks at labels and terminators. Each
+// block records the blocks control can go to next and the blocks it can
+// come from, with an edge listed once for every label that leads to it, so a
+// `br` to the same label twice shows up twice on both ends.
+//
+// A `speculate` or a `guard` ends its block too. The block of a `guard`
+// goes on to the next one, and to the guard's label when it aborts. An abort
+// also puts the variables back as they were at the `speculate` it rolls
+// back, so the block of each `speculate` has an edge to the label of every
+// `guard` it can reach, as if control went there straight from it; analyses
+// then see the variables reach the label both as the `speculate` left them
+// and, like the memory, which is not rolled back, as the `guard` did.
+pub mod alias;
+pub mod callgraph;
+pub mod dataflow;
+pub mod dominators;
+pub mod liveness;
+pub mod loops;
+pub mod reverse;
+pub mod split;
+
+use bril_ir::error::BrilError;
+use bril_ir::intern::Interner;
+use bril_ir::{Function, Instruction};
+use std::collections::HashSet;
+
+#[derive(Debug, Default)]
+pub struct Block {
+    pub instrs: Vec<Instruction>,
+    pub next_blocks: Vec<usize>,
+    pub prev_blocks: Vec<usize>,
+}
+
+impl Block {
+    pub fn label(&self) -> Option<&str> {
+        self.instrs.first().and_then(|instr| instr.label.as_deref())
+    }
+}
+
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<Block>,
+}
+
+impl ControlFlowGraph {
+    // The CFG of a function with these instructions, as passes rewriting
+    // the blocks of one build it again.
+    pub fn from_instrs(instrs: &[Instruction]) -> Self {
+        Self::from_owned_instrs(instrs.to_vec())
+    }
+
+    // The same, moving the instructions into the blocks instead of copying
+    // them.
+    pub fn from_owned_instrs(instrs: Vec<Instruction>) -> Self {
+        build(instrs).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // A copy of the instructions of the blocks, in order, for code that goes
+    // on using the CFG. A pass writing them back into its function is done
+    // with it and should use `into_instrs`.
+    pub fn to_instrs(&self) -> Vec<Instruction> {
+        let mut result = Vec::<Instruction>::new();
+        for block in &self.blocks {
+            result.extend(block.instrs.iter().cloned());
+        }
+        result
+    }
+
+    // The instructions of the blocks, in order, moved out of them.
+    pub fn into_instrs(self) -> Vec<Instruction> {
+        let mut result = Vec::with_capacity(self.blocks.iter().map(|b| b.instrs.len()).sum());
+        for block in self.blocks {
+            result.extend(block.instrs);
+        }
+        result
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    // The block a call starts in, which is the first one; `None` for a
+    // function without instructions.
+    pub fn entry(&self) -> Option<usize> {
+        if self.blocks.is_empty() {
+            None
+        } else {
+            Some(0)
+        }
+    }
+
+    // The blocks a call can return from: those without successors.
+    pub fn exits(&self) -> Vec<usize> {
+        self.iter()
+            .filter(|(_, block)| block.next_blocks.is_empty())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // The label a `phi` names for control coming from block `b`: the last
+    // label control went past, which for the part of a block a `guard` or
+    // `speculate` split off is that of the block it falls through from, and
+    // so on back along the split. `None` when there is none, as for an
+    // unlabeled entry or a block nothing falls into.
+    pub fn phi_label(&self, mut b: usize) -> Option<&str> {
+        loop {
+            if let Some(label) = self.blocks[b].label() {
+                return Some(label);
+            }
+            if b == 0 || !self.blocks[b - 1].next_blocks.contains(&b) {
+                return None;
+            }
+            b -= 1;
+        }
+    }
+
+    // Every block with its index, in program order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Block)> {
+        self.blocks.iter().enumerate()
+    }
+
+    // The blocks reachable from the entry, each before its successors except
+    // along back edges.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        reverse_postorder(self.len(), self.entry(), |b| &self.blocks[b].next_blocks)
+    }
+}
+
+// Reverse postorder of the nodes of a graph reachable from `entry`, given the
+// successors of each node.
+pub(crate) fn reverse_postorder<'a>(
+    num_nodes: usize,
+    entry: Option<usize>,
+    next: impl Fn(usize) -> &'a [usize],
+) -> Vec<usize> {
+    let mut visited = vec![false; num_nodes];
+    let mut postorder = Vec::new();
+    // Each entry is a node and how many of its successors were visited.
+    let mut stack: Vec<(usize, usize)> = entry.map(|entry| (entry, 0)).into_iter().collect();
+    if let Some(entry) = entry {
+        visited[entry] = true;
+    }
+    while let Some((b, i)) = stack.pop() {
+        match next(b).get(i) {
+            Some(&succ) => {
+                stack.push((b, i + 1));
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            }
+            None => postorder.push(b),
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+pub fn construct_control_flow_graph(function: &Function) -> ControlFlowGraph {
+    ControlFlowGraph::from_instrs(&function.instrs)
+}
+
+// The CFG of `function` for a pass that rewrites it, holding the function's
+// own instructions rather than copies: the function is left without any
+// until the pass puts them back with `function.instrs = cfg.into_instrs()`.
+// Passes that still read `function.instrs` meanwhile, or that may finish
+// without putting them back, build their CFG with
+// `construct_control_flow_graph` instead.
+pub fn take_control_flow_graph(function: &mut Function) -> ControlFlowGraph {
+    ControlFlowGraph::from_owned_instrs(std::mem::take(&mut function.instrs))
+}
+
+// The CFG of a function read from the user, or what keeps it from having
+// one: an instruction that is neither an operation nor a label, a jump or
+// branch without the labels and arguments it takes, or a label that is
+// jumped to but not defined, or defined twice. Passes can then build the CFGs
+// of the functions they rewrite without checking again.
+pub fn try_construct_control_flow_graph(
+    function: &Function,
+) -> Result<ControlFlowGraph, BrilError> {
+    let mut labels = HashSet::new();
+    for instr in &function.instrs {
+        let (labels_taken, args_taken, message) = match instr.op.as_deref() {
+            Some("jmp") => (1, 0, "`jmp` takes one label"),
+            Some("br") => (2, 1, "`br` takes one argument and two labels"),
+            Some("guard") => (1, 1, "`guard` takes one argument and one label"),
+            Some(_) => continue,
+            None => match &instr.label {
+                Some(label) if !labels.insert(label.as_str()) => {
+                    let message = format!("label .{} is defined twice", label);
+                    return Err(BrilError::malformed(instr, message).in_function(function));
+                }
+                Some(_) => continue,
+                None => {
+                    let message = "an instruction needs an `op` or a `label`";
+                    return Err(BrilError::malformed(instr, message).in_function(function));
+                }
+            },
+        };
+        if instr.labels.len() != labels_taken || instr.args.len() != args_taken {
+            return Err(BrilError::malformed(instr, message).in_function(function));
+        }
+    }
+    build(function.instrs.clone()).map_err(|e| e.in_function(function))
+}
+
+fn build(instrs: Vec<Instruction>) -> Result<ControlFlowGraph, BrilError> {
+    let mut cfg = ControlFlowGraph { blocks: Vec::new() };
+
+    let mut cur_block = Block::default();
+    let mut flush_block = |block: &mut Block| {
+        if !block.instrs.is_empty() {
+            cfg.blocks.push(std::mem::take(block));
+        }
+    };
+
+    for instr in instrs {
+        // Label is always starting a new block.
+        if instr.is_label() {
+            flush_block(&mut cur_block);
+        }
+
+        // Terminator always ends the block, and so do the instructions
+        // speculation may leave from.
+        let ends_block = instr.ends_block();
+        cur_block.instrs.push(instr);
+        if ends_block {
+            flush_block(&mut cur_block);
+        }
+    }
+    flush_block(&mut cur_block);
+
+    // Populate mapping from labels to block indices, indexed by the symbol
+    // of each label.
+    let mut labels = Interner::new();
+    let mut label_to_block_index: Vec<usize> = Vec::new();
+    for (i, block) in cfg.blocks.iter().enumerate() {
+        if let Some(label) = block.label() {
+            let symbol = labels.intern(label);
+            if symbol.index() == label_to_block_index.len() {
+                label_to_block_index.push(i);
+            } else {
+                label_to_block_index[symbol.index()] = i;
+            }
+        }
+    }
+
+    // Populate next block pointers: the block after it for a block that
+    // falls through, first, and then the labels it jumps to.
+    let num_blocks = cfg.blocks.len();
+    for i in 0..num_blocks {
+        let block = &mut cfg.blocks[i];
+        if let Some(instr) = block.instrs.last() {
+            if !instr.is_terminator() && i + 1 < num_blocks {
+                block.next_blocks.push(i + 1);
+            }
+            for label in instr.jump_targets() {
+                match labels.get(label) {
+                    Some(symbol) => block.next_blocks.push(label_to_block_index[symbol.index()]),
+                    None => {
+                        let message = format!("no label .{} to jump to", label);
+                        return Err(BrilError::malformed(instr, message));
+                    }
+                }
+            }
+        }
+    }
+    add_rollback_edges(&mut cfg);
+
+    // And the previous block pointers from them.
+    for i in 0..num_blocks {
+        for j in 0..cfg.blocks[i].next_blocks.len() {
+            let next = cfg.blocks[i].next_blocks[j];
+            cfg.blocks[next].prev_blocks.push(i);
+        }
+    }
+
+    Ok(cfg)
+}
+
+// Adds an edge from the block of each `speculate` to the label of every
+// `guard` reachable from it. Only the innermost speculation open at the
+// `guard` is rolled back, but which one that is depends on the path, and
+// the `speculate` of any path reaches the `guard`.
+fn add_rollback_edges(cfg: &mut ControlFlowGraph) {
+    let mut rollbacks = Vec::new();
+    for (s, block) in cfg.iter() {
+        if block.instrs.last().and_then(|i| i.op.as_deref()) != Some("speculate") {
+            continue;
+        }
+        let mut visited = vec![false; cfg.len()];
+        let mut stack = block.next_blocks.clone();
+        while let Some(b) = stack.pop() {
+            if std::mem::replace(&mut visited[b], true) {
+                continue;
+            }
+            let next = &cfg.blocks[b].next_blocks;
+            let guard = cfg.blocks[b]
+                .instrs
+                .last()
+                .filter(|instr| instr.op.as_deref() == Some("guard") && !instr.labels.is_empty());
+            if guard.is_some() {
+                // The label is last, after the block falling through.
+                rollbacks.push((s, *next.last().unwrap()));
+            }
+            stack.extend(next);
+        }
+    }
+    rollbacks.sort_unstable();
+    rollbacks.dedup();
+    for (s, target) in rollbacks {
+        cfg.blocks[s].next_blocks.push(target);
+    }
+}
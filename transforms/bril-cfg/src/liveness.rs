@@ -0,0 +1,132 @@
+// Live variables: a variable is live at a point when some path from there
+// reads it before assigning it again.
+//
+// This is a backward dataflow problem, solved per block with
+// in = use ∪ (out − def) and out = ∪ in(successors). The arguments of a
+// `phi` are read as control leaves the predecessor they are paired with, so
+// they are live out of that predecessor only, not into the `phi`'s block.
+// Likewise the first argument of `set` names a shadow variable and is not
+// read at all.
+use crate::ControlFlowGraph;
+use bit_set::BitSet;
+use bril_ir::intern::{Interner, Symbol};
+use bril_ir::Instruction;
+use std::collections::BTreeSet;
+
+#[derive(Debug)]
+pub struct Liveness {
+    pub live_in: Vec<BTreeSet<String>>,
+    pub live_out: Vec<BTreeSet<String>>,
+}
+
+// Moves liveness from after `instr` to before it.
+fn step_back(live: &mut BTreeSet<String>, instr: &Instruction) {
+    if let Some(dest) = &instr.dest {
+        live.remove(dest);
+    }
+    live.extend(instr.local_args().iter().cloned());
+}
+
+// The variables the `phi` nodes of `block` read when control comes from the
+// a block whose `phi_label` is `from`.
+fn phi_uses<'a>(
+    cfg: &'a ControlFlowGraph,
+    block: usize,
+    from: Option<&'a str>,
+) -> impl Iterator<Item = String> + 'a {
+    cfg.blocks[block]
+        .instrs
+        .iter()
+        .filter(|instr| instr.is_phi())
+        .filter_map(move |phi| phi.phi_source(from?))
+        .map(String::from)
+}
+
+impl Liveness {
+    // The fixpoint runs on sets of variables as bits, one per symbol of an
+    // interner, and with each block summed up as the variables it reads
+    // before assigning them (`uses`) and those it assigns (`defs`), so that
+    // an iteration is a few bitwise operations per block. The sets of names
+    // are only built once it settles.
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let n = cfg.len();
+        let mut vars = Interner::new();
+        let mut uses = vec![BitSet::new(); n];
+        let mut defs = vec![BitSet::new(); n];
+        for (b, block) in cfg.iter() {
+            for instr in block.instrs.iter().rev() {
+                if let Some(dest) = &instr.dest {
+                    let dest = vars.intern(dest).index();
+                    uses[b].remove(dest);
+                    defs[b].insert(dest);
+                }
+                for arg in instr.local_args() {
+                    uses[b].insert(vars.intern(arg).index());
+                }
+            }
+        }
+        // What the `phi` nodes of its successors read as control leaves
+        // each block.
+        let phi_out: Vec<BitSet> = cfg
+            .iter()
+            .map(|(b, block)| {
+                let mut read = BitSet::new();
+                for &next in &block.next_blocks {
+                    for var in phi_uses(cfg, next, cfg.phi_label(b)) {
+                        read.insert(vars.intern(&var).index());
+                    }
+                }
+                read
+            })
+            .collect();
+
+        let mut live_in = vec![BitSet::new(); n];
+        let mut live_out = vec![BitSet::new(); n];
+        // Visiting blocks in reverse of the order control reaches them lets
+        // liveness flow backward through most of the graph in one pass.
+        let mut order = cfg.reverse_postorder();
+        order.reverse();
+        let reachable: BTreeSet<usize> = order.iter().copied().collect();
+        order.extend((0..n).filter(|b| !reachable.contains(b)));
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in &order {
+                let mut live = phi_out[b].clone();
+                for &next in &cfg.blocks[b].next_blocks {
+                    live.union_with(&live_in[next]);
+                }
+                live_out[b] = live.clone();
+                live.difference_with(&defs[b]);
+                live.union_with(&uses[b]);
+                if live != live_in[b] {
+                    live_in[b] = live;
+                    changed = true;
+                }
+            }
+        }
+
+        let names = |live: &BitSet| -> BTreeSet<String> {
+            live.iter()
+                .map(|var| vars.resolve(Symbol::from_index(var)).to_string())
+                .collect()
+        };
+        Liveness {
+            live_in: live_in.iter().map(names).collect(),
+            live_out: live_out.iter().map(names).collect(),
+        }
+    }
+
+    // The variables live right after each instruction of block `b`.
+    pub fn live_after(&self, cfg: &ControlFlowGraph, b: usize) -> Vec<BTreeSet<String>> {
+        let instrs = &cfg.blocks[b].instrs;
+        let mut result = vec![BTreeSet::new(); instrs.len()];
+        let mut live = self.live_out[b].clone();
+        for (i, instr) in instrs.iter().enumerate().rev() {
+            result[i] = live.clone();
+            step_back(&mut live, instr);
+        }
+        result
+    }
+}
@@ -0,0 +1,181 @@
+// The natural loops of a CFG and how they nest.
+//
+// An edge is a back edge when its target dominates its source. The natural
+// loop of a back edge is its target, the header, and every block that can
+// reach the source without going through the header; back edges sharing a
+// header make up one loop. Two natural loops are either disjoint or one
+// contains the other, so they form a forest. Retreating edges into the
+// middle of a cycle, as in irreducible control flow, are not back edges and
+// make no loop.
+use crate::dominators::DominatorTree;
+use crate::ControlFlowGraph;
+use std::collections::BTreeSet;
+
+#[derive(Debug)]
+pub struct Loop {
+    pub header: usize,
+    // The blocks of the loop, the header and those of nested loops included.
+    pub body: BTreeSet<usize>,
+    // The blocks in the loop branching back to the header.
+    pub latches: Vec<usize>,
+    // The blocks outside the loop that blocks in it branch to.
+    pub exits: Vec<usize>,
+    // The one block outside the loop leading to the header, if there is one
+    // and the header is all it leads to, so code can go there to run once
+    // before the loop.
+    pub preheader: Option<usize>,
+    // The innermost loop containing this one, and the loops this one
+    // immediately contains, as indices into `LoopForest::loops`.
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+impl Loop {
+    pub fn contains(&self, b: usize) -> bool {
+        self.body.contains(&b)
+    }
+
+    // How many loops this one is nested in.
+    pub fn depth(&self, forest: &LoopForest) -> usize {
+        let mut depth = 0;
+        let mut cur = self.parent;
+        while let Some(parent) = cur {
+            depth += 1;
+            cur = forest.loops[parent].parent;
+        }
+        depth
+    }
+}
+
+#[derive(Debug)]
+pub struct LoopForest {
+    // Outer loops come before the loops they contain, and loops with the
+    // same parent are in the order of their headers.
+    pub loops: Vec<Loop>,
+    // The innermost loop each block is in.
+    innermost: Vec<Option<usize>>,
+}
+
+impl LoopForest {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        Self::with_dominators(cfg, &DominatorTree::new(cfg))
+    }
+
+    pub fn with_dominators(cfg: &ControlFlowGraph, tree: &DominatorTree) -> Self {
+        let mut loops: Vec<Loop> = Vec::new();
+        for (header, block) in cfg.iter() {
+            let latches: Vec<usize> = block
+                .prev_blocks
+                .iter()
+                .copied()
+                .filter(|&p| tree.is_reachable(p) && tree.dominates(header, p))
+                .collect::<BTreeSet<usize>>()
+                .into_iter()
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+            let mut body = BTreeSet::from([header]);
+            let mut stack = latches.clone();
+            while let Some(b) = stack.pop() {
+                if body.insert(b) {
+                    let prev_blocks = cfg.blocks[b].prev_blocks.iter();
+                    stack.extend(prev_blocks.filter(|&&p| tree.is_reachable(p)));
+                }
+            }
+            let exits: BTreeSet<usize> = body
+                .iter()
+                .flat_map(|&b| cfg.blocks[b].next_blocks.iter().copied())
+                .filter(|next| !body.contains(next))
+                .collect();
+            let outside: BTreeSet<usize> = block
+                .prev_blocks
+                .iter()
+                .copied()
+                .filter(|p| !body.contains(p))
+                .collect();
+            let preheader = match outside.into_iter().collect::<Vec<_>>()[..] {
+                [p] if cfg.blocks[p].next_blocks.iter().all(|&next| next == header) => Some(p),
+                _ => None,
+            };
+            loops.push(Loop {
+                header,
+                body,
+                latches,
+                exits: exits.into_iter().collect(),
+                preheader,
+                parent: None,
+                children: Vec::new(),
+            });
+        }
+
+        // A loop contains exactly the loops whose bodies are subsets of its
+        // own, so with the larger ones first each loop's parent is the last
+        // loop before it containing its header.
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.body.len()), l.header));
+        for i in 0..loops.len() {
+            let header = loops[i].header;
+            loops[i].parent = (0..i).rev().find(|&j| loops[j].contains(header));
+        }
+        let mut order: Vec<usize> = Vec::new();
+        let mut stack: Vec<usize> = (0..loops.len())
+            .filter(|&i| loops[i].parent.is_none())
+            .collect();
+        stack.sort_by_key(|&i| std::cmp::Reverse(loops[i].header));
+        while let Some(i) = stack.pop() {
+            order.push(i);
+            let mut children: Vec<usize> = (0..loops.len())
+                .filter(|&j| loops[j].parent == Some(i))
+                .collect();
+            children.sort_by_key(|&j| std::cmp::Reverse(loops[j].header));
+            stack.extend(children);
+        }
+        // Renumber the loops in that order.
+        let mut index = vec![0; loops.len()];
+        for (new, &old) in order.iter().enumerate() {
+            index[old] = new;
+        }
+        let mut slots: Vec<Option<Loop>> = loops.into_iter().map(Some).collect();
+        let mut loops: Vec<Loop> = order
+            .iter()
+            .map(|&old| slots[old].take().unwrap())
+            .collect();
+        for l in loops.iter_mut() {
+            l.parent = l.parent.map(|p| index[p]);
+        }
+        for i in 0..loops.len() {
+            if let Some(p) = loops[i].parent {
+                loops[p].children.push(i);
+            }
+        }
+
+        let mut innermost = vec![None; cfg.len()];
+        for (i, l) in loops.iter().enumerate() {
+            for &b in &l.body {
+                innermost[b] = Some(i);
+            }
+        }
+        LoopForest { loops, innermost }
+    }
+
+    // The loops not nested in any other.
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.loops.len()).filter(move |&i| self.loops[i].parent.is_none())
+    }
+
+    // The innermost loop `b` is in, if any.
+    pub fn innermost(&self, b: usize) -> Option<usize> {
+        self.innermost[b]
+    }
+
+    // The loops from the innermost containing `b` out to the outermost.
+    pub fn enclosing(&self, b: usize) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut cur = self.innermost[b];
+        while let Some(i) = cur {
+            result.push(i);
+            cur = self.loops[i].parent;
+        }
+        result
+    }
+}
@@ -0,0 +1,41 @@
+// The CFG with its edges reversed, as backward analyses and post-dominators
+// see it.
+//
+// Nodes `0..cfg.len()` are the blocks of the CFG and node `cfg.len()` is a
+// synthetic exit, which becomes the entry of the reverse graph: it has an
+// edge to every block that returns or falls off the end of the function, so
+// a function with several exits still has a single root.
+use crate::ControlFlowGraph;
+
+#[derive(Debug)]
+pub struct ReverseCfg {
+    pub next_nodes: Vec<Vec<usize>>,
+    pub prev_nodes: Vec<Vec<usize>>,
+    pub exit: usize,
+}
+
+impl ReverseCfg {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let exit = cfg.len();
+        let mut next_nodes: Vec<Vec<usize>> =
+            cfg.blocks.iter().map(|b| b.prev_blocks.clone()).collect();
+        let mut prev_nodes: Vec<Vec<usize>> =
+            cfg.blocks.iter().map(|b| b.next_blocks.clone()).collect();
+        let exits = cfg.exits();
+        for &b in &exits {
+            prev_nodes[b].push(exit);
+        }
+        next_nodes.push(exits);
+        prev_nodes.push(Vec::new());
+        ReverseCfg {
+            next_nodes,
+            prev_nodes,
+            exit,
+        }
+    }
+
+    // The number of nodes, counting the synthetic exit.
+    pub fn num_nodes(&self) -> usize {
+        self.next_nodes.len()
+    }
+}
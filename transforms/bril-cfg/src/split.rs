@@ -0,0 +1,106 @@
+// Splitting edges of a CFG by putting a block of their own on them.
+//
+// An edge is critical when it leaves a block with several successors for a
+// block with several predecessors. Code for such an edge, like the copies
+// replacing a `phi` or a counter for how often it is taken, can go in
+// neither block without running on other edges too, so it needs a block in
+// between. The new block is only a label and a jump on to the target, and
+// goes right after the block the edge leaves; `phi`s in the target name it
+// in place of the block the edge left.
+use crate::ControlFlowGraph;
+use bril_ir::Instruction;
+use std::collections::BTreeSet;
+
+// The critical edges `(from, to)`, by the block they leave and then the
+// block they enter. An edge shows up once however many labels make it.
+pub fn critical_edges(cfg: &ControlFlowGraph) -> Vec<(usize, usize)> {
+    let distinct = |blocks: &[usize]| blocks.iter().copied().collect::<BTreeSet<usize>>();
+    let mut edges = Vec::new();
+    for (from, block) in cfg.iter() {
+        let next = distinct(&block.next_blocks);
+        if next.len() < 2 {
+            continue;
+        }
+        for to in next {
+            if distinct(&cfg.blocks[to].prev_blocks).len() > 1 {
+                edges.push((from, to));
+            }
+        }
+    }
+    edges
+}
+
+// Splits each of `edges` with a new block labeled `label(from, to)` given
+// the labels of the blocks it leaves and enters, and rebuilds the CFG. Gives
+// the index of the new block for each edge in the new CFG.
+pub fn split_edges(
+    cfg: &mut ControlFlowGraph,
+    edges: &[(usize, usize)],
+    mut label: impl FnMut(Option<&str>, &str) -> String,
+) -> Vec<usize> {
+    // The blocks going after each block, with the edge each one is for.
+    let mut after: Vec<Vec<(usize, [Instruction; 2])>> = vec![Vec::new(); cfg.len()];
+    for (e, &(from, to)) in edges.iter().enumerate() {
+        let target = cfg.blocks[to]
+            .label()
+            .expect("a block with several predecessors has a label")
+            .to_string();
+        let name = label(cfg.blocks[from].label(), &target);
+        // A block falling through into its successor falls into the new
+        // block instead, which is right after it.
+        if let Some(branch) = cfg.blocks[from]
+            .instrs
+            .last_mut()
+            .filter(|instr| instr.is_terminator())
+        {
+            for l in branch.labels.iter_mut().filter(|l| **l == target) {
+                *l = name.clone();
+            }
+        }
+        // The `phi`s of the target now come from the new block.
+        if let Some(source) = cfg.phi_label(from).map(String::from) {
+            for phi in cfg.blocks[to].instrs.iter_mut().filter(|i| i.is_phi()) {
+                for l in phi.labels.iter_mut().filter(|l| **l == source) {
+                    *l = name.clone();
+                }
+            }
+        }
+        let head = Instruction {
+            label: Some(name),
+            ..Default::default()
+        };
+        let jump = Instruction {
+            op: Some(String::from("jmp")),
+            labels: vec![target],
+            ..Default::default()
+        };
+        after[from].push((e, [head, jump]));
+    }
+
+    let mut instrs = Vec::new();
+    let mut blocks = vec![0; edges.len()];
+    let mut count = 0;
+    for (block, split) in cfg.blocks.iter().zip(after) {
+        // A block a pass emptied is gone once the CFG is built again.
+        if !block.instrs.is_empty() {
+            instrs.extend(block.instrs.iter().cloned());
+            count += 1;
+        }
+        for (e, new) in split {
+            instrs.extend(new);
+            blocks[e] = count;
+            count += 1;
+        }
+    }
+    *cfg = ControlFlowGraph::from_instrs(&instrs);
+    blocks
+}
+
+// Splits every critical edge, giving the new blocks.
+pub fn split_critical_edges(
+    cfg: &mut ControlFlowGraph,
+    label: impl FnMut(Option<&str>, &str) -> String,
+) -> Vec<usize> {
+    let edges = critical_edges(cfg);
+    split_edges(cfg, &edges, label)
+}
@@ -0,0 +1,286 @@
+// The command line the Rust transforms have in common, so each reads and
+// writes programs like an ordinary tool:
+//
+//     dce program.json -o out.json --pretty
+//     dce --input program.json --output out.json
+//     bril2json < program.bril | lvn --passes gvn,dce - | bril2txt
+//
+// A tool flattens `CommonArgs` into its own clap arguments, reads its input
+// with `read_to_string` or `read_program`, writes its output with `write` or
+// `write_program`, and ends with `exit_on_error`, which prints what went
+// wrong as `tool: message` and exits with the code `BrilError` gives it.
+//
+// A program using the import extension is read as it is, imports and all;
+// `resolve_imports` then checks them against the files they name, for the
+// tools that look at the program as a whole.
+//
+// Programs are parsed as they are read and serialized as they are written,
+// through buffers, so a large one is never held as text besides the
+// program itself.
+use bril_ir::error::BrilError;
+use bril_ir::import::Import;
+use bril_ir::Program;
+use clap::Args;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug, Clone)]
+pub struct CommonArgs {
+    /// The program to read, in JSON; stdin if it is `-` or not given
+    #[arg(value_name = "FILE")]
+    pub input: Option<PathBuf>,
+
+    /// The program to read, the same as giving it as FILE
+    #[arg(long = "input", value_name = "FILE", conflicts_with = "input")]
+    pub input_file: Option<PathBuf>,
+
+    /// Write the output to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Indent JSON output and sort its keys, to diff it
+    #[arg(long)]
+    pub pretty: bool,
+
+    /// Report what is being done on stderr
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl CommonArgs {
+    // The file the input is read from, or `None` for stdin.
+    pub fn input_path(&self) -> Option<&PathBuf> {
+        self.input
+            .as_ref()
+            .or(self.input_file.as_ref())
+            .filter(|path| path.as_os_str() != "-")
+    }
+
+    // The name of the input for errors.
+    fn input_name(&self) -> String {
+        self.input_path()
+            .map_or_else(|| String::from("stdin"), |path| path.display().to_string())
+    }
+
+    // The input, buffered.
+    pub fn reader(&self) -> Result<Box<dyn BufRead>, BrilError> {
+        match self.input_path() {
+            Some(path) => {
+                let file =
+                    File::open(path).map_err(|e| BrilError::io(path.display().to_string(), e))?;
+                Ok(Box::new(BufReader::new(file)))
+            }
+            None => Ok(Box::new(BufReader::new(std::io::stdin()))),
+        }
+    }
+
+    pub fn read_to_string(&self) -> Result<String, BrilError> {
+        let mut text = String::new();
+        self.reader()?
+            .read_to_string(&mut text)
+            .map_err(|e| BrilError::io(self.input_name(), e))?;
+        Ok(text)
+    }
+
+    pub fn read_program(&self) -> Result<Program, BrilError> {
+        serde_json::from_reader(self.reader()?).map_err(|e| {
+            if e.is_io() {
+                BrilError::io(self.input_name(), e.into())
+            } else {
+                BrilError::Parse(e.to_string())
+            }
+        })
+    }
+
+    // Checks the imports of `program`, as read from the input, and of the
+    // files they name in turn: each file is looked for next to the one
+    // importing from it, then in each of `libs`, and must define the
+    // functions taken from it, with no two functions of a program under
+    // the same name. Gives the functions of `program` that the files it
+    // imports from import back, which it cannot drop or change the
+    // arguments of.
+    pub fn resolve_imports(
+        &self,
+        program: &Program,
+        libs: &[PathBuf],
+    ) -> Result<BTreeSet<String>, BrilError> {
+        let (input, dir) = match self.input_path() {
+            Some(path) => (
+                path.canonicalize().ok(),
+                path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+            ),
+            None => (None, PathBuf::new()),
+        };
+        check_names(program, &self.input_name())?;
+        let mut exported = BTreeSet::new();
+        let mut programs: HashMap<PathBuf, Program> = HashMap::new();
+        let mut queue: VecDeque<(PathBuf, Import)> = program
+            .imports
+            .iter()
+            .map(|import| (dir.clone(), import.clone()))
+            .collect();
+        while let Some((dir, import)) = queue.pop_front() {
+            let path = locate(&dir, &import, libs)?;
+            if Some(&path) == input.as_ref() {
+                exported.extend(import.functions.into_iter().map(|f| f.name));
+                continue;
+            }
+            let name = path.display().to_string();
+            if !programs.contains_key(&path) {
+                let file = File::open(&path).map_err(|e| BrilError::io(&name, e))?;
+                let imported: Program = serde_json::from_reader(BufReader::new(file))
+                    .map_err(|e| BrilError::Parse(format!("{}: {}", name, e)))?;
+                check_names(&imported, &name)?;
+                let dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+                queue.extend(
+                    imported
+                        .imports
+                        .iter()
+                        .map(|import| (dir.clone(), import.clone())),
+                );
+                programs.insert(path.clone(), imported);
+            }
+            let defined = &programs[&path].functions;
+            for function in &import.functions {
+                if !defined.iter().any(|f| f.name == function.name) {
+                    return Err(BrilError::malformed_program(format!(
+                        "{} has no function @{}",
+                        name, function.name
+                    )));
+                }
+            }
+        }
+        Ok(exported)
+    }
+
+    // Writes the output with `write`, through a buffer that is flushed at
+    // the end.
+    fn write_with(
+        &self,
+        write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+    ) -> Result<(), BrilError> {
+        let (mut out, name): (Box<dyn Write>, String) = match &self.output {
+            Some(path) => {
+                let name = path.display().to_string();
+                let file = File::create(path).map_err(|e| BrilError::io(&name, e))?;
+                (Box::new(BufWriter::new(file)), name)
+            }
+            None => (
+                Box::new(BufWriter::new(std::io::stdout().lock())),
+                String::from("stdout"),
+            ),
+        };
+        write(&mut out)
+            .and_then(|_| out.flush())
+            .map_err(|e| BrilError::io(name, e))
+    }
+
+    // Writes `text` as it is.
+    pub fn write(&self, text: &str) -> Result<(), BrilError> {
+        self.write_with(|out| out.write_all(text.as_bytes()))
+    }
+
+    // Writes `value` as JSON on a line of its own, as `to_json` gives it.
+    pub fn write_json(&self, value: &impl Serialize) -> Result<(), BrilError> {
+        let pretty = self.pretty;
+        self.write_with(|out| {
+            if pretty {
+                let value = serde_json::to_value(value).expect("Failed to serialize the output");
+                serde_json::to_writer_pretty(&mut *out, &sorted(value))?;
+            } else {
+                serde_json::to_writer(&mut *out, value)?;
+            }
+            out.write_all(b"\n")
+        })
+    }
+
+    pub fn write_program(&self, program: &Program) -> Result<(), BrilError> {
+        self.write_json(program)
+    }
+
+    // Prints `message` on stderr with `--verbose`.
+    pub fn note(&self, tool: &str, message: impl Display) {
+        if self.verbose {
+            eprintln!("{}: {}", tool, message);
+        }
+    }
+}
+
+// Checks that no two functions of `program`, defined or imported, go by the
+// same name.
+fn check_names(program: &Program, name: &str) -> Result<(), BrilError> {
+    let mut names = BTreeSet::new();
+    let defined = program.functions.iter().map(|f| f.name.as_str());
+    let imported = program
+        .imports
+        .iter()
+        .flat_map(|import| import.functions.iter().map(|f| f.local_name()));
+    for function in defined.chain(imported) {
+        if !names.insert(function) {
+            return Err(BrilError::malformed_program(format!(
+                "{} has more than one function named @{}",
+                name, function
+            )));
+        }
+    }
+    Ok(())
+}
+
+// The file `import` names, next to the file in `dir` importing from it or
+// else in the first of `libs` that has it.
+fn locate(dir: &Path, import: &Import, libs: &[PathBuf]) -> Result<PathBuf, BrilError> {
+    std::iter::once(dir)
+        .chain(libs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(&import.path))
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok())
+        .ok_or_else(|| {
+            let message = format!("cannot find {} to import", import.path);
+            BrilError::malformed_program(message)
+        })
+}
+
+// `value` as JSON on a line of its own. When `pretty`, each value goes on a
+// line of its own too, indented by two spaces, and the keys of objects are
+// sorted, like the Python tools write JSON with
+// `json.dump(..., indent=2, sort_keys=True)`: the same program then always
+// comes out the same way, whatever order its fields were read in.
+pub fn to_json(value: &impl Serialize, pretty: bool) -> String {
+    let json = if pretty {
+        serde_json::to_value(value).and_then(|value| serde_json::to_string_pretty(&sorted(value)))
+    } else {
+        serde_json::to_string(value)
+    };
+    json.expect("Failed to serialize the output") + "\n"
+}
+
+// `value` with the keys of each object in order.
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Array(values) => Value::Array(values.into_iter().map(sorted).collect()),
+        Value::Object(fields) => {
+            let mut fields: Vec<(String, Value)> = fields.into_iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect(),
+            )
+        }
+        value => value,
+    }
+}
+
+// Exits with the error of a run of `tool`, if there is one.
+pub fn exit_on_error(tool: &str, result: Result<(), BrilError>) {
+    if let Err(e) = result {
+        eprintln!("{}: {}", tool, e);
+        std::process::exit(e.exit_code());
+    }
+}
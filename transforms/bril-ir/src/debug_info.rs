@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Side table from IR variables to the source variables whose values they
+// hold. A variable without an entry holds only its own value, so programs
+// straight out of a frontend need no table at all.
+//
+// Any pass that renames variables or makes one variable stand in for another
+// must update the table, or the debugger will lose track of the source names.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebugInfo {
+    #[serde(default)]
+    vars: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DebugInfo {
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    pub fn source_names(&self, var: &str) -> BTreeSet<String> {
+        match self.vars.get(var) {
+            Some(names) => names.clone(),
+            None => BTreeSet::from([var.to_string()]),
+        }
+    }
+
+    // Records that `to` holds what `from` held, for a variable given a new
+    // name.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let names = self.source_names(from);
+        self.vars.insert(to.to_string(), names);
+    }
+
+    // Records that `to` now also stands in for everything `from` held.
+    pub fn merge_into(&mut self, from: &str, to: &str) {
+        if from == to {
+            return;
+        }
+        let mut names = self.source_names(to);
+        names.extend(self.source_names(from));
+        self.vars.insert(to.to_string(), names);
+    }
+}
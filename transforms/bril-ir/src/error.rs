@@ -0,0 +1,105 @@
+// What can go wrong running a tool over a program, with enough context to
+// tell the user where: the function and the instruction, when there are
+// some. The tools print the error to stderr and exit with `exit_code`, which
+// tells the kinds apart for scripts driving them:
+//
+// - 2: the command line is wrong.
+// - 3: a file could not be read or written.
+// - 4: the input is not a Bril program in JSON.
+// - 5: the program is, but is malformed, like a jump to a label it lacks.
+// - 6: a configuration or pipeline file is wrong.
+use crate::{Function, Instruction};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BrilError {
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("cannot access {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("cannot parse the program: {0}")]
+    Parse(String),
+
+    #[error("{}{message}", context(.function, .instr))]
+    Malformed {
+        // Empty until the error is put `in_function`.
+        function: String,
+        instr: Option<String>,
+        message: String,
+    },
+
+    #[error("{path}: {message}")]
+    Config { path: String, message: String },
+}
+
+fn context(function: &str, instr: &Option<String>) -> String {
+    match (function, instr) {
+        ("", None) => String::new(),
+        ("", Some(instr)) => format!("at `{}`: ", instr),
+        (function, None) => format!("in @{}: ", function),
+        (function, Some(instr)) => format!("in @{}, at `{}`: ", function, instr),
+    }
+}
+
+impl BrilError {
+    pub fn usage(message: impl Into<String>) -> Self {
+        BrilError::Usage(message.into())
+    }
+
+    pub fn io(path: impl Into<String>, source: std::io::Error) -> Self {
+        BrilError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    // A problem with `instr` of a program.
+    pub fn malformed(instr: &Instruction, message: impl Into<String>) -> Self {
+        BrilError::Malformed {
+            function: String::new(),
+            instr: Some(instr.to_string()),
+            message: message.into(),
+        }
+    }
+
+    // A problem with the program as a whole, like an import of a function
+    // its file lacks.
+    pub fn malformed_program(message: impl Into<String>) -> Self {
+        BrilError::Malformed {
+            function: String::new(),
+            instr: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn config(path: impl Into<String>, message: impl Into<String>) -> Self {
+        BrilError::Config {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    // The same error, from `function`.
+    pub fn in_function(mut self, function: &Function) -> Self {
+        if let BrilError::Malformed { function: name, .. } = &mut self {
+            *name = function.name.clone();
+        }
+        self
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BrilError::Usage(_) => 2,
+            BrilError::Io { .. } => 3,
+            BrilError::Parse(_) => 4,
+            BrilError::Malformed { .. } => 5,
+            BrilError::Config { .. } => 6,
+        }
+    }
+}
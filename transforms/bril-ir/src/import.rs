@@ -0,0 +1,27 @@
+// The import extension: a program can list functions of other files, which
+// it then calls as if they were its own. Each import names a Bril JSON file
+// and the functions to take from it, under another name with `alias`.
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Import {
+    pub path: String,
+    pub functions: Vec<ImportedFunction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportedFunction {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+impl ImportedFunction {
+    // The name the importing program calls the function by.
+    pub fn local_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
@@ -0,0 +1,67 @@
+// Interned names. Each distinct string is stored once and stood for by a
+// `Symbol`, a number that is free to copy, hash and compare, so tables
+// keyed by variables or labels stop cloning a `String` every time they
+// record one. Symbols are numbered from zero in the order their names were
+// first interned, which lets such a table be a `Vec` indexed by
+// `Symbol::index` as well.
+//
+// A symbol only means something to the interner that gave it out.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    // The symbol with this index, for a table indexed by symbol that gives
+    // the indices back, like a set of symbols as bits.
+    pub fn from_index(index: usize) -> Self {
+        Symbol(u32::try_from(index).expect("Not the index of a symbol"))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    names: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The symbol for `name`, a new one the first time it is seen.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.names.len()).expect("Too many names to intern"));
+        let name: Rc<str> = Rc::from(name);
+        self.names.push(Rc::clone(&name));
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    // The symbol for `name` if it has been interned, without interning it.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
@@ -0,0 +1,414 @@
+// The Bril IR as the Rust transforms read and write it: programs,
+// functions and instructions that round-trip through JSON, plus the
+// bookkeeping the passes attach to them (provenance and debug info), and the
+// errors reading and checking them can run into.
+//
+// Instructions keep their opcode as a string, so programs using extensions
+// that a pass knows nothing about still load and pass through unchanged.
+pub mod debug_info;
+pub mod error;
+pub mod import;
+pub mod intern;
+
+use debug_info::DebugInfo;
+use import::Import;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Program {
+    pub functions: Vec<Function>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<Import>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NamedArg {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub type_: Type,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Type {
+    Prim(String),
+    // Parameterized types are objects with the constructor as the only key.
+    Ptr { ptr: Box<Type> },
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Prim(name) => write!(f, "{}", name),
+            Type::Ptr { ptr } => write!(f, "ptr<{}>", ptr),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Literal {
+    Int(i64),
+    Bool(bool),
+    Float(f64),
+    // Written in JSON as a string holding exactly one character.
+    Char(char),
+}
+
+// Floats are compared bitwise so that constants can be value-numbered.
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Literal::Int(a), Literal::Int(b)) => a == b,
+            (Literal::Bool(a), Literal::Bool(b)) => a == b,
+            (Literal::Float(a), Literal::Float(b)) => a.to_bits() == b.to_bits(),
+            (Literal::Char(a), Literal::Char(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Literal {}
+
+impl std::hash::Hash for Literal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Literal::Int(value) => value.hash(state),
+            Literal::Bool(value) => value.hash(state),
+            Literal::Float(value) => value.to_bits().hash(state),
+            Literal::Char(value) => value.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Int(value) => write!(f, "{}", value),
+            Literal::Bool(value) => write!(f, "{}", value),
+            Literal::Float(value) => write!(f, "{}", value),
+            Literal::Char(value) => write!(f, "'{}'", value),
+        }
+    }
+}
+
+// A row and column in a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColRow {
+    pub row: u64,
+    pub col: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Function {
+    pub name: String,
+
+    pub instrs: Vec<Instruction>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<NamedArg>,
+
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_: Option<Type>,
+
+    // Only kept in the output with `--debug-info`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "DebugInfo::is_empty")]
+    pub debug: DebugInfo,
+
+    // Where the function was in the source, see `Instruction::pos`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<ColRow>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos_end: Option<ColRow>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Instruction {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub op: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dest: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Literal>,
+
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_: Option<Type>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub funcs: Vec<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    // Names of the passes that created or modified this instruction, oldest
+    // first. Only kept in the output with `--provenance`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transformed_by: Vec<String>,
+
+    // Identifies the instruction from the moment the program is read, however
+    // the passes rewrite it, so tools can trace an optimized instruction back
+    // to the source. Given by `assign_ids` unless the input already has one,
+    // and only kept in the output with `--provenance`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    // The IDs of instructions that were deleted because this one computes the
+    // same value. Only kept in the output with `--provenance`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub merged_from: Vec<String>,
+
+    // Where it was in the source, as `bril2json -p` records it. Passes carry
+    // these along with the instruction, so errors and debuggers can still
+    // point at the source of an optimized program.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<ColRow>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos_end: Option<ColRow>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+}
+
+// Operations whose result depends only on their arguments, so that two
+// evaluations with the same arguments always agree.
+pub const PURE_OPS: [&str; 27] = [
+    "add", "mul", "sub", "div", "eq", "lt", "gt", "le", "ge", "not", "and", "or", "fadd", "fmul",
+    "fsub", "fdiv", "feq", "flt", "fgt", "fle", "fge", "ptradd", "ceq", "clt", "cgt", "cle", "cge",
+];
+
+// What running an instruction does besides computing its result, which is
+// what decides whether a pass may merge two of them or delete an unused one.
+// Whether it can trap is a separate question, see `DivByZero` in lvn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    // Nothing: two with the same arguments agree, and an unused one can go.
+    Pure,
+    // It also depends on state it does not change, memory for a `load` and
+    // the way control came in for a `phi` or `get`, so two with the same
+    // arguments may disagree, but an unused one can still go. Two loads of
+    // the same pointer agree when nothing between them `writes_memory`.
+    Reads,
+    // It changes something the rest of the program can tell: it calls,
+    // allocates, writes memory or a shadow variable, prints or transfers
+    // control. So is any operation not listed here.
+    Writes,
+}
+
+// Operations that change something without changing memory already
+// allocated: a new allocation leaves every other one as it was, and aborting
+// speculation does not roll the heap back.
+const MEMORY_PRESERVING_OPS: [&str; 10] = [
+    "alloc",
+    "print",
+    "set",
+    "jmp",
+    "br",
+    "ret",
+    "nop",
+    "speculate",
+    "commit",
+    "guard",
+];
+
+impl Instruction {
+    pub fn effect(&self) -> Effect {
+        match self.op.as_deref() {
+            Some("const" | "id") => Effect::Pure,
+            Some(op) if PURE_OPS.contains(&op) => Effect::Pure,
+            Some("load" | "phi" | "get") => Effect::Reads,
+            _ => Effect::Writes,
+        }
+    }
+
+    // Whether running the instruction may change what a `load` gives: a
+    // `store`, a `free`, a call, which may do either, or an operation not
+    // known to keep memory as it was.
+    pub fn writes_memory(&self) -> bool {
+        match self.op.as_deref() {
+            Some(op) => self.effect() == Effect::Writes && !MEMORY_PRESERVING_OPS.contains(&op),
+            None => false,
+        }
+    }
+
+    // The value of a `const` as its type reads it: a float written without a
+    // fraction, like `0`, comes out of JSON as an integer.
+    pub fn literal(&self) -> Option<Literal> {
+        match (self.value?, &self.type_) {
+            (Literal::Int(value), Some(Type::Prim(name))) if name == "float" => {
+                Some(Literal::Float(value as f64))
+            }
+            (value, _) => Some(value),
+        }
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        match &self.op {
+            Some(op) => op == "jmp" || op == "br" || op == "ret",
+            None => false,
+        }
+    }
+
+    // Whether the block holding the instruction ends with it: a terminator,
+    // or a `speculate` or `guard`, after which control may go elsewhere than
+    // right after them with the variables as they left them.
+    pub fn ends_block(&self) -> bool {
+        self.is_terminator() || matches!(self.op.as_deref(), Some("speculate" | "guard"))
+    }
+
+    pub fn is_label(&self) -> bool {
+        self.label.is_some()
+    }
+
+    pub fn is_phi(&self) -> bool {
+        self.op.as_deref() == Some("phi")
+    }
+
+    // Whether the instruction is one of the SSA extension's: a `phi`, or a
+    // `get` or `set` of a shadow variable.
+    pub fn is_ssa_op(&self) -> bool {
+        matches!(self.op.as_deref(), Some("phi" | "get" | "set"))
+    }
+
+    // Whether the instruction is one of the speculative execution
+    // extension's: a `speculate`, `commit` or `guard`.
+    pub fn is_speculation_op(&self) -> bool {
+        matches!(self.op.as_deref(), Some("speculate" | "commit" | "guard"))
+    }
+
+    // The labels control can go to next, besides the next instruction for
+    // one that is not a terminator: a `guard` jumps to its label when it
+    // aborts. A `phi` has labels too, but they name the blocks control came
+    // from.
+    pub fn jump_targets(&self) -> &[String] {
+        if self.is_terminator() || self.op.as_deref() == Some("guard") {
+            &self.labels
+        } else {
+            &[]
+        }
+    }
+
+    // The arguments of a `phi`, each with the label of the predecessor it is
+    // taken from.
+    pub fn phi_sources(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels
+            .iter()
+            .map(String::as_str)
+            .zip(self.args.iter().map(String::as_str))
+    }
+
+    // The argument a `phi` takes when control comes from the block labeled
+    // `label`.
+    pub fn phi_source(&self, label: &str) -> Option<&str> {
+        self.phi_sources()
+            .find(|(from, _)| *from == label)
+            .map(|(_, arg)| arg)
+    }
+
+    pub fn add_phi_source(&mut self, label: &str, arg: &str) {
+        self.labels.push(label.to_string());
+        self.args.push(arg.to_string());
+    }
+
+    // Records that `other` was deleted in favor of this instruction.
+    pub fn absorb(&mut self, other: &Instruction) {
+        self.merged_from.extend(other.id.iter().cloned());
+        self.merged_from.extend(other.merged_from.iter().cloned());
+    }
+
+    // How many of the leading arguments are not values read in this block:
+    // a `phi` reads its arguments as control leaves a predecessor, and the
+    // first argument of `set` names the shadow variable it writes.
+    fn nonlocal_args(&self) -> usize {
+        match self.op.as_deref() {
+            Some("phi") => self.args.len(),
+            Some("set") => self.args.len().min(1),
+            _ => 0,
+        }
+    }
+
+    // The arguments whose values the instruction reads where it stands.
+    pub fn local_args(&self) -> &[String] {
+        &self.args[self.nonlocal_args()..]
+    }
+
+    pub fn local_args_mut(&mut self) -> &mut [String] {
+        let start = self.nonlocal_args();
+        &mut self.args[start..]
+    }
+
+    pub fn mark_transformed(&mut self, pass: &str) {
+        if self.transformed_by.last().map(String::as_str) != Some(pass) {
+            self.transformed_by.push(pass.to_string());
+        }
+    }
+}
+
+// Prints the instruction in Bril's text format.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(label) = &self.label {
+            return write!(f, ".{}:", label);
+        }
+        if let Some(dest) = &self.dest {
+            write!(f, "{}", dest)?;
+            if let Some(type_) = &self.type_ {
+                write!(f, ": {}", type_)?;
+            }
+            write!(f, " = ")?;
+        }
+        write!(f, "{}", self.op.as_deref().unwrap_or("nop"))?;
+        if let Some(value) = &self.value {
+            write!(f, " {}", value)?;
+        }
+        for func in &self.funcs {
+            write!(f, " @{}", func)?;
+        }
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        for label in &self.labels {
+            write!(f, " .{}", label)?;
+        }
+        write!(f, ";")
+    }
+}
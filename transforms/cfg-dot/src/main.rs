@@ -0,0 +1,225 @@
+// Draws the control flow graphs of a program with Graphviz, to see how a
+// program splits into blocks and what a pass did to them.
+//
+//     bril2json < program.bril | cfg-dot [--dominators | --loops] \
+//         [-f FUNCTION] | dot -Tpdf -O
+//     cfg-dot program.json -o program.dot
+//
+// Each function becomes a `digraph` of its own, or only the one named with
+// `--function`. A block is a box listing its instructions, headed by
+// its label or, for a block without one, its index. The edges are those of
+// `bril_cfg`: a `br` gives one marked `T` to the label control goes to when
+// its condition holds and one marked `F` to the other, and a block not ending
+// in a terminator falls through to the next.
+//
+// With `--dominators` the edges are those of the dominator tree instead, from
+// each block to the blocks it immediately dominates; blocks the entry does
+// not reach stand alone. With `--loops` the CFG is drawn with each natural
+// loop as a dashed box around its blocks, nested as the loops are, and the
+// back edges to their headers in bold red.
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::loops::LoopForest;
+use bril_cfg::{construct_control_flow_graph, ControlFlowGraph};
+use bril_cli::CommonArgs;
+use bril_ir::error::BrilError;
+use bril_ir::Function;
+use clap::Parser;
+use std::fmt::Write;
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Quotes `text` as a DOT string.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+fn signature(function: &Function) -> String {
+    let args: Vec<String> = function
+        .args
+        .iter()
+        .map(|arg| format!("{}: {}", arg.name, arg.type_))
+        .collect();
+    let mut signature = format!("@{}({})", function.name, args.join(", "));
+    if let Some(type_) = &function.type_ {
+        write!(signature, ": {}", type_).unwrap();
+    }
+    signature
+}
+
+// The box for block `b`, one left-aligned line per instruction. A label is
+// the heading rather than a line of its own.
+fn block_label(cfg: &ControlFlowGraph, b: usize) -> String {
+    let block = &cfg.blocks[b];
+    let mut lines = vec![match block.label() {
+        Some(label) => format!(".{}", label),
+        None => format!("<block {}>", b),
+    }];
+    for instr in block.instrs.iter().filter(|instr| !instr.is_label()) {
+        lines.push(format!("  {}", instr));
+    }
+    // `\l` ends a line of a DOT label aligned to the left.
+    let lines: String = lines.iter().map(|line| escape(line) + "\\l").collect();
+    format!("\"{}\"", lines)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum View {
+    Cfg,
+    Dominators,
+    Loops,
+}
+
+// The edges of the CFG leaving block `b`, with `extra` attributes for those
+// in `marked`.
+fn write_edges(dot: &mut String, cfg: &ControlFlowGraph, b: usize, marked: &[usize], extra: &str) {
+    let block = &cfg.blocks[b];
+    let branches = block
+        .instrs
+        .last()
+        .is_some_and(|instr| instr.op.as_deref() == Some("br"));
+    for (i, &next) in block.next_blocks.iter().enumerate() {
+        let mut attrs = Vec::new();
+        if branches {
+            attrs.push(format!("label={}", if i == 0 { "T" } else { "F" }));
+        }
+        if marked.contains(&next) {
+            attrs.push(extra.to_string());
+        }
+        if attrs.is_empty() {
+            writeln!(dot, "  b{} -> b{};", b, next).unwrap();
+        } else {
+            writeln!(dot, "  b{} -> b{} [{}];", b, next, attrs.join(", ")).unwrap();
+        }
+    }
+}
+
+// Loop `l` of `forest` as a cluster holding the blocks it is the innermost
+// loop of and, inside them, the clusters of the loops nested in it.
+fn write_loop(dot: &mut String, cfg: &ControlFlowGraph, forest: &LoopForest, l: usize) {
+    let outer = "  ".repeat(forest.loops[l].depth(forest) + 1);
+    let header = forest.loops[l].header;
+    let name = match cfg.blocks[header].label() {
+        Some(label) => format!("loop .{}", label),
+        None => format!("loop <block {}>", header),
+    };
+    writeln!(dot, "{}subgraph cluster_{} {{", outer, l).unwrap();
+    writeln!(dot, "{}  label={};", outer, quote(&name)).unwrap();
+    writeln!(dot, "{}  style=dashed;", outer).unwrap();
+    for &b in &forest.loops[l].body {
+        if forest.innermost(b) == Some(l) {
+            writeln!(dot, "{}  b{} [label={}];", outer, b, block_label(cfg, b)).unwrap();
+        }
+    }
+    for &child in &forest.loops[l].children {
+        write_loop(dot, cfg, forest, child);
+    }
+    writeln!(dot, "{}}}", outer).unwrap();
+}
+
+fn to_dot(function: &Function, view: View) -> String {
+    let cfg = construct_control_flow_graph(function);
+    let tree = DominatorTree::new(&cfg);
+    let forest = LoopForest::with_dominators(&cfg, &tree);
+    let mut dot = String::new();
+    writeln!(dot, "digraph {} {{", quote(&function.name)).unwrap();
+    writeln!(dot, "  label={};", quote(&signature(function))).unwrap();
+    writeln!(dot, "  labelloc=t;").unwrap();
+    writeln!(dot, "  node [shape=box, fontname=monospace];").unwrap();
+    for (b, _) in cfg.iter() {
+        if view != View::Loops || forest.innermost(b).is_none() {
+            writeln!(dot, "  b{} [label={}];", b, block_label(&cfg, b)).unwrap();
+        }
+    }
+    match view {
+        View::Cfg => {
+            for (b, _) in cfg.iter() {
+                write_edges(&mut dot, &cfg, b, &[], "");
+            }
+        }
+        View::Dominators => {
+            for (b, _) in cfg.iter() {
+                for child in tree.children(b) {
+                    writeln!(dot, "  b{} -> b{};", b, child).unwrap();
+                }
+            }
+        }
+        View::Loops => {
+            for l in forest.roots() {
+                write_loop(&mut dot, &cfg, &forest, l);
+            }
+            for (b, _) in cfg.iter() {
+                // The edges from a latch back to the header of its loop.
+                let headers: Vec<usize> = forest
+                    .enclosing(b)
+                    .into_iter()
+                    .filter(|&l| forest.loops[l].latches.contains(&b))
+                    .map(|l| forest.loops[l].header)
+                    .collect();
+                write_edges(&mut dot, &cfg, b, &headers, "color=red, style=bold");
+            }
+        }
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "cfg-dot",
+    about = "Draws the control flow graphs of Bril programs"
+)]
+struct Cli {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Draw dominator trees instead of the CFGs
+    #[arg(long, conflicts_with = "loops")]
+    dominators: bool,
+
+    /// Draw the natural loops around the blocks they are made of
+    #[arg(long)]
+    loops: bool,
+
+    /// Only draw this function
+    #[arg(short, long, value_name = "FUNCTION")]
+    function: Option<String>,
+}
+
+fn run() -> Result<(), BrilError> {
+    let cli = Cli::parse();
+    let view = match (cli.dominators, cli.loops) {
+        (true, _) => View::Dominators,
+        (_, true) => View::Loops,
+        _ => View::Cfg,
+    };
+    let only = cli
+        .function
+        .as_deref()
+        .map(|name| name.trim_start_matches('@'));
+
+    let program = cli.common.read_program()?;
+    let functions: Vec<&Function> = program
+        .functions
+        .iter()
+        .filter(|function| only.is_none_or(|name| function.name == name))
+        .collect();
+    if let (Some(name), []) = (only, &functions[..]) {
+        return Err(BrilError::usage(format!(
+            "no function @{} in the program",
+            name
+        )));
+    }
+    let mut dot = String::new();
+    for function in functions {
+        cli.common
+            .note("cfg-dot", format!("drawing @{}", function.name));
+        dot.push_str(&to_dot(function, view));
+    }
+    cli.common.write(&dot)
+}
+
+fn main() {
+    bril_cli::exit_on_error("cfg-dot", run());
+}
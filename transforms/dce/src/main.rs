@@ -0,0 +1,141 @@
+// Trivial dead code elimination, on its own so it can be composed with and
+// measured apart from value numbering.
+//
+//     bril2json < program.bril | dce [MODE] | bril2txt
+//     dce [MODE] [FILE] [-o FILE] [--pretty] [--verbose]
+//
+// The modes are those of `examples/tdce.py`:
+//
+// - `tdce`, the default: deletes instructions whose result is never used
+//   anywhere in the function, over and over until none are left.
+// - `tdcep`: a single round of the above.
+// - `dkp`: deletes instructions whose result is assigned again, in the same
+//   block, before anything reads it.
+// - `tdce+`: both `tdce` and `dkp`, until neither deletes anything.
+//
+// A program file comes after the mode, which has to be given along with it.
+//
+// Instructions without a result, like `print`, `store` and `ret`, always
+// stay, as do those doing something besides computing it, like `call` and
+// `alloc`.
+use bril_cfg::{take_control_flow_graph, Block};
+use bril_cli::CommonArgs;
+use bril_ir::error::BrilError;
+use bril_ir::{Effect, Function};
+use clap::{Parser, ValueEnum};
+use std::collections::{HashMap, HashSet};
+
+// Whether deleting `instr` only deletes the value it computes.
+fn removable(instr: &bril_ir::Instruction) -> bool {
+    instr.dest.is_some() && instr.effect() != Effect::Writes
+}
+
+// Deletes the instructions whose result no instruction of `function` reads,
+// and reports whether there were any.
+fn trivial_dce_pass(function: &mut Function) -> bool {
+    let used: HashSet<String> = function
+        .instrs
+        .iter()
+        .flat_map(|instr| instr.args.iter().cloned())
+        .collect();
+    let before = function.instrs.len();
+    function
+        .instrs
+        .retain(|instr| !removable(instr) || used.contains(instr.dest.as_ref().unwrap()));
+    function.instrs.len() != before
+}
+
+fn trivial_dce(function: &mut Function) {
+    while trivial_dce_pass(function) {}
+}
+
+// Deletes the instructions of `block` whose result is assigned again before
+// it is read, and reports whether there were any.
+fn drop_killed_local(block: &mut Block) -> bool {
+    // Where each variable was last assigned since it was last read.
+    let mut last_def: HashMap<&str, usize> = HashMap::new();
+    let mut to_drop = HashSet::new();
+    for (i, instr) in block.instrs.iter().enumerate() {
+        // Uses come first, so `a = add a one` reads `a` before it kills it.
+        for arg in &instr.args {
+            last_def.remove(arg.as_str());
+        }
+        if let Some(dest) = &instr.dest {
+            if let Some(def) = last_def.insert(dest, i) {
+                if removable(&block.instrs[def]) {
+                    to_drop.insert(def);
+                }
+            }
+        }
+    }
+    let mut i = 0;
+    block.instrs.retain(|_| {
+        i += 1;
+        !to_drop.contains(&(i - 1))
+    });
+    !to_drop.is_empty()
+}
+
+fn drop_killed_pass(function: &mut Function) -> bool {
+    let mut cfg = take_control_flow_graph(function);
+    let mut changed = false;
+    for block in cfg.blocks.iter_mut() {
+        changed |= drop_killed_local(block);
+    }
+    function.instrs = cfg.into_instrs();
+    changed
+}
+
+fn trivial_dce_plus(function: &mut Function) {
+    while trivial_dce_pass(function) || drop_killed_pass(function) {}
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Mode {
+    Tdce,
+    Tdcep,
+    Dkp,
+    #[value(name = "tdce+")]
+    TdcePlus,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "dce", about = "Deletes dead code from Bril programs")]
+struct Cli {
+    /// Which dead code to delete
+    #[arg(value_enum, default_value = "tdce")]
+    mode: Mode,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn run() -> Result<(), BrilError> {
+    let cli = Cli::parse();
+    let mode: fn(&mut Function) = match cli.mode {
+        Mode::Tdce => trivial_dce,
+        Mode::Tdcep => |function| {
+            trivial_dce_pass(function);
+        },
+        Mode::Dkp => |function| {
+            drop_killed_pass(function);
+        },
+        Mode::TdcePlus => trivial_dce_plus,
+    };
+
+    let mut program = cli.common.read_program()?;
+    for function in &mut program.functions {
+        let before = function.instrs.len();
+        mode(function);
+        let deleted = before - function.instrs.len();
+        if deleted > 0 {
+            let message = format!("deleted {} instructions of @{}", deleted, function.name);
+            cli.common.note("dce", message);
+        }
+    }
+    cli.common.write_program(&program)
+}
+
+fn main() {
+    bril_cli::exit_on_error("dce", run());
+}
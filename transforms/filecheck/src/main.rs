@@ -0,0 +1,142 @@
+// Checks the output of a pass against directives embedded in its test file,
+// in the style of LLVM's FileCheck.
+//
+//     bril2json < test.bril | lvn | bril2txt | bril-filecheck test.bril
+//
+// A line of the test file containing `CHECK: pattern` requires a later line
+// of the output than the previous match to contain `pattern`, and
+// `CHECK-NOT: pattern` requires that no line between the neighbouring
+// matches does. The directives usually live in comments, as in
+// `# CHECK: v2: int = add a b;`. Runs of whitespace compare equal to a
+// single space, so the indentation of the output does not matter.
+//
+// Nothing is printed when every directive holds. Otherwise each failing one
+// is reported with its line in the test file and the exit status is 1.
+use std::io::Read;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Check,
+    CheckNot,
+}
+
+#[derive(Debug)]
+struct Directive {
+    kind: Kind,
+    pattern: String,
+    line: usize,
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn parse_directives(test: &str) -> Vec<Directive> {
+    let mut directives = Vec::new();
+    for (i, line) in test.lines().enumerate() {
+        // `CHECK-NOT:` is looked for first, since it does not contain
+        // `CHECK:`.
+        for (prefix, kind) in [("CHECK-NOT:", Kind::CheckNot), ("CHECK:", Kind::Check)] {
+            if let Some(start) = line.find(prefix) {
+                directives.push(Directive {
+                    kind,
+                    pattern: normalize(&line[start + prefix.len()..]),
+                    line: i + 1,
+                });
+                break;
+            }
+        }
+    }
+    directives
+}
+
+// Fails the pending `CHECK-NOT`s matching any output line in `range`.
+fn check_not(
+    lines: &[String],
+    pending: &mut Vec<&Directive>,
+    range: std::ops::Range<usize>,
+    failures: &mut Vec<(usize, String)>,
+) {
+    for directive in pending.drain(..) {
+        let found = lines[range.clone()]
+            .iter()
+            .position(|line| line.contains(&directive.pattern));
+        if let Some(i) = found {
+            failures.push((
+                directive.line,
+                format!(
+                    "CHECK-NOT: excluded string {:?} found in output line {}: {}",
+                    directive.pattern,
+                    range.start + i + 1,
+                    lines[range.start + i]
+                ),
+            ));
+        }
+    }
+}
+
+// Returns a message for each directive the output breaks.
+fn check(directives: &[Directive], output: &str) -> Vec<(usize, String)> {
+    let lines: Vec<String> = output.lines().map(normalize).collect();
+    let mut failures = Vec::new();
+    // The next output line a `CHECK` may match, and the `CHECK-NOT`s
+    // waiting for it to bound the lines they must not match.
+    let mut start = 0;
+    let mut pending: Vec<&Directive> = Vec::new();
+    for directive in directives {
+        match directive.kind {
+            Kind::CheckNot => pending.push(directive),
+            Kind::Check => {
+                let found = lines[start..]
+                    .iter()
+                    .position(|line| line.contains(&directive.pattern));
+                match found {
+                    Some(i) => {
+                        check_not(&lines, &mut pending, start..start + i, &mut failures);
+                        start += i + 1;
+                    }
+                    None => {
+                        failures.push((
+                            directive.line,
+                            format!(
+                                "CHECK: expected string {:?} not found in output after line {}",
+                                directive.pattern, start
+                            ),
+                        ));
+                        // Later directives are still checked from here.
+                        check_not(&lines, &mut pending, start..start, &mut failures);
+                    }
+                }
+            }
+        }
+    }
+    check_not(&lines, &mut pending, start..lines.len(), &mut failures);
+    failures.sort();
+    failures
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("usage: bril-filecheck TEST_FILE < OUTPUT");
+        std::process::exit(2);
+    }
+    let path = &args[0];
+    let test =
+        std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+    let directives = parse_directives(&test);
+    if directives.is_empty() {
+        eprintln!("{}: error: no CHECK directives found", path);
+        std::process::exit(2);
+    }
+
+    let mut output = String::new();
+    std::io::stdin().read_to_string(&mut output).unwrap();
+    let failures = check(&directives, &output);
+    for (line, message) in &failures {
+        eprintln!("{}:{}: error: {}", path, line, message);
+    }
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+}
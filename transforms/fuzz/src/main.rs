@@ -0,0 +1,260 @@
+// Differential testing of a Bril interpreter against a reference one, or of
+// a pass against the programs it is given.
+//
+//     bril-fuzz programs/*.json [--interp CMD] [--oracle CMD] [--reducer CMD]
+//     bril-fuzz programs/*.json --pass CMD [--interp CMD] [--reducer CMD]
+//
+// Each program is run through `--interp` (default `brilirs`) and the
+// reference `--oracle` (default `brili`), both with `-p`. The interpreters
+// disagree when one fails and the other doesn't, when they print different
+// output, or when they report a different dynamic instruction count. Every
+// disagreement is shrunk with `bril-reduce` and the reduced program is
+// written next to the original as `<name>.reduced.json`.
+//
+// With `--pass`, each program is run through the pass and `--interp` runs
+// both the program and what the pass made of it. They disagree when the pass
+// fails, or when the optimized program fails, or prints something else,
+// while the original runs fine; the instruction counts are what a pass is
+// meant to change, so they are not compared. Programs from `bril-gen` always
+// run fine, so every disagreement is a miscompilation. The reducer shrinks
+// it with its own `--pass` check.
+//
+// When the oracle is not installed there is nothing to compare against, so
+// the run is skipped rather than failed.
+//
+// The reducer calls back into this binary with `--disagree`, which reads a
+// candidate from stdin and exits successfully while the interpreters still
+// disagree on it. The interpreter commands are passed down through the
+// environment because the reducer splits its test command on whitespace.
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+
+const INTERP_VAR: &str = "BRIL_FUZZ_INTERP";
+const ORACLE_VAR: &str = "BRIL_FUZZ_ORACLE";
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    success: bool,
+    stdout: Vec<u8>,
+    dyn_insts: Option<u64>,
+}
+
+fn split_command(command: &str) -> Vec<String> {
+    let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+    if words.is_empty() {
+        panic!("Empty command");
+    }
+    words
+}
+
+// Returns `None` when the interpreter could not be started at all.
+fn interpret(command: &[String], program: &[u8]) -> Option<Outcome> {
+    let mut child = match Command::new(&command[0])
+        .args(&command[1..])
+        .arg("-p")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+        Err(e) => panic!("Failed to run {}: {}", command[0], e),
+    };
+    let _ = child.stdin.take().unwrap().write_all(program);
+    let output = child
+        .wait_with_output()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", command[0], e));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let dyn_insts = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("total_dyn_inst: "))
+        .and_then(|count| count.trim().parse().ok());
+    Some(Outcome {
+        success: output.status.success(),
+        // Whatever was printed before an error is not comparable.
+        stdout: if output.status.success() {
+            output.stdout
+        } else {
+            Vec::new()
+        },
+        dyn_insts,
+    })
+}
+
+// Describes how the two outcomes differ, if they do.
+fn mismatch(interp: &Outcome, oracle: &Outcome) -> Option<&'static str> {
+    if interp.success != oracle.success {
+        Some("exit status")
+    } else if interp.stdout != oracle.stdout {
+        Some("output")
+    } else if interp.success && interp.dyn_insts != oracle.dyn_insts {
+        Some("dynamic instruction count")
+    } else {
+        None
+    }
+}
+
+fn disagree() {
+    let interp = split_command(&std::env::var(INTERP_VAR).expect("BRIL_FUZZ_INTERP not set"));
+    let oracle = split_command(&std::env::var(ORACLE_VAR).expect("BRIL_FUZZ_ORACLE not set"));
+    let mut program = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut program)
+        .expect("Failed to read program");
+    let interp = interpret(&interp, &program).expect("Interpreter not found");
+    let oracle = interpret(&oracle, &program).expect("Oracle not found");
+    let failing = mismatch(&interp, &oracle).is_some();
+    std::process::exit(if failing { 0 } else { 1 });
+}
+
+fn reduce(path: &str, reducer: &[String], interp: &str, oracle: &str) {
+    let this = std::env::current_exe().expect("Failed to locate bril-fuzz");
+    let test = format!("{} --disagree", this.display());
+    let output = Command::new(&reducer[0])
+        .args(&reducer[1..])
+        .arg(path)
+        .arg("--test")
+        .arg(&test)
+        .env(INTERP_VAR, interp)
+        .env(ORACLE_VAR, oracle)
+        .stderr(Stdio::inherit())
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", reducer[0], e));
+    if !output.status.success() {
+        eprintln!("  reducer failed on {}", path);
+        return;
+    }
+    let reduced = format!("{}.reduced.json", path.trim_end_matches(".json"));
+    std::fs::write(&reduced, &output.stdout).expect("Failed to write reduced program");
+    eprintln!("  reduced program written to {}", reduced);
+}
+
+// Describes how the optimized program goes wrong, if it does.
+fn miscompiled(original: &Outcome, optimized: Option<&Outcome>) -> Option<&'static str> {
+    match optimized {
+        _ if !original.success => None,
+        None => Some("the pass failed"),
+        Some(optimized) if !optimized.success => Some("the optimized program fails"),
+        Some(optimized) if optimized.stdout != original.stdout => {
+            Some("the optimized program prints something else")
+        }
+        Some(_) => None,
+    }
+}
+
+fn optimize(pass: &[String], program: &[u8]) -> Option<Vec<u8>> {
+    let mut child = Command::new(&pass[0])
+        .args(&pass[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", pass[0], e));
+    let _ = child.stdin.take().unwrap().write_all(program);
+    let output = child
+        .wait_with_output()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", pass[0], e));
+    if output.status.success() {
+        Some(output.stdout)
+    } else {
+        None
+    }
+}
+
+fn reduce_miscompilation(path: &str, reducer: &[String], pass: &str, interp: &str) {
+    let output = Command::new(&reducer[0])
+        .args(&reducer[1..])
+        .args([path, "--pass", pass, "--interp", interp])
+        .stderr(Stdio::inherit())
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", reducer[0], e));
+    if !output.status.success() {
+        eprintln!("  reducer failed on {}", path);
+        return;
+    }
+    let reduced = format!("{}.reduced.json", path.trim_end_matches(".json"));
+    std::fs::write(&reduced, &output.stdout).expect("Failed to write reduced program");
+    eprintln!("  reduced program written to {}", reduced);
+}
+
+fn fuzz_pass(paths: &[String], pass: &str, interp: &str, reducer: &str) {
+    let pass_command = split_command(pass);
+    let interp_command = split_command(interp);
+    let reducer_command = split_command(reducer);
+    let mut mismatches = 0;
+    for path in paths {
+        let program = std::fs::read(path).expect("Failed to read program");
+        let original = interpret(&interp_command, &program)
+            .unwrap_or_else(|| panic!("{} is not available", interp));
+        let optimized = optimize(&pass_command, &program).map(|optimized| {
+            interpret(&interp_command, &optimized)
+                .unwrap_or_else(|| panic!("{} is not available", interp))
+        });
+        if let Some(what) = miscompiled(&original, optimized.as_ref()) {
+            mismatches += 1;
+            eprintln!("{}: {} under {}", path, what, pass);
+            reduce_miscompilation(path, &reducer_command, pass, interp);
+        }
+    }
+    eprintln!("{} of {} programs miscompiled", mismatches, paths.len());
+    if mismatches > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let usage =
+        "Usage: bril-fuzz programs... [--interp CMD] [--oracle CMD | --pass CMD] [--reducer CMD]";
+    let mut paths = Vec::new();
+    let mut interp = String::from("brilirs");
+    let mut oracle = String::from("brili");
+    let mut reducer = String::from("bril-reduce");
+    let mut pass = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--disagree" => return disagree(),
+            "--interp" => interp = args.next().expect(usage),
+            "--oracle" => oracle = args.next().expect(usage),
+            "--reducer" => reducer = args.next().expect(usage),
+            "--pass" => pass = Some(args.next().expect(usage)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        panic!("{}", usage);
+    }
+    if let Some(pass) = pass {
+        return fuzz_pass(&paths, &pass, &interp, &reducer);
+    }
+    let interp_command = split_command(&interp);
+    let oracle_command = split_command(&oracle);
+    let reducer_command = split_command(&reducer);
+
+    let mut mismatches = 0;
+    for path in &paths {
+        let program = std::fs::read(path).expect("Failed to read program");
+        let oracle_outcome = match interpret(&oracle_command, &program) {
+            Some(outcome) => outcome,
+            None => {
+                eprintln!("{} is not available, skipping differential testing", oracle);
+                return;
+            }
+        };
+        let interp_outcome = interpret(&interp_command, &program)
+            .unwrap_or_else(|| panic!("{} is not available", interp));
+        if let Some(what) = mismatch(&interp_outcome, &oracle_outcome) {
+            mismatches += 1;
+            eprintln!(
+                "{}: {} differs between {} and {}",
+                path, what, interp, oracle
+            );
+            reduce(path, &reducer_command, &interp, &oracle);
+        }
+    }
+    eprintln!("{} of {} programs disagree", mismatches, paths.len());
+    if mismatches > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,115 @@
+// Checks how bril-fuzz compares interpreters and passes, using shell scripts
+// that stand in for the interpreters, the pass and the reducer.
+use std::path::PathBuf;
+use std::process::Command;
+
+struct Dir(PathBuf);
+
+impl Dir {
+    fn new(name: &str) -> Dir {
+        let dir = std::env::temp_dir().join(format!("bril-fuzz-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("program.json"), r#"{"functions":[]}"#).unwrap();
+        Dir(dir)
+    }
+
+    // A command running `script` with `sh`.
+    fn script(&self, name: &str, script: &str) -> String {
+        let path = self.0.join(name);
+        std::fs::write(&path, script).unwrap();
+        format!("sh {}", path.display())
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// An interpreter that prints `output` and reports `count` instructions.
+fn interp(output: &str, count: u64) -> String {
+    format!(
+        "cat > /dev/null\necho {}\necho total_dyn_inst: {} >&2\n",
+        output, count
+    )
+}
+
+// The exit code and stderr of bril-fuzz on the program in `dir`.
+fn fuzz(dir: &Dir, args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_bril-fuzz"))
+        .arg(dir.path("program.json"))
+        .args(args)
+        .output()
+        .expect("Failed to run bril-fuzz");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (output.status.code(), stderr)
+}
+
+#[test]
+fn fuzz_interpreters() {
+    let dir = Dir::new("interp");
+    let interp_command = dir.script("interp.sh", &interp("1", 3));
+    let oracle = dir.script("oracle.sh", &interp("1", 3));
+    let args = ["--interp", &interp_command, "--oracle", &oracle];
+    let (code, stderr) = fuzz(&dir, &args);
+    assert_eq!(code, Some(0), "{}", stderr);
+    assert!(stderr.contains("0 of 1 programs disagree"), "{}", stderr);
+
+    // A disagreement is handed to the reducer with the program's path.
+    let oracle = dir.script("counts.sh", &interp("1", 4));
+    let reducer = dir.script("reducer.sh", "cat \"$1\"\n");
+    let args = [
+        "--interp",
+        &interp_command,
+        "--oracle",
+        &oracle,
+        "--reducer",
+        &reducer,
+    ];
+    let (code, stderr) = fuzz(&dir, &args);
+    assert_eq!(code, Some(1), "{}", stderr);
+    assert!(
+        stderr.contains("dynamic instruction count differs"),
+        "{}",
+        stderr
+    );
+    let reduced = std::fs::read_to_string(dir.path("program.reduced.json")).unwrap();
+    assert_eq!(reduced, r#"{"functions":[]}"#);
+
+    let args = ["--interp", &interp_command, "--oracle", "no-such-oracle"];
+    let (code, stderr) = fuzz(&dir, &args);
+    assert_eq!(code, Some(0), "{}", stderr);
+    assert!(
+        stderr.contains("skipping differential testing"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn fuzz_pass() {
+    let dir = Dir::new("pass");
+    let interp_command = dir.script("interp.sh", &interp("1", 3));
+    let (code, stderr) = fuzz(&dir, &["--pass", "cat", "--interp", &interp_command]);
+    assert_eq!(code, Some(0), "{}", stderr);
+    assert!(stderr.contains("0 of 1 programs miscompiled"), "{}", stderr);
+
+    let reducer = dir.script("reducer.sh", "cat \"$1\"\n");
+    let args = [
+        "--pass",
+        "false",
+        "--interp",
+        &interp_command,
+        "--reducer",
+        &reducer,
+    ];
+    let (code, stderr) = fuzz(&dir, &args);
+    assert_eq!(code, Some(1), "{}", stderr);
+    assert!(stderr.contains("the pass failed under false"), "{}", stderr);
+    assert!(dir.path("program.reduced.json").exists());
+}
@@ -0,0 +1,374 @@
+// Random programs for fuzzing the passes. They are well typed and always
+// terminate, so any difference between a program and its optimized version
+// is a miscompilation:
+//
+// - Each function is a sequence of statements, some of them branches and
+//   loops holding statements of their own, nested at most `depth` deep.
+// - A statement only reads variables assigned on every path to it, so
+//   nothing reads an undefined variable. A variable first assigned in an arm
+//   of a branch or in a loop body is out of reach after it.
+// - Loops count a variable nothing else assigns up to a constant of at most
+//   `loop_trips`, and functions only call the functions after them, so there
+//   is no recursion.
+// - Divisors are constants other than zero, unless `traps` allows any
+//   integer, when a program may stop with an error on some paths.
+//
+// Values are `int` and `bool`. Variables are assigned again now and then,
+// which gives the passes killed definitions and values to keep apart.
+use crate::rng::Rng;
+use bril_ir::{Function, Instruction, Literal, NamedArg, Program, Type};
+
+// The operations a program may use, as `--ops` names them.
+pub const OPS: [&str; 15] = [
+    "add", "sub", "mul", "div", "eq", "lt", "gt", "le", "ge", "and", "or", "not", "id", "call",
+    "print",
+];
+
+pub struct Config {
+    pub functions: usize,
+    pub statements: usize,
+    pub depth: usize,
+    pub loop_depth: usize,
+    pub loop_trips: i64,
+    pub ops: Vec<String>,
+    pub traps: bool,
+}
+
+impl Config {
+    fn allows(&self, op: &str) -> bool {
+        self.ops.iter().any(|allowed| allowed == op)
+    }
+}
+
+fn int() -> Type {
+    Type::Prim(String::from("int"))
+}
+
+fn boolean() -> Type {
+    Type::Prim(String::from("bool"))
+}
+
+fn op(op: &str) -> Instruction {
+    Instruction {
+        op: Some(op.to_string()),
+        ..Default::default()
+    }
+}
+
+// Builds the instructions of one function.
+struct Builder<'a> {
+    config: &'a Config,
+    rng: &'a mut Rng,
+    instrs: Vec<Instruction>,
+    // The variables assigned on every path to the end of `instrs`.
+    scope: Vec<(String, Type)>,
+    // Loop counters, which only their loop assigns.
+    counters: Vec<String>,
+    // The functions this one may call, with the types of their parameters.
+    callees: &'a [(String, Vec<Type>)],
+    fresh: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.fresh += 1;
+        format!("{}{}", prefix, self.fresh)
+    }
+
+    fn constant(&mut self, type_: &Type) -> String {
+        let dest = self.fresh("c");
+        let value = if *type_ == boolean() {
+            Literal::Bool(self.rng.chance(50))
+        } else {
+            Literal::Int(self.rng.between(-8, 8))
+        };
+        self.instrs.push(Instruction {
+            dest: Some(dest.clone()),
+            type_: Some(type_.clone()),
+            value: Some(value),
+            ..op("const")
+        });
+        self.scope.push((dest.clone(), type_.clone()));
+        dest
+    }
+
+    // A variable of `type_` to read, made up when there is none.
+    fn read(&mut self, type_: &Type) -> String {
+        let vars: Vec<&String> = self
+            .scope
+            .iter()
+            .filter(|(_, t)| t == type_)
+            .map(|(name, _)| name)
+            .collect();
+        if vars.is_empty() || self.rng.chance(10) {
+            return self.constant(type_);
+        }
+        self.rng.pick(&vars).to_string()
+    }
+
+    // A variable of `type_` to assign, now and then one assigned before.
+    fn write(&mut self, type_: &Type) -> String {
+        let vars: Vec<String> = self
+            .scope
+            .iter()
+            .filter(|(name, t)| t == type_ && !self.counters.contains(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !vars.is_empty() && self.rng.chance(30) {
+            return self.rng.pick(&vars).clone();
+        }
+        self.fresh("v")
+    }
+
+    fn assign(&mut self, mut instr: Instruction, type_: Type) {
+        let dest = self.write(&type_);
+        instr.dest = Some(dest.clone());
+        instr.type_ = Some(type_.clone());
+        self.instrs.push(instr);
+        if !self.scope.iter().any(|(name, _)| *name == dest) {
+            self.scope.push((dest, type_));
+        }
+    }
+
+    // An instruction computing a value with one of the allowed operations.
+    fn value(&mut self) -> bool {
+        let ops: Vec<&str> = OPS
+            .iter()
+            .copied()
+            .filter(|op| !["call", "print"].contains(op) && self.config.allows(op))
+            .collect();
+        if ops.is_empty() {
+            return false;
+        }
+        let name = *self.rng.pick(&ops);
+        let (args, type_) = match name {
+            "add" | "sub" | "mul" => (vec![self.read(&int()), self.read(&int())], int()),
+            "div" => {
+                let divisor = if self.config.traps {
+                    self.read(&int())
+                } else {
+                    let dest = self.fresh("c");
+                    let value = self.rng.between(1, 8) * if self.rng.chance(50) { 1 } else { -1 };
+                    self.instrs.push(Instruction {
+                        dest: Some(dest.clone()),
+                        type_: Some(int()),
+                        value: Some(Literal::Int(value)),
+                        ..op("const")
+                    });
+                    dest
+                };
+                (vec![self.read(&int()), divisor], int())
+            }
+            "eq" | "lt" | "gt" | "le" | "ge" => {
+                (vec![self.read(&int()), self.read(&int())], boolean())
+            }
+            "and" | "or" => (
+                vec![self.read(&boolean()), self.read(&boolean())],
+                boolean(),
+            ),
+            "not" => (vec![self.read(&boolean())], boolean()),
+            _ => {
+                let type_ = if self.rng.chance(50) {
+                    int()
+                } else {
+                    boolean()
+                };
+                (vec![self.read(&type_)], type_)
+            }
+        };
+        self.assign(Instruction { args, ..op(name) }, type_);
+        true
+    }
+
+    fn call(&mut self) -> bool {
+        if !self.config.allows("call") || self.callees.is_empty() {
+            return false;
+        }
+        let (callee, params) = self.rng.pick(self.callees);
+        let args = params.iter().map(|type_| self.read(type_)).collect();
+        let call = Instruction {
+            args,
+            funcs: vec![callee.clone()],
+            ..op("call")
+        };
+        self.assign(call, int());
+        true
+    }
+
+    fn print_one(&mut self) -> bool {
+        if self.config.allows("print") {
+            self.print(1);
+        }
+        self.config.allows("print")
+    }
+
+    fn print(&mut self, count: usize) {
+        let args = (0..count)
+            .map(|_| {
+                let type_ = if self.rng.chance(70) {
+                    int()
+                } else {
+                    boolean()
+                };
+                self.read(&type_)
+            })
+            .collect();
+        self.instrs.push(Instruction {
+            args,
+            ..op("print")
+        });
+    }
+
+    fn label(&mut self, name: &str) {
+        self.instrs.push(Instruction {
+            label: Some(name.to_string()),
+            ..Default::default()
+        });
+    }
+
+    fn jump(&mut self, op_name: &str, args: Vec<String>, labels: Vec<String>) {
+        self.instrs.push(Instruction {
+            args,
+            labels,
+            ..op(op_name)
+        });
+    }
+
+    // Statements whose variables are out of reach once they are done.
+    fn nested(&mut self, depth: usize, loops: usize) {
+        let reachable = self.scope.len();
+        let count = self.rng.between(1, self.config.statements as i64) as usize;
+        self.statements(count, depth, loops);
+        self.scope.truncate(reachable);
+    }
+
+    fn branch(&mut self, depth: usize, loops: usize) {
+        let cond = self.read(&boolean());
+        let (then, otherwise, join) = (self.fresh("then"), self.fresh("else"), self.fresh("join"));
+        self.jump("br", vec![cond], vec![then.clone(), otherwise.clone()]);
+        self.label(&then);
+        self.nested(depth + 1, loops);
+        self.jump("jmp", Vec::new(), vec![join.clone()]);
+        self.label(&otherwise);
+        self.nested(depth + 1, loops);
+        self.label(&join);
+    }
+
+    fn repeat(&mut self, depth: usize, loops: usize) {
+        let counter = self.fresh("i");
+        self.counters.push(counter.clone());
+        let trips = self.rng.between(1, self.config.loop_trips.max(1));
+        let (bound, one) = (self.fresh("n"), self.fresh("one"));
+        for (dest, value) in [(&counter, 0), (&bound, trips), (&one, 1)] {
+            self.instrs.push(Instruction {
+                dest: Some(dest.clone()),
+                type_: Some(int()),
+                value: Some(Literal::Int(value)),
+                ..op("const")
+            });
+        }
+        self.scope.push((counter.clone(), int()));
+        let (head, body, exit, more) = (
+            self.fresh("head"),
+            self.fresh("body"),
+            self.fresh("exit"),
+            self.fresh("more"),
+        );
+        self.label(&head);
+        self.instrs.push(Instruction {
+            dest: Some(more.clone()),
+            type_: Some(boolean()),
+            args: vec![counter.clone(), bound],
+            ..op("lt")
+        });
+        self.jump("br", vec![more], vec![body.clone(), exit.clone()]);
+        self.label(&body);
+        self.nested(depth + 1, loops + 1);
+        self.instrs.push(Instruction {
+            dest: Some(counter.clone()),
+            type_: Some(int()),
+            args: vec![counter, one],
+            ..op("add")
+        });
+        self.jump("jmp", Vec::new(), vec![head]);
+        self.label(&exit);
+    }
+
+    fn statements(&mut self, count: usize, depth: usize, loops: usize) {
+        for _ in 0..count {
+            let roll = self.rng.below(100);
+            let nests = depth < self.config.depth;
+            if roll < 10 && nests {
+                self.branch(depth, loops);
+            } else if roll < 18 && nests && loops < self.config.loop_depth {
+                self.repeat(depth, loops);
+            } else {
+                let done = (roll < 26 && self.call()) || (roll < 32 && self.print_one());
+                if !done && !self.value() {
+                    self.print_one();
+                }
+            }
+        }
+    }
+}
+
+// A random program; the same seed gives the same one.
+pub fn program(config: &Config, seed: u64) -> Program {
+    let mut rng = Rng::new(seed);
+    let signatures: Vec<(String, Vec<Type>)> = (0..config.functions.max(1))
+        .map(|f| {
+            if f == 0 {
+                return (String::from("main"), Vec::new());
+            }
+            let params = (0..rng.below(4))
+                .map(|_| if rng.chance(70) { int() } else { boolean() })
+                .collect();
+            (format!("f{}", f), params)
+        })
+        .collect();
+
+    let mut functions = Vec::new();
+    for (f, (name, params)) in signatures.iter().enumerate() {
+        let args: Vec<NamedArg> = params
+            .iter()
+            .enumerate()
+            .map(|(i, type_)| NamedArg {
+                name: format!("a{}", i),
+                type_: type_.clone(),
+            })
+            .collect();
+        let mut builder = Builder {
+            config,
+            rng: &mut rng,
+            instrs: Vec::new(),
+            scope: args
+                .iter()
+                .map(|arg| (arg.name.clone(), arg.type_.clone()))
+                .collect(),
+            counters: Vec::new(),
+            callees: &signatures[f + 1..],
+            fresh: 0,
+        };
+        builder.statements(config.statements, 0, 0);
+        if f == 0 {
+            builder.print(3);
+        } else {
+            let result = builder.read(&int());
+            builder.jump("ret", vec![result], Vec::new());
+        }
+        functions.push(Function {
+            name: name.clone(),
+            instrs: builder.instrs,
+            args,
+            type_: if f == 0 { None } else { Some(int()) },
+            debug: Default::default(),
+            pos: None,
+            pos_end: None,
+            src: None,
+        });
+    }
+    Program {
+        functions,
+        imports: Vec::new(),
+    }
+}
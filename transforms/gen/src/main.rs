@@ -0,0 +1,102 @@
+// Writes random Bril programs to fuzz the passes with, see `generate.rs` for
+// what they look like.
+//
+//     bril-gen --seed 7 | bril2txt
+//     bril-gen --count 100 --out-dir programs --loop-depth 3 --ops add,mul,lt,print
+//     bril-fuzz programs/*.json --pass "lvn --passes gvn,dce"
+//
+// The same flags and seed always give the same program. With `--count`, the
+// programs for the seeds from `--seed` on are written to `--out-dir`, each
+// named after its seed.
+mod generate;
+mod rng;
+
+use bril_ir::error::BrilError;
+use clap::Parser;
+use generate::{Config, OPS};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "bril-gen", about = "Generates random well-typed Bril programs")]
+struct Cli {
+    /// The seed of the first program
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// How many programs to write, one per seed
+    #[arg(long, default_value_t = 1)]
+    count: u64,
+
+    /// The directory to write the programs to instead of stdout
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+
+    /// How many functions a program has, `main` included
+    #[arg(long, default_value_t = 3)]
+    functions: usize,
+
+    /// How many statements a function has, and at most a block nested in it
+    #[arg(long, default_value_t = 12)]
+    statements: usize,
+
+    /// How deep branches and loops nest
+    #[arg(long, default_value_t = 3)]
+    depth: usize,
+
+    /// How deep loops nest
+    #[arg(long, default_value_t = 2)]
+    loop_depth: usize,
+
+    /// The most times a loop goes around
+    #[arg(long, default_value_t = 4)]
+    loop_trips: i64,
+
+    /// The operations to use, separated by commas
+    #[arg(long, value_delimiter = ',', default_values_t = OPS.map(String::from))]
+    ops: Vec<String>,
+
+    /// Divide by any integer, zero included, rather than by constants
+    #[arg(long)]
+    traps: bool,
+
+    /// Indent the JSON and sort its keys
+    #[arg(long)]
+    pretty: bool,
+}
+
+fn run() -> Result<(), BrilError> {
+    let cli = Cli::parse();
+    if let Some(op) = cli.ops.iter().find(|op| !OPS.contains(&op.as_str())) {
+        return Err(BrilError::usage(format!("unknown operation: {}", op)));
+    }
+    if cli.count > 1 && cli.out_dir.is_none() {
+        return Err(BrilError::usage("--count needs an --out-dir"));
+    }
+    let config = Config {
+        functions: cli.functions,
+        statements: cli.statements.max(1),
+        depth: cli.depth,
+        loop_depth: cli.loop_depth,
+        loop_trips: cli.loop_trips,
+        ops: cli.ops.clone(),
+        traps: cli.traps,
+    };
+    for seed in cli.seed..cli.seed + cli.count {
+        let json = bril_cli::to_json(&generate::program(&config, seed), cli.pretty);
+        match &cli.out_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| BrilError::io(dir.display().to_string(), e))?;
+                let path = dir.join(format!("{}.json", seed));
+                std::fs::write(&path, json)
+                    .map_err(|e| BrilError::io(path.display().to_string(), e))?;
+            }
+            None => print!("{}", json),
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    bril_cli::exit_on_error("bril-gen", run());
+}
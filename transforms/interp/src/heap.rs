@@ -0,0 +1,88 @@
+// The memory `alloc` takes from, modelled as `brili` models it: each
+// allocation is an array of its own, found by a base number handed out in
+// order, and a pointer is a base and an offset into the array. An offset
+// can go anywhere with `ptradd`; it is only checked when the pointer is
+// used, so reading or writing outside an allocation, or one already freed,
+// stops the program instead of reaching some other allocation.
+use crate::{error, InterpError};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub base: usize,
+    pub offset: i64,
+}
+
+impl Key {
+    pub fn add(self, offset: i64) -> Key {
+        Key {
+            base: self.base,
+            offset: self.offset.wrapping_add(offset),
+        }
+    }
+}
+
+// The allocations still live, by base, with what has been stored in each
+// of their slots so far.
+pub struct Heap<X> {
+    storage: HashMap<usize, Vec<Option<X>>>,
+    count: usize,
+}
+
+impl<X: Copy> Heap<X> {
+    pub fn new() -> Self {
+        Heap {
+            storage: HashMap::new(),
+            count: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn alloc(&mut self, amount: i64) -> Result<Key, InterpError> {
+        if amount <= 0 {
+            return Err(error(format!(
+                "must allocate a positive amount of memory: {} <= 0",
+                amount
+            )));
+        }
+        let base = self.count;
+        self.count += 1;
+        self.storage.insert(base, vec![None; amount as usize]);
+        Ok(Key { base, offset: 0 })
+    }
+
+    pub fn free(&mut self, key: Key) -> Result<(), InterpError> {
+        if key.offset == 0 && self.storage.remove(&key.base).is_some() {
+            return Ok(());
+        }
+        Err(error(format!(
+            "Tried to free illegal memory location base: {}, offset: {}. Offset must be 0.",
+            key.base, key.offset
+        )))
+    }
+
+    pub fn write(&mut self, key: Key, value: X) -> Result<(), InterpError> {
+        *self.slot(key)? = Some(value);
+        Ok(())
+    }
+
+    // What was last stored at `key`, or `None` if nothing has been yet.
+    pub fn read(&mut self, key: Key) -> Result<Option<X>, InterpError> {
+        self.slot(key).map(|slot| *slot)
+    }
+
+    fn slot(&mut self, key: Key) -> Result<&mut Option<X>, InterpError> {
+        let data = self.storage.get_mut(&key.base);
+        match (data, usize::try_from(key.offset)) {
+            (Some(data), Ok(offset)) if offset < data.len() => Ok(&mut data[offset]),
+            _ => Err(error(format!(
+                "Uninitialized heap location {} and/or illegal offset {}",
+                key.base, key.offset
+            ))),
+        }
+    }
+}
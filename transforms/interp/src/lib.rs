@@ -0,0 +1,546 @@
+// An interpreter for Bril programs as the transforms read them, so that what
+// a pass makes of a program can be run right away, without the TypeScript
+// `brili`:
+//
+//     bril2json < program.bril | lvn | bril-interp -p 5
+//
+// It runs the core language: integer and boolean arithmetic, comparisons and
+// logic, `const`, `id`, `print`, `nop`, and control flow with `jmp`, `br`,
+// `call` and `ret`, along with the memory extension: `alloc`, `free`,
+// `load`, `store` and `ptradd` on the heap in `heap`. `main` is called with
+// the arguments on the command line, read as the types of its parameters.
+//
+// It follows `brili` in what it checks as the program runs and in what it
+// says when a check fails, so the two can be compared on the same tests:
+// the arguments and results of calls are checked against the types of the
+// function, the operations get as many arguments as they take and of the
+// types they take, and integers wrap around at 64 bits. A `div` by zero
+// stops the program, as does an operation `brili` does not know. So does
+// memory used after it is freed or outside what was allocated, memory read
+// before anything is stored in it, and memory still allocated when `main`
+// returns.
+//
+// Calls do not recurse in Rust: each one pushes a frame, so a program can
+// recurse as deep as it has memory for.
+mod heap;
+
+use bril_ir::{Function, Instruction, Literal, Program, Type};
+use heap::{Heap, Key};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    Int(i64),
+    Bool(bool),
+    // Where in the heap it points, and the type of what it points to.
+    Pointer(Key, &'a Type),
+}
+
+impl Value<'_> {
+    // Whether the value is one of type `type_`. Like `brili`, any pointer is
+    // taken to be of any pointer type.
+    fn has_type(&self, type_: &Type) -> Result<bool, InterpError> {
+        match (type_, self) {
+            (Type::Prim(name), Value::Int(_)) if name == "int" => Ok(true),
+            (Type::Prim(name), Value::Bool(_)) if name == "bool" => Ok(true),
+            (Type::Prim(name), _) if name == "int" || name == "bool" => Ok(false),
+            (Type::Ptr { .. }, value) => Ok(matches!(value, Value::Pointer(..))),
+            _ => Err(error(format!("unknown type {}", type_))),
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            // What `brili` prints for a pointer, which is any JavaScript
+            // object made a string.
+            Value::Pointer(..) => write!(f, "[object Object]"),
+        }
+    }
+}
+
+// What stops a program before it ends: something wrong with it, like a
+// variable read before it is assigned, or a trap, like a division by zero.
+#[derive(Debug, Error)]
+pub enum InterpError {
+    #[error("{0}")]
+    Program(String),
+
+    #[error("cannot write the output: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+fn error(message: impl Into<String>) -> InterpError {
+    InterpError::Program(message.into())
+}
+
+// How many arguments each operation takes, or `None` for those taking any
+// number.
+fn arity(op: &str) -> Option<Option<usize>> {
+    let count = match op {
+        "id" | "not" | "br" | "alloc" | "free" | "load" => Some(1),
+        "add" | "mul" | "sub" | "div" | "eq" | "lt" | "gt" | "le" | "ge" | "and" | "or" => Some(2),
+        "store" | "ptradd" => Some(2),
+        "jmp" | "nop" => Some(0),
+        "print" | "ret" | "call" => None,
+        _ => return None,
+    };
+    Some(count)
+}
+
+// The functions of a program by name, with where each of their labels is.
+struct Functions<'a> {
+    functions: HashMap<&'a str, Vec<&'a Function>>,
+    labels: HashMap<&'a str, HashMap<&'a str, usize>>,
+}
+
+impl<'a> Functions<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut functions: HashMap<&str, Vec<&Function>> = HashMap::new();
+        let mut labels = HashMap::new();
+        for function in &program.functions {
+            functions.entry(&function.name).or_default().push(function);
+            let mut positions = HashMap::new();
+            for (i, instr) in function.instrs.iter().enumerate() {
+                if let Some(label) = &instr.label {
+                    positions.entry(label.as_str()).or_insert(i);
+                }
+            }
+            labels.entry(function.name.as_str()).or_insert(positions);
+        }
+        Functions { functions, labels }
+    }
+
+    fn find(&self, name: &str) -> Result<&'a Function, InterpError> {
+        match self.functions.get(name).map(Vec::as_slice) {
+            Some([function]) => Ok(function),
+            Some(_) => Err(error(format!("multiple functions of name {} found", name))),
+            None => Err(error(format!("no function of name {} found", name))),
+        }
+    }
+
+    fn label(&self, function: &Function, label: &str) -> Result<usize, InterpError> {
+        self.labels[function.name.as_str()]
+            .get(label)
+            .copied()
+            .ok_or_else(|| error(format!("label {} not found", label)))
+    }
+}
+
+// A call being run: the function, the next instruction of it to run, its
+// variables, and the call it was made by, which says where its result goes.
+struct Frame<'a> {
+    function: &'a Function,
+    pc: usize,
+    env: HashMap<&'a str, Value<'a>>,
+    call: Option<&'a Instruction>,
+}
+
+// What to do after running an instruction.
+enum Action<'a> {
+    Next,
+    Jump(&'a str),
+    Call(Frame<'a>),
+    Return(Option<Value<'a>>),
+}
+
+fn op(instr: &Instruction) -> &str {
+    instr.op.as_deref().unwrap_or("nop")
+}
+
+fn get<'a>(env: &HashMap<&str, Value<'a>>, var: &str) -> Result<Value<'a>, InterpError> {
+    env.get(var)
+        .copied()
+        .ok_or_else(|| error(format!("undefined variable {}", var)))
+}
+
+fn argument<'a>(
+    instr: &Instruction,
+    env: &HashMap<&str, Value<'a>>,
+    index: usize,
+) -> Result<Value<'a>, InterpError> {
+    match instr.args.get(index) {
+        Some(var) => get(env, var),
+        None => Err(error(format!(
+            "{} expected at least {} arguments; got {}",
+            op(instr),
+            index + 1,
+            instr.args.len()
+        ))),
+    }
+}
+
+fn get_int(
+    instr: &Instruction,
+    env: &HashMap<&str, Value>,
+    index: usize,
+) -> Result<i64, InterpError> {
+    match argument(instr, env, index)? {
+        Value::Int(value) => Ok(value),
+        _ => Err(error(format!(
+            "{} argument {} must be a int",
+            op(instr),
+            index
+        ))),
+    }
+}
+
+fn get_bool(
+    instr: &Instruction,
+    env: &HashMap<&str, Value>,
+    index: usize,
+) -> Result<bool, InterpError> {
+    match argument(instr, env, index)? {
+        Value::Bool(value) => Ok(value),
+        _ => Err(error(format!(
+            "{} argument {} must be a bool",
+            op(instr),
+            index
+        ))),
+    }
+}
+
+fn get_pointer<'a>(
+    instr: &Instruction,
+    env: &HashMap<&str, Value<'a>>,
+    index: usize,
+) -> Result<(Key, &'a Type), InterpError> {
+    match argument(instr, env, index)? {
+        Value::Pointer(key, type_) => Ok((key, type_)),
+        _ => Err(error(format!(
+            "{} argument {} must be a Pointer",
+            op(instr),
+            index
+        ))),
+    }
+}
+
+fn label(instr: &Instruction, index: usize) -> Result<&str, InterpError> {
+    match instr.labels.get(index) {
+        Some(label) => Ok(label),
+        None if instr.labels.is_empty() => Err(error(format!(
+            "missing labels; expected at least {}",
+            index + 1
+        ))),
+        None => Err(error(format!(
+            "expecting {} labels; found {}",
+            index + 1,
+            instr.labels.len()
+        ))),
+    }
+}
+
+fn dest(instr: &Instruction) -> Result<&str, InterpError> {
+    instr
+        .dest
+        .as_deref()
+        .ok_or_else(|| error(format!("{} needs a destination", op(instr))))
+}
+
+// The value of a `const`. A number written with a fraction is rounded down
+// for an integer, as `brili` does.
+fn constant(instr: &Instruction) -> Result<Value<'static>, InterpError> {
+    let type_ = instr
+        .type_
+        .as_ref()
+        .ok_or_else(|| error("const needs a type"))?;
+    match (instr.literal(), type_) {
+        (Some(Literal::Int(value)), Type::Prim(name)) if name == "int" => Ok(Value::Int(value)),
+        (Some(Literal::Float(value)), Type::Prim(name)) if name == "int" => {
+            Ok(Value::Int(value.floor() as i64))
+        }
+        (Some(Literal::Bool(value)), Type::Prim(name)) if name == "bool" => Ok(Value::Bool(value)),
+        (None, _) => Err(error("const needs a value")),
+        (Some(value), _) => Err(error(format!("cannot use {} as a {}", value, type_))),
+    }
+}
+
+fn eval<'a>(
+    instr: &'a Instruction,
+    frame: &mut Frame<'a>,
+    functions: &Functions<'a>,
+    heap: &mut Heap<Value<'a>>,
+    out: &mut impl Write,
+) -> Result<Action<'a>, InterpError> {
+    let op = op(instr);
+    if op != "const" {
+        match arity(op) {
+            None => return Err(error(format!("unknown opcode {}", op))),
+            Some(Some(count)) if instr.args.len() != count => {
+                return Err(error(format!(
+                    "{} takes {} argument(s); got {}",
+                    op,
+                    count,
+                    instr.args.len()
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let env = &frame.env;
+    let value = match op {
+        "const" => constant(instr)?,
+        "id" => argument(instr, env, 0)?,
+        "add" => Value::Int(get_int(instr, env, 0)?.wrapping_add(get_int(instr, env, 1)?)),
+        "mul" => Value::Int(get_int(instr, env, 0)?.wrapping_mul(get_int(instr, env, 1)?)),
+        "sub" => Value::Int(get_int(instr, env, 0)?.wrapping_sub(get_int(instr, env, 1)?)),
+        "div" => {
+            let (lhs, rhs) = (get_int(instr, env, 0)?, get_int(instr, env, 1)?);
+            if rhs == 0 {
+                return Err(error("division by zero"));
+            }
+            Value::Int(lhs.wrapping_div(rhs))
+        }
+        "eq" => Value::Bool(get_int(instr, env, 0)? == get_int(instr, env, 1)?),
+        "lt" => Value::Bool(get_int(instr, env, 0)? < get_int(instr, env, 1)?),
+        "gt" => Value::Bool(get_int(instr, env, 0)? > get_int(instr, env, 1)?),
+        "le" => Value::Bool(get_int(instr, env, 0)? <= get_int(instr, env, 1)?),
+        "ge" => Value::Bool(get_int(instr, env, 0)? >= get_int(instr, env, 1)?),
+        "not" => Value::Bool(!get_bool(instr, env, 0)?),
+        "and" => Value::Bool(get_bool(instr, env, 0)? && get_bool(instr, env, 1)?),
+        "or" => Value::Bool(get_bool(instr, env, 0)? || get_bool(instr, env, 1)?),
+        "print" => {
+            let values = instr
+                .args
+                .iter()
+                .map(|var| get(env, var).map(|value| value.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            writeln!(out, "{}", values.join(" "))?;
+            return Ok(Action::Next);
+        }
+        "jmp" => return Ok(Action::Jump(label(instr, 0)?)),
+        "br" => {
+            let index = if get_bool(instr, env, 0)? { 0 } else { 1 };
+            return Ok(Action::Jump(label(instr, index)?));
+        }
+        "ret" => {
+            return match instr.args.as_slice() {
+                [] => Ok(Action::Return(None)),
+                [var] => Ok(Action::Return(Some(get(env, var)?))),
+                args => Err(error(format!(
+                    "ret takes 0 or 1 argument(s); got {}",
+                    args.len()
+                ))),
+            }
+        }
+        "call" => return call(instr, env, functions).map(Action::Call),
+        "alloc" => {
+            let amount = get_int(instr, env, 0)?;
+            let type_ = match &instr.type_ {
+                Some(Type::Ptr { ptr }) => ptr,
+                Some(type_) => {
+                    return Err(error(format!("cannot allocate non-pointer type {}", type_)))
+                }
+                None => return Err(error("cannot allocate non-pointer type undefined")),
+            };
+            Value::Pointer(heap.alloc(amount)?, type_)
+        }
+        "free" => {
+            heap.free(get_pointer(instr, env, 0)?.0)?;
+            return Ok(Action::Next);
+        }
+        "store" => {
+            let (key, type_) = get_pointer(instr, env, 0)?;
+            let value = argument(instr, env, 1)?;
+            if !value.has_type(type_)? {
+                return Err(error(format!("store argument 1 must be a {}", type_)));
+            }
+            heap.write(key, value)?;
+            return Ok(Action::Next);
+        }
+        "load" => {
+            let (key, _) = get_pointer(instr, env, 0)?;
+            heap.read(key)?.ok_or_else(|| {
+                error(format!(
+                    "Pointer {} points to uninitialized data",
+                    instr.args[0]
+                ))
+            })?
+        }
+        "ptradd" => {
+            let (key, type_) = get_pointer(instr, env, 0)?;
+            Value::Pointer(key.add(get_int(instr, env, 1)?), type_)
+        }
+        _ => return Ok(Action::Next),
+    };
+    frame.env.insert(dest(instr)?, value);
+    Ok(Action::Next)
+}
+
+// The frame of the function `instr` calls, with its parameters bound to the
+// arguments of the call.
+fn call<'a>(
+    instr: &'a Instruction,
+    env: &HashMap<&'a str, Value<'a>>,
+    functions: &Functions<'a>,
+) -> Result<Frame<'a>, InterpError> {
+    let name = instr
+        .funcs
+        .first()
+        .ok_or_else(|| error("missing functions; expected at least 1"))?;
+    let function = functions.find(name)?;
+    if function.args.len() != instr.args.len() {
+        return Err(error(format!(
+            "function expected {} arguments, got {}",
+            function.args.len(),
+            instr.args.len()
+        )));
+    }
+    let mut callee = HashMap::new();
+    for (param, var) in function.args.iter().zip(&instr.args) {
+        let value = get(env, var)?;
+        if !value.has_type(&param.type_)? {
+            return Err(error("function argument type mismatch"));
+        }
+        callee.insert(param.name.as_str(), value);
+    }
+    Ok(Frame {
+        function,
+        pc: 0,
+        env: callee,
+        call: Some(instr),
+    })
+}
+
+// Checks what `function` returned to `call` against the types of both, and
+// gives the variable the result goes to, if any.
+fn returned<'a>(
+    call: &'a Instruction,
+    function: &Function,
+    value: Option<Value<'a>>,
+) -> Result<Option<(&'a str, Value<'a>)>, InterpError> {
+    let declared = || {
+        function
+            .type_
+            .as_ref()
+            .map_or(String::from("undefined"), Type::to_string)
+    };
+    let dest = match &call.dest {
+        None if value.is_some() => {
+            return Err(error("unexpected value returned without destination"))
+        }
+        None if function.type_.is_some() => {
+            let message = format!(
+                "non-void function (type: {}) doesn't return anything",
+                declared()
+            );
+            return Err(error(message));
+        }
+        None => return Ok(None),
+        Some(dest) => dest,
+    };
+    let type_ = call
+        .type_
+        .as_ref()
+        .ok_or_else(|| error("function call must include a type if it has a destination"))?;
+    let value = value.ok_or_else(|| {
+        error(format!(
+            "non-void function (type: {}) doesn't return anything",
+            declared()
+        ))
+    })?;
+    if !value.has_type(type_)? {
+        return Err(error(
+            "type of value returned by function does not match destination type",
+        ));
+    }
+    match &function.type_ {
+        None => Err(error("function with void return type used in value call")),
+        Some(declared) if declared != type_ => Err(error(
+            "type of value returned by function does not match declaration",
+        )),
+        Some(_) => Ok(Some((dest, value))),
+    }
+}
+
+// The arguments of `main` from the command line, as the types of its
+// parameters read them.
+fn main_args<'a>(
+    main: &'a Function,
+    args: &[String],
+) -> Result<HashMap<&'a str, Value<'a>>, InterpError> {
+    if main.args.len() != args.len() {
+        return Err(error(format!(
+            "mismatched main argument arity: expected {}; got {}",
+            main.args.len(),
+            args.len()
+        )));
+    }
+    let mut env = HashMap::new();
+    for (param, arg) in main.args.iter().zip(args) {
+        let value = match &param.type_ {
+            Type::Prim(name) if name == "int" => arg.parse().map(Value::Int).map_err(|_| {
+                error(format!(
+                    "int argument to main must be an integer; got {}",
+                    arg
+                ))
+            })?,
+            Type::Prim(name) if name == "bool" => match arg.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => {
+                    return Err(error(format!(
+                        "boolean argument to main must be 'true'/'false'; got {}",
+                        arg
+                    )))
+                }
+            },
+            type_ => return Err(error(format!("unknown type {}", type_))),
+        };
+        env.insert(param.name.as_str(), value);
+    }
+    Ok(env)
+}
+
+// Runs the `main` of `program` on `args`, printing to `out`, and gives the
+// number of instructions it ran, labels aside, which `-p` reports.
+pub fn run(program: &Program, args: &[String], out: &mut impl Write) -> Result<u64, InterpError> {
+    let functions = Functions::new(program);
+    let main = functions.find("main")?;
+    let mut stack = vec![Frame {
+        function: main,
+        pc: 0,
+        env: main_args(main, args)?,
+        call: None,
+    }];
+    let mut heap = Heap::new();
+    let mut count = 0;
+    while let Some(frame) = stack.last_mut() {
+        let action = match frame.function.instrs.get(frame.pc) {
+            // Running off the end of a function returns nothing.
+            None => Action::Return(None),
+            Some(instr) if instr.is_label() => Action::Next,
+            Some(instr) => {
+                count += 1;
+                eval(instr, frame, &functions, &mut heap, out)?
+            }
+        };
+        frame.pc += 1;
+        match action {
+            Action::Next => {}
+            Action::Jump(label) => frame.pc = functions.label(frame.function, label)?,
+            Action::Call(callee) => stack.push(callee),
+            Action::Return(value) => {
+                let callee = stack.pop().unwrap();
+                if let (Some(call), Some(caller)) = (callee.call, stack.last_mut()) {
+                    if let Some((dest, value)) = returned(call, callee.function, value)? {
+                        caller.env.insert(dest, value);
+                    }
+                }
+            }
+        }
+    }
+    if !heap.is_empty() {
+        return Err(error(
+            "Some memory locations have not been freed by end of execution.",
+        ));
+    }
+    Ok(count)
+}
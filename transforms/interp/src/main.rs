@@ -0,0 +1,53 @@
+// Runs a Bril program read from stdin as JSON, with the arguments of its
+// `main` on the command line, like `brili`:
+//
+//     bril2json < program.bril | bril-interp [-p] [ARGS...]
+//
+// An error in the program, or a trap, is printed as `error: message` and the
+// interpreter exits with 2, as `brili` does; with `-p`, the number of
+// instructions run is printed to stderr as `total_dyn_inst: N` once the program
+// ends. Input that is not a Bril program is reported as by the other tools.
+use bril_ir::error::BrilError;
+use bril_ir::Program;
+use clap::Parser;
+use std::io::{BufReader, BufWriter, Write};
+
+#[derive(Parser, Debug)]
+#[command(name = "bril-interp", about = "Runs a Bril program")]
+struct Cli {
+    /// Print the number of instructions run to stderr
+    #[arg(short = 'p', long)]
+    profile: bool,
+
+    /// The arguments of `main`
+    #[arg(value_name = "ARGS", allow_negative_numbers = true)]
+    args: Vec<String>,
+}
+
+fn run(cli: &Cli) -> Result<(), BrilError> {
+    let program: Program =
+        serde_json::from_reader(BufReader::new(std::io::stdin())).map_err(|e| {
+            if e.is_io() {
+                BrilError::io("stdin", e.into())
+            } else {
+                BrilError::Parse(e.to_string())
+            }
+        })?;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let result = bril_interp::run(&program, &cli.args, &mut out);
+    // What the program printed comes before what stopped it.
+    let result = out.flush().map_err(Into::into).and(result);
+    match result {
+        Ok(count) if cli.profile => eprintln!("total_dyn_inst: {}", count),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    bril_cli::exit_on_error("bril-interp", run(&Cli::parse()));
+}
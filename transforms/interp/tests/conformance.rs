@@ -0,0 +1,167 @@
+// Runs the interpreter over the test programs `brili` is checked with and
+// compares what it prints, and the errors it stops with, to what `brili`
+// does.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn to_json(source: &str) -> String {
+    let program =
+        bril2json::parse_abstract_program_from_read(source.as_bytes(), false, false, None);
+    serde_json::to_string(&program).expect("Failed to serialize program")
+}
+
+// The arguments for `main` a test program asks for with `# ARGS:`.
+fn arguments(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("# ARGS:"))
+        .map_or_else(Vec::new, |args| args.split_whitespace().collect())
+}
+
+fn interp(source: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bril-interp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run bril-interp");
+    let program = to_json(source);
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(program.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// The test programs in `dir`, relative to the root of the repository.
+fn programs(dir: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .join(dir);
+    let mut programs: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "bril"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no programs in {}", dir.display());
+    programs
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+// Each program prints what its `.out` file holds.
+fn check_outputs(dir: &str) {
+    for path in programs(dir) {
+        let source = read(&path);
+        let output = interp(&source, &arguments(&source));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            stdout,
+            read(&path.with_extension("out")),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+// Each program stops with exit code 2 and the error its `.err` file holds.
+fn check_errors(dir: &str) {
+    for path in programs(dir) {
+        let source = read(&path);
+        let output = interp(&source, &arguments(&source));
+        assert_eq!(output.status.code(), Some(2), "{}", path.display());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(
+            stderr,
+            read(&path.with_extension("err")),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn core_programs_print() {
+    check_outputs("test/interp/core");
+}
+
+#[test]
+fn core_errors_stop() {
+    check_errors("test/interp-error/core-error");
+}
+
+#[test]
+fn memory_programs_print() {
+    check_outputs("test/interp/mem");
+    check_outputs("benchmarks/mem");
+}
+
+#[test]
+fn memory_errors_stop() {
+    check_errors("test/interp-error/mem-error");
+
+    let program = r#"
+@main {
+  one: int = const 1;
+  x: int = load one;
+}
+"#;
+    let output = interp(program, &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: load argument 0 must be a Pointer\n"
+    );
+    let output = interp(&program.replace("load one", "alloc one"), &[]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: cannot allocate non-pointer type int\n"
+    );
+}
+
+#[test]
+fn profile_counts_instructions() {
+    let program = r#"
+@main(n: int) {
+  one: int = const 1;
+  i: int = const 0;
+.loop:
+  done: bool = eq i n;
+  br done .end .body;
+.body:
+  i: int = add i one;
+  jmp .loop;
+.end:
+  print i;
+}
+"#;
+    // Two to start, four for each time round the loop, two to leave it and
+    // the print; labels are not counted.
+    let output = interp(program, &["-p", "3"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "total_dyn_inst: 17\n"
+    );
+
+    let output = interp(program, &["-p", "-1", "2"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: mismatched main argument arity: expected 1; got 2\n"
+    );
+}
@@ -0,0 +1,198 @@
+// Measures how fast CFG construction and the passes get through large
+// generated functions, to compare a change that is meant to make them faster
+// with the code before it:
+//
+//     cargo bench --bench passes [FILTER...]
+//
+// Each benchmark runs until it has taken a second, at least five times, and
+// reports the median and the fastest run with the instructions handled per
+// second. Only the benchmarks whose names contain one of the filters run.
+//
+// CFG construction is timed in process. The passes live in the lvn binary,
+// so it is run on each program with `--metrics`, and the time the pass
+// itself took over the function comes from there; `lvn/...` is that time and
+// `process/...` the whole run, reading and writing the JSON included.
+//
+// The programs are `straight`, one block of arithmetic on a few arguments
+// with many expressions computed more than once, and `branchy`, diamonds of
+// a few instructions each.
+use bril_cfg::construct_control_flow_graph;
+use bril_ir::Function;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 3] = [1_000, 10_000, 50_000];
+
+// The passes to time with the pipelines to run them in; gvn only changes
+// programs in SSA form.
+const PASSES: [(&str, &str); 3] = [("lvn", "lvn"), ("gvn", "to-ssa,gvn"), ("dce", "dce")];
+
+fn instr(dest: &str, op: &str, args: &[&str]) -> Value {
+    json!({"dest": dest, "type": "int", "op": op, "args": args})
+}
+
+fn program(instrs: Vec<Value>) -> Value {
+    json!({"functions": [{
+        "name": "main",
+        "args": [{"name": "a", "type": "int"}, {"name": "b", "type": "int"}],
+        "instrs": instrs,
+    }]})
+}
+
+// About `size` instructions of arithmetic, each reading the arguments or the
+// values of the last few, and every third one recomputing an earlier
+// expression. Now and then a variable is assigned again.
+fn straight(size: usize) -> Value {
+    const OPS: [&str; 3] = ["add", "mul", "sub"];
+    let mut instrs = Vec::new();
+    let mut vars = vec![String::from("a"), String::from("b")];
+    for i in 0..size {
+        let op = OPS[i % OPS.len()];
+        let (x, y) = if i % 3 == 2 && i >= 6 {
+            (&vars[vars.len() - 5], &vars[vars.len() - 6])
+        } else {
+            (&vars[vars.len() - 1], &vars[vars.len() - 2])
+        };
+        let dest = if i % 17 == 16 {
+            vars[vars.len() / 2].clone()
+        } else {
+            format!("v{}", i)
+        };
+        instrs.push(instr(&dest, op, &[x, y]));
+        vars.push(dest);
+    }
+    instrs.push(json!({"op": "print", "args": [vars.last().unwrap()]}));
+    program(instrs)
+}
+
+// Diamonds of a branch, two arms and a join, about `size` instructions in all.
+fn branchy(size: usize) -> Value {
+    let mut instrs = vec![instr("x", "id", &["a"])];
+    for d in 0..size / 8 {
+        let (then, otherwise, join) = (
+            format!("then{}", d),
+            format!("else{}", d),
+            format!("join{}", d),
+        );
+        instrs.push(json!({"dest": "c", "type": "bool", "op": "lt", "args": ["x", "b"]}));
+        instrs.push(json!({"op": "br", "args": ["c"], "labels": [then, otherwise]}));
+        instrs.push(json!({ "label": then }));
+        instrs.push(instr("x", "add", &["x", "a"]));
+        instrs.push(json!({"op": "jmp", "labels": [join]}));
+        instrs.push(json!({ "label": otherwise }));
+        instrs.push(instr("x", "sub", &["x", "b"]));
+        instrs.push(json!({ "label": join }));
+    }
+    instrs.push(json!({"op": "print", "args": ["x"]}));
+    program(instrs)
+}
+
+// Runs `run` until a second has gone by, at least five times and at most a
+// hundred, and gives the time of each run in order.
+fn measure(mut run: impl FnMut() -> Duration) -> Vec<Duration> {
+    let start = Instant::now();
+    let mut times = Vec::new();
+    while times.len() < 5 || (times.len() < 100 && start.elapsed() < Duration::from_secs(1)) {
+        times.push(run());
+    }
+    times
+}
+
+fn report(name: &str, instrs: usize, mut times: Vec<Duration>) {
+    times.sort();
+    let median = times[times.len() / 2];
+    println!(
+        "{:<28} median {:>9.3} ms  fastest {:>9.3} ms  {:>7.2} M instrs/s  ({} runs)",
+        name,
+        median.as_secs_f64() * 1e3,
+        times[0].as_secs_f64() * 1e3,
+        instrs as f64 / median.as_secs_f64() / 1e6,
+        times.len()
+    );
+}
+
+// Runs lvn with `passes` over `program`, and gives the time `pass` took and
+// that of the whole run.
+fn run_lvn(program: &[u8], pass: &str, passes: &str) -> (Duration, Duration) {
+    let metrics = std::env::temp_dir().join(format!("lvn-bench-{}.json", std::process::id()));
+    let start = Instant::now();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lvn"))
+        .arg("--passes")
+        .arg(passes)
+        .arg("--metrics")
+        .arg(&metrics)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .expect("Failed to run lvn");
+    child.stdin.take().unwrap().write_all(program).unwrap();
+    let status = child.wait().expect("Failed to run lvn");
+    let process = start.elapsed();
+    assert!(status.success(), "lvn failed with {}", passes);
+
+    let contents = std::fs::read_to_string(&metrics).expect("lvn wrote no metrics");
+    let _ = std::fs::remove_file(&metrics);
+    let metrics: Value = serde_json::from_str(&contents).expect("Failed to parse metrics");
+    let seconds = metrics["passes"]
+        .as_array()
+        .and_then(|metrics| metrics.iter().find(|metric| metric["pass"] == pass))
+        .and_then(|metric| metric["seconds"].as_f64())
+        .expect("No time for the pass in the metrics");
+    (Duration::from_secs_f64(seconds), process)
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`, which is not a filter.
+    let filters: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    let wanted = |name: &str| filters.is_empty() || filters.iter().any(|f| name.contains(f));
+
+    for size in SIZES {
+        let programs = [("straight", straight(size)), ("branchy", branchy(size))];
+        for (shape, program) in &programs {
+            let function: Function = serde_json::from_value(program["functions"][0].clone())
+                .expect("Failed to build the function");
+            let instrs = function.instrs.len();
+
+            let name = format!("cfg/{}-{}", shape, size);
+            if wanted(&name) {
+                let times = measure(|| {
+                    let start = Instant::now();
+                    let cfg = construct_control_flow_graph(&function);
+                    let elapsed = start.elapsed();
+                    assert!(!cfg.is_empty());
+                    elapsed
+                });
+                report(&name, instrs, times);
+            }
+
+            let json = program.to_string();
+            for (pass, passes) in PASSES {
+                let (name, process_name) = (
+                    format!("{}/{}-{}", pass, shape, size),
+                    format!("process/{}/{}-{}", pass, shape, size),
+                );
+                if !wanted(&name) && !wanted(&process_name) {
+                    continue;
+                }
+                let mut processes = Vec::new();
+                let times = measure(|| {
+                    let (pass_time, process) = run_lvn(json.as_bytes(), pass, passes);
+                    processes.push(process);
+                    pass_time
+                });
+                if wanted(&name) {
+                    report(&name, instrs, times);
+                }
+                if wanted(&process_name) {
+                    report(&process_name, instrs, processes);
+                }
+            }
+        }
+    }
+}
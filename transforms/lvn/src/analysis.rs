@@ -0,0 +1,200 @@
+use crate::{construct_control_flow_graph, ControlFlowGraph, Program};
+use bril_cfg::alias::{AliasAnalysis, Location};
+use bril_cfg::callgraph::CallGraph;
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::liveness::Liveness;
+use bril_cfg::loops::LoopForest;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// The dominators of each block as a set, read off the dominator tree.
+pub fn compute_dominators(cfg: &ControlFlowGraph) -> Vec<BTreeSet<usize>> {
+    let tree = DominatorTree::new(cfg);
+    (0..cfg.blocks.len())
+        .map(|b| tree.dominators(b).into_iter().collect())
+        .collect()
+}
+
+// The post-dominators of each block as a set, without the synthetic exit
+// that every block returning from the function leads to.
+pub fn compute_postdominators(cfg: &ControlFlowGraph) -> Vec<BTreeSet<usize>> {
+    let tree = DominatorTree::postdominators(cfg);
+    let exit = cfg.blocks.len();
+    (0..exit)
+        .map(|b| {
+            tree.dominators(b)
+                .into_iter()
+                .filter(|&d| d != exit)
+                .collect()
+        })
+        .collect()
+}
+
+fn names(vars: &BTreeSet<String>) -> String {
+    if vars.is_empty() {
+        String::from("-")
+    } else {
+        vars.iter().cloned().collect::<Vec<_>>().join(" ")
+    }
+}
+
+fn block_name(cfg: &ControlFlowGraph, b: usize) -> String {
+    match cfg.blocks[b].label() {
+        Some(label) => format!(".{}", label),
+        None => format!("<block {}>", b),
+    }
+}
+
+fn block_names(cfg: &ControlFlowGraph, blocks: impl IntoIterator<Item = usize>) -> String {
+    let names: Vec<String> = blocks.into_iter().map(|b| block_name(cfg, b)).collect();
+    if names.is_empty() {
+        String::from("-")
+    } else {
+        names.join(" ")
+    }
+}
+
+// Prints the natural loops of each function, one per line by header, with
+// nested loops indented under the loops containing them.
+pub fn program_loops(program: &Program) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        writeln!(out, "@{}", function.name).unwrap();
+        let cfg = construct_control_flow_graph(function);
+        let forest = LoopForest::new(&cfg);
+        for l in &forest.loops {
+            writeln!(
+                out,
+                "{}{}: body: {} | latches: {} | exits: {} | preheader: {}",
+                "  ".repeat(1 + l.depth(&forest)),
+                block_name(&cfg, l.header),
+                block_names(&cfg, l.body.iter().copied()),
+                block_names(&cfg, l.latches.iter().copied()),
+                block_names(&cfg, l.exits.iter().copied()),
+                block_names(&cfg, l.preheader)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+fn function_names(names: impl IntoIterator<Item = String>) -> String {
+    let names: Vec<String> = names.into_iter().map(|name| format!("@{}", name)).collect();
+    if names.is_empty() {
+        String::from("-")
+    } else {
+        names.join(" ")
+    }
+}
+
+// Prints the call graph, one line per function with the functions it calls,
+// those it calls that the program does not define, and its callers, then the
+// strongly connected components, callees first.
+pub fn program_calls(program: &Program) -> String {
+    let graph = CallGraph::new(&program.functions);
+    let named = |fs: BTreeSet<usize>| fs.into_iter().map(|f| graph.names[f].clone());
+    let mut out = String::new();
+    for (f, name) in graph.names.iter().enumerate() {
+        let undefined: BTreeSet<String> = graph.calls[f]
+            .iter()
+            .map(|&s| &graph.sites[s])
+            .filter(|site| site.callee.is_none())
+            .map(|site| site.name.clone())
+            .collect();
+        writeln!(
+            out,
+            "@{}: calls: {} | undefined: {} | callers: {}{}",
+            name,
+            function_names(named(graph.callees(f))),
+            function_names(undefined),
+            function_names(named(graph.callers_of(f))),
+            if graph.is_recursive(f) {
+                " | recursive"
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+    let components: Vec<String> = graph
+        .components
+        .iter()
+        .map(|c| function_names(c.iter().map(|&f| graph.names[f].clone())))
+        .collect();
+    writeln!(out, "components: {}", components.join(" | ")).unwrap();
+    out
+}
+
+// Prints the variables live into and out of each block, one block per line.
+pub fn program_liveness(program: &Program) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        writeln!(out, "@{}", function.name).unwrap();
+        let cfg = construct_control_flow_graph(function);
+        let liveness = Liveness::new(&cfg);
+        for (b, block) in cfg.iter() {
+            let name = match block.label() {
+                Some(label) => format!(".{}", label),
+                None => format!("<block {}>", b),
+            };
+            writeln!(
+                out,
+                "  {}: in: {} | out: {}",
+                name,
+                names(&liveness.live_in[b]),
+                names(&liveness.live_out[b])
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+// Prints the allocation sites of each function, with whether they escape,
+// then each pointer variable with where it may point and the others it may
+// alias.
+pub fn program_aliases(program: &Program) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        writeln!(out, "@{}", function.name).unwrap();
+        let cfg = construct_control_flow_graph(function);
+        let aliases = AliasAnalysis::new(&function.args, &cfg);
+        for (s, &(b, i)) in aliases.sites.iter().enumerate() {
+            writeln!(
+                out,
+                "  site {}: {} {}{}",
+                s,
+                block_name(&cfg, b),
+                cfg.blocks[b].instrs[i],
+                if aliases.escapes(s) { " | escapes" } else { "" }
+            )
+            .unwrap();
+        }
+        let pointers = aliases.pointers();
+        for &var in &pointers {
+            let locations: Vec<String> = aliases
+                .points_to(var)
+                .iter()
+                .map(|location| match location {
+                    Location::Site(s) => format!("site {}", s),
+                    Location::Unknown => String::from("unknown"),
+                })
+                .collect();
+            let others: BTreeSet<String> = pointers
+                .iter()
+                .filter(|&&other| other != var && aliases.may_alias(var, other))
+                .map(|other| other.to_string())
+                .collect();
+            writeln!(
+                out,
+                "  {}: {} | may alias: {}",
+                var,
+                locations.join(" "),
+                names(&others)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
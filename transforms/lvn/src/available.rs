@@ -0,0 +1,199 @@
+use crate::superlocal::Key;
+use crate::{
+    construct_control_flow_graph, ControlFlowGraph, Expression, Function, Instruction, Program,
+};
+use bril_ir::PURE_OPS;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+// Numbers every variable of a function, in the order they first appear, so
+// an expression keyed the way value numbering keys it means the same thing in
+// every block: `Expression::Op("add", [a, b])` reads the variables numbered
+// `a` and `b`, whichever block it is in.
+#[derive(Debug, Default)]
+pub struct Variables {
+    numbers: HashMap<String, usize>,
+    names: Vec<String>,
+}
+
+impl Variables {
+    pub fn new(function: &Function) -> Self {
+        let mut vars = Variables::default();
+        for arg in &function.args {
+            vars.number(&arg.name);
+        }
+        for instr in &function.instrs {
+            for arg in instr.local_args() {
+                vars.number(arg);
+            }
+            if let Some(dest) = &instr.dest {
+                vars.number(dest);
+            }
+        }
+        vars
+    }
+
+    pub fn number(&mut self, var: &str) -> usize {
+        if let Some(&number) = self.numbers.get(var) {
+            return number;
+        }
+        self.names.push(var.to_string());
+        self.numbers.insert(var.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    pub fn name(&self, number: usize) -> &str {
+        &self.names[number]
+    }
+
+    // The expression `instr` computes, if it is one two evaluations with the
+    // same arguments always agree on.
+    pub fn expression(&mut self, instr: &Instruction) -> Option<Key> {
+        let op = instr.op.as_deref()?;
+        instr.dest.as_ref()?;
+        if !PURE_OPS.contains(&op) {
+            return None;
+        }
+        let args = instr
+            .local_args()
+            .iter()
+            .map(|arg| self.number(arg))
+            .collect();
+        Some((
+            instr.type_.clone(),
+            Expression::pure_op(op.to_string(), args),
+        ))
+    }
+
+    // Prints the expression as the instruction computing it would read.
+    pub fn describe(&self, key: &Key) -> String {
+        match &key.1 {
+            Expression::Op(op, args) => {
+                let args: Vec<&str> = args.iter().map(|&arg| self.name(arg)).collect();
+                format!("{} {}", op, args.join(" "))
+            }
+            Expression::Const(value) => format!("const {}", value),
+        }
+    }
+}
+
+// Available expressions: an expression is available at a point when every
+// path from the entry evaluates it, and none of its arguments is assigned
+// after the last evaluation. A forward analysis, solved per block with
+// in = ∩ out(predecessors) and out = gen ∪ (in − kill); the entry starts
+// with nothing available.
+#[derive(Debug)]
+pub struct AvailableExpressions {
+    pub vars: Variables,
+    pub avail_in: Vec<HashSet<Key>>,
+    pub avail_out: Vec<HashSet<Key>>,
+}
+
+fn uses(key: &Key, var: usize) -> bool {
+    match &key.1 {
+        Expression::Op(_, args) => args.contains(&var),
+        Expression::Const(_) => false,
+    }
+}
+
+// Moves availability from before `instr` to after it.
+fn step(vars: &mut Variables, avail: &mut HashSet<Key>, instr: &Instruction) {
+    let expression = vars.expression(instr);
+    if let Some(dest) = &instr.dest {
+        let dest = vars.number(dest);
+        avail.retain(|key| !uses(key, dest));
+    }
+    // `x = add x y` evaluates the expression but then changes its argument.
+    if let Some(key) = expression {
+        let dest = vars.number(instr.dest.as_ref().unwrap());
+        if !uses(&key, dest) {
+            avail.insert(key);
+        }
+    }
+}
+
+impl AvailableExpressions {
+    pub fn new(function: &Function, cfg: &ControlFlowGraph) -> Self {
+        let mut vars = Variables::new(function);
+        let n = cfg.len();
+        // Blocks not visited yet could have anything available, which is
+        // what the intersection starts from.
+        let mut avail_out: Vec<Option<HashSet<Key>>> = vec![None; n];
+        let mut avail_in = vec![HashSet::new(); n];
+        let mut order = cfg.reverse_postorder();
+        let reachable: HashSet<usize> = order.iter().copied().collect();
+        order.extend((0..n).filter(|b| !reachable.contains(b)));
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in &order {
+                let block = &cfg.blocks[b];
+                let mut avail: Option<HashSet<Key>> = None;
+                if cfg.entry() != Some(b) {
+                    for &prev in &block.prev_blocks {
+                        if let Some(out) = &avail_out[prev] {
+                            avail = Some(match avail {
+                                None => out.clone(),
+                                Some(avail) => avail.intersection(out).cloned().collect(),
+                            });
+                        }
+                    }
+                }
+                let mut avail = avail.unwrap_or_default();
+                avail_in[b] = avail.clone();
+                for instr in &block.instrs {
+                    step(&mut vars, &mut avail, instr);
+                }
+                if avail_out[b].as_ref() != Some(&avail) {
+                    avail_out[b] = Some(avail);
+                    changed = true;
+                }
+            }
+        }
+        AvailableExpressions {
+            vars,
+            avail_in,
+            avail_out: avail_out
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect(),
+        }
+    }
+
+    fn describe_all(&self, keys: &HashSet<Key>) -> String {
+        let mut names: Vec<String> = keys.iter().map(|key| self.vars.describe(key)).collect();
+        names.sort();
+        if names.is_empty() {
+            String::from("-")
+        } else {
+            names.join(", ")
+        }
+    }
+}
+
+// Prints the expressions available on entry to and exit from each block, one
+// block per line.
+pub fn program_available(program: &Program) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        writeln!(out, "@{}", function.name).unwrap();
+        let cfg = construct_control_flow_graph(function);
+        let available = AvailableExpressions::new(function, &cfg);
+        for (b, block) in cfg.iter() {
+            let name = match block.label() {
+                Some(label) => format!(".{}", label),
+                None => format!("<block {}>", b),
+            };
+            writeln!(
+                out,
+                "  {}: in: {} | out: {}",
+                name,
+                available.describe_all(&available.avail_in[b]),
+                available.describe_all(&available.avail_out[b])
+            )
+            .unwrap();
+        }
+    }
+    out
+}
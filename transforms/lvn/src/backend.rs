@@ -0,0 +1,157 @@
+use crate::bisect::OptBisect;
+use crate::remarks::Remarks;
+use crate::ssa::from_ssa;
+use crate::structure::Node;
+use crate::{BrilError, Function, Instruction, Literal, Type};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+// Helpers shared by the backends that print Bril as source code in another
+// language.
+
+// Turns a Bril name into an identifier: letters and digits are kept and
+// everything else is escaped with an underscore.
+pub fn mangle(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => out.push(c),
+            '_' => out.push_str("__"),
+            '.' => out.push_str("_d"),
+            _ => write!(out, "_u{:x}_", c as u32).unwrap(),
+        }
+    }
+    out
+}
+
+// Whether a backend has a representation for `type_`: the core types and
+// pointers to them.
+pub fn supported_type(type_: &Type) -> bool {
+    match type_ {
+        Type::Prim(name) => matches!(name.as_str(), "int" | "bool" | "float"),
+        Type::Ptr { ptr } => supported_type(ptr),
+    }
+}
+
+// The function the way the backends translate it: out of SSA form, with
+// only types they support and without speculation, which they have no way
+// to roll back.
+pub fn lower(function: &Function, backend: &str) -> Result<Function, BrilError> {
+    let unsupported = |instr: &Instruction, what: String| {
+        BrilError::malformed(
+            instr,
+            format!("the {} backend does not support {}", backend, what),
+        )
+        .in_function(function)
+    };
+    for instr in &function.instrs {
+        if instr.is_speculation_op() {
+            return Err(unsupported(instr, String::from("speculation")));
+        }
+        if let Some(Literal::Char(_)) = instr.value {
+            return Err(unsupported(instr, String::from("the type char")));
+        }
+        match &instr.type_ {
+            Some(type_) if !supported_type(type_) => {
+                return Err(unsupported(instr, format!("the type {}", type_)));
+            }
+            _ => {}
+        }
+    }
+    let signature = function
+        .args
+        .iter()
+        .map(|arg| &arg.type_)
+        .chain(&function.type_);
+    for type_ in signature {
+        if !supported_type(type_) {
+            return Err(BrilError::malformed_program(format!(
+                "the {} backend does not support the type {}",
+                backend, type_
+            ))
+            .in_function(function));
+        }
+    }
+    let mut function = function.clone();
+    from_ssa(
+        &mut function,
+        &mut Remarks::new(false),
+        &mut OptBisect::new(None),
+        0,
+    );
+    Ok(function)
+}
+
+// The variables whose shadows a function `set`s or `get`s, with the type of
+// each; `types` are those of its variables.
+pub fn shadow_types(function: &Function, types: &BTreeMap<String, Type>) -> BTreeMap<String, Type> {
+    let mut shadows = BTreeMap::new();
+    for instr in &function.instrs {
+        let shadow = match (instr.op.as_deref(), &instr.dest, instr.args.as_slice()) {
+            (Some("get"), Some(dest), _) => instr.type_.clone().map(|type_| (dest, type_)),
+            (Some("set"), _, [dest, value]) => types.get(value).cloned().map(|type_| (dest, type_)),
+            _ => None,
+        };
+        if let Some((name, type_)) = shadow {
+            shadows.entry(name.clone()).or_insert(type_);
+        }
+    }
+    shadows
+}
+
+// The type of every variable of a function, taken from its first definition.
+pub fn variable_types(function: &Function) -> BTreeMap<String, Type> {
+    let mut types: BTreeMap<String, Type> = function
+        .args
+        .iter()
+        .map(|arg| (arg.name.clone(), arg.type_.clone()))
+        .collect();
+    for instr in &function.instrs {
+        if let (Some(dest), Some(type_)) = (&instr.dest, &instr.type_) {
+            types.entry(dest.clone()).or_insert_with(|| type_.clone());
+        }
+    }
+    types
+}
+
+// Collects the labels control transfers in `node` refer to, named
+// `break_<block>`, `top_<block>` and `block_<block>`. Continuing the
+// innermost loop needs no label. `loops` holds the enclosing loop headers.
+pub fn collect_targets(node: &Node, targets: &mut HashSet<String>, loops: &mut Vec<usize>) {
+    match node {
+        Node::Seq(nodes) => {
+            for node in nodes {
+                collect_targets(node, targets, loops);
+            }
+        }
+        Node::If {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_targets(then_branch, targets, loops);
+            collect_targets(else_branch, targets, loops);
+        }
+        Node::Block { body, .. } => collect_targets(body, targets, loops),
+        Node::Loop { label, body } => {
+            loops.push(*label);
+            collect_targets(body, targets, loops);
+            loops.pop();
+        }
+        Node::Break(label) => {
+            targets.insert(format!("break_{}", label));
+        }
+        Node::Continue(label) if loops.last() != Some(label) => {
+            targets.insert(format!("top_{}", label));
+        }
+        Node::Dispatch { arms, .. } => {
+            for (_, arm) in arms {
+                collect_targets(arm, targets, loops);
+            }
+        }
+        Node::Goto(label) => {
+            targets.insert(format!("block_{}", label));
+        }
+        Node::Code(_) | Node::Continue(_) | Node::Return(_) => {}
+    }
+}
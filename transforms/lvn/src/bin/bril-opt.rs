@@ -0,0 +1,377 @@
+// Optimizes a program the way lvn does and summarizes what changed.
+//
+//     bril-opt [--watch] program.bril [-p PASSES | --pipeline FILE]
+//              [--config bril.toml] [--interp CMD] [--check CMD]
+//              [--fold-div-by-zero POLICY] [-- lvn-args...]
+//     bril-opt [-p PASSES | --pipeline FILE] [--config bril.toml]
+//              [--fold-div-by-zero POLICY] [-o FILE] [--pretty]
+//              [-- lvn-args...] < program.json
+//
+// `-p` gives the pipeline, like `-p lvn,dce,licm`, which runs over each
+// function in order; it defaults to the `passes` setting
+// of `bril.toml`, or just `lvn`. `--pipeline` reads it from a file instead,
+// see `pipeline.rs`. Without a program file, or with `-`, bril-opt is a
+// filter like lvn itself: the program is read from stdin as JSON and the
+// optimized one is written to stdout, or `-o`, without a summary. The
+// options are those of the other Rust tools, see `bril-cli`.
+//
+// Text programs are converted with `bril2json` first. For each function the
+// summary gives its instruction count before and after optimizing. With
+// `--interp`, the original and the optimized program are both run with `-p`
+// on the arguments from the file's `ARGS:` line, and their output and
+// dynamic instruction counts are compared. With `--check`, the optimized
+// program is piped into the command, a verifier for example, which should
+// exit successfully.
+//
+// `--interp` and `--check` default to the `interp` and `check` settings of
+// the `[driver]` table in `bril.toml`, which lvn reads its defaults from too.
+//
+// The comparison follows `--fold-div-by-zero`, which is passed on to lvn as
+// well: under `preserve`, the default, a program that traps must still trap
+// the same way once optimized. Under `poison` and `zero` the output of a
+// program that traps is undefined or not what the interpreter computes, so it
+// is not compared.
+//
+// With `--watch` the file is polled for changes and everything is run again
+// after each one. Lines of the summary that differ from the previous run are
+// marked with `*`, so the effect of an edit stands out.
+use bril_cli::CommonArgs;
+use bril_ir::error::BrilError;
+use bril_ir::Program;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use lvn::cli::Cli;
+use lvn::config;
+use lvn::trapping::DivByZero;
+use lvn::Optimizer;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "bril-opt",
+    about = "Optimizes a program the way lvn does and summarizes what changed"
+)]
+struct Args {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Run everything again each time the program file changes
+    #[arg(long)]
+    watch: bool,
+
+    /// The passes to run, separated by commas, like `lvn,dce`
+    #[arg(short, long, value_name = "PASSES")]
+    passes: Option<String>,
+
+    /// Run the pipeline described in this TOML or JSON file
+    #[arg(long, value_name = "FILE", conflicts_with = "passes")]
+    pipeline: Option<String>,
+
+    /// Read the defaults from this file instead of the nearest bril.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
+    /// Run the original and the optimized program with this interpreter
+    /// and compare what they do
+    #[arg(long, value_name = "CMD")]
+    interp: Option<String>,
+
+    /// Pipe the optimized program into this command, which should succeed
+    #[arg(long, value_name = "CMD")]
+    check: Option<String>,
+
+    /// Whether dividing by zero may be folded away, and into what
+    #[arg(long, value_name = "POLICY", value_parser = ["preserve", "poison", "zero"])]
+    fold_div_by_zero: Option<String>,
+
+    /// More flags for the optimizer, as lvn takes them
+    #[arg(last = true, value_name = "LVN-ARGS")]
+    lvn_args: Vec<String>,
+}
+
+struct Options {
+    path: String,
+    watch: bool,
+    interp: Option<Vec<String>>,
+    check: Option<Vec<String>>,
+    div_by_zero: DivByZero,
+    // The optimizer's command line.
+    lvn: Cli,
+}
+
+struct Output {
+    success: bool,
+    stdout: Vec<u8>,
+    stderr: String,
+}
+
+// The words of `command`, or `error` if there are none.
+fn split_command(
+    command: &str,
+    error: impl FnOnce() -> BrilError,
+) -> Result<Vec<String>, BrilError> {
+    let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+    if words.is_empty() {
+        return Err(error());
+    }
+    Ok(words)
+}
+
+fn parse_options() -> Result<Options, BrilError> {
+    let args = Args::parse();
+    let driver = config::driver(args.config.as_deref().map(Path::new))?;
+    // The command of `--<key>`, or else of the `<key>` setting.
+    let command = |flag: &Option<String>, key: &str| match flag {
+        Some(command) => split_command(command, || {
+            BrilError::usage(format!("--{} needs a command", key))
+        })
+        .map(Some),
+        None => driver
+            .string(key)?
+            .map(|command| {
+                split_command(command, || driver.error(format!("{} needs a command", key)))
+            })
+            .transpose(),
+    };
+    let fold_div_by_zero = match &args.fold_div_by_zero {
+        Some(name) => Some(name.clone()),
+        None => driver.string("fold-div-by-zero")?.map(String::from),
+    };
+
+    // The flags lvn shares are passed on to it.
+    let mut lvn_args = vec![String::from("lvn")];
+    let shared = [
+        ("--passes", &args.passes),
+        ("--pipeline", &args.pipeline),
+        ("--config", &args.config),
+        ("--fold-div-by-zero", &args.fold_div_by_zero),
+    ];
+    for (flag, value) in shared {
+        if let Some(value) = value {
+            lvn_args.extend([String::from(flag), value.clone()]);
+        }
+    }
+    lvn_args.extend(args.lvn_args.iter().cloned());
+    let mut lvn = Cli::try_parse_from(lvn_args).unwrap_or_else(|e| e.exit());
+    lvn.common = args.common.clone();
+
+    let path = args
+        .common
+        .input_path()
+        .map(|path| path.display().to_string());
+    if path.is_none() && (args.watch || args.interp.is_some() || args.check.is_some()) {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--watch, --interp and --check need a program file",
+            )
+            .exit();
+    }
+    Ok(Options {
+        path: path.unwrap_or_default(),
+        watch: args.watch,
+        interp: command(&args.interp, "interp")?,
+        check: command(&args.check, "check")?,
+        div_by_zero: fold_div_by_zero
+            .as_deref()
+            .and_then(DivByZero::parse)
+            .unwrap_or_default(),
+        lvn,
+    })
+}
+
+fn run(command: &Path, args: &[String], input: &[u8]) -> Result<Output, BrilError> {
+    let failed = |e| BrilError::io(command.display().to_string(), e);
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+    // The command may not read all of its input, so ignore broken pipes.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output().map_err(failed)?;
+    Ok(Output {
+        success: output.status.success(),
+        stdout: output.stdout,
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+fn run_command(command: &[String], args: &[String], input: &[u8]) -> Result<Output, BrilError> {
+    let args: Vec<String> = command[1..].iter().chain(args).cloned().collect();
+    run(Path::new(&command[0]), &args, input)
+}
+
+// The program as JSON.
+fn load(path: &str, source: &str) -> Result<Vec<u8>, BrilError> {
+    if path.ends_with(".json") {
+        return Ok(source.as_bytes().to_vec());
+    }
+    let output = run(Path::new("bril2json"), &[], source.as_bytes())?;
+    if output.success {
+        Ok(output.stdout)
+    } else {
+        let message = format!("bril2json failed:\n{}", output.stderr.trim_end());
+        Err(BrilError::Parse(message))
+    }
+}
+
+fn instruction_counts(program: &Program) -> Vec<(&str, usize)> {
+    program
+        .functions
+        .iter()
+        .map(|function| {
+            let count = function.instrs.iter().filter(|i| i.op.is_some()).count();
+            (function.name.as_str(), count)
+        })
+        .collect()
+}
+
+// `program` optimized as `cli` says, with the original as it was read.
+fn optimize(cli: &Cli, program: &[u8]) -> Result<(Program, Program), BrilError> {
+    let optimizer = Optimizer::new(cli)?;
+    let original: Program =
+        serde_json::from_slice(program).map_err(|e| BrilError::Parse(e.to_string()))?;
+    let mut optimized = original.clone();
+    let exported = lvn::prepare(&mut optimized, &cli.common, &cli.libs)?;
+    optimizer.optimize(&mut optimized, exported)?;
+    Ok((original, optimized))
+}
+
+// The arguments on the first `ARGS:` line, as turnt reads them.
+fn program_args(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .find_map(|line| line.split_once("ARGS:"))
+        .map(|(_, args)| args.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn dyn_insts(interp: &Output) -> Option<u64> {
+    interp
+        .stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("total_dyn_inst: "))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+fn describe_run(interp: &Output) -> String {
+    match (interp.success, dyn_insts(interp)) {
+        (true, Some(count)) => count.to_string(),
+        (true, None) => String::from("?"),
+        (false, _) => String::from("error"),
+    }
+}
+
+fn summarize(options: &Options) -> Result<Vec<String>, BrilError> {
+    let source =
+        std::fs::read_to_string(&options.path).map_err(|e| BrilError::io(&options.path, e))?;
+    let original = load(&options.path, &source)?;
+    let (program, optimized) = optimize(&options.lvn, &original)?;
+
+    let mut lines = Vec::new();
+    let before = instruction_counts(&program);
+    let after = instruction_counts(&optimized);
+    let optimized = serde_json::to_vec(&optimized).expect("Failed to serialize the program");
+    for ((name, before), (_, after)) in before.iter().zip(&after) {
+        lines.push(format!("@{}: {} -> {} instructions", name, before, after));
+    }
+
+    if let Some(interp) = &options.interp {
+        let mut args = vec![String::from("-p")];
+        args.extend(program_args(&source));
+        let expected = run_command(interp, &args, &original)?;
+        let actual = run_command(interp, &args, &optimized)?;
+        let verdict = if !expected.success && options.div_by_zero != DivByZero::Preserve {
+            format!(
+                "not compared, the original traps under --fold-div-by-zero={}",
+                options.div_by_zero.name()
+            )
+        } else if expected.success == actual.success && expected.stdout == actual.stdout {
+            String::from("same")
+        } else {
+            String::from("DIFFERS")
+        };
+        lines.push(format!(
+            "output: {} (dynamic instructions: {} -> {})",
+            verdict,
+            describe_run(&expected),
+            describe_run(&actual)
+        ));
+    }
+
+    if let Some(check) = &options.check {
+        let output = run_command(check, &[], &optimized)?;
+        if output.success {
+            lines.push(String::from("check: passed"));
+        } else {
+            lines.push(format!("check: FAILED\n{}", output.stderr.trim_end()));
+        }
+    }
+    Ok(lines)
+}
+
+// The summary of a run while watching, which goes on after an error.
+fn watched(summary: Result<Vec<String>, BrilError>) -> Vec<String> {
+    summary.unwrap_or_else(|e| vec![format!("error: {}", e)])
+}
+
+fn print_summary(lines: &[String], previous: Option<&[String]>) {
+    for (i, line) in lines.iter().enumerate() {
+        let marker = match previous {
+            Some(previous) if previous.get(i) != Some(line) => "* ",
+            Some(_) => "  ",
+            None => "",
+        };
+        println!("{}{}", marker, line);
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn run_driver() -> Result<(), BrilError> {
+    let options = parse_options()?;
+    if options.path.is_empty() {
+        return lvn::run(&options.lvn);
+    }
+
+    if !options.watch {
+        print_summary(&summarize(&options)?, None);
+        return Ok(());
+    }
+    // The time is taken first so that an edit made during a run starts
+    // another one.
+    let mut last_modified = modified(&options.path);
+    let mut previous = watched(summarize(&options));
+    print_summary(&previous, None);
+
+    for run in 2.. {
+        // Editors often write a file in several steps, so wait for it to
+        // settle before reading it.
+        loop {
+            std::thread::sleep(Duration::from_millis(200));
+            let now = modified(&options.path);
+            if now != last_modified {
+                last_modified = now;
+                std::thread::sleep(Duration::from_millis(50));
+                break;
+            }
+        }
+        let lines = watched(summarize(&options));
+        println!("\n== {} (run {})", options.path, run);
+        print_summary(&lines, Some(&previous));
+        previous = lines;
+    }
+    Ok(())
+}
+
+fn main() {
+    bril_cli::exit_on_error("bril-opt", run_driver());
+}
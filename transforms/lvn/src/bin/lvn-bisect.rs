@@ -0,0 +1,110 @@
+// Finds the first LVN decision that breaks a program.
+//
+//     lvn-bisect program.json -- test-command args...
+//
+// The test command receives the optimized program on stdin and should exit
+// successfully if it behaves correctly. lvn-bisect binary-searches over
+// `lvn --opt-bisect-limit N` for the smallest N that makes the test fail.
+// When lvn itself fails, lvn-bisect passes on what lvn said and its exit
+// code.
+use bril_ir::error::BrilError;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+struct Run {
+    optimized: Vec<u8>,
+    log: String,
+}
+
+const USAGE: &str = "usage: lvn-bisect program.json -- test-command args...";
+
+fn run_lvn(lvn: &PathBuf, program: &[u8], limit: usize) -> Result<Run, BrilError> {
+    let failed = |e| BrilError::io(lvn.display().to_string(), e);
+    let mut child = Command::new(lvn)
+        .arg("--opt-bisect-limit")
+        .arg(limit.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+    // lvn reads all of its input unless it fails, which it then says.
+    let _ = child.stdin.take().unwrap().write_all(program);
+    let output = child.wait_with_output().map_err(failed)?;
+    if !output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        std::process::exit(output.status.code().unwrap_or(1));
+    }
+    Ok(Run {
+        optimized: output.stdout,
+        log: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+fn passes(test: &[String], optimized: &[u8]) -> Result<bool, BrilError> {
+    let failed = |e| BrilError::io(&test[0], e);
+    let mut child = Command::new(&test[0])
+        .args(&test[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(failed)?;
+    // The test may not read all of its input, so ignore broken pipes.
+    let _ = child.stdin.take().unwrap().write_all(optimized);
+    Ok(child.wait().map_err(failed)?.success())
+}
+
+fn run() -> Result<(), BrilError> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let split = args.iter().position(|a| a == "--");
+    if split != Some(1) || args.len() == 2 {
+        return Err(BrilError::usage(USAGE));
+    }
+    let program = std::fs::read(&args[0]).map_err(|e| BrilError::io(&args[0], e))?;
+    let test = &args[2..];
+    let lvn = std::env::current_exe()
+        .map_err(|e| BrilError::io("lvn-bisect", e))?
+        .with_file_name("lvn");
+
+    if !passes(test, &run_lvn(&lvn, &program, 0)?.optimized)? {
+        println!("The test fails even without any LVN decisions.");
+        std::process::exit(1);
+    }
+    let full = run_lvn(&lvn, &program, usize::MAX)?;
+    let total = full
+        .log
+        .lines()
+        .filter(|l| l.starts_with("BISECT:"))
+        .count();
+    if passes(test, &full.optimized)? {
+        println!("The test passes with all {} LVN decisions.", total);
+        return Ok(());
+    }
+
+    // Invariant: `good` decisions pass, `bad` decisions fail.
+    let (mut good, mut bad) = (0, total);
+    while bad - good > 1 {
+        let mid = good + (bad - good) / 2;
+        eprintln!("Trying --opt-bisect-limit {}", mid);
+        if passes(test, &run_lvn(&lvn, &program, mid)?.optimized)? {
+            good = mid;
+        } else {
+            bad = mid;
+        }
+    }
+    let culprit = full
+        .log
+        .lines()
+        .filter(|l| l.starts_with("BISECT:"))
+        .nth(bad - 1)
+        .unwrap();
+    println!("First bad decision is {} of {}:", bad, total);
+    println!("{}", culprit.replacen("BISECT: running ", "", 1));
+    Ok(())
+}
+
+fn main() {
+    bril_cli::exit_on_error("lvn-bisect", run());
+}
@@ -0,0 +1,32 @@
+// Counts transformation decisions across the whole run and vetoes every
+// decision after the first `limit`, so a miscompilation can be narrowed down
+// to the exact decision that introduced it. Every decision is logged to
+// stderr in the same style as LLVM's -opt-bisect-limit.
+#[derive(Debug, Default)]
+pub struct OptBisect {
+    limit: Option<usize>,
+    count: usize,
+}
+
+impl OptBisect {
+    pub fn new(limit: Option<usize>) -> Self {
+        OptBisect { limit, count: 0 }
+    }
+
+    pub fn should_run(&mut self, pass: &str, description: &str) -> bool {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return true,
+        };
+        self.count += 1;
+        let run = self.count <= limit;
+        eprintln!(
+            "BISECT: {} decision ({}) {}: {}",
+            if run { "running" } else { "NOT running" },
+            self.count,
+            pass,
+            description
+        );
+        run
+    }
+}
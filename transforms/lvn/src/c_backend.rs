@@ -0,0 +1,378 @@
+use crate::backend::{collect_targets, lower, mangle, shadow_types, variable_types};
+use crate::structure::{structure, Node};
+use crate::{
+    construct_control_flow_graph, BrilError, ControlFlowGraph, Function, Instruction, Literal,
+    Program, Type,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+// Printing and allocation behave like the reference interpreter: floats are
+// printed with 17 decimals, and running out of the heap, dividing by zero or
+// leaking memory are errors with exit status 2.
+const RUNTIME: &str = r#"#include <inttypes.h>
+#include <math.h>
+#include <stdbool.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static int64_t bril_live_allocations = 0;
+
+static inline void bril_error(const char *message) {
+  fprintf(stderr, "error: %s\n", message);
+  exit(2);
+}
+
+static inline void bril_print_int(int64_t v) { printf("%" PRId64, v); }
+
+static inline void bril_print_bool(bool v) { fputs(v ? "true" : "false", stdout); }
+
+static inline void bril_print_float(double v) {
+  if (isnan(v)) {
+    fputs("NaN", stdout);
+  } else if (isinf(v)) {
+    fputs(v > 0 ? "Infinity" : "-Infinity", stdout);
+  } else {
+    printf("%.17f", v);
+  }
+}
+
+static inline int64_t bril_div(int64_t a, int64_t b) {
+  if (b == 0) bril_error("division by zero");
+  if (a == INT64_MIN && b == -1) return INT64_MIN;
+  return a / b;
+}
+
+static inline void *bril_alloc(int64_t count, size_t size) {
+  if (count <= 0) bril_error("must allocate a positive amount of memory");
+  void *p = calloc((size_t)count, size);
+  if (!p) bril_error("out of memory");
+  bril_live_allocations++;
+  return p;
+}
+
+static inline void bril_free(void *p) {
+  free(p);
+  bril_live_allocations--;
+}
+
+static inline void bril_check_leaks(void) {
+  if (bril_live_allocations != 0)
+    bril_error("Some memory locations have not been freed by end of execution.");
+}
+"#;
+
+fn var(name: &str) -> String {
+    format!("v_{}", mangle(name))
+}
+
+// Where `set` leaves a value for the `get` of the variable.
+fn shadow(name: &str) -> String {
+    format!("s_{}", mangle(name))
+}
+
+fn func(name: &str) -> String {
+    format!("f_{}", mangle(name))
+}
+
+fn c_type(type_: &Type) -> String {
+    match type_ {
+        Type::Prim(name) => match name.as_str() {
+            "int" => String::from("int64_t"),
+            "bool" => String::from("bool"),
+            "float" => String::from("double"),
+            // `lower` rejects the rest.
+            _ => unreachable!("unsupported type {}", name),
+        },
+        Type::Ptr { ptr } => format!("{} *", c_type(ptr)),
+    }
+}
+
+fn return_type(function: &Function) -> String {
+    match &function.type_ {
+        Some(type_) => c_type(type_),
+        None => String::from("void"),
+    }
+}
+
+fn literal(value: Literal) -> String {
+    match value {
+        Literal::Int(i64::MIN) => String::from("INT64_MIN"),
+        Literal::Int(value) => format!("INT64_C({})", value),
+        Literal::Bool(value) => value.to_string(),
+        Literal::Float(value) if value.is_nan() => String::from("NAN"),
+        Literal::Float(value) if value.is_infinite() => {
+            String::from(if value > 0.0 { "INFINITY" } else { "-INFINITY" })
+        }
+        Literal::Float(value) => format!("{:?}", value),
+        Literal::Char(_) => unreachable!("`lower` rejects char"),
+    }
+}
+
+struct Emitter<'a> {
+    cfg: &'a ControlFlowGraph,
+    types: BTreeMap<String, Type>,
+    // Labels something jumps to, so that no unused ones are emitted.
+    targets: HashSet<String>,
+    // Headers of the loops being emitted, innermost last.
+    loops: Vec<usize>,
+    out: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn line(&mut self, depth: usize, text: &str) {
+        writeln!(self.out, "{}{}", "  ".repeat(depth), text).unwrap();
+    }
+
+    // A label is a statement of its own, so it may end a block.
+    fn label(&mut self, depth: usize, name: String) {
+        if self.targets.contains(&name) {
+            self.line(depth, &format!("{}:;", name));
+        }
+    }
+
+    fn instr(&mut self, depth: usize, instr: &Instruction) -> Result<(), BrilError> {
+        let op = instr.op.as_deref().unwrap_or_default();
+        let a: Vec<String> = instr.args.iter().map(|arg| var(arg)).collect();
+        let binary = |symbol: &str| format!("{} {} {}", a[0], symbol, a[1]);
+        // Signed overflow is undefined in C but wraps in Bril.
+        let wrapping = |symbol: &str| {
+            format!(
+                "(int64_t)((uint64_t){} {} (uint64_t){})",
+                a[0], symbol, a[1]
+            )
+        };
+        let value = match op {
+            "const" => match instr.value {
+                Some(value) => literal(value),
+                None => return Err(BrilError::malformed(instr, "a constant needs a value")),
+            },
+            "id" => a[0].clone(),
+            "add" => wrapping("+"),
+            "sub" => wrapping("-"),
+            "mul" => wrapping("*"),
+            "div" => format!("bril_div({}, {})", a[0], a[1]),
+            "eq" | "feq" => binary("=="),
+            "lt" | "flt" => binary("<"),
+            "gt" | "fgt" => binary(">"),
+            "le" | "fle" => binary("<="),
+            "ge" | "fge" => binary(">="),
+            "fadd" => binary("+"),
+            "fsub" => binary("-"),
+            "fmul" => binary("*"),
+            "fdiv" => binary("/"),
+            "not" => format!("!{}", a[0]),
+            "and" => binary("&&"),
+            "or" => binary("||"),
+            "call" => format!("{}({})", func(&instr.funcs[0]), a.join(", ")),
+            "alloc" => {
+                let pointee = match &instr.type_ {
+                    Some(Type::Ptr { ptr }) => c_type(ptr),
+                    _ => return Err(BrilError::malformed(instr, "`alloc` needs a pointer type")),
+                };
+                format!("bril_alloc({}, sizeof({}))", a[0], pointee)
+            }
+            "load" => format!("*{}", a[0]),
+            "ptradd" => format!("{} + {}", a[0], a[1]),
+            "free" => format!("bril_free({})", a[0]),
+            "store" => format!("*{} = {}", a[0], a[1]),
+            "get" => instr.dest.as_deref().map(shadow).unwrap_or_default(),
+            "set" => format!("{} = {}", shadow(&instr.args[0]), a[1]),
+            "nop" => return Ok(()),
+            "print" => {
+                for (i, arg) in instr.args.iter().enumerate() {
+                    if i > 0 {
+                        self.line(depth, "putchar(' ');");
+                    }
+                    let printer = match self.types.get(arg) {
+                        Some(Type::Prim(name)) => format!("bril_print_{}", name),
+                        Some(Type::Ptr { .. }) => {
+                            return Err(BrilError::malformed(instr, "cannot print a pointer"))
+                        }
+                        None => {
+                            let message = format!("{} is not defined", arg);
+                            return Err(BrilError::malformed(instr, message));
+                        }
+                    };
+                    self.line(depth, &format!("{}({});", printer, var(arg)));
+                }
+                self.line(depth, "putchar('\\n');");
+                return Ok(());
+            }
+            _ => {
+                let message = format!("the C backend does not support `{}`", op);
+                return Err(BrilError::malformed(instr, message));
+            }
+        };
+        match &instr.dest {
+            Some(dest) => self.line(depth, &format!("{} = {};", var(dest), value)),
+            None => self.line(depth, &format!("{};", value)),
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node, depth: usize) -> Result<(), BrilError> {
+        match node {
+            Node::Code(b) => {
+                let cfg = self.cfg;
+                for instr in &cfg.blocks[*b].instrs {
+                    if !instr.is_label() && !instr.is_terminator() {
+                        self.instr(depth, instr)?;
+                    }
+                }
+            }
+            Node::Seq(nodes) => {
+                for node in nodes {
+                    self.node(node, depth)?;
+                }
+            }
+            Node::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.line(depth, &format!("if ({}) {{", var(cond)));
+                self.node(then_branch, depth + 1)?;
+                self.line(depth, "} else {");
+                self.node(else_branch, depth + 1)?;
+                self.line(depth, "}");
+            }
+            Node::Block { label, body } => {
+                self.node(body, depth)?;
+                self.label(depth, format!("break_{}", label));
+            }
+            Node::Loop { label, body } => {
+                self.label(depth, format!("top_{}", label));
+                self.line(depth, "for (;;) {");
+                self.loops.push(*label);
+                self.node(body, depth + 1)?;
+                self.loops.pop();
+                self.line(depth, "}");
+            }
+            Node::Break(label) => self.line(depth, &format!("goto break_{};", label)),
+            Node::Continue(label) if self.loops.last() == Some(label) => {
+                self.line(depth, "continue;")
+            }
+            Node::Continue(label) => self.line(depth, &format!("goto top_{};", label)),
+            Node::Return(Some(value)) => self.line(depth, &format!("return {};", var(value))),
+            Node::Return(None) => self.line(depth, "return;"),
+            Node::Dispatch { arms, .. } => {
+                // The entry block comes first, so control starts there.
+                for (b, arm) in arms {
+                    self.label(depth, format!("block_{}", b));
+                    self.node(arm, depth)?;
+                }
+            }
+            Node::Goto(label) => self.line(depth, &format!("goto block_{};", label)),
+        }
+        Ok(())
+    }
+}
+
+fn signature(function: &Function) -> String {
+    let params: Vec<String> = function
+        .args
+        .iter()
+        .map(|arg| format!("{} {}", c_type(&arg.type_), var(&arg.name)))
+        .collect();
+    let params = if params.is_empty() {
+        String::from("void")
+    } else {
+        params.join(", ")
+    };
+    format!(
+        "{} {}({})",
+        return_type(function),
+        func(&function.name),
+        params
+    )
+}
+
+fn emit_function(function: &Function, out: &mut String) -> Result<(), BrilError> {
+    let function = &lower(function, "C")?;
+    let cfg = construct_control_flow_graph(function);
+    let types = variable_types(function);
+
+    writeln!(out, "{} {{", signature(function)).unwrap();
+    let params: HashSet<&String> = function.args.iter().map(|arg| &arg.name).collect();
+    for (name, type_) in &types {
+        if !params.contains(name) {
+            writeln!(out, "  {} {} = 0;", c_type(type_), var(name)).unwrap();
+        }
+    }
+    for (name, type_) in shadow_types(function, &types) {
+        writeln!(out, "  {} {} = 0;", c_type(&type_), shadow(&name)).unwrap();
+    }
+    if !cfg.blocks.is_empty() {
+        let tree = structure(&cfg);
+        let mut targets = HashSet::new();
+        collect_targets(&tree, &mut targets, &mut Vec::new());
+        let mut emitter = Emitter {
+            cfg: &cfg,
+            types,
+            targets,
+            loops: Vec::new(),
+            out: String::new(),
+        };
+        emitter
+            .node(&tree, 1)
+            .map_err(|e| e.in_function(function))?;
+        out.push_str(&emitter.out);
+    }
+    writeln!(out, "}}").unwrap();
+    Ok(())
+}
+
+fn parse_arg(type_: &Type, source: &str) -> Result<String, BrilError> {
+    match type_ {
+        Type::Prim(name) if name == "int" => Ok(format!("strtoll({}, NULL, 10)", source)),
+        Type::Prim(name) if name == "bool" => Ok(format!("strcmp({}, \"true\") == 0", source)),
+        Type::Prim(name) if name == "float" => Ok(format!("strtod({}, NULL)", source)),
+        _ => Err(BrilError::malformed_program(format!(
+            "@main cannot take an argument of type {}",
+            type_
+        ))),
+    }
+}
+
+// Translates a program to a single C file with its runtime included. Control
+// flow uses loops and conditionals where the CFG is reducible and `goto`
+// between blocks where it is not.
+pub fn program_to_c(program: &Program) -> Result<String, BrilError> {
+    let main = program
+        .functions
+        .iter()
+        .find(|f| f.name == "main")
+        .ok_or_else(|| BrilError::malformed_program("there is no @main to run"))?;
+    let mut out = String::from(RUNTIME);
+    let mut functions = String::new();
+    for function in &program.functions {
+        functions.push('\n');
+        emit_function(function, &mut functions)?;
+    }
+    out.push('\n');
+    for function in &program.functions {
+        writeln!(out, "{};", signature(function)).unwrap();
+    }
+    out.push_str(&functions);
+
+    let args = main
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| parse_arg(&arg.type_, &format!("argv[{}]", i + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    writeln!(out, "\nint main(int argc, char **argv) {{").unwrap();
+    writeln!(
+        out,
+        "  if (argc != {}) bril_error(\"wrong number of arguments\");",
+        main.args.len() + 1
+    )
+    .unwrap();
+    writeln!(out, "  {}({});", func("main"), args.join(", ")).unwrap();
+    writeln!(out, "  bril_check_leaks();").unwrap();
+    writeln!(out, "  return 0;").unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
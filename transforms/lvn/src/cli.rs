@@ -0,0 +1,108 @@
+// The command line of lvn. Flags left out default to the `[driver]` table of
+// `bril.toml`, see `config.rs`.
+use bril_cli::CommonArgs;
+use clap::Parser;
+
+pub const FORMATS: [&str; 12] = [
+    "json",
+    "html",
+    "diff-html",
+    "regions",
+    "liveness",
+    "available",
+    "loops",
+    "calls",
+    "aliases",
+    "structured",
+    "c",
+    "js",
+];
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "lvn",
+    about = "Optimizes Bril programs with a pipeline of passes"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Read the defaults from this file instead of the nearest bril.toml
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+
+    /// Look for the files the program imports in this directory too, after
+    /// the one the program is in
+    #[arg(long, value_name = "DIR")]
+    pub libs: Vec<std::path::PathBuf>,
+
+    /// The passes to run, separated by commas, like `lvn,dce`
+    #[arg(short, long, value_name = "PASSES")]
+    pub passes: Option<String>,
+
+    /// Run the pipeline described in this TOML or JSON file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["passes", "run_directive"])]
+    pub pipeline: Option<String>,
+
+    /// Run the passes of the `RUN:` line of this test file, if it has one
+    #[arg(long, value_name = "FILE")]
+    pub run_directive: Option<String>,
+
+    /// What to write: the optimized program, or a view or translation of it
+    #[arg(long, value_name = "FORMAT", value_parser = FORMATS)]
+    pub emit: Option<String>,
+
+    /// Write the program in the conformance format instead of JSON
+    #[arg(long)]
+    pub conform: bool,
+
+    /// Keep the IDs and pass provenance of each instruction
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Keep the debug info mapping variables to source names
+    #[arg(long)]
+    pub debug_info: bool,
+
+    /// Write the remarks of the passes to this file, in YAML for `.yaml`
+    #[arg(long, value_name = "FILE")]
+    pub remarks: Option<String>,
+
+    /// Write metrics to this file, as Prometheus text for `.prom` or `.txt`
+    #[arg(long, value_name = "FILE")]
+    pub metrics: Option<String>,
+
+    /// Make only the first N transformations the passes would
+    #[arg(long, value_name = "N")]
+    pub opt_bisect_limit: Option<usize>,
+
+    /// Optimize up to N functions at once, or one per CPU for 0
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        conflicts_with = "opt_bisect_limit"
+    )]
+    pub jobs: usize,
+
+    /// Repeat the passes until none changes anything, at most 10 times
+    #[arg(long)]
+    pub fixpoint: bool,
+
+    /// Repeat the passes until none changes anything, at most N times
+    #[arg(long, value_name = "N")]
+    pub max_iterations: Option<usize>,
+
+    /// How many times unroll copies loops it cannot unroll fully
+    #[arg(long, value_name = "N")]
+    pub unroll_factor: Option<usize>,
+
+    /// The number to start numbering generated names from in each function
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Whether dividing by zero may be folded away, and into what
+    #[arg(long, value_name = "POLICY", value_parser = ["preserve", "poison", "zero"])]
+    pub fold_div_by_zero: Option<String>,
+}
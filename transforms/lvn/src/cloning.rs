@@ -0,0 +1,232 @@
+use crate::names::Names;
+use crate::{ControlFlowGraph, Instruction};
+use std::collections::{BTreeSet, HashMap};
+
+pub fn jmp(target: String) -> Instruction {
+    Instruction {
+        op: Some(String::from("jmp")),
+        labels: vec![target],
+        ..Default::default()
+    }
+}
+
+pub fn label(name: String) -> Instruction {
+    Instruction {
+        label: Some(name),
+        ..Default::default()
+    }
+}
+
+// How a block kept or copied ends.
+#[derive(Clone, Copy)]
+pub enum End {
+    // With its terminator, or a jump to the block it fell through to.
+    Same,
+    // With a jump to this one of its successors, wherever the terminator
+    // would have gone.
+    Jump(usize),
+}
+
+// Duplicating a set of blocks of a CFG, for passes like unrolling and
+// unswitching. The copies go right after the last block of the set, under
+// new labels, and every copied block ends in an explicit jump, so where it
+// lands does not matter. The blocks of the set can stay where they are, with
+// only the jumps the pass asks for changed.
+pub struct Region<'a> {
+    cfg: &'a ControlFlowGraph,
+    last: usize,
+    // The pass making the copies, to mark them with.
+    pass: &'a str,
+    // New labels for the blocks after the set that it falls through into,
+    // which the copies need to jump to.
+    exit_labels: HashMap<usize, String>,
+}
+
+impl<'a> Region<'a> {
+    // New labels are named after `base`.
+    pub fn new(
+        cfg: &'a ControlFlowGraph,
+        blocks: &BTreeSet<usize>,
+        names: &mut Names,
+        pass: &'a str,
+        base: &str,
+    ) -> Self {
+        let mut exit_labels = HashMap::new();
+        for &b in blocks {
+            let block = &cfg.blocks[b];
+            if let [next] = block.next_blocks[..] {
+                let falls_through = !block.instrs.last().is_some_and(Instruction::is_terminator);
+                if falls_through && !blocks.contains(&next) && cfg.blocks[next].label().is_none() {
+                    exit_labels.insert(next, names.fresh(&format!("{}.exit", base)));
+                }
+            }
+        }
+        Region {
+            cfg,
+            last: *blocks.iter().next_back().expect("an empty region"),
+            pass,
+            exit_labels,
+        }
+    }
+
+    // Labels for a copy of each of `blocks`, named after the labels they
+    // have, or after `base`.
+    pub fn fresh_labels(
+        &self,
+        blocks: impl IntoIterator<Item = usize>,
+        names: &mut Names,
+        base: &str,
+    ) -> HashMap<usize, String> {
+        blocks
+            .into_iter()
+            .map(|b| (b, names.fresh(self.cfg.blocks[b].label().unwrap_or(base))))
+            .collect()
+    }
+
+    // The label to jump to block `t` by, which has to be labeled or outside
+    // the set.
+    pub fn label_of(&self, t: usize) -> String {
+        match self.cfg.blocks[t].label() {
+            Some(name) => name.to_string(),
+            None => self.exit_labels[&t].clone(),
+        }
+    }
+
+    // Block `b` as it stays where it is, except that an edge to a successor
+    // `t` goes to `redirect(t)` instead, where that is given.
+    pub fn keep(
+        &self,
+        b: usize,
+        end: End,
+        redirect: impl Fn(usize) -> Option<String>,
+    ) -> Vec<Instruction> {
+        self.rebuild(b, None, &HashMap::new(), end, redirect)
+    }
+
+    // A copy of block `b` labeled `name`, reading and writing the variables
+    // `renames` maps in place of theirs, with the edge to each successor `t`
+    // going to `target(t)`.
+    pub fn copy(
+        &self,
+        b: usize,
+        name: &str,
+        renames: &HashMap<String, String>,
+        end: End,
+        target: impl Fn(usize) -> String,
+    ) -> Vec<Instruction> {
+        self.rebuild(b, Some(name), renames, end, |t| Some(target(t)))
+    }
+
+    fn rebuild(
+        &self,
+        b: usize,
+        name: Option<&str>,
+        renames: &HashMap<String, String>,
+        end: End,
+        target: impl Fn(usize) -> Option<String>,
+    ) -> Vec<Instruction> {
+        let copied = name.is_some();
+        let block = &self.cfg.blocks[b];
+        let mut body = &block.instrs[..];
+        let first = body.first().filter(|instr| instr.is_label());
+        if first.is_some() {
+            body = &body[1..];
+        }
+        let terminator = body.last().filter(|instr| instr.is_terminator());
+        if terminator.is_some() {
+            body = &body[..body.len() - 1];
+        }
+        let rename = |instr: &mut Instruction| {
+            if let Some(dest) = &mut instr.dest {
+                if let Some(fresh) = renames.get(dest.as_str()) {
+                    *dest = fresh.clone();
+                }
+            }
+            for arg in instr.local_args_mut() {
+                if let Some(fresh) = renames.get(arg.as_str()) {
+                    *arg = fresh.clone();
+                }
+            }
+            instr.mark_transformed(self.pass);
+        };
+        let to = |t: usize| target(t).unwrap_or_else(|| self.label_of(t));
+
+        let mut instrs = Vec::new();
+        match name {
+            Some(name) => instrs.push(label(name.to_string())),
+            None => instrs.extend(first.cloned()),
+        }
+        for instr in body {
+            let mut instr = instr.clone();
+            if copied {
+                rename(&mut instr);
+            }
+            instrs.push(instr);
+        }
+        let end = match (end, terminator, block.next_blocks.first()) {
+            (End::Jump(t), _, _) => {
+                let mut instr = jmp(to(t));
+                instr.id = terminator.and_then(|original| original.id.clone());
+                instr.mark_transformed(self.pass);
+                Some(instr)
+            }
+            (End::Same, Some(original), _) => {
+                let mut instr = original.clone();
+                instr.labels = block.next_blocks.iter().map(|&t| to(t)).collect();
+                if copied {
+                    rename(&mut instr);
+                } else if instr.labels != original.labels {
+                    instr.mark_transformed(self.pass);
+                }
+                Some(instr)
+            }
+            // Falling through still works in place, unless the copies come
+            // in between.
+            (End::Same, None, Some(&next))
+                if !copied && b != self.last && target(next).is_none() =>
+            {
+                None
+            }
+            (End::Same, None, next) => {
+                // Falling off the end of the function returns.
+                let mut instr = match next {
+                    Some(&next) => jmp(to(next)),
+                    None => Instruction {
+                        op: Some(String::from("ret")),
+                        ..Default::default()
+                    },
+                };
+                instr.mark_transformed(self.pass);
+                Some(instr)
+            }
+        };
+        instrs.extend(end);
+        instrs
+    }
+
+    // The instructions of the function, with each block replaced by
+    // `replace(b)` where that is given, and `copies` after the last block of
+    // the set. A block falling through into one replaced with nothing has to
+    // be given a jump instead.
+    pub fn layout(
+        &self,
+        mut replace: impl FnMut(usize) -> Option<Vec<Instruction>>,
+        copies: Vec<Instruction>,
+    ) -> Vec<Instruction> {
+        let mut instrs = Vec::new();
+        let mut copies = Some(copies);
+        for (b, block) in self.cfg.iter() {
+            if let Some(name) = self.exit_labels.get(&b) {
+                instrs.push(label(name.clone()));
+            }
+            match replace(b) {
+                Some(replacement) => instrs.extend(replacement),
+                None => instrs.extend(block.instrs.iter().cloned()),
+            }
+            if b == self.last {
+                instrs.extend(copies.take().into_iter().flatten());
+            }
+        }
+        instrs
+    }
+}
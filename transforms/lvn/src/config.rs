@@ -0,0 +1,370 @@
+// The project configuration in `bril.toml`, which gives the defaults for
+// flags that are not passed on the command line. Only the `[driver]` table
+// is read here; `[bench]` belongs to brench.
+//
+//     [driver]
+//     passes = ["svn", "dce"]
+//     emit = "json"
+//     max-iterations = 10
+//     interp = "brilirs"
+//     check = "brilirs --check"
+//
+// This is the subset of TOML those settings need: tables, strings, integers,
+// booleans and arrays of them, plus the inline tables of pipeline files.
+use bril_ir::error::BrilError;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(table) => table.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    // A string array, or a single string standing for an array of one.
+    pub fn as_strings(&self) -> Option<Vec<String>> {
+        match self {
+            Value::String(s) => Some(vec![s.clone()]),
+            Value::Array(values) => values
+                .iter()
+                .map(|value| value.as_str().map(String::from))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+pub const FILE_NAME: &str = "bril.toml";
+
+// The nearest `bril.toml` in the current directory or one of its parents.
+pub fn find() -> Option<PathBuf> {
+    let mut dir = std::env::current_dir().ok()?;
+    loop {
+        let path = dir.join(FILE_NAME);
+        if path.is_file() {
+            return Some(path);
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Value, BrilError> {
+    let name = path.display().to_string();
+    let text = std::fs::read_to_string(path).map_err(|e| BrilError::io(&name, e))?;
+    parse(&text).map_err(|e| BrilError::config(name, e))
+}
+
+// The `[driver]` table of the file given with `--config`, or else of the
+// nearest `bril.toml`. Empty when there is neither.
+pub fn driver(path: Option<&Path>) -> Result<Settings, BrilError> {
+    let path = path.map(PathBuf::from).or_else(find);
+    let table = match &path {
+        Some(path) => load(path)?.get("driver").cloned(),
+        None => None,
+    };
+    Ok(Settings {
+        file: path.map_or_else(|| String::from(FILE_NAME), |p| p.display().to_string()),
+        table: table.unwrap_or_else(|| Value::Table(BTreeMap::new())),
+    })
+}
+
+// The settings of a table, which are errors in the file they come from when
+// they are not of the type asked for.
+pub struct Settings {
+    file: String,
+    table: Value,
+}
+
+impl Settings {
+    pub fn error(&self, message: impl Into<String>) -> BrilError {
+        BrilError::config(&self.file, message)
+    }
+
+    fn get<'a, T>(
+        &'a self,
+        key: &str,
+        expected: &str,
+        convert: impl FnOnce(&'a Value) -> Option<T>,
+    ) -> Result<Option<T>, BrilError> {
+        match self.table.get(key) {
+            Some(value) => match convert(value) {
+                Some(value) => Ok(Some(value)),
+                None => Err(self.error(format!("{} must be {}", key, expected))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn string(&self, key: &str) -> Result<Option<&str>, BrilError> {
+        self.get(key, "a string", Value::as_str)
+    }
+
+    pub fn strings(&self, key: &str) -> Result<Option<Vec<String>>, BrilError> {
+        self.get(key, "a string or an array of strings", Value::as_strings)
+    }
+
+    pub fn count(&self, key: &str) -> Result<Option<u64>, BrilError> {
+        let count = |value: &Value| value.as_integer().and_then(|i| u64::try_from(i).ok());
+        self.get(key, "a non-negative integer", count)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line, message))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.bump() == Some(expected) {
+            Ok(())
+        } else {
+            self.error(&format!("expected `{}`", expected))
+        }
+    }
+
+    // Skips spaces and comments, and newlines too if `newlines` is set.
+    fn skip(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump();
+                }
+            } else if c == ' ' || c == '\t' || c == '\r' || (newlines && c == '\n') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip(false);
+        match self.bump() {
+            None | Some('\n') => Ok(()),
+            _ => self.error("expected the end of the line"),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.bump();
+                }
+                if start == self.pos {
+                    return self.error("expected a key");
+                }
+                Ok(self.text[start..self.pos].to_string())
+            }
+        }
+    }
+
+    // A key of dotted parts, like `bench.args`.
+    fn dotted_key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip(false);
+            parts.push(self.key()?);
+            self.skip(false);
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.bump().unwrap();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return self.error("unterminated string"),
+                Some(c) if c == quote => return Ok(s),
+                Some('\\') if quote == '"' => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    _ => return self.error("unsupported escape in string"),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::String(self.string()?)),
+            // An inline table, like `{ pass = "unroll", unroll-factor = 8 }`,
+            // which has to fit on one line.
+            Some('{') => {
+                self.bump();
+                let mut root = BTreeMap::new();
+                self.skip(false);
+                if self.peek() == Some('}') {
+                    self.bump();
+                    return Ok(Value::Table(root));
+                }
+                loop {
+                    let mut key = self.dotted_key()?;
+                    self.expect('=')?;
+                    self.skip(false);
+                    let value = self.value()?;
+                    let name = key.pop().unwrap();
+                    let table = match table_at(&mut root, &key) {
+                        Ok(table) => table,
+                        Err(e) => return self.error(&e),
+                    };
+                    if table.insert(name.clone(), value).is_some() {
+                        return self.error(&format!("`{}` is defined twice", name));
+                    }
+                    self.skip(false);
+                    match self.bump() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Value::Table(root)),
+                        _ => return self.error("expected `,` or `}`"),
+                    }
+                }
+            }
+            Some('[') => {
+                self.bump();
+                let mut values = Vec::new();
+                loop {
+                    self.skip(true);
+                    if self.peek() == Some(']') {
+                        self.bump();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip(true);
+                    match self.bump() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return self.error("expected `,` or `]`"),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '_')
+                {
+                    self.bump();
+                }
+                match &self.text[start..self.pos] {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    word => match word.replace('_', "").parse() {
+                        Ok(i) => Ok(Value::Integer(i)),
+                        Err(_) => self.error("expected a value"),
+                    },
+                }
+            }
+        }
+    }
+}
+
+// The table at `path` inside `root`, created if it does not exist yet.
+fn table_at<'t>(
+    root: &'t mut BTreeMap<String, Value>,
+    path: &[String],
+) -> Result<&'t mut BTreeMap<String, Value>, String> {
+    let mut table = root;
+    for part in path {
+        let entry = table
+            .entry(part.clone())
+            .or_insert_with(|| Value::Table(BTreeMap::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            _ => return Err(format!("`{}` is not a table", part)),
+        };
+    }
+    Ok(table)
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        line: 1,
+    };
+    let mut root = BTreeMap::new();
+    let mut current = Vec::new();
+    loop {
+        parser.skip(true);
+        match parser.peek() {
+            None => return Ok(Value::Table(root)),
+            Some('[') => {
+                parser.bump();
+                current = parser.dotted_key()?;
+                parser.expect(']')?;
+                table_at(&mut root, &current)
+                    .map_err(|e| format!("line {}: {}", parser.line, e))?;
+            }
+            Some(_) => {
+                let mut key = parser.dotted_key()?;
+                parser.expect('=')?;
+                parser.skip(false);
+                let value = parser.value()?;
+                let line = parser.line;
+                let name = key.pop().unwrap();
+                let path: Vec<String> = current.iter().cloned().chain(key).collect();
+                let table =
+                    table_at(&mut root, &path).map_err(|e| format!("line {}: {}", line, e))?;
+                if table.insert(name.clone(), value).is_some() {
+                    return parser.error(&format!("`{}` is defined twice", name));
+                }
+            }
+        }
+        parser.end_of_line()?;
+    }
+}
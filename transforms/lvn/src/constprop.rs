@@ -0,0 +1,255 @@
+use crate::bisect::OptBisect;
+use crate::fold::fold;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{
+    construct_control_flow_graph, Block, ControlFlowGraph, Function, Instruction, Literal,
+};
+use bril_cfg::dataflow::{solve, Analysis, Direction};
+use std::collections::{BTreeMap, HashMap};
+
+// What is known about a variable at a point: the constant it holds on every
+// path there, or that it may hold other values or none at all.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Const(Literal),
+    Varying,
+}
+
+// What is known about the variables defined at a point, or `None` while no
+// path from the entry is known to reach it.
+type Fact = Option<BTreeMap<String, Value>>;
+type Values = BTreeMap<String, Value>;
+
+// The constant `instr` computes given what is known before it.
+fn evaluate(instr: &Instruction, values: &Values, div_by_zero: DivByZero) -> Option<Literal> {
+    let op = instr.op.as_deref()?;
+    if op == "const" {
+        return instr.literal();
+    }
+    let args = instr
+        .local_args()
+        .iter()
+        .map(|arg| match values.get(arg) {
+            Some(Value::Const(value)) => Some(*value),
+            _ => None,
+        })
+        .collect::<Option<Vec<Literal>>>()?;
+    fold(op, &args, div_by_zero)
+}
+
+fn step(values: &mut Values, instr: &Instruction, div_by_zero: DivByZero) {
+    if let Some(dest) = &instr.dest {
+        let value = match evaluate(instr, values, div_by_zero) {
+            Some(value) => Value::Const(value),
+            None => Value::Varying,
+        };
+        values.insert(dest.clone(), value);
+    }
+}
+
+struct ConstantPropagation<'a> {
+    function: &'a Function,
+    div_by_zero: DivByZero,
+}
+
+impl Analysis for ConstantPropagation<'_> {
+    type Fact = Fact;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    // The arguments could be anything.
+    fn boundary(&self) -> Fact {
+        let args = self.function.args.iter();
+        Some(args.map(|arg| (arg.name.clone(), Value::Varying)).collect())
+    }
+
+    fn initial(&self) -> Fact {
+        None
+    }
+
+    // A variable defined on only one of the paths is not a constant
+    // either: reading it where it is undefined is an error that folding
+    // would hide.
+    fn merge(&self, into: &mut Fact, other: &Fact) {
+        let other = match other {
+            Some(other) => other,
+            None => return,
+        };
+        let into = match into {
+            Some(into) => into,
+            None => {
+                *into = Some(other.clone());
+                return;
+            }
+        };
+        for (var, value) in into.iter_mut() {
+            if other.get(var) != Some(value) {
+                *value = Value::Varying;
+            }
+        }
+        for var in other.keys() {
+            into.entry(var.clone()).or_insert(Value::Varying);
+        }
+    }
+
+    fn transfer(&self, block: &Block, fact: &Fact) -> Fact {
+        let mut values = fact.clone()?;
+        for instr in &block.instrs {
+            step(&mut values, instr, self.div_by_zero);
+        }
+        Some(values)
+    }
+}
+
+// The constants the variables hold as control leaves each block, or `None`
+// for a block no path from the entry reaches.
+pub fn constants_after(
+    function: &Function,
+    cfg: &ControlFlowGraph,
+    div_by_zero: DivByZero,
+) -> Vec<Option<BTreeMap<String, Literal>>> {
+    let analysis = ConstantPropagation {
+        function,
+        div_by_zero,
+    };
+    solve(cfg, &analysis)
+        .after
+        .into_iter()
+        .map(|fact| {
+            let values = fact?.into_iter();
+            Some(
+                values
+                    .filter_map(|(var, value)| match value {
+                        Value::Const(value) => Some((var, value)),
+                        Value::Varying => None,
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+// The constants the arguments of each `call` in `function` hold, by the
+// index of the call in its instructions, with `None` for an argument that may
+// hold other values. Calls no path from the entry reaches are left out.
+pub fn call_arguments(
+    function: &Function,
+    div_by_zero: DivByZero,
+) -> HashMap<usize, Vec<Option<Literal>>> {
+    let cfg = construct_control_flow_graph(function);
+    let analysis = ConstantPropagation {
+        function,
+        div_by_zero,
+    };
+    let solution = solve(&cfg, &analysis);
+    let mut calls = HashMap::new();
+    let mut i = 0;
+    for (b, block) in cfg.iter() {
+        let mut values = solution.before[b].clone();
+        for instr in &block.instrs {
+            if let Some(values) = &mut values {
+                if instr.op.as_deref() == Some("call") {
+                    let args = instr.args.iter().map(|arg| match values.get(arg) {
+                        Some(Value::Const(value)) => Some(*value),
+                        _ => None,
+                    });
+                    calls.insert(i, args.collect());
+                }
+                step(values, instr, div_by_zero);
+            }
+            i += 1;
+        }
+    }
+    calls
+}
+
+// Global constant propagation: finds the variables that hold the same
+// constant on every path to a point, across blocks, and replaces each
+// instruction computing a known constant, a copy of one included, with a
+// `const`. The instructions they were computed from are left for dce. A `br`
+// on a known condition becomes a `jmp` the way it goes, and the arm it no
+// longer goes to is left for simplify-cfg.
+pub fn propagate_constants(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let mut cfg = construct_control_flow_graph(function);
+    let analysis = ConstantPropagation {
+        function,
+        div_by_zero,
+    };
+    let solution = solve(&cfg, &analysis);
+
+    let mut changed = false;
+    for (b, block) in cfg.blocks.iter_mut().enumerate() {
+        remarks.enter(&function.name, b);
+        // Nothing is known about code that never runs.
+        let mut values = match &solution.before[b] {
+            Some(values) => values.clone(),
+            None => continue,
+        };
+        for instr in block.instrs.iter_mut() {
+            if instr.op.as_deref() == Some("br") {
+                let cond = instr.args[0].clone();
+                let taken = match values.get(&cond) {
+                    Some(Value::Const(Literal::Bool(taken))) => *taken,
+                    _ => continue,
+                };
+                if !bisect.should_run("constprop", &format!("fold `{}`", instr)) {
+                    continue;
+                }
+                let original = instr.to_string();
+                let target = instr.labels[if taken { 0 } else { 1 }].clone();
+                instr.op = Some(String::from("jmp"));
+                instr.args.clear();
+                instr.labels = vec![target];
+                instr.mark_transformed("constprop");
+                remarks.passed(
+                    "constprop",
+                    "FoldedBranch",
+                    instr,
+                    format!(
+                        "replaced `{}` with `{}`, as `{}` is always {}",
+                        original, instr, cond, taken
+                    ),
+                );
+                changed = true;
+                continue;
+            }
+            let known = match evaluate(instr, &values, div_by_zero) {
+                Some(value) if instr.dest.is_some() && instr.op.as_deref() != Some("const") => {
+                    Some(value)
+                }
+                _ => None,
+            };
+            step(&mut values, instr, div_by_zero);
+            let value = match known {
+                Some(value) if bisect.should_run("constprop", &format!("fold `{}`", instr)) => {
+                    value
+                }
+                _ => continue,
+            };
+            let original = instr.to_string();
+            instr.op = Some(String::from("const"));
+            instr.value = Some(value);
+            instr.args.clear();
+            instr.mark_transformed("constprop");
+            remarks.passed(
+                "constprop",
+                "Folded",
+                instr,
+                format!("replaced `{}` with `{}`", original, instr),
+            );
+            changed = true;
+        }
+    }
+    if changed {
+        function.instrs = cfg.into_instrs();
+    }
+    changed
+}
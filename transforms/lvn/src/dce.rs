@@ -0,0 +1,71 @@
+use crate::bisect::OptBisect;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::ControlFlowGraph;
+use bril_cfg::liveness::Liveness;
+use bril_ir::Effect;
+use std::collections::HashSet;
+
+// Global dead code elimination: deletes instructions whose result is dead,
+// meaning no path from them reads it before it is assigned again, until no
+// more can be deleted. That covers results never used anywhere in the
+// function, such as the copies superlocal value numbering leaves that only
+// turn out dead once every block has been seen, and also assignments that
+// are always overwritten before they are read.
+//
+// Only the result of an instruction is deleted with it, so the operations
+// without one, like `print` and `store`, always stay, and so do those that
+// do something besides computing it, like `call` and `alloc`.
+pub fn eliminate_dead_code(
+    function: &str,
+    cfg: &mut ControlFlowGraph,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let mut changed = false;
+    // Unused instructions kept because they may trap, reported only once.
+    let mut kept = HashSet::new();
+    loop {
+        let liveness = Liveness::new(cfg);
+        let live_after: Vec<_> = (0..cfg.len())
+            .map(|b| liveness.live_after(cfg, b))
+            .collect();
+        let mut deleted = false;
+        for (i, block) in cfg.blocks.iter_mut().enumerate() {
+            remarks.enter(function, i);
+            let mut live = live_after[i].iter();
+            block.instrs.retain(|instr| {
+                let live = live.next().unwrap();
+                let dead = match (&instr.dest, instr.op.as_deref()) {
+                    (Some(dest), Some(op)) if !live.contains(dest) => {
+                        if div_by_zero.keeps_unused(op) && kept.insert(dest.clone()) {
+                            remarks.missed(
+                                "dce",
+                                "MayTrap",
+                                instr,
+                                format!(
+                                    "kept unused `{}`, which may trap under --fold-div-by-zero={}",
+                                    instr,
+                                    div_by_zero.name()
+                                ),
+                            );
+                        }
+                        instr.effect() != Effect::Writes && !div_by_zero.keeps_unused(op)
+                    }
+                    _ => false,
+                };
+                if dead && bisect.should_run("dce", &format!("delete `{}`", instr)) {
+                    remarks.passed("dce", "Dead", instr, format!("removed unused `{}`", instr));
+                    deleted = true;
+                    return false;
+                }
+                true
+            });
+        }
+        if !deleted {
+            return changed;
+        }
+        changed = true;
+    }
+}
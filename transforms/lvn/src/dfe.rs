@@ -0,0 +1,49 @@
+use crate::bisect::OptBisect;
+use crate::remarks::Remarks;
+use crate::{Function, Instruction};
+use bril_cfg::callgraph::CallGraph;
+use std::collections::BTreeSet;
+
+// Dead function elimination: deletes the functions no chain of calls from
+// `@main` reaches, since nothing can run them. A program without a `@main`,
+// like a library to link with others, is left alone. The functions in
+// `exported`, which the files the program imports from call back, are kept
+// like `@main`, with what they call.
+pub fn eliminate_dead_functions(
+    functions: &mut Vec<Function>,
+    exported: &BTreeSet<String>,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+) -> bool {
+    let graph = CallGraph::new(functions);
+    let mut reached = match graph.function("main") {
+        Some(main) => graph.reachable_from(main),
+        None => return false,
+    };
+    for name in exported {
+        if let Some(f) = graph.function(name) {
+            reached.extend(graph.reachable_from(f));
+        }
+    }
+
+    let mut deleted = BTreeSet::new();
+    for (f, function) in functions.iter().enumerate() {
+        let name = function.name.as_str();
+        if reached.contains(&f) || !bisect.should_run("dfe", &format!("delete @{}", name)) {
+            continue;
+        }
+        remarks.enter(name, 0);
+        remarks.passed(
+            "dfe",
+            "Deleted",
+            function.instrs.first().unwrap_or(&Instruction::default()),
+            format!(
+                "deleted @{}, which nothing reachable from @main calls",
+                name
+            ),
+        );
+        deleted.insert(name.to_string());
+    }
+    functions.retain(|function| !deleted.contains(&function.name));
+    !deleted.is_empty()
+}
@@ -0,0 +1,137 @@
+use crate::ControlFlowGraph;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Same,
+    Added,
+    Removed,
+    // The instruction still defines the same variable but computes it differently.
+    Changed,
+}
+
+impl Change {
+    pub fn class(self) -> &'static str {
+        match self {
+            Change::Same => "",
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        }
+    }
+}
+
+// Per-block, per-instruction classification of both sides of a diff.
+#[derive(Debug)]
+pub struct CfgDiff {
+    pub before: Vec<Vec<Change>>,
+    pub after: Vec<Vec<Change>>,
+}
+
+fn block_key(cfg: &ControlFlowGraph, i: usize) -> String {
+    match &cfg.blocks[i].instrs[0].label {
+        Some(label) => label.clone(),
+        // Unlabeled blocks can only be lined up by position.
+        None => format!("<block {}>", i),
+    }
+}
+
+// Longest-common-subsequence edit script over the textual form of the
+// instructions, with deletions paired up with insertions of the same
+// instruction to mark them as changed. Instructions are the same if they have
+// the same ID, or failing that if they define the same variable.
+fn diff_instrs(
+    before: &[String],
+    after: &[String],
+    keys: (&[Option<String>], &[Option<String>]),
+) -> (Vec<Change>, Vec<Change>) {
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if before[i] == after[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut before_changes = vec![Change::Removed; n];
+    let mut after_changes = vec![Change::Added; m];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if before[i] == after[j] {
+            before_changes[i] = Change::Same;
+            after_changes[j] = Change::Same;
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    let (before_keys, after_keys) = keys;
+    for (bi, change) in before_changes.iter_mut().enumerate() {
+        if *change != Change::Removed || before_keys[bi].is_none() {
+            continue;
+        }
+        let matching = (0..m)
+            .find(|aj| after_changes[*aj] == Change::Added && after_keys[*aj] == before_keys[bi]);
+        if let Some(aj) = matching {
+            *change = Change::Changed;
+            after_changes[aj] = Change::Changed;
+        }
+    }
+    (before_changes, after_changes)
+}
+
+pub fn diff_cfgs(before: &ControlFlowGraph, after: &ControlFlowGraph) -> CfgDiff {
+    let after_blocks: HashMap<String, usize> = (0..after.blocks.len())
+        .map(|i| (block_key(after, i), i))
+        .collect();
+
+    let mut diff = CfgDiff {
+        before: before
+            .blocks
+            .iter()
+            .map(|b| vec![Change::Removed; b.instrs.len()])
+            .collect(),
+        after: after
+            .blocks
+            .iter()
+            .map(|b| vec![Change::Added; b.instrs.len()])
+            .collect(),
+    };
+    for i in 0..before.blocks.len() {
+        if let Some(&j) = after_blocks.get(&block_key(before, i)) {
+            let text = |cfg: &ControlFlowGraph, b: usize| -> Vec<String> {
+                cfg.blocks[b]
+                    .instrs
+                    .iter()
+                    .map(|instr| instr.to_string())
+                    .collect()
+            };
+            let keys = |cfg: &ControlFlowGraph, b: usize| -> Vec<Option<String>> {
+                cfg.blocks[b]
+                    .instrs
+                    .iter()
+                    .map(|instr| match &instr.id {
+                        Some(id) => Some(format!("id {}", id)),
+                        None => instr.dest.as_ref().map(|dest| format!("dest {}", dest)),
+                    })
+                    .collect()
+            };
+            let (before_changes, after_changes) = diff_instrs(
+                &text(before, i),
+                &text(after, j),
+                (&keys(before, i), &keys(after, j)),
+            );
+            diff.before[i] = before_changes;
+            diff.after[j] = after_changes;
+        }
+    }
+    diff
+}
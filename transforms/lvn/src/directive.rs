@@ -0,0 +1,17 @@
+// Test files pick their own pipeline with a line like
+//
+//     # RUN: lvn | dce
+//
+// near the top, so that one test tree can cover many pass combinations.
+// Returns the passes of the first such line in order, if there is one.
+pub fn run_directive(test: &str) -> Option<Vec<String>> {
+    let line = test.lines().find_map(|line| {
+        let comment = line.trim_start().strip_prefix('#')?;
+        comment.trim_start().strip_prefix("RUN:")
+    })?;
+    Some(
+        line.split('|')
+            .map(|pass| pass.trim().to_string())
+            .collect(),
+    )
+}
@@ -0,0 +1,137 @@
+use crate::bisect::OptBisect;
+use crate::remarks::Remarks;
+use crate::{take_control_flow_graph, Block, Function, Instruction};
+use bril_cfg::alias::AliasAnalysis;
+use bril_cfg::dataflow::{solve, Analysis, Direction};
+use bril_ir::PURE_OPS;
+use std::collections::BTreeSet;
+
+// The pointer variables whose memory every path from a point stores to
+// again, through the same variable, before anything may read it, or `None`
+// while no path to the exit is known to go through the point.
+type Fact = Option<BTreeSet<String>>;
+
+// Operations that neither read memory nor do anything a store could be
+// seen through.
+fn is_unobservable(op: &str) -> bool {
+    PURE_OPS.contains(&op)
+        || matches!(
+            op,
+            "const" | "id" | "phi" | "get" | "set" | "alloc" | "jmp" | "br" | "nop"
+        )
+}
+
+struct Overwritten<'a> {
+    aliases: &'a AliasAnalysis,
+}
+
+impl Overwritten<'_> {
+    // Moves the fact from after `instr` to before it.
+    fn step(&self, overwritten: &mut BTreeSet<String>, instr: &Instruction) {
+        let op = match instr.op.as_deref() {
+            Some(op) => op,
+            None => return,
+        };
+        // Before the assignment the variable holds another pointer.
+        if let Some(dest) = &instr.dest {
+            overwritten.remove(dest);
+        }
+        match op {
+            "store" => {
+                overwritten.insert(instr.args[0].clone());
+            }
+            // Freeing memory is like a last read of it.
+            "load" | "free" => {
+                let read = &instr.args[0];
+                overwritten.retain(|var| !self.aliases.may_alias(var, read));
+            }
+            op if is_unobservable(op) => {}
+            // A call may read anything, and a `print` or any other effect
+            // would show that an earlier store trapped.
+            _ => overwritten.clear(),
+        }
+    }
+}
+
+impl Analysis for Overwritten<'_> {
+    type Fact = Fact;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    // The caller may read whatever the function left in memory.
+    fn boundary(&self) -> Fact {
+        Some(BTreeSet::new())
+    }
+
+    fn initial(&self) -> Fact {
+        None
+    }
+
+    fn merge(&self, into: &mut Fact, other: &Fact) {
+        let other = match other {
+            Some(other) => other,
+            None => return,
+        };
+        match into {
+            Some(into) => into.retain(|var| other.contains(var)),
+            None => *into = Some(other.clone()),
+        }
+    }
+
+    fn transfer(&self, block: &Block, fact: &Fact) -> Fact {
+        let mut overwritten = fact.clone()?;
+        for instr in block.instrs.iter().rev() {
+            self.step(&mut overwritten, instr);
+        }
+        Some(overwritten)
+    }
+}
+
+// Dead store elimination: deletes each `store` whose value no one can read,
+// because every path from it stores through the same pointer variable again
+// before any `load` or `free` of memory it may alias, as the alias analysis
+// tells, and before any call. Stores followed by a `print` or another effect
+// stay, in case they trap.
+pub fn eliminate_dead_stores(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+) -> bool {
+    let mut cfg = take_control_flow_graph(function);
+    let aliases = AliasAnalysis::new(&function.args, &cfg);
+    let analysis = Overwritten { aliases: &aliases };
+    let solution = solve(&cfg, &analysis);
+
+    let mut changed = false;
+    for (b, block) in cfg.blocks.iter_mut().enumerate() {
+        remarks.enter(&function.name, b);
+        // Nothing is known of a block no path to the exit goes through.
+        let mut overwritten = match &solution.after[b] {
+            Some(overwritten) => overwritten.clone(),
+            None => continue,
+        };
+        let mut dead = vec![false; block.instrs.len()];
+        for (i, instr) in block.instrs.iter().enumerate().rev() {
+            if instr.op.as_deref() == Some("store")
+                && overwritten.contains(&instr.args[0])
+                && bisect.should_run("dse", &format!("delete `{}`", instr))
+            {
+                remarks.passed(
+                    "dse",
+                    "DeadStore",
+                    instr,
+                    format!("removed `{}`, stored over before it is read", instr),
+                );
+                dead[i] = true;
+                changed = true;
+            }
+            analysis.step(&mut overwritten, instr);
+        }
+        let mut dead = dead.into_iter();
+        block.instrs.retain(|_| !dead.next().unwrap());
+    }
+    function.instrs = cfg.into_instrs();
+    changed
+}
@@ -0,0 +1,86 @@
+use crate::ControlFlowGraph;
+
+// Maximal extended basic blocks: trees of blocks in which every block but the
+// root has its parent as its only predecessor. Every block of the CFG is in
+// exactly one tree.
+#[derive(Debug)]
+pub struct ExtendedBasicBlocks {
+    roots: Vec<usize>,
+    children: Vec<Vec<usize>>,
+}
+
+// A walk over a tree enters a block before any of its descendants and leaves
+// it after all of them, so analyses can open and close a scope per block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Push(usize),
+    Pop(usize),
+}
+
+impl ExtendedBasicBlocks {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let n = cfg.blocks.len();
+        let mut ebbs = ExtendedBasicBlocks {
+            roots: Vec::new(),
+            children: vec![Vec::new(); n],
+        };
+        // The entry block is also entered from outside the function.
+        let mut parent = vec![None; n];
+        for (b, block) in cfg.iter() {
+            let prev_blocks = &block.prev_blocks;
+            if b != 0 && prev_blocks.len() == 1 {
+                parent[b] = Some(prev_blocks[0]);
+                ebbs.children[prev_blocks[0]].push(b);
+            } else {
+                ebbs.roots.push(b);
+            }
+        }
+
+        // Unreachable cycles of single-predecessor blocks have no root yet, so
+        // one is cut out of its parent to start a tree of its own.
+        let mut visited = vec![false; n];
+        let mut stack = ebbs.roots.clone();
+        loop {
+            while let Some(b) = stack.pop() {
+                visited[b] = true;
+                stack.extend(&ebbs.children[b]);
+            }
+            let b = match (0..n).find(|&b| !visited[b]) {
+                Some(b) => b,
+                None => break,
+            };
+            let p = parent[b].unwrap();
+            ebbs.children[p].retain(|&c| c != b);
+            ebbs.roots.push(b);
+            stack.push(b);
+        }
+        ebbs
+    }
+
+    // Walks every tree depth-first, visiting children in block order.
+    pub fn events(&self) -> Events<'_> {
+        Events {
+            ebbs: self,
+            stack: self.roots.iter().rev().map(|&b| Event::Push(b)).collect(),
+        }
+    }
+}
+
+pub struct Events<'a> {
+    ebbs: &'a ExtendedBasicBlocks,
+    stack: Vec<Event>,
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let event = self.stack.pop()?;
+        if let Event::Push(b) = event {
+            self.stack.push(Event::Pop(b));
+            let children = self.ebbs.children[b].iter().rev();
+            self.stack.extend(children.map(|&c| Event::Push(c)));
+        }
+        Some(event)
+    }
+}
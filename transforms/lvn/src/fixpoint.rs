@@ -0,0 +1,46 @@
+use crate::Function;
+use std::fmt::Display;
+
+fn count_instrs(functions: &[Function]) -> usize {
+    functions.iter().map(|f| f.instrs.len()).sum()
+}
+
+// Runs `passes` in order, each over every function, over and over, until a
+// whole round goes by without any pass reporting a change or
+// `max_iterations` rounds have run. Statistics for every round are logged to
+// stderr. The passes can be anything `run_pass` knows how to run, like the
+// steps of a pipeline file.
+pub fn run_to_fixpoint<P, F>(
+    functions: &mut Vec<Function>,
+    passes: &[P],
+    max_iterations: usize,
+    mut run_pass: F,
+) where
+    P: Display,
+    F: FnMut(&P, &mut Vec<Function>) -> bool,
+{
+    for iteration in 1..=max_iterations {
+        let instrs_before = count_instrs(functions);
+        let mut changed_by = Vec::new();
+        for pass in passes {
+            if run_pass(pass, functions) {
+                changed_by.push(pass.to_string());
+            }
+        }
+        eprintln!(
+            "fixpoint: iteration {}: {} -> {} instructions, changed by [{}]",
+            iteration,
+            instrs_before,
+            count_instrs(functions),
+            changed_by.join(", ")
+        );
+        if changed_by.is_empty() {
+            eprintln!("fixpoint: converged after {} iterations", iteration);
+            return;
+        }
+    }
+    eprintln!(
+        "fixpoint: stopped after {} iterations without converging",
+        max_iterations
+    );
+}
@@ -0,0 +1,74 @@
+use crate::trapping::DivByZero;
+use crate::{Instruction, Literal};
+use bril_ir::Effect;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+// Evaluates `op` on constant arguments the way the interpreters do, or gives
+// `None` when the result is not a constant: the operation may have an
+// effect, read memory or trap.
+pub fn fold(op: &str, args: &[Literal], div_by_zero: DivByZero) -> Option<Literal> {
+    use Literal::{Bool, Char, Float, Int};
+    let value = match (op, args) {
+        ("id", [a]) => *a,
+        ("add", [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        ("mul", [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        ("sub", [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        ("div", [Int(_), Int(0)]) if div_by_zero == DivByZero::Zero => Int(0),
+        ("div", [Int(_), Int(0)]) => return None,
+        ("div", [Int(a), Int(b)]) => Int(a.wrapping_div(*b)),
+        ("eq", [Int(a), Int(b)]) => Bool(a == b),
+        ("lt", [Int(a), Int(b)]) => Bool(a < b),
+        ("gt", [Int(a), Int(b)]) => Bool(a > b),
+        ("le", [Int(a), Int(b)]) => Bool(a <= b),
+        ("ge", [Int(a), Int(b)]) => Bool(a >= b),
+        ("not", [Bool(a)]) => Bool(!a),
+        ("and", [Bool(a), Bool(b)]) => Bool(*a && *b),
+        ("or", [Bool(a), Bool(b)]) => Bool(*a || *b),
+        ("fadd", [Float(a), Float(b)]) => Float(a + b),
+        ("fmul", [Float(a), Float(b)]) => Float(a * b),
+        ("fsub", [Float(a), Float(b)]) => Float(a - b),
+        ("fdiv", [Float(a), Float(b)]) => Float(a / b),
+        ("feq", [Float(a), Float(b)]) => Bool(a == b),
+        ("flt", [Float(a), Float(b)]) => Bool(a < b),
+        ("fgt", [Float(a), Float(b)]) => Bool(a > b),
+        ("fle", [Float(a), Float(b)]) => Bool(a <= b),
+        ("fge", [Float(a), Float(b)]) => Bool(a >= b),
+        ("ceq", [Char(a), Char(b)]) => Bool(a == b),
+        ("clt", [Char(a), Char(b)]) => Bool(a < b),
+        ("cgt", [Char(a), Char(b)]) => Bool(a > b),
+        ("cle", [Char(a), Char(b)]) => Bool(a <= b),
+        ("cge", [Char(a), Char(b)]) => Bool(a >= b),
+        ("char2int", [Char(a)]) => Int(i64::from(u32::from(*a))),
+        ("int2char", [Int(a)]) => Char(char::from_u32(u32::try_from(*a).ok()?)?),
+        _ => return None,
+    };
+    // JSON has no way to write NaN or an infinity, so those stay computed.
+    match value {
+        Float(value) if !value.is_finite() => None,
+        _ => Some(value),
+    }
+}
+
+// Moves what is known about the variables from before `instr` to after it.
+pub fn step<'a>(
+    values: &mut HashMap<&'a str, Option<Literal>>,
+    instr: &'a Instruction,
+    div_by_zero: DivByZero,
+) {
+    let dest = match &instr.dest {
+        Some(dest) => dest,
+        None => return,
+    };
+    let value = match instr.op.as_deref() {
+        Some("const") => instr.literal(),
+        Some(op) if instr.effect() == Effect::Pure => instr
+            .args
+            .iter()
+            .map(|arg| values.get(arg.as_str()).copied().flatten())
+            .collect::<Option<Vec<Literal>>>()
+            .and_then(|args| fold(op, &args, div_by_zero)),
+        _ => None,
+    };
+    values.insert(dest, value);
+}
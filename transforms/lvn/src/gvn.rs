@@ -0,0 +1,226 @@
+use crate::bisect::OptBisect;
+use crate::remarks::Remarks;
+use crate::ssa::{is_ssa, UNDEFINED};
+use crate::superlocal::Key;
+use crate::{take_control_flow_graph, Expression, Function, Instruction};
+use bril_cfg::dominators::DominatorTree;
+use bril_ir::PURE_OPS;
+use fxhash::FxHashMap;
+use std::collections::BTreeSet;
+
+// The value numbering state of the walk down the dominator tree. A value is
+// named by the variable first computing it: in SSA form that variable holds
+// it everywhere its definition dominates, which is everywhere the scope of
+// the table entry reaches.
+#[derive(Default)]
+struct Values {
+    // The value of each variable found to hold one computed earlier.
+    value: FxHashMap<String, String>,
+    // Numbers for the values, to key expressions by.
+    numbers: FxHashMap<String, usize>,
+    table: FxHashMap<Key, String>,
+    // The keys added in each open scope, removed again when it closes.
+    scopes: Vec<Vec<Key>>,
+}
+
+impl Values {
+    fn value_of(&self, var: &str) -> String {
+        self.value
+            .get(var)
+            .cloned()
+            .unwrap_or_else(|| var.to_string())
+    }
+
+    fn number(&mut self, var: &str) -> usize {
+        let value = self.value_of(var);
+        let next = self.numbers.len();
+        *self.numbers.entry(value).or_insert(next)
+    }
+
+    // The expression `instr` computes, if it is one two evaluations with the
+    // same arguments always agree on.
+    fn key(&mut self, instr: &Instruction) -> Option<Key> {
+        let op = instr.op.as_deref()?;
+        let expression = match op {
+            "const" => Expression::Const(instr.literal()?),
+            _ if PURE_OPS.contains(&op) => {
+                let args = instr
+                    .local_args()
+                    .iter()
+                    .map(|arg| self.number(arg))
+                    .collect();
+                Expression::pure_op(op.to_string(), args)
+            }
+            _ => return None,
+        };
+        Some((instr.type_.clone(), expression))
+    }
+
+    fn insert(&mut self, key: Key, var: &str) {
+        if !self.table.contains_key(&key) {
+            self.table.insert(key.clone(), var.to_string());
+            self.scopes.last_mut().unwrap().push(key);
+        }
+    }
+
+    fn close_scope(&mut self) {
+        for key in self.scopes.pop().unwrap() {
+            self.table.remove(&key);
+        }
+    }
+}
+
+// The value a `phi` picks, if it is the same whichever way control comes
+// in; a loop carrying the value around unchanged does not count as another.
+fn single_value(instr: &Instruction, values: &Values) -> Option<String> {
+    let dest = instr.dest.as_ref()?;
+    let incoming: BTreeSet<String> = instr
+        .args
+        .iter()
+        .map(|arg| values.value_of(arg))
+        .filter(|value| value != dest)
+        .collect();
+    match incoming.into_iter().collect::<Vec<_>>()[..] {
+        [ref value] if value != UNDEFINED => Some(value.clone()),
+        _ => None,
+    }
+}
+
+// Dominator-based value numbering, after Briggs, Cooper and Simpson, "Value
+// Numbering". Walking the dominator tree with a scoped table, each block sees
+// the expressions computed in the blocks dominating it, and only those, so an
+// instruction computing one again is deleted and its result read from the
+// variable that first computed it instead. A `phi` picking the same value on
+// every edge, or the same values as another in its block, goes as well, as
+// do copies.
+//
+// This relies on every variable being assigned once, so functions not in SSA
+// form are left alone; run `to-ssa` first.
+pub fn global_value_numbering(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+) -> bool {
+    if function.instrs.is_empty() || !is_ssa(function) {
+        return false;
+    }
+    let mut cfg = take_control_flow_graph(function);
+    let tree = DominatorTree::new(&cfg);
+    let mut def_site = FxHashMap::default();
+    for (b, block) in cfg.iter() {
+        for (i, instr) in block.instrs.iter().enumerate() {
+            if let Some(dest) = &instr.dest {
+                def_site.insert(dest.clone(), (b, i));
+            }
+        }
+    }
+
+    let mut values = Values::default();
+    // The instructions to delete, with the value that replaces each.
+    let mut removed = Vec::new();
+    // Each entry is a block and whether the walk is entering it.
+    let mut stack: Vec<(usize, bool)> =
+        cfg.entry().map(|entry| (entry, true)).into_iter().collect();
+    while let Some((b, entering)) = stack.pop() {
+        if !entering {
+            values.close_scope();
+            continue;
+        }
+        values.scopes.push(Vec::new());
+        stack.push((b, false));
+        stack.extend(tree.children(b).iter().rev().map(|&child| (child, true)));
+
+        remarks.enter(&function.name, b);
+        // The `phi` nodes of the block by the value they pick from each
+        // predecessor.
+        let mut phis: FxHashMap<Vec<(String, String)>, String> = FxHashMap::default();
+        for (i, instr) in cfg.blocks[b].instrs.iter().enumerate() {
+            let dest = match &instr.dest {
+                Some(dest) => dest,
+                None => continue,
+            };
+            let found = if instr.is_phi() {
+                let mut sources: Vec<(String, String)> = instr
+                    .phi_sources()
+                    .map(|(label, arg)| (label.to_string(), values.value_of(arg)))
+                    .collect();
+                sources.sort();
+                if let Some(value) = single_value(instr, &values) {
+                    Some((value, "Meaningless"))
+                } else if let Some(value) = phis.get(&sources) {
+                    Some((value.clone(), "Redundant"))
+                } else {
+                    phis.insert(sources, dest.clone());
+                    None
+                }
+            } else if instr.op.as_deref() == Some("id") {
+                Some((values.value_of(&instr.args[0]), "Copy"))
+            } else if let Some(key) = values.key(instr) {
+                match values.table.get(&key) {
+                    Some(value) => Some((value.clone(), "Redundant")),
+                    None => {
+                        values.insert(key, dest);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            if let Some((value, name)) = found {
+                if bisect.should_run("gvn", &format!("reuse value for `{}`", instr)) {
+                    remarks.passed(
+                        "gvn",
+                        name,
+                        instr,
+                        format!("removed `{}`, reusing `{}`", instr, value),
+                    );
+                    values.value.insert(dest.clone(), value.clone());
+                    removed.push((b, i, value));
+                }
+            }
+        }
+    }
+    if removed.is_empty() {
+        function.instrs = cfg.into_instrs();
+        return false;
+    }
+
+    let mut deleted = vec![Vec::new(); cfg.len()];
+    for (b, i, value) in removed {
+        let instr = cfg.blocks[b].instrs[i].clone();
+        if let Some(&(vb, vi)) = def_site.get(&value) {
+            cfg.blocks[vb].instrs[vi].absorb(&instr);
+        }
+        function
+            .debug
+            .merge_into(instr.dest.as_ref().unwrap(), &value);
+        deleted[b].push(i);
+    }
+    // Every use, reachable or not, reads the value from where it stays.
+    for (b, block) in cfg.blocks.iter_mut().enumerate() {
+        let mut i = 0;
+        block.instrs.retain(|_| {
+            i += 1;
+            !deleted[b].contains(&(i - 1))
+        });
+        for instr in block.instrs.iter_mut() {
+            let mut renamed = false;
+            let args = if instr.is_phi() {
+                &mut instr.args[..]
+            } else {
+                instr.local_args_mut()
+            };
+            for arg in args {
+                if let Some(value) = values.value.get(arg) {
+                    *arg = value.clone();
+                    renamed = true;
+                }
+            }
+            if renamed {
+                instr.mark_transformed("gvn");
+            }
+        }
+    }
+    function.instrs = cfg.into_instrs();
+    true
+}
@@ -0,0 +1,383 @@
+use crate::analysis::compute_dominators;
+use crate::diff::{diff_cfgs, Change};
+use crate::{construct_control_flow_graph, ControlFlowGraph, Program};
+use bril_cfg::liveness::Liveness;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+const CHAR_WIDTH: usize = 8;
+const LINE_HEIGHT: usize = 16;
+const LAYER_GAP: usize = 60;
+const NODE_GAP: usize = 40;
+
+fn block_name(cfg: &ControlFlowGraph, i: usize) -> String {
+    match &cfg.blocks[i].instrs[0].label {
+        Some(label) => format!(".{}", label),
+        None => format!("b{}", i),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+struct Node {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+// Layers blocks by their BFS distance from the entry, putting unreachable
+// blocks in one extra layer at the bottom.
+fn layout(cfg: &ControlFlowGraph) -> Vec<Node> {
+    let n = cfg.blocks.len();
+    let mut depth = vec![usize::MAX; n];
+    let mut queue = VecDeque::new();
+    if n > 0 {
+        depth[0] = 0;
+        queue.push_back(0);
+    }
+    while let Some(i) = queue.pop_front() {
+        for &next in &cfg.blocks[i].next_blocks {
+            if depth[next] == usize::MAX {
+                depth[next] = depth[i] + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+    let max_depth = depth.iter().filter(|d| **d != usize::MAX).max().copied();
+    let unreachable_depth = max_depth.map_or(0, |d| d + 1);
+    for d in depth.iter_mut() {
+        if *d == usize::MAX {
+            *d = unreachable_depth;
+        }
+    }
+
+    let sizes: Vec<(usize, usize)> = cfg
+        .blocks
+        .iter()
+        .map(|block| {
+            let longest = block
+                .instrs
+                .iter()
+                .map(|instr| instr.to_string().len())
+                .max()
+                .unwrap_or(0);
+            // One extra line each for the block name and the liveness overlay.
+            (
+                (longest + 2) * CHAR_WIDTH,
+                (block.instrs.len() + 3) * LINE_HEIGHT,
+            )
+        })
+        .collect();
+
+    let mut nodes: Vec<Node> = sizes
+        .iter()
+        .map(|(width, height)| Node {
+            x: 0,
+            y: 0,
+            width: *width,
+            height: *height,
+        })
+        .collect();
+    let mut y = NODE_GAP;
+    for layer in 0..=unreachable_depth {
+        let mut x = NODE_GAP;
+        let mut layer_height = 0;
+        for i in (0..n).filter(|i| depth[*i] == layer) {
+            nodes[i].x = x;
+            nodes[i].y = y;
+            x += nodes[i].width + NODE_GAP;
+            layer_height = layer_height.max(nodes[i].height);
+        }
+        y += layer_height + LAYER_GAP;
+    }
+    nodes
+}
+
+// Draws one CFG. `changes`, when given, colors every instruction by how it
+// differs from the other side of a diff.
+fn function_svg(
+    key: &str,
+    cfg: &ControlFlowGraph,
+    changes: Option<&[Vec<Change>]>,
+    out: &mut String,
+) {
+    let nodes = layout(cfg);
+    let width = nodes.iter().map(|n| n.x + n.width).max().unwrap_or(0) + NODE_GAP;
+    let height = nodes.iter().map(|n| n.y + n.height).max().unwrap_or(0) + NODE_GAP;
+    let liveness = Liveness::new(cfg);
+
+    writeln!(
+        out,
+        r#"<svg class="cfg" data-function="{}" viewBox="0 0 {} {}" width="{}" height="{}">"#,
+        escape(key),
+        width,
+        height,
+        width,
+        height
+    )
+    .unwrap();
+    for (i, block) in cfg.blocks.iter().enumerate() {
+        for &next in &block.next_blocks {
+            let (from, to) = (&nodes[i], &nodes[next]);
+            let (x1, y1) = (from.x + from.width / 2, from.y + from.height);
+            let (x2, y2) = (to.x + to.width / 2, to.y);
+            if to.y > from.y {
+                writeln!(
+                    out,
+                    r#"<line class="edge" x1="{}" y1="{}" x2="{}" y2="{}"/>"#,
+                    x1, y1, x2, y2
+                )
+                .unwrap();
+            } else {
+                // Back edges loop around the right-hand side of the source block.
+                let side = from.x.max(to.x) + from.width.max(to.width) + NODE_GAP / 2;
+                writeln!(
+                    out,
+                    r#"<path class="edge back" d="M {} {} C {} {}, {} {}, {} {}"/>"#,
+                    x1,
+                    y1,
+                    side,
+                    y1 + LAYER_GAP,
+                    side,
+                    y2.saturating_sub(LAYER_GAP),
+                    x2,
+                    y2
+                )
+                .unwrap();
+            }
+        }
+    }
+    for (i, block) in cfg.blocks.iter().enumerate() {
+        let node = &nodes[i];
+        writeln!(
+            out,
+            r#"<g class="block" data-block="{}" transform="translate({} {})">"#,
+            i, node.x, node.y
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"<rect width="{}" height="{}" rx="4"/>"#,
+            node.width, node.height
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"<text class="name" x="{}" y="{}">{}</text>"#,
+            CHAR_WIDTH,
+            LINE_HEIGHT,
+            escape(&block_name(cfg, i))
+        )
+        .unwrap();
+        for (line, instr) in block.instrs.iter().enumerate() {
+            let class = changes.map_or("", |changes| changes[i][line].class());
+            writeln!(
+                out,
+                r#"<text class="{}" data-id="{}" x="{}" y="{}">{}</text>"#,
+                class,
+                escape(instr.id.as_deref().unwrap_or("")),
+                CHAR_WIDTH,
+                (line + 2) * LINE_HEIGHT,
+                escape(&instr.to_string())
+            )
+            .unwrap();
+        }
+        let live: Vec<&str> = liveness.live_in[i].iter().map(String::as_str).collect();
+        writeln!(
+            out,
+            r#"<text class="overlay-liveness" x="{}" y="{}">in: {}</text>"#,
+            CHAR_WIDTH,
+            (block.instrs.len() + 2) * LINE_HEIGHT,
+            escape(&live.join(" "))
+        )
+        .unwrap();
+        writeln!(out, "</g>").unwrap();
+    }
+    writeln!(out, "</svg>").unwrap();
+}
+
+fn function_analyses(cfg: &ControlFlowGraph) -> serde_json::Value {
+    let liveness = Liveness::new(cfg);
+    let dominators = compute_dominators(cfg);
+    let blocks: Vec<serde_json::Value> = (0..cfg.blocks.len())
+        .map(|i| {
+            json!({
+                "name": block_name(cfg, i),
+                "instrs": cfg.blocks[i].instrs.iter().map(|instr| instr.to_string()).collect::<Vec<_>>(),
+                "live_in": liveness.live_in[i],
+                "live_out": liveness.live_out[i],
+                "dominators": dominators[i],
+            })
+        })
+        .collect();
+    json!(blocks)
+}
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 0; display: flex; height: 100vh; }
+#graphs { flex: 1; overflow: hidden; background: #fafafa; cursor: grab; }
+#side { width: 24em; padding: 1em; overflow: auto; border-left: 1px solid #ccc; }
+h2 { font-size: 1em; margin: 0.5em; }
+.cfg text { font-family: monospace; font-size: 13px; }
+.cfg .name { font-weight: bold; }
+.block rect { fill: white; stroke: #333; }
+.block.selected rect { stroke: #06c; stroke-width: 3; }
+.block.dom rect { fill: #dfe9ff; }
+.edge { stroke: #555; fill: none; marker-end: url(#arrow); }
+.overlay-liveness { fill: #a30; display: none; }
+.show-liveness .overlay-liveness { display: inline; }
+.diff { display: flex; gap: 2em; }
+.added { fill: #080; font-weight: bold; }
+.removed { fill: #c00; text-decoration: line-through; }
+.changed { fill: #c70; font-weight: bold; }
+pre { background: #f0f0f0; padding: 0.5em; }
+"#;
+
+const SCRIPT: &str = r#"
+const graphs = document.getElementById('graphs');
+const side = document.getElementById('details');
+let scale = 1, dx = 0, dy = 0, drag = null;
+function apply() {
+  document.getElementById('canvas').style.transform =
+    `translate(${dx}px, ${dy}px) scale(${scale})`;
+}
+graphs.addEventListener('wheel', (e) => {
+  e.preventDefault();
+  scale *= e.deltaY < 0 ? 1.1 : 1 / 1.1;
+  apply();
+});
+graphs.addEventListener('mousedown', (e) => { drag = [e.clientX - dx, e.clientY - dy]; });
+window.addEventListener('mouseup', () => { drag = null; });
+window.addEventListener('mousemove', (e) => {
+  if (drag) { dx = e.clientX - drag[0]; dy = e.clientY - drag[1]; apply(); }
+});
+document.getElementById('toggle-liveness').addEventListener('change', (e) => {
+  graphs.classList.toggle('show-liveness', e.target.checked);
+});
+let selected = null;
+function highlight() {
+  document.querySelectorAll('.block').forEach((g) => g.classList.remove('selected', 'dom'));
+  if (!selected) return;
+  const [svg, g] = selected;
+  g.classList.add('selected');
+  if (!document.getElementById('toggle-dominators').checked) return;
+  const info = ANALYSES[svg.dataset.function][g.dataset.block];
+  for (const d of info.dominators) {
+    svg.querySelector(`[data-block="${d}"]`).classList.add('dom');
+  }
+}
+document.getElementById('toggle-dominators').addEventListener('change', highlight);
+document.querySelectorAll('.cfg').forEach((svg) => {
+  svg.querySelectorAll('.block').forEach((g) => {
+    g.addEventListener('click', () => {
+      selected = [svg, g];
+      highlight();
+      const blocks = ANALYSES[svg.dataset.function];
+      const info = blocks[g.dataset.block];
+      side.innerHTML = '';
+      const add = (tag, text) => {
+        const el = document.createElement(tag);
+        el.textContent = text;
+        side.appendChild(el);
+      };
+      add('h3', `@${svg.dataset.function} ${info.name}`);
+      add('pre', info.instrs.join('\n'));
+      add('p', `live in: ${info.live_in.join(' ')}`);
+      add('p', `live out: ${info.live_out.join(' ')}`);
+      add('p', `dominators: ${info.dominators.map((d) => blocks[d].name).join(' ')}`);
+    });
+  });
+});
+"#;
+
+pub fn program_to_html(program: &Program) -> String {
+    let mut graphs = String::new();
+    let mut analyses = serde_json::Map::new();
+    for function in &program.functions {
+        let cfg = construct_control_flow_graph(function);
+        writeln!(graphs, "<h2>@{}</h2>", escape(&function.name)).unwrap();
+        function_svg(&function.name, &cfg, None, &mut graphs);
+        analyses.insert(function.name.clone(), function_analyses(&cfg));
+    }
+    page(&graphs, analyses)
+}
+
+// Renders every function of `after` next to its counterpart in `before`.
+pub fn diff_to_html(before: &Program, after: &Program) -> String {
+    let mut graphs = String::new();
+    let mut analyses = serde_json::Map::new();
+    for function in &after.functions {
+        let after_cfg = construct_control_flow_graph(function);
+        let before_cfg = match before.functions.iter().find(|f| f.name == function.name) {
+            Some(old) => construct_control_flow_graph(old),
+            None => ControlFlowGraph { blocks: Vec::new() },
+        };
+        let diff = diff_cfgs(&before_cfg, &after_cfg);
+
+        writeln!(
+            graphs,
+            "<h2>@{}</h2><div class=\"diff\">",
+            escape(&function.name)
+        )
+        .unwrap();
+        for (side, cfg, changes) in [
+            ("before", &before_cfg, &diff.before),
+            ("after", &after_cfg, &diff.after),
+        ] {
+            let key = format!("{} ({})", function.name, side);
+            writeln!(graphs, "<div><h2>{}</h2>", side).unwrap();
+            function_svg(&key, cfg, Some(changes), &mut graphs);
+            writeln!(graphs, "</div>").unwrap();
+            analyses.insert(key, function_analyses(cfg));
+        }
+        writeln!(graphs, "</div>").unwrap();
+    }
+    page(&graphs, analyses)
+}
+
+fn page(graphs: &str, analyses: serde_json::Map<String, serde_json::Value>) -> String {
+    // Keep the embedded JSON from closing the script element early.
+    let analyses = serde_json::Value::Object(analyses)
+        .to_string()
+        .replace("</", "<\\/");
+
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(
+        out,
+        "<html><head><meta charset=\"utf-8\"><title>CFG</title>"
+    )
+    .unwrap();
+    writeln!(out, "<style>{}</style></head><body>", STYLE).unwrap();
+    writeln!(
+        out,
+        r#"<svg width="0" height="0"><defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M 0 0 L 10 5 L 0 10 z"/></marker></defs></svg>"#
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"<div id="graphs"><div id="canvas" style="transform-origin: 0 0">{}</div></div>"#,
+        graphs
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"<div id="side"><label><input type="checkbox" id="toggle-liveness"> liveness</label> <label><input type="checkbox" id="toggle-dominators"> dominators</label><div id="details"><p>Click a block to inspect it.</p></div></div>"#
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "<script>const ANALYSES = {};{}</script>",
+        analyses, SCRIPT
+    )
+    .unwrap();
+    writeln!(out, "</body></html>").unwrap();
+    out
+}
@@ -0,0 +1,234 @@
+use crate::bisect::OptBisect;
+use crate::constprop::call_arguments;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{Function, Instruction, Literal};
+use bril_cfg::callgraph::CallGraph;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// The most instructions a function may have to be cloned.
+const IPCP_BUDGET: usize = 128;
+// The most clones made of one function.
+const IPCP_CLONES: usize = 4;
+
+// The constant each argument of a call holds, where it is known and the
+// callee reads it.
+type Pattern = Vec<Option<Literal>>;
+
+fn describe(function: &Function, pattern: &Pattern) -> String {
+    let known = function
+        .args
+        .iter()
+        .zip(pattern)
+        .filter_map(|(arg, value)| value.map(|value| format!("{} = {}", arg.name, value)));
+    known.collect::<Vec<_>>().join(", ")
+}
+
+// Sets the arguments `pattern` knows to their constants on entry, leaving
+// the rest to constant propagation.
+fn specialize(function: &mut Function, pattern: &Pattern) {
+    let consts: Vec<Instruction> = function
+        .args
+        .iter()
+        .zip(pattern)
+        .filter_map(|(arg, value)| {
+            let mut instr = Instruction {
+                op: Some(String::from("const")),
+                dest: Some(arg.name.clone()),
+                type_: Some(arg.type_.clone()),
+                value: Some((*value)?),
+                ..Default::default()
+            };
+            instr.mark_transformed("ipcp");
+            Some(instr)
+        })
+        .collect();
+    function.instrs.splice(0..0, consts);
+}
+
+// Specializes `@name` for the constants its calls pass it.
+fn propagate_into(
+    functions: &mut Vec<Function>,
+    name: &str,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let graph = CallGraph::new(functions);
+    let g = match graph.function(name) {
+        Some(g) => g,
+        None => return false,
+    };
+    let callee = &functions[g];
+    if callee.instrs.iter().any(Instruction::is_ssa_op) {
+        return false;
+    }
+    let read: HashSet<&str> = callee
+        .instrs
+        .iter()
+        .flat_map(|instr| instr.args.iter().map(String::as_str))
+        .collect();
+    let mut arguments = HashMap::new();
+    let mut sites: Vec<(usize, usize, Pattern)> = Vec::new();
+    for &s in &graph.callers[g] {
+        let site = &graph.sites[s];
+        let args = arguments
+            .entry(site.caller)
+            .or_insert_with(|| call_arguments(&functions[site.caller], div_by_zero));
+        let values = match args.get(&site.instr) {
+            Some(values) if values.len() == callee.args.len() => values,
+            Some(_) => return false,
+            None => continue,
+        };
+        let pattern = callee.args.iter().zip(values);
+        let pattern =
+            pattern.map(|(arg, value)| value.filter(|_| read.contains(arg.name.as_str())));
+        sites.push((site.caller, site.instr, pattern.collect()));
+    }
+    if sites.is_empty() {
+        return false;
+    }
+
+    let mut changed = false;
+    let common: Pattern = (0..callee.args.len())
+        .map(|a| {
+            let value = sites[0].2[a];
+            value.filter(|_| sites.iter().all(|site| site.2[a] == value))
+        })
+        .collect();
+    if common.iter().any(Option::is_some)
+        && bisect.should_run(
+            "ipcp",
+            &format!("specialize @{} for {}", name, describe(callee, &common)),
+        )
+    {
+        let message = format!(
+            "set {} on entry to @{}, as every call passes the same",
+            describe(callee, &common),
+            name
+        );
+        specialize(&mut functions[g], &common);
+        remarks.enter(name, 0);
+        remarks.passed("ipcp", "Specialized", &functions[g].instrs[0], message);
+        for site in &mut sites {
+            for (value, known) in site.2.iter_mut().zip(&common) {
+                if known.is_some() {
+                    *value = None;
+                }
+            }
+        }
+        changed = true;
+    }
+
+    // The calls passing the same constants share a clone, and the clones
+    // for the most calls are made first.
+    let mut groups: Vec<(Pattern, Vec<(usize, usize)>)> = Vec::new();
+    for (caller, instr, pattern) in sites {
+        if pattern.iter().all(Option::is_none) {
+            continue;
+        }
+        match groups.iter_mut().find(|(p, _)| *p == pattern) {
+            Some((_, calls)) => calls.push((caller, instr)),
+            None => groups.push((pattern, vec![(caller, instr)])),
+        }
+    }
+    groups.sort_by_key(|(_, calls)| std::cmp::Reverse(calls.len()));
+    if groups.is_empty() {
+        return changed;
+    }
+    let size = functions[g].instrs.len();
+    if size > IPCP_BUDGET {
+        remarks.enter(name, 0);
+        remarks.missed(
+            "ipcp",
+            "TooLarge",
+            functions[g]
+                .instrs
+                .first()
+                .unwrap_or(&Instruction::default()),
+            format!(
+                "kept @{} for calls passing constants, its {} instructions being too many to clone",
+                name, size
+            ),
+        );
+        return changed;
+    }
+    let mut taken: HashSet<String> = functions.iter().map(|f| f.name.clone()).collect();
+    for (pattern, calls) in groups.into_iter().take(IPCP_CLONES) {
+        let what = describe(&functions[g], &pattern);
+        if !bisect.should_run("ipcp", &format!("clone @{} for {}", name, what)) {
+            continue;
+        }
+        let clone_name = (0..)
+            .map(|k| format!("{}.{}", name, k))
+            .find(|candidate| !taken.contains(candidate))
+            .unwrap();
+        taken.insert(clone_name.clone());
+        let mut clone = functions[g].clone();
+        clone.name = clone_name.clone();
+        for instr in &mut clone.instrs {
+            instr.mark_transformed("ipcp");
+        }
+        specialize(&mut clone, &pattern);
+        for (caller, i) in calls {
+            remarks.enter_at(&functions[caller], i);
+            let call = &mut functions[caller].instrs[i];
+            call.funcs = vec![clone_name.clone()];
+            call.mark_transformed("ipcp");
+            remarks.passed(
+                "ipcp",
+                "Cloned",
+                call,
+                format!(
+                    "called @{}, a copy of @{} specialized for {}",
+                    clone_name, name, what
+                ),
+            );
+        }
+        functions.push(clone);
+        changed = true;
+    }
+    changed
+}
+
+// Interprocedural constant propagation: an argument every call to a function
+// passes the same constant for is set to it on entry, and calls passing
+// constants for arguments others do not go to a clone of the function set up
+// the same way, so constant propagation can fold what follows from them. The
+// calls to each function are looked at only after its callers have been
+// specialized, so constants flow down chains of calls.
+//
+// This takes the program to be whole, so one without a `@main`, which could be
+// called from anywhere, is left alone, and so are `@main` itself and the
+// functions in `exported`, which the files the program imports from call
+// with arguments it cannot see. Functions in SSA form are too, as are
+// functions over `IPCP_BUDGET` instructions for cloning, and at most
+// `IPCP_CLONES` clones are made of each function. The originals are left for
+// dfe once nothing calls them.
+pub fn propagate_arguments(
+    functions: &mut Vec<Function>,
+    exported: &BTreeSet<String>,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let graph = CallGraph::new(functions);
+    if graph.function("main").is_none() {
+        return false;
+    }
+    let order: Vec<String> = graph
+        .components
+        .iter()
+        .rev()
+        .flatten()
+        .map(|&f| graph.names[f].clone())
+        .collect();
+    let mut changed = false;
+    for name in order
+        .iter()
+        .filter(|&name| name != "main" && !exported.contains(name))
+    {
+        changed |= propagate_into(functions, name, remarks, bisect, div_by_zero);
+    }
+    changed
+}
@@ -0,0 +1,342 @@
+use crate::backend::{collect_targets, lower, mangle, shadow_types, variable_types};
+use crate::structure::{structure, Node};
+use crate::{
+    construct_control_flow_graph, BrilError, ControlFlowGraph, Function, Instruction, Literal,
+    Program, Type,
+};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+// Values are represented the way the reference interpreter represents them
+// (BigInt for int, number for float, boolean for bool), so printing and
+// errors match it exactly. Runs under both Node and Deno.
+const RUNTIME: &str = r#""use strict";
+
+class BrilError extends Error {}
+
+const brilArgs = typeof Deno !== "undefined" ? Deno.args : process.argv.slice(2);
+const brilExit = (code) => (typeof Deno !== "undefined" ? Deno.exit(code) : process.exit(code));
+let brilLiveAllocations = 0;
+
+function bril_error(message) {
+  throw new BrilError(message);
+}
+
+function bril_print(...values) {
+  console.log(...values.map((v) => {
+    if (Object.is(-0, v)) return "-0.00000000000000000";
+    return typeof v === "number" ? v.toFixed(17) : v.toString();
+  }));
+}
+
+function bril_div(a, b) {
+  if (b === 0n) bril_error("division by zero");
+  return BigInt.asIntN(64, a / b);
+}
+
+function bril_alloc(count) {
+  if (count <= 0n) bril_error(`must allocate a positive amount of memory: ${count} <= 0`);
+  brilLiveAllocations++;
+  return { cells: new Array(Number(count)), offset: 0, freed: false };
+}
+
+function bril_free(p) {
+  if (p.offset !== 0 || p.freed) bril_error("tried to free illegal memory location");
+  p.freed = true;
+  brilLiveAllocations--;
+}
+
+function bril_ptradd(p, n) {
+  return { cells: p.cells, offset: p.offset + Number(n), freed: p.freed };
+}
+
+function bril_check(p) {
+  if (p.offset < 0 || p.offset >= p.cells.length) bril_error("uninitialized heap location");
+}
+
+function bril_load(p) {
+  bril_check(p);
+  const value = p.cells[p.offset];
+  if (value === undefined) bril_error("pointer has not been initialized");
+  return value;
+}
+
+function bril_store(p, value) {
+  bril_check(p);
+  p.cells[p.offset] = value;
+}
+
+function bril_bool(s) {
+  if (s !== "true" && s !== "false") bril_error(`${s} is not a boolean`);
+  return s === "true";
+}
+"#;
+
+fn var(name: &str) -> String {
+    format!("v_{}", mangle(name))
+}
+
+// Where `set` leaves a value for the `get` of the variable.
+fn shadow(name: &str) -> String {
+    format!("s_{}", mangle(name))
+}
+
+fn func(name: &str) -> String {
+    format!("f_{}", mangle(name))
+}
+
+fn initial_value(type_: &Type) -> &'static str {
+    match type_ {
+        Type::Prim(name) if name == "int" => "0n",
+        Type::Prim(name) if name == "bool" => "false",
+        Type::Prim(name) if name == "float" => "0",
+        // `lower` rejects the rest.
+        Type::Prim(name) => unreachable!("unsupported type {}", name),
+        Type::Ptr { .. } => "null",
+    }
+}
+
+fn literal(value: Literal, type_: Option<&Type>) -> String {
+    let is_float = matches!(type_, Some(Type::Prim(name)) if name == "float");
+    match value {
+        Literal::Int(value) if is_float => value.to_string(),
+        Literal::Int(value) => format!("{}n", value),
+        Literal::Bool(value) => value.to_string(),
+        Literal::Float(value) if value.is_nan() => String::from("NaN"),
+        Literal::Float(value) if value.is_infinite() => {
+            String::from(if value > 0.0 { "Infinity" } else { "-Infinity" })
+        }
+        Literal::Float(value) => format!("{:?}", value),
+        Literal::Char(_) => unreachable!("`lower` rejects char"),
+    }
+}
+
+struct Emitter<'a> {
+    cfg: &'a ControlFlowGraph,
+    targets: HashSet<String>,
+    loops: Vec<usize>,
+    out: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn line(&mut self, depth: usize, text: &str) {
+        writeln!(self.out, "{}{}", "  ".repeat(depth), text).unwrap();
+    }
+
+    fn instr(&mut self, depth: usize, instr: &Instruction) -> Result<(), BrilError> {
+        let op = instr.op.as_deref().unwrap_or_default();
+        let a: Vec<String> = instr.args.iter().map(|arg| var(arg)).collect();
+        let binary = |symbol: &str| format!("{} {} {}", a[0], symbol, a[1]);
+        let wrapping = |symbol: &str| format!("BigInt.asIntN(64, {} {} {})", a[0], symbol, a[1]);
+        let value = match op {
+            "const" => match instr.value {
+                Some(value) => literal(value, instr.type_.as_ref()),
+                None => return Err(BrilError::malformed(instr, "a constant needs a value")),
+            },
+            "id" => a[0].clone(),
+            "add" => wrapping("+"),
+            "sub" => wrapping("-"),
+            "mul" => wrapping("*"),
+            "div" => format!("bril_div({}, {})", a[0], a[1]),
+            "eq" | "feq" => binary("==="),
+            "lt" | "flt" => binary("<"),
+            "gt" | "fgt" => binary(">"),
+            "le" | "fle" => binary("<="),
+            "ge" | "fge" => binary(">="),
+            "fadd" => binary("+"),
+            "fsub" => binary("-"),
+            "fmul" => binary("*"),
+            "fdiv" => binary("/"),
+            "not" => format!("!{}", a[0]),
+            "and" => binary("&&"),
+            "or" => binary("||"),
+            "call" => format!("{}({})", func(&instr.funcs[0]), a.join(", ")),
+            "alloc" => format!("bril_alloc({})", a[0]),
+            "load" => format!("bril_load({})", a[0]),
+            "ptradd" => format!("bril_ptradd({}, {})", a[0], a[1]),
+            "free" => format!("bril_free({})", a[0]),
+            "store" => format!("bril_store({}, {})", a[0], a[1]),
+            "print" => format!("bril_print({})", a.join(", ")),
+            "get" => instr.dest.as_deref().map(shadow).unwrap_or_default(),
+            "set" => format!("{} = {}", shadow(&instr.args[0]), a[1]),
+            "nop" => return Ok(()),
+            _ => {
+                let message = format!("the JavaScript backend does not support `{}`", op);
+                return Err(BrilError::malformed(instr, message));
+            }
+        };
+        match &instr.dest {
+            Some(dest) => self.line(depth, &format!("{} = {};", var(dest), value)),
+            None => self.line(depth, &format!("{};", value)),
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node, depth: usize) -> Result<(), BrilError> {
+        match node {
+            Node::Code(b) => {
+                let cfg = self.cfg;
+                for instr in &cfg.blocks[*b].instrs {
+                    if !instr.is_label() && !instr.is_terminator() {
+                        self.instr(depth, instr)?;
+                    }
+                }
+            }
+            Node::Seq(nodes) => {
+                for node in nodes {
+                    self.node(node, depth)?;
+                }
+            }
+            Node::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.line(depth, &format!("if ({}) {{", var(cond)));
+                self.node(then_branch, depth + 1)?;
+                self.line(depth, "} else {");
+                self.node(else_branch, depth + 1)?;
+                self.line(depth, "}");
+            }
+            Node::Block { label, body } => {
+                let name = format!("break_{}", label);
+                if self.targets.contains(&name) {
+                    self.line(depth, &format!("{}: {{", name));
+                    self.node(body, depth + 1)?;
+                    self.line(depth, "}");
+                } else {
+                    self.node(body, depth)?;
+                }
+            }
+            Node::Loop { label, body } => {
+                let name = format!("top_{}", label);
+                if self.targets.contains(&name) {
+                    self.line(depth, &format!("{}: for (;;) {{", name));
+                } else {
+                    self.line(depth, "for (;;) {");
+                }
+                self.loops.push(*label);
+                self.node(body, depth + 1)?;
+                self.loops.pop();
+                self.line(depth, "}");
+            }
+            Node::Break(label) => self.line(depth, &format!("break break_{};", label)),
+            Node::Continue(label) if self.loops.last() == Some(label) => {
+                self.line(depth, "continue;")
+            }
+            Node::Continue(label) => self.line(depth, &format!("continue top_{};", label)),
+            Node::Return(Some(value)) => self.line(depth, &format!("return {};", var(value))),
+            Node::Return(None) => self.line(depth, "return;"),
+            // The Relooper's label variable and switch.
+            Node::Dispatch { entry, arms } => {
+                self.line(depth, &format!("let label = {};", entry));
+                self.line(depth, "dispatch: for (;;) {");
+                self.line(depth + 1, "switch (label) {");
+                for (b, arm) in arms {
+                    self.line(depth + 2, &format!("case {}: {{", b));
+                    self.node(arm, depth + 3)?;
+                    self.line(depth + 2, "}");
+                }
+                self.line(depth + 1, "}");
+                self.line(depth, "}");
+            }
+            Node::Goto(label) => {
+                self.line(depth, &format!("label = {};", label));
+                self.line(depth, "continue dispatch;");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn emit_function(function: &Function, out: &mut String) -> Result<(), BrilError> {
+    let function = &lower(function, "JavaScript")?;
+    let params: Vec<String> = function.args.iter().map(|arg| var(&arg.name)).collect();
+    writeln!(
+        out,
+        "function {}({}) {{",
+        func(&function.name),
+        params.join(", ")
+    )
+    .unwrap();
+    let types = variable_types(function);
+    for (name, type_) in &types {
+        if !function.args.iter().any(|arg| &arg.name == name) {
+            writeln!(out, "  let {} = {};", var(name), initial_value(type_)).unwrap();
+        }
+    }
+    for (name, type_) in shadow_types(function, &types) {
+        writeln!(out, "  let {} = {};", shadow(&name), initial_value(&type_)).unwrap();
+    }
+    let cfg = construct_control_flow_graph(function);
+    if !cfg.blocks.is_empty() {
+        let tree = structure(&cfg);
+        let mut targets = HashSet::new();
+        collect_targets(&tree, &mut targets, &mut Vec::new());
+        let mut emitter = Emitter {
+            cfg: &cfg,
+            targets,
+            loops: Vec::new(),
+            out: String::new(),
+        };
+        emitter
+            .node(&tree, 1)
+            .map_err(|e| e.in_function(function))?;
+        out.push_str(&emitter.out);
+    }
+    writeln!(out, "}}").unwrap();
+    Ok(())
+}
+
+fn parse_arg(type_: &Type, source: &str) -> Result<String, BrilError> {
+    match type_ {
+        Type::Prim(name) if name == "int" => Ok(format!("BigInt({})", source)),
+        Type::Prim(name) if name == "bool" => Ok(format!("bril_bool({})", source)),
+        Type::Prim(name) if name == "float" => Ok(format!("Number({})", source)),
+        _ => Err(BrilError::malformed_program(format!(
+            "@main cannot take an argument of type {}",
+            type_
+        ))),
+    }
+}
+
+// Translates a program to a standalone script that takes the arguments of
+// `main` on the command line.
+pub fn program_to_js(program: &Program) -> Result<String, BrilError> {
+    let main = program
+        .functions
+        .iter()
+        .find(|f| f.name == "main")
+        .ok_or_else(|| BrilError::malformed_program("there is no @main to run"))?;
+    let mut out = String::from(RUNTIME);
+    for function in &program.functions {
+        out.push('\n');
+        emit_function(function, &mut out)?;
+    }
+
+    let args = main
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| parse_arg(&arg.type_, &format!("brilArgs[{}]", i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    writeln!(out, "\ntry {{").unwrap();
+    writeln!(
+        out,
+        "  if (brilArgs.length !== {0}) bril_error(`mismatched main argument arity: expected {0}; got ${{brilArgs.length}}`);",
+        main.args.len()
+    )
+    .unwrap();
+    writeln!(out, "  {}({});", func("main"), args.join(", ")).unwrap();
+    writeln!(
+        out,
+        "  if (brilLiveAllocations !== 0) bril_error(\"Some memory locations have not been freed by end of execution.\");"
+    )
+    .unwrap();
+    writeln!(out, "}} catch (e) {{").unwrap();
+    writeln!(out, "  if (!(e instanceof BrilError)) throw e;").unwrap();
+    writeln!(out, "  console.error(`error: ${{e.message}}`);").unwrap();
+    writeln!(out, "  brilExit(2);").unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
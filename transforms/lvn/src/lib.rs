@@ -0,0 +1,1150 @@
+// The optimizer behind lvn and bril-opt: the passes, the driver running
+// them over a program, and the views and backends of `--emit`.
+use fxhash::{FxHashMap, FxHashSet};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+mod analysis;
+mod available;
+mod backend;
+mod bisect;
+mod c_backend;
+pub mod cli;
+mod cloning;
+pub mod config;
+mod conform;
+mod constprop;
+mod dce;
+mod dfe;
+mod diff;
+mod directive;
+mod dse;
+pub mod ebb;
+mod fixpoint;
+mod fold;
+mod gvn;
+mod html;
+mod ipcp;
+mod js_backend;
+mod licm;
+mod mem2reg;
+mod metrics;
+mod names;
+mod pass;
+mod pipeline;
+mod regions;
+mod remarks;
+mod rle;
+mod simplify;
+mod split;
+mod ssa;
+mod structure;
+mod superlocal;
+mod tce;
+mod threading;
+pub mod trapping;
+mod unroll;
+mod unswitch;
+
+use bisect::OptBisect;
+use bril_cfg::liveness::Liveness;
+use bril_cfg::{
+    construct_control_flow_graph, take_control_flow_graph, try_construct_control_flow_graph, Block,
+    ControlFlowGraph,
+};
+use bril_cli::CommonArgs;
+use bril_ir::debug_info::DebugInfo;
+use bril_ir::error::BrilError;
+use bril_ir::intern::{Interner, Symbol};
+use bril_ir::{Effect, Function, Instruction, Literal, Program, Type};
+use cli::Cli;
+use metrics::Metrics;
+use names::Names;
+use pass::{AnalysisManager, Pass, Registry};
+use pipeline::Step;
+use remarks::Remarks;
+use trapping::DivByZero;
+
+// Gives every instruction without an ID one made of its function's name and
+// its position there, like `main.3`.
+fn assign_ids(program: &mut Program) {
+    for function in &mut program.functions {
+        for (i, instr) in function.instrs.iter_mut().enumerate() {
+            if instr.op.is_some() && instr.id.is_none() {
+                instr.id = Some(format!("{}.{}", function.name, i));
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+enum Expression {
+    Op(String, Vec<usize>),
+    Const(Literal),
+}
+
+// Operations whose result does not depend on the order of their arguments.
+const COMMUTATIVE_OPS: [&str; 9] = [
+    "add", "mul", "eq", "and", "or", "fadd", "fmul", "feq", "ceq",
+];
+
+impl Expression {
+    // The expression for `op` applied to `args`, with the arguments of a
+    // commutative operation sorted so that `add a b` and `add b a` are the
+    // same expression.
+    fn pure_op(op: String, mut args: Vec<usize>) -> Self {
+        if COMMUTATIVE_OPS.contains(&op.as_str()) {
+            args.sort_unstable();
+        }
+        Expression::Op(op, args)
+    }
+}
+
+// The expressions of a block, each stored once however many values compute
+// it, and which of them each value is.
+#[derive(Default)]
+struct Values {
+    expressions: Vec<Expression>,
+    ids: FxHashMap<Expression, usize>,
+    // The first value computing each expression, for those later values
+    // may reuse.
+    first: Vec<Option<usize>>,
+    of_number: FxHashMap<usize, usize>,
+}
+
+impl Values {
+    // The index of `expression`, stored the first time it is seen.
+    fn intern(&mut self, expression: Expression) -> usize {
+        if let Some(&id) = self.ids.get(&expression) {
+            return id;
+        }
+        let id = self.expressions.len();
+        self.ids.insert(expression.clone(), id);
+        self.expressions.push(expression);
+        self.first.push(None);
+        id
+    }
+
+    // Records that value `number` computes expression `id`.
+    fn define(&mut self, number: usize, id: usize) {
+        self.of_number.insert(number, id);
+    }
+
+    // The expression value `number` computes; values from outside the block
+    // have none.
+    fn expression(&self, number: usize) -> Option<&Expression> {
+        self.of_number.get(&number).map(|&id| &self.expressions[id])
+    }
+}
+
+enum Identity {
+    // The result is a copy of the argument at this index.
+    Copy(usize),
+    Const(Literal),
+}
+
+// Algebraic identities that hold for any value of the operands. `args` are
+// the value numbers of the instruction's arguments.
+fn simplify(
+    instr: &Instruction,
+    args: &[usize],
+    values: &Values,
+    div_by_zero: DivByZero,
+) -> Option<Identity> {
+    let is_int = |number: usize, value: i64| {
+        values.expression(number) == Some(&Expression::Const(Literal::Int(value)))
+    };
+    let is_bool = |number: usize, value: bool| {
+        values.expression(number) == Some(&Expression::Const(Literal::Bool(value)))
+    };
+    // Float constants compare bitwise, so `0.0` and `-0.0` are told apart.
+    let is_float = |number: usize, value: f64| {
+        values.expression(number) == Some(&Expression::Const(Literal::Float(value)))
+    };
+    match (instr.op.as_deref()?, args) {
+        ("add", &[_, b]) if is_int(b, 0) => Some(Identity::Copy(0)),
+        ("add", &[a, _]) if is_int(a, 0) => Some(Identity::Copy(1)),
+        ("sub", &[_, b]) if is_int(b, 0) => Some(Identity::Copy(0)),
+        ("mul", &[_, b]) if is_int(b, 1) => Some(Identity::Copy(0)),
+        ("mul", &[a, _]) if is_int(a, 1) => Some(Identity::Copy(1)),
+        ("mul", &[a, b]) if is_int(a, 0) || is_int(b, 0) => Some(Identity::Const(Literal::Int(0))),
+        ("div", &[_, b]) if is_int(b, 1) => Some(Identity::Copy(0)),
+        ("ptradd", &[_, b]) if is_int(b, 0) => Some(Identity::Copy(0)),
+        ("sub", &[a, b]) if a == b => Some(Identity::Const(Literal::Int(0))),
+        ("eq" | "le" | "ge", &[a, b]) if a == b => Some(Identity::Const(Literal::Bool(true))),
+        ("lt" | "gt", &[a, b]) if a == b => Some(Identity::Const(Literal::Bool(false))),
+        ("and" | "or", &[a, b]) if a == b => Some(Identity::Copy(0)),
+        ("and", &[_, b]) if is_bool(b, true) => Some(Identity::Copy(0)),
+        ("and", &[a, _]) if is_bool(a, true) => Some(Identity::Copy(1)),
+        ("and", &[a, b]) if is_bool(a, false) || is_bool(b, false) => {
+            Some(Identity::Const(Literal::Bool(false)))
+        }
+        ("or", &[_, b]) if is_bool(b, false) => Some(Identity::Copy(0)),
+        ("or", &[a, _]) if is_bool(a, false) => Some(Identity::Copy(1)),
+        ("or", &[a, b]) if is_bool(a, true) || is_bool(b, true) => {
+            Some(Identity::Const(Literal::Bool(true)))
+        }
+        ("div", &[_, b]) if is_int(b, 0) && div_by_zero == DivByZero::Zero => {
+            Some(Identity::Const(Literal::Int(0)))
+        }
+        // The float identities have to hold for NaN and both zeros as well:
+        // `x + 0.0` is `0.0` for `x = -0.0`, and `feq x x` and `fsub x x`
+        // are not `true` and `0.0` for NaN, so those stay.
+        ("fadd", &[_, b]) if is_float(b, -0.0) => Some(Identity::Copy(0)),
+        ("fadd", &[a, _]) if is_float(a, -0.0) => Some(Identity::Copy(1)),
+        ("fsub", &[_, b]) if is_float(b, 0.0) => Some(Identity::Copy(0)),
+        ("fmul", &[_, b]) if is_float(b, 1.0) => Some(Identity::Copy(0)),
+        ("fmul", &[a, _]) if is_float(a, 1.0) => Some(Identity::Copy(1)),
+        ("fdiv", &[_, b]) if is_float(b, 1.0) => Some(Identity::Copy(0)),
+        ("flt" | "fgt", &[a, b]) if a == b => Some(Identity::Const(Literal::Bool(false))),
+        _ => None,
+    }
+}
+
+// The constant `instr` computes when every argument is one. Copies are left
+// to value numbering, which already treats them as their argument's value.
+fn fold_constants(
+    instr: &Instruction,
+    args: &[usize],
+    values: &Values,
+    div_by_zero: DivByZero,
+) -> Option<Literal> {
+    let op = instr.op.as_deref()?;
+    if op == "id" || op == "const" {
+        return None;
+    }
+    let values = args
+        .iter()
+        .map(|&number| match values.expression(number) {
+            Some(Expression::Const(value)) => Some(*value),
+            _ => None,
+        })
+        .collect::<Option<Vec<Literal>>>()?;
+    fold::fold(op, &values, div_by_zero)
+}
+
+// Marks everything the expressions in `queue` are computed from as used.
+fn mark_operands_used(
+    mut queue: VecDeque<usize>,
+    used_numbers: &mut FxHashSet<usize>,
+    values: &Values,
+) {
+    while !queue.is_empty() {
+        let number = queue.pop_front().unwrap();
+        // Values from outside the block and constants are not computed from
+        // anything.
+        if let Some(Expression::Op(_, args)) = values.expression(number) {
+            for arg in args {
+                if used_numbers.contains(arg) {
+                    continue;
+                }
+                used_numbers.insert(*arg);
+                queue.push_back(*arg);
+            }
+        }
+    }
+}
+
+// The value number of `var`. A variable the block reads before assigning it
+// comes from outside, a function argument or a predecessor, so it gets a
+// fresh number of its own the first time, recorded in `inputs`.
+fn number_of(
+    var: Symbol,
+    variable_to_number: &mut FxHashMap<Symbol, usize>,
+    next_number: &mut usize,
+    inputs: &mut Vec<(Symbol, usize)>,
+) -> usize {
+    if let Some(&number) = variable_to_number.get(&var) {
+        return number;
+    }
+    let number = *next_number;
+    *next_number += 1;
+    variable_to_number.insert(var, number);
+    inputs.push((var, number));
+    number
+}
+
+// `live_out` holds the variables later blocks may read, whose last
+// assignment in the block has to stay; `names` hands out the names for
+// values whose variable is assigned again while they are still needed.
+//
+// The tables keyed by variable use the symbols of the block's variables, so
+// recording one does not copy its name.
+fn run_local_value_numbering(
+    block: &mut Block,
+    live_out: &BTreeSet<String>,
+    names: &mut Names,
+    remarks: &mut Remarks,
+    debug_info: &mut DebugInfo,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let mut symbols = Interner::new();
+    let mut variable_to_number: FxHashMap<Symbol, usize> = FxHashMap::default();
+    let mut values = Values::default();
+    let mut next_number = 0;
+    let mut inputs = Vec::new();
+    let mut used_numbers = FxHashSet::default();
+    // The value number of memory as it is between instructions, a new one
+    // after each that writes it, which loads are numbered with.
+    let mut memory = next_number;
+    next_number += 1;
+    let mut instruction_numbers = Vec::new();
+    // The values each instruction reads.
+    let mut instruction_args = Vec::new();
+    let mut changed = false;
+    for instr in block.instrs.iter_mut() {
+        if let Some(dest) = instr.dest.as_deref().map(|dest| symbols.intern(dest)) {
+            // Convert args to value numbers.
+            let args: Vec<usize> = instr
+                .local_args()
+                .iter()
+                .map(|arg| {
+                    let arg = symbols.intern(arg);
+                    number_of(arg, &mut variable_to_number, &mut next_number, &mut inputs)
+                })
+                .collect();
+            instruction_args.push(args.clone());
+            let mut alias = None;
+            if let Some(value) = fold_constants(instr, &args, &values, div_by_zero) {
+                if bisect.should_run("lvn", &format!("fold `{}`", instr)) {
+                    let original = instr.to_string();
+                    instr.op = Some(String::from("const"));
+                    instr.args.clear();
+                    instr.value = Some(value);
+                    instr.mark_transformed("lvn");
+                    changed = true;
+                    remarks.passed(
+                        "lvn",
+                        "Folded",
+                        instr,
+                        format!("folded `{}` to `{}`", original, instr),
+                    );
+                }
+            } else if let Some(identity) = simplify(instr, &args, &values, div_by_zero) {
+                if bisect.should_run("lvn", &format!("simplify `{}`", instr)) {
+                    let original = instr.to_string();
+                    match identity {
+                        Identity::Copy(i) => {
+                            instr.op = Some(String::from("id"));
+                            instr.args = vec![instr.args[i].clone()];
+                            alias = Some(args[i]);
+                        }
+                        Identity::Const(value) => {
+                            instr.op = Some(String::from("const"));
+                            instr.args.clear();
+                            instr.value = Some(value);
+                        }
+                    }
+                    instr.mark_transformed("lvn");
+                    changed = true;
+                    remarks.passed(
+                        "lvn",
+                        "Identity",
+                        instr,
+                        format!("simplified `{}` to `{}`", original, instr),
+                    );
+                }
+            }
+            let op = instr.op.as_ref().expect("No op found").clone();
+            let number = if let Some(number) = alias {
+                // The copy is the very same value as its argument.
+                number
+            } else if op == "id" && bisect.should_run("lvn", &format!("forward copy `{}`", instr)) {
+                // So is any other copy, which makes later uses read the
+                // argument's variable and chains of copies collapse.
+                args[0]
+            } else if instr.effect() != Effect::Pure && op != "load" {
+                // What these produce depends on more than their arguments,
+                // so each is a value of its own. Those that change anything
+                // run even when their result goes unused, like those that
+                // may trap.
+                let number = next_number;
+                next_number += 1;
+                if instr.effect() == Effect::Writes || div_by_zero.keeps_unused(&op) {
+                    used_numbers.insert(number);
+                }
+                let id = values.intern(Expression::Op(op, args));
+                values.define(number, id);
+                number
+            } else {
+                // A trap may be the only thing an unused value does.
+                let keep = div_by_zero.keeps_unused(&op);
+                let expression = if op == "const" {
+                    Expression::Const(instr.literal().unwrap())
+                } else if op == "load" {
+                    // A load gives what the memory it reads holds, so it is
+                    // the same value as another only until memory changes.
+                    Expression::Op(op, vec![args[0], memory])
+                } else {
+                    // Construct expression (op, vn1, vn2, ...)
+                    Expression::pure_op(op, args)
+                };
+                // Look it up, create if missing or reuse.
+                let id = values.intern(expression);
+                let number = match values.first[id] {
+                    Some(number)
+                        if bisect.should_run("lvn", &format!("reuse value for `{}`", instr)) =>
+                    {
+                        number
+                    }
+                    _ => {
+                        let number = next_number;
+                        next_number += 1;
+                        values.first[id].get_or_insert(number);
+                        number
+                    }
+                };
+                if keep {
+                    used_numbers.insert(number);
+                }
+                values.define(number, id);
+                number
+            };
+            // Update the mapping from variable name (dest) to value number.
+            variable_to_number.insert(dest, number);
+            instruction_numbers.push(Some(number));
+        } else {
+            let mut args = Vec::new();
+            if instr.op.is_some() {
+                for arg in instr.local_args() {
+                    args.push(number_of(
+                        symbols.intern(arg),
+                        &mut variable_to_number,
+                        &mut next_number,
+                        &mut inputs,
+                    ));
+                }
+            }
+            used_numbers.extend(&args);
+            instruction_args.push(args);
+            instruction_numbers.push(None);
+        }
+        if instr.writes_memory() {
+            memory = next_number;
+            next_number += 1;
+        }
+    }
+    // Every variable of the block has a symbol by now.
+    let symbol = |var: &str| symbols.get(var).expect("No symbol for variable");
+
+    // Later blocks read what the variables hold when the block ends.
+    for var in live_out {
+        let number = symbols
+            .get(var)
+            .and_then(|var| variable_to_number.get(&var));
+        if let Some(&number) = number {
+            used_numbers.insert(number);
+        }
+    }
+    let queue = used_numbers.iter().copied().collect();
+    mark_operands_used(queue, &mut used_numbers, &values);
+
+    // Deleting a dead instruction is a decision of its own; keeping one
+    // keeps everything it is computed from as well.
+    for (i, instr) in block.instrs.iter().enumerate() {
+        if let Some(number) = instruction_numbers[i] {
+            if !used_numbers.contains(&number)
+                && !bisect.should_run("lvn", &format!("delete dead `{}`", instr))
+            {
+                used_numbers.insert(number);
+                let queue = VecDeque::from([number]);
+                mark_operands_used(queue, &mut used_numbers, &values);
+            }
+        }
+    }
+
+    let mut last_def = FxHashMap::default();
+    for (i, instr) in block.instrs.iter().enumerate() {
+        if let Some(dest) = &instr.dest {
+            last_def.insert(symbol(dest), i);
+        }
+    }
+
+    // Which instructions stay, as the loop below decides, and the last one
+    // reading each value. A variable assigned again while its value is still
+    // to be read cannot keep holding that value.
+    let mut kept = vec![false; block.instrs.len()];
+    let mut last_read: FxHashMap<usize, usize> = FxHashMap::default();
+    let mut placed: FxHashSet<usize> = inputs.iter().map(|(_, number)| *number).collect();
+    for (i, instr) in block.instrs.iter().enumerate() {
+        let reads = match instruction_numbers[i] {
+            None => instruction_args[i].clone(),
+            Some(number) if used_numbers.contains(&number) && placed.insert(number) => {
+                instruction_args[i].clone()
+            }
+            Some(number) => {
+                let dest = instr.dest.as_ref().unwrap();
+                if !placed.contains(&number)
+                    || !live_out.contains(dest)
+                    || last_def[&symbol(dest)] != i
+                {
+                    continue;
+                }
+                vec![number]
+            }
+        };
+        kept[i] = true;
+        for number in reads {
+            last_read.insert(number, i);
+        }
+    }
+    // Whether `var`, assigned before instruction `i`, is assigned again by an
+    // instruction that stays before the last read of `number`.
+    let clobbered = |var: &str, i: usize, number: usize| {
+        let last = match last_read.get(&number) {
+            Some(&last) => last,
+            None => return false,
+        };
+        (i..last).any(|j| kept[j] && block.instrs[j].dest.as_deref() == Some(var))
+    };
+
+    // Remove unused instructions.
+    let mut new_instrs = Vec::new();
+    let mut number_to_canonical_dest: FxHashMap<usize, String> = FxHashMap::default();
+    // Where the instruction holding each value went in `new_instrs`.
+    let mut number_to_canonical_index: FxHashMap<usize, usize> = FxHashMap::default();
+    let mut new_variable_to_number: FxHashMap<Symbol, usize> = FxHashMap::default();
+    // Values from outside the block stay in the variables they came in,
+    // until those are assigned.
+    let mut input_numbers = FxHashMap::default();
+    for (var, number) in inputs {
+        new_variable_to_number.insert(var, number);
+        number_to_canonical_dest.insert(number, symbols.resolve(var).to_string());
+        input_numbers.insert(var, number);
+    }
+    for (i, instr) in block.instrs.iter().enumerate() {
+        // An input still to be read moves to a copy before its variable
+        // is assigned.
+        if let Some(dest) = instr.dest.as_ref().filter(|_| kept[i]) {
+            if let Some(&input) = input_numbers.get(&symbol(dest)) {
+                if number_to_canonical_dest[&input] == *dest && clobbered(dest, i, input) {
+                    let fresh = names.fresh(dest);
+                    let mut copy = instr.clone();
+                    copy.dest = Some(fresh.clone());
+                    copy.op = Some(String::from("id"));
+                    copy.args = vec![dest.clone()];
+                    copy.funcs.clear();
+                    copy.labels.clear();
+                    copy.value = None;
+                    copy.mark_transformed("lvn");
+                    debug_info.rename(dest, &fresh);
+                    new_instrs.push(copy);
+                    number_to_canonical_dest.insert(input, fresh);
+                    changed = true;
+                }
+            }
+        }
+        if let Some(number) = instruction_numbers[i] {
+            let dest = instr.dest.as_deref().unwrap();
+            if used_numbers.contains(&number) && !number_to_canonical_dest.contains_key(&number) {
+                let mut new_instr = instr.clone();
+                for arg in new_instr.local_args_mut() {
+                    let arg_number = new_variable_to_number
+                        .get(&symbol(arg))
+                        .expect("No number for variable");
+                    *arg = number_to_canonical_dest
+                        .get(arg_number)
+                        .expect("No canonical dest for number")
+                        .clone();
+                }
+                if new_instr.args != instr.args {
+                    new_instr.mark_transformed("lvn");
+                    changed = true;
+                }
+                // The value outlives the variable, so it gets a name of
+                // its own; what the variable holds afterwards comes from
+                // the later assignment.
+                if clobbered(dest, i + 1, number) {
+                    let fresh = names.fresh(dest);
+                    debug_info.rename(dest, &fresh);
+                    new_instr.dest = Some(fresh);
+                    new_instr.mark_transformed("lvn");
+                    changed = true;
+                }
+                new_variable_to_number.insert(symbol(dest), number);
+                number_to_canonical_dest.insert(number, new_instr.dest.clone().unwrap());
+                number_to_canonical_index.insert(number, new_instrs.len());
+                new_instrs.push(new_instr);
+                used_numbers.remove(&number);
+            } else if let Some(canonical) = number_to_canonical_dest.get(&number) {
+                new_variable_to_number.insert(symbol(dest), number);
+                if live_out.contains(dest) && last_def[&symbol(dest)] == i {
+                    // Later blocks read the variable, so it still has to
+                    // get the value, which is cheapest as a copy.
+                    let mut copy = instr.clone();
+                    copy.op = Some(String::from("id"));
+                    copy.args = vec![canonical.clone()];
+                    copy.funcs.clear();
+                    copy.value = None;
+                    copy.mark_transformed("lvn");
+                    if copy.to_string() != instr.to_string() {
+                        remarks.passed(
+                            "lvn",
+                            "Redundant",
+                            instr,
+                            format!("replaced `{}` with `{}`", instr, copy),
+                        );
+                        changed = true;
+                    }
+                    new_instrs.push(copy);
+                    continue;
+                }
+                remarks.passed(
+                    "lvn",
+                    "Redundant",
+                    instr,
+                    format!("removed `{}`, reusing `{}`", instr, canonical),
+                );
+                if let Some(&index) = number_to_canonical_index.get(&number) {
+                    new_instrs[index].absorb(instr);
+                }
+                debug_info.merge_into(dest, canonical);
+            } else {
+                new_variable_to_number.insert(symbol(dest), number);
+                remarks.passed("lvn", "Dead", instr, format!("removed unused `{}`", instr));
+            }
+        } else {
+            let mut new_instr = instr.clone();
+            for arg in new_instr.local_args_mut() {
+                let arg_number = new_variable_to_number
+                    .get(&symbol(arg))
+                    .expect("No number for variable");
+                *arg = number_to_canonical_dest
+                    .get(arg_number)
+                    .expect("No canonical dest for number")
+                    .clone();
+            }
+            if new_instr.args != instr.args {
+                new_instr.mark_transformed("lvn");
+                changed = true;
+            }
+            new_instrs.push(new_instr);
+        }
+    }
+    changed |= new_instrs.len() != block.instrs.len();
+    block.instrs = new_instrs;
+
+    changed
+}
+
+// Runs local value numbering over each block of `function`; it deletes the
+// values a block computes but never uses as it goes.
+fn local_value_numbering(
+    function: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+    seed: u64,
+) -> bool {
+    let mut changed = false;
+    let liveness = Liveness::new(cfg);
+    let mut names = Names::new(function, seed);
+    for (i, block) in cfg.blocks.iter_mut().enumerate() {
+        remarks.enter(&function.name, i);
+        changed |= run_local_value_numbering(
+            block,
+            &liveness.live_out[i],
+            &mut names,
+            remarks,
+            &mut function.debug,
+            bisect,
+            div_by_zero,
+        );
+    }
+    changed
+}
+
+// Passes over the whole program rather than one function at a time, which
+// the driver runs itself.
+const PROGRAM_PASSES: [&str; 2] = ["dfe", "ipcp"];
+
+enum ProgramPass {
+    Dfe,
+    Ipcp,
+}
+
+impl ProgramPass {
+    // The pass of one of `PROGRAM_PASSES`.
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "dfe" => Some(ProgramPass::Dfe),
+            "ipcp" => Some(ProgramPass::Ipcp),
+            _ => None,
+        }
+    }
+}
+
+// What the passes of one run share.
+struct Driver {
+    registry: Registry,
+    // The passes run so far, which keep their state from one function to
+    // the next.
+    instances: HashMap<String, Box<dyn Pass>>,
+    analyses: AnalysisManager,
+    metrics: Metrics,
+    // Whether to say which passes changed what, for `--verbose`.
+    verbose: bool,
+    // How many functions to optimize at once, for `--jobs`.
+    jobs: usize,
+    // The functions the files the program imports from import back, which
+    // the passes over the whole program keep as they are called.
+    exported: BTreeSet<String>,
+}
+
+impl Driver {
+    fn new(registry: Registry, analyses: AnalysisManager, verbose: bool, jobs: usize) -> Self {
+        Driver {
+            registry,
+            instances: HashMap::new(),
+            analyses,
+            metrics: Metrics::new(),
+            verbose,
+            jobs,
+            exported: BTreeSet::new(),
+        }
+    }
+
+    fn run(&mut self, pass: &str, function: &mut Function) -> bool {
+        let start = Instant::now();
+        let registry = &self.registry;
+        let instance = self
+            .instances
+            .entry(pass.to_string())
+            .or_insert_with(|| registry.create(pass).expect("Unknown pass"));
+        let changed = instance.run(function, &mut self.analyses).is_yes();
+        if changed {
+            self.analyses.invalidate(function);
+            if self.verbose {
+                eprintln!("lvn: {} changed @{}", pass, function.name);
+            }
+        }
+        self.metrics.record_pass(pass, start.elapsed(), changed);
+        changed
+    }
+
+    // Runs `passes` in order over each function. With `jobs` above one, up
+    // to that many functions go through them at once, each on a thread of its
+    // own with a driver of its own: the passes of a thread keep their state
+    // from one of its functions to the next, and the analyses it computes
+    // stay with it. The remarks come out in the order of the functions, as
+    // they would one function at a time.
+    fn run_functions(&mut self, passes: &[String], functions: &mut [Function]) -> bool {
+        if self.jobs <= 1 || functions.len() <= 1 {
+            let mut changed = false;
+            for function in functions.iter_mut() {
+                for pass in passes {
+                    changed |= self.run(pass, function);
+                }
+            }
+            return changed;
+        }
+        let workers = self.jobs.min(functions.len());
+        let queue = Mutex::new(functions.iter_mut().enumerate());
+        let (registry, verbose) = (&self.registry, self.verbose);
+        let (remarks, div_by_zero, unroll_factor, seed) = (
+            self.analyses.remarks.is_enabled(),
+            self.analyses.div_by_zero,
+            self.analyses.unroll_factor,
+            self.analyses.seed,
+        );
+        let results = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    let queue = &queue;
+                    scope.spawn(move || {
+                        let analyses = AnalysisManager::new(
+                            Remarks::new(remarks),
+                            OptBisect::new(None),
+                            div_by_zero,
+                            unroll_factor,
+                            seed,
+                        );
+                        let mut worker = Driver::new(registry.clone(), analyses, verbose, 1);
+                        let mut collected = Vec::new();
+                        let mut changed = false;
+                        loop {
+                            let next = queue.lock().unwrap().next();
+                            let (i, function) = match next {
+                                Some(next) => next,
+                                None => break,
+                            };
+                            for pass in passes {
+                                changed |= worker.run(pass, function);
+                            }
+                            let done = std::mem::replace(
+                                &mut worker.analyses.remarks,
+                                Remarks::new(remarks),
+                            );
+                            collected.push((i, done));
+                        }
+                        (worker.metrics, collected, changed)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut changed = false;
+        let mut collected = Vec::new();
+        for (metrics, remarks, worker_changed) in results {
+            self.metrics.merge(metrics);
+            collected.extend(remarks);
+            changed |= worker_changed;
+        }
+        collected.sort_by_key(|(i, _)| *i);
+        for (_, remarks) in collected {
+            self.analyses.remarks.append(remarks);
+        }
+        // What this driver knew of the functions may be out of date.
+        if changed {
+            self.analyses.invalidate_all();
+        }
+        changed
+    }
+
+    // Runs `pass` over every function, or over the program as a whole for
+    // one of `PROGRAM_PASSES`.
+    fn run_all(&mut self, pass: &str, functions: &mut Vec<Function>) -> bool {
+        let program_pass = match ProgramPass::parse(pass) {
+            Some(program_pass) => program_pass,
+            None => return self.run_functions(&[pass.to_string()], functions),
+        };
+        let start = Instant::now();
+        let analyses = &mut self.analyses;
+        let changed = match program_pass {
+            ProgramPass::Dfe => dfe::eliminate_dead_functions(
+                functions,
+                &self.exported,
+                &mut analyses.remarks,
+                &mut analyses.bisect,
+            ),
+            ProgramPass::Ipcp => ipcp::propagate_arguments(
+                functions,
+                &self.exported,
+                &mut analyses.remarks,
+                &mut analyses.bisect,
+                analyses.div_by_zero,
+            ),
+        };
+        if changed {
+            analyses.invalidate_all();
+            if self.verbose {
+                eprintln!("lvn: {} changed the program", pass);
+            }
+        }
+        self.metrics.record_pass(pass, start.elapsed(), changed);
+        changed
+    }
+
+    // Runs a step of a pipeline file over every function, with its options
+    // in place of those of the run while it does.
+    fn run_step(&mut self, step: &Step, functions: &mut Vec<Function>) -> bool {
+        match step {
+            Step::Pass(pass, options) => {
+                let analyses = &mut self.analyses;
+                let (div_by_zero, unroll_factor) = (analyses.div_by_zero, analyses.unroll_factor);
+                analyses.div_by_zero = options.div_by_zero.unwrap_or(div_by_zero);
+                analyses.unroll_factor = options.unroll_factor.unwrap_or(unroll_factor);
+                let changed = self.run_all(pass, functions);
+                self.analyses.div_by_zero = div_by_zero;
+                self.analyses.unroll_factor = unroll_factor;
+                changed
+            }
+            Step::Repeat(steps, max_iterations) => {
+                let mut changed = false;
+                fixpoint::run_to_fixpoint(functions, steps, *max_iterations, |step, functions| {
+                    let step_changed = self.run_step(step, functions);
+                    changed |= step_changed;
+                    step_changed
+                });
+                changed
+            }
+        }
+    }
+}
+
+// An optimizing run, with the settings left off the command line taken from
+// the `[driver]` table of `bril.toml`.
+pub struct Optimizer {
+    registry: Registry,
+    emit: String,
+    passes: Vec<String>,
+    pipeline: Option<Vec<Step>>,
+    max_iterations: Option<usize>,
+    div_by_zero: DivByZero,
+    unroll_factor: usize,
+    seed: u64,
+    jobs: usize,
+    verbose: bool,
+    remarks: Option<String>,
+    metrics: Option<String>,
+    opt_bisect_limit: Option<usize>,
+    provenance: bool,
+    debug_info: bool,
+}
+
+impl Optimizer {
+    pub fn new(cli: &Cli) -> Result<Self, BrilError> {
+        // Flags not given on the command line default to the project's settings.
+        let driver = config::driver(cli.config.as_deref().map(std::path::Path::new))?;
+        let emit = match (&cli.emit, driver.string("emit")?) {
+            (Some(emit), _) => emit.clone(),
+            (None, Some(emit)) if cli::FORMATS.contains(&emit) => emit.to_string(),
+            (None, Some(emit)) => {
+                return Err(driver.error(format!("unknown output format: {}", emit)));
+            }
+            (None, None) => String::from("json"),
+        };
+        let passes = match (&cli.passes, driver.strings("passes")?) {
+            (Some(passes), _) => passes.clone(),
+            (None, Some(passes)) => passes.join(","),
+            (None, None) => String::from("lvn"),
+        };
+        let config_max_iterations = driver.count("max-iterations")?.map(|limit| limit as usize);
+        let max_iterations = match (cli.max_iterations, cli.fixpoint) {
+            (Some(limit), _) => Some(limit),
+            (None, true) => config_max_iterations.or(Some(10)),
+            (None, false) => config_max_iterations,
+        };
+        let div_by_zero = match &cli.fold_div_by_zero {
+            Some(name) => DivByZero::parse(name).unwrap(),
+            None => driver
+                .string("fold-div-by-zero")?
+                .map(|name| {
+                    DivByZero::parse(name).ok_or_else(|| {
+                        driver.error("invalid fold-div-by-zero, expected preserve, poison or zero")
+                    })
+                })
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        let unroll_factor = match cli.unroll_factor {
+            Some(factor) => factor,
+            None => driver
+                .count("unroll-factor")?
+                .map_or(4, |factor| factor as usize),
+        };
+        let seed = match cli.seed {
+            Some(seed) => seed,
+            None => driver.count("seed")?.unwrap_or(0),
+        };
+        let mut passes: Vec<String> = passes.split(',').map(String::from).collect();
+        if let Some(path) = &cli.run_directive {
+            let test = std::fs::read_to_string(path).map_err(|e| BrilError::io(path, e))?;
+            passes = directive::run_directive(&test).unwrap_or(passes);
+        }
+        let registry = Registry::builtin();
+        let known: Vec<&str> = registry.names().chain(PROGRAM_PASSES).collect();
+        for pass in &passes {
+            if !known.contains(&pass.as_str()) {
+                return Err(BrilError::usage(format!("unknown pass: {}", pass)));
+            }
+        }
+        let pipeline = cli
+            .pipeline
+            .as_ref()
+            .map(|path| pipeline::load(std::path::Path::new(&path), &known))
+            .transpose()?;
+        let jobs = match cli.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        };
+        Ok(Optimizer {
+            registry,
+            emit,
+            passes,
+            pipeline,
+            max_iterations,
+            div_by_zero,
+            unroll_factor,
+            seed,
+            jobs,
+            verbose: cli.common.verbose,
+            remarks: cli.remarks.clone(),
+            metrics: cli.metrics.clone(),
+            opt_bisect_limit: cli.opt_bisect_limit,
+            provenance: cli.provenance,
+            debug_info: cli.debug_info,
+        })
+    }
+
+    // Runs the passes over `program`, which `prepare` has checked, and
+    // writes the remarks and metrics files of the run.
+    pub fn optimize(
+        &self,
+        program: &mut Program,
+        exported: BTreeSet<String>,
+    ) -> Result<(), BrilError> {
+        let original = self.metrics.as_ref().map(|_| program.clone());
+        let analyses = AnalysisManager::new(
+            Remarks::new(self.remarks.is_some()),
+            OptBisect::new(self.opt_bisect_limit),
+            self.div_by_zero,
+            self.unroll_factor,
+            self.seed,
+        );
+        let mut driver = Driver::new(self.registry.clone(), analyses, self.verbose, self.jobs);
+        driver.exported = exported;
+        if let Some(steps) = &self.pipeline {
+            // `--fixpoint` repeats the whole pipeline.
+            let steps = match self.max_iterations {
+                Some(max_iterations) => vec![Step::Repeat(steps.clone(), max_iterations)],
+                None => steps.clone(),
+            };
+            for step in &steps {
+                driver.run_step(step, &mut program.functions);
+            }
+        } else {
+            let passes = &self.passes;
+            match self.max_iterations {
+                Some(max_iterations) => fixpoint::run_to_fixpoint(
+                    &mut program.functions,
+                    passes,
+                    max_iterations,
+                    |pass, functions| driver.run_all(pass, functions),
+                ),
+                None => {
+                    // Each function goes through the passes on its own, up to a pass
+                    // over the whole program, which waits for all of them.
+                    let mut rest = &passes[..];
+                    while !rest.is_empty() {
+                        let n = rest
+                            .iter()
+                            .position(|pass| PROGRAM_PASSES.contains(&pass.as_str()))
+                            .unwrap_or(rest.len());
+                        driver.run_functions(&rest[..n], &mut program.functions);
+                        if let Some(pass) = rest.get(n) {
+                            driver.run_all(pass, &mut program.functions);
+                        }
+                        rest = &rest[rest.len().min(n + 1)..];
+                    }
+                }
+            }
+        }
+        let Driver {
+            analyses,
+            mut metrics,
+            ..
+        } = driver;
+        let remarks = analyses.remarks;
+        for function in &mut program.functions {
+            if !self.provenance {
+                for instr in &mut function.instrs {
+                    instr.transformed_by.clear();
+                    instr.id = None;
+                    instr.merged_from.clear();
+                }
+            }
+            if !self.debug_info {
+                function.debug = DebugInfo::default();
+            }
+        }
+
+        if let Some(path) = &self.remarks {
+            let contents = if path.ends_with(".yaml") || path.ends_with(".yml") {
+                remarks.to_yaml()
+            } else {
+                remarks.to_json()
+            };
+            std::fs::write(path, contents).map_err(|e| BrilError::io(path, e))?;
+        }
+
+        // Metrics go in a Prometheus text file when the name says so, and in
+        // JSON otherwise.
+        if let (Some(path), Some(original)) = (&self.metrics, &original) {
+            metrics.record_sizes(original, program);
+            let contents = if path.ends_with(".prom") || path.ends_with(".txt") {
+                metrics.to_prometheus()
+            } else {
+                metrics.to_json()
+            };
+            std::fs::write(path, contents).map_err(|e| BrilError::io(path, e))?;
+        }
+        Ok(())
+    }
+}
+
+// Readies a program read from `common` for the passes: checks that the CFG
+// of each function makes sense, which everything after builds expecting it
+// to, resolves its imports and gives its instructions IDs. Gives the
+// functions the files it imports from call back.
+pub fn prepare(
+    program: &mut Program,
+    common: &CommonArgs,
+    libs: &[PathBuf],
+) -> Result<BTreeSet<String>, BrilError> {
+    for function in &program.functions {
+        try_construct_control_flow_graph(function)?;
+    }
+    let exported = if program.imports.is_empty() {
+        BTreeSet::new()
+    } else {
+        common.resolve_imports(program, libs)?
+    };
+    assign_ids(program);
+    Ok(exported)
+}
+
+// Does what the lvn command line says.
+pub fn run(cli: &Cli) -> Result<(), BrilError> {
+    let common = &cli.common;
+    let optimizer = Optimizer::new(cli)?;
+    let emit = optimizer.emit.as_str();
+    let mut program = common.read_program()?;
+    let exported = prepare(&mut program, common, &cli.libs)?;
+
+    // The explorer and the backends take the program exactly as it was given,
+    // so pipe the output of an optimizing run into them to see the result.
+    if emit == "html" {
+        return common.write(&html::program_to_html(&program));
+    }
+    if emit == "regions" {
+        return common.write(&regions::program_regions(&program));
+    }
+    if emit == "liveness" {
+        return common.write(&analysis::program_liveness(&program));
+    }
+    if emit == "available" {
+        return common.write(&available::program_available(&program));
+    }
+    if emit == "loops" {
+        return common.write(&analysis::program_loops(&program));
+    }
+    if emit == "calls" {
+        return common.write(&analysis::program_calls(&program));
+    }
+    if emit == "aliases" {
+        return common.write(&analysis::program_aliases(&program));
+    }
+    if emit == "structured" {
+        return common.write(&structure::program_structured(&program));
+    }
+    if emit == "c" {
+        return common.write(&c_backend::program_to_c(&program)?);
+    }
+    if emit == "js" {
+        return common.write(&js_backend::program_to_js(&program)?);
+    }
+
+    let original = program.clone();
+    optimizer.optimize(&mut program, exported)?;
+    if emit == "diff-html" {
+        return common.write(&html::diff_to_html(&original, &program));
+    }
+
+    if cli.conform {
+        return common.write(&(conform::to_string(&program) + "\n"));
+    }
+    common.write_program(&program)
+}
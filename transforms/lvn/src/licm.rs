@@ -0,0 +1,281 @@
+use crate::bisect::OptBisect;
+use crate::constprop::constants_after;
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Function, Instruction, Literal};
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::liveness::Liveness;
+use bril_cfg::loops::{Loop, LoopForest};
+use bril_ir::Effect;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Whether running `instr` can be told apart from not running it: anything
+// but a pure instruction, a jump, a `nop`, or a `phi` or `get`, which only
+// read the way control came in. A `load` counts, since it can trap.
+fn observable(instr: &Instruction) -> bool {
+    match instr.op.as_deref() {
+        None | Some("jmp" | "br" | "nop" | "phi" | "get") => false,
+        Some(_) => instr.effect() != Effect::Pure,
+    }
+}
+
+// Whether some block of `l` with an observable instruction can run before
+// control first gets to `b` from the header, around the loop any number of
+// times.
+fn observed_before(cfg: &ControlFlowGraph, l: &Loop, b: usize) -> bool {
+    let mut seen = HashSet::new();
+    let mut work: Vec<usize> = if b == l.header {
+        Vec::new()
+    } else {
+        cfg.blocks[b].prev_blocks.clone()
+    };
+    while let Some(p) = work.pop() {
+        if p == b || !l.contains(p) || !seen.insert(p) {
+            continue;
+        }
+        if cfg.blocks[p].instrs.iter().any(observable) {
+            return true;
+        }
+        work.extend(&cfg.blocks[p].prev_blocks);
+    }
+    false
+}
+
+// The instructions of `l` that can move to its preheader, in an order that
+// keeps each after the instructions it reads: pure instructions all of whose
+// arguments are assigned outside the loop, or once inside it by an
+// instruction moving as well that comes before the read on every path.
+//
+// Besides, so that moving an instruction leaves the values every variable
+// holds as they were:
+//
+// - its destination is assigned nowhere else in the loop, and not read in
+//   the loop before it is assigned;
+// - it runs on every path out of the loop, or its destination is not read
+//   after the loop, since otherwise it could be assigned when it was not;
+// - it cannot trap, or it runs on every path out of the loop, since
+//   otherwise it could trap when it did not, and nothing observable runs in
+//   the loop before it, since otherwise the trap would come too early. A
+//   `div` by a constant other than zero cannot trap.
+fn hoistable(
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    tree: &DominatorTree,
+    liveness: &Liveness,
+    constants: &[Option<BTreeMap<String, Literal>>],
+    div_by_zero: DivByZero,
+) -> Vec<(usize, usize)> {
+    let mut defs: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for &b in &l.body {
+        for (i, instr) in cfg.blocks[b].instrs.iter().enumerate() {
+            if let Some(dest) = &instr.dest {
+                defs.entry(dest).or_default().push((b, i));
+            }
+        }
+    }
+    let exiting: Vec<usize> = l
+        .body
+        .iter()
+        .copied()
+        .filter(|&b| cfg.blocks[b].next_blocks.iter().any(|n| !l.contains(*n)))
+        .collect();
+    let read_after = |var: &str| l.exits.iter().any(|&e| liveness.live_in[e].contains(var));
+
+    // The loop's blocks with each before those it dominates.
+    let order: Vec<usize> = cfg
+        .reverse_postorder()
+        .into_iter()
+        .filter(|&b| l.contains(b))
+        .collect();
+    let mut hoisted = Vec::new();
+    let mut moving = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in &order {
+            let runs_on_exit = exiting.iter().all(|&e| tree.dominates(b, e));
+            for (i, instr) in cfg.blocks[b].instrs.iter().enumerate() {
+                let (dest, op) = match (&instr.dest, &instr.op) {
+                    (Some(dest), Some(op)) => (dest, op),
+                    _ => continue,
+                };
+                if moving.contains(&(b, i)) || instr.effect() != Effect::Pure {
+                    continue;
+                }
+                let invariant = instr.args.iter().all(|arg| match defs.get(arg.as_str()) {
+                    None => true,
+                    Some(sites) => match sites[..] {
+                        [(db, di)] => {
+                            moving.contains(&(db, di))
+                                && ((db == b && di < i) || tree.strictly_dominates(db, b))
+                        }
+                        _ => false,
+                    },
+                });
+                let nonzero = || {
+                    let divisor = instr
+                        .args
+                        .get(1)
+                        .and_then(|arg| constants[b].as_ref()?.get(arg));
+                    op == "div" && matches!(divisor, Some(Literal::Int(n)) if *n != 0)
+                };
+                let observed = || {
+                    cfg.blocks[b].instrs[..i].iter().any(observable) || observed_before(cfg, l, b)
+                };
+                let safe = defs[dest.as_str()].len() == 1
+                    && !liveness.live_in[l.header].contains(dest)
+                    && (runs_on_exit || !read_after(dest))
+                    && (!div_by_zero.keeps_unused(op)
+                        || nonzero()
+                        || (runs_on_exit && !observed()));
+                if invariant && safe {
+                    moving.insert((b, i));
+                    hoisted.push((b, i));
+                    changed = true;
+                }
+            }
+        }
+    }
+    hoisted
+}
+
+// Loop-invariant code motion: moves the instructions of a loop computing the
+// same value on every iteration to its preheader, so they run once before
+// the loop instead. A loop without a preheader gets a new block for one,
+// placed right before its header, unless its header has `phi` nodes, whose
+// labels would then need to change as well.
+//
+// Functions using speculation are left alone: an instruction moved out of a
+// `speculate` would no longer be rolled back.
+//
+// Inner loops go first, so an instruction can move out of several loops one
+// at a time.
+pub fn hoist_invariants(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+    seed: u64,
+) -> bool {
+    if function.instrs.iter().any(Instruction::is_speculation_op) {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let mut changed = false;
+    // Each round moves code out of one loop, which changes the loops.
+    'rounds: loop {
+        let mut cfg = construct_control_flow_graph(function);
+        let tree = DominatorTree::new(&cfg);
+        let forest = LoopForest::with_dominators(&cfg, &tree);
+        let liveness = Liveness::new(&cfg);
+        let constants = constants_after(function, &cfg, div_by_zero);
+        for l in forest.loops.iter().rev() {
+            let header_label = match cfg.blocks[l.header].label() {
+                Some(label) => label.to_string(),
+                None => continue,
+            };
+            let mut moves = Vec::new();
+            for (b, i) in hoistable(&cfg, l, &tree, &liveness, &constants, div_by_zero) {
+                let instr = &cfg.blocks[b].instrs[i];
+                if !bisect.should_run("licm", &format!("hoist `{}`", instr)) {
+                    break;
+                }
+                moves.push((b, i));
+            }
+            if moves.is_empty() {
+                continue;
+            }
+            if l.preheader.is_none() && cfg.blocks[l.header].instrs.iter().any(|i| i.is_phi()) {
+                remarks.enter(&function.name, l.header);
+                remarks.missed(
+                    "licm",
+                    "NoPreheader",
+                    &cfg.blocks[l.header].instrs[0],
+                    format!(
+                        "kept {} invariant instructions in the loop at .{}, which has no preheader",
+                        moves.len(),
+                        header_label
+                    ),
+                );
+                continue;
+            }
+
+            let mut instrs: Vec<Instruction> = Vec::new();
+            for &(b, i) in &moves {
+                remarks.enter(&function.name, b);
+                let mut instr = cfg.blocks[b].instrs[i].clone();
+                instr.mark_transformed("licm");
+                remarks.passed(
+                    "licm",
+                    "Hoisted",
+                    &instr,
+                    format!("moved `{}` out of the loop at .{}", instr, header_label),
+                );
+                instrs.push(instr);
+            }
+            let moved: HashSet<(usize, usize)> = moves.into_iter().collect();
+            for (b, block) in cfg.blocks.iter_mut().enumerate() {
+                let mut i = 0;
+                block.instrs.retain(|_| {
+                    i += 1;
+                    !moved.contains(&(b, i - 1))
+                });
+            }
+
+            match l.preheader {
+                Some(p) => {
+                    let block = &mut cfg.blocks[p];
+                    let at = match block.instrs.last() {
+                        Some(last) if last.is_terminator() => block.instrs.len() - 1,
+                        _ => block.instrs.len(),
+                    };
+                    block.instrs.splice(at..at, instrs);
+                }
+                None => {
+                    // Everything entering the loop from outside goes through
+                    // the new block, which falls through to the header.
+                    let label = names.fresh(&format!("{}.preheader", header_label));
+                    for (b, block) in cfg.blocks.iter_mut().enumerate() {
+                        if l.contains(b) {
+                            continue;
+                        }
+                        if let Some(last) = block.instrs.last_mut() {
+                            if last.is_terminator() {
+                                for target in last.labels.iter_mut() {
+                                    if *target == header_label {
+                                        *target = label.clone();
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    // A block of the loop falling through to the header
+                    // would fall into the new block instead.
+                    if l.header > 0 && l.contains(l.header - 1) {
+                        let before = &mut cfg.blocks[l.header - 1];
+                        if !before.instrs.last().is_some_and(Instruction::is_terminator) {
+                            before.instrs.push(Instruction {
+                                op: Some(String::from("jmp")),
+                                labels: vec![header_label.clone()],
+                                ..Default::default()
+                            });
+                        }
+                    }
+                    instrs.insert(
+                        0,
+                        Instruction {
+                            label: Some(label),
+                            ..Default::default()
+                        },
+                    );
+                    cfg.blocks[l.header].instrs.splice(0..0, instrs);
+                }
+            }
+            function.instrs = cfg.into_instrs();
+            changed = true;
+            continue 'rounds;
+        }
+        return changed;
+    }
+}
@@ -0,0 +1,359 @@
+use crate::bisect::OptBisect;
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::{
+    take_control_flow_graph, Block, ControlFlowGraph, Function, Instruction, Literal, Type,
+};
+use bril_cfg::alias::AliasAnalysis;
+use bril_cfg::dataflow::{solve, Analysis, Direction};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// A cell of an allocation: its site, as the alias analysis numbers them, and
+// the offset from the start.
+type Cell = (usize, i64);
+
+// The constant each variable holds when the function assigns it only once,
+// with a `const`.
+fn single_constants(function: &Function, cfg: &ControlFlowGraph) -> HashMap<String, i64> {
+    let mut definitions: HashMap<&str, usize> = HashMap::new();
+    for arg in &function.args {
+        *definitions.entry(&arg.name).or_default() += 1;
+    }
+    let mut constants = HashMap::new();
+    for (_, block) in cfg.iter() {
+        for instr in &block.instrs {
+            if let Some(dest) = &instr.dest {
+                *definitions.entry(dest).or_default() += 1;
+                if let (Some("const"), Some(Literal::Int(value))) =
+                    (instr.op.as_deref(), instr.value)
+                {
+                    constants.insert(dest.clone(), value);
+                }
+            }
+        }
+    }
+    constants.retain(|var, _| definitions[var.as_str()] == 1);
+    constants
+}
+
+// The allocations that can be promoted, with what they hold and the cell
+// each pointer variable into them points to.
+struct Candidates {
+    // The size of each site that is one, and the type of what it holds.
+    sites: BTreeMap<usize, (i64, Type)>,
+    pointers: HashMap<String, Cell>,
+}
+
+impl Candidates {
+    fn new(function: &Function, cfg: &ControlFlowGraph, aliases: &AliasAnalysis) -> Self {
+        let constants = single_constants(function, cfg);
+        let mut definitions: HashMap<&str, usize> = HashMap::new();
+        for arg in &function.args {
+            *definitions.entry(&arg.name).or_default() += 1;
+        }
+        for (_, block) in cfg.iter() {
+            for instr in &block.instrs {
+                if let Some(dest) = &instr.dest {
+                    *definitions.entry(dest).or_default() += 1;
+                }
+            }
+        }
+        let once = |var: &str| definitions.get(var) == Some(&1);
+
+        // Sites of a known size whose address stays in the function, and
+        // variables assigned once with a pointer a constant offset into one.
+        let mut sites = BTreeMap::new();
+        let mut pointers = HashMap::new();
+        for (s, &(b, i)) in aliases.sites.iter().enumerate() {
+            let alloc = &cfg.blocks[b].instrs[i];
+            let (dest, type_) = match (&alloc.dest, &alloc.type_) {
+                (Some(dest), Some(Type::Ptr { ptr })) => (dest, ptr),
+                _ => continue,
+            };
+            let size = alloc.args.first().and_then(|n| constants.get(n));
+            if let Some(&size) = size.filter(|_| once(dest) && !aliases.escapes(s)) {
+                sites.insert(s, (size, (**type_).clone()));
+                pointers.insert(dest.clone(), (s, 0));
+            }
+        }
+        let mut grown = true;
+        while grown {
+            grown = false;
+            for (_, block) in cfg.iter() {
+                for instr in &block.instrs {
+                    let dest = match &instr.dest {
+                        Some(dest) if once(dest) && !pointers.contains_key(dest) => dest,
+                        _ => continue,
+                    };
+                    let base = instr.args.first().and_then(|arg| pointers.get(arg));
+                    let cell = match (instr.op.as_deref(), base, instr.args.get(1)) {
+                        (Some("id"), Some(&cell), None) => cell,
+                        (Some("ptradd"), Some(&(s, offset)), Some(by)) => match constants.get(by) {
+                            Some(by) => (s, offset + by),
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    pointers.insert(dest.clone(), cell);
+                    grown = true;
+                }
+            }
+        }
+
+        let mut candidates = Candidates { sites, pointers };
+        // A pointer used any other way than to reach a cell in bounds, or
+        // to free the allocation, keeps it in memory.
+        for (_, block) in cfg.iter() {
+            for instr in &block.instrs {
+                for (a, arg) in instr.args.iter().enumerate() {
+                    let (s, offset) = match candidates.pointers.get(arg) {
+                        Some(&cell) => cell,
+                        None => continue,
+                    };
+                    let in_bounds = candidates
+                        .sites
+                        .get(&s)
+                        .is_some_and(|&(size, _)| (0..size).contains(&offset));
+                    let dest_derived = instr
+                        .dest
+                        .as_ref()
+                        .is_some_and(|dest| candidates.pointers.contains_key(dest));
+                    let allowed = match (instr.op.as_deref(), a) {
+                        (Some("load" | "store"), 0) => in_bounds,
+                        (Some("id" | "ptradd"), 0) => dest_derived,
+                        (Some("free"), 0) => offset == 0,
+                        _ => false,
+                    };
+                    if !allowed {
+                        candidates.sites.remove(&s);
+                    }
+                }
+            }
+        }
+        let sites = &candidates.sites;
+        candidates
+            .pointers
+            .retain(|_, (s, _)| sites.contains_key(s));
+        candidates
+    }
+
+    fn cell(&self, instr: &Instruction) -> Option<Cell> {
+        match instr.op.as_deref() {
+            Some("load" | "store" | "free") => self.pointers.get(&instr.args[0]).copied(),
+            _ => None,
+        }
+    }
+
+    fn site_of_alloc(&self, instr: &Instruction) -> Option<usize> {
+        match (instr.op.as_deref(), &instr.dest) {
+            (Some("alloc"), Some(dest)) => self.pointers.get(dest).map(|&(s, _)| s),
+            _ => None,
+        }
+    }
+}
+
+// What holds of the candidate sites at a point, on the paths reaching it.
+#[derive(Clone, Debug, PartialEq)]
+struct State {
+    // The sites that may not be allocated, before their `alloc` or after
+    // their `free`; every one is at the entry.
+    unallocated: BTreeSet<usize>,
+    // The sites that may be allocated and not freed yet.
+    allocated: BTreeSet<usize>,
+    // The cells stored to since their allocation on every path.
+    initialized: BTreeSet<Cell>,
+}
+
+// Checks the accesses the interpreter would stop with an error: loads of
+// cells nothing was stored to, accesses and frees when the memory may not be
+// allocated, and allocations that are never freed or allocated again first.
+// Variables do not have those errors, so such sites stay in memory.
+struct Lifetimes<'a> {
+    candidates: &'a Candidates,
+}
+
+impl Lifetimes<'_> {
+    // Moves `state` past `instr`, and gives the site it misuses, if any.
+    fn step(&self, state: &mut State, instr: &Instruction) -> Option<usize> {
+        if let Some(s) = self.candidates.site_of_alloc(instr) {
+            let reallocated = state.allocated.contains(&s);
+            state.unallocated.remove(&s);
+            state.allocated.insert(s);
+            state.initialized.retain(|&(site, _)| site != s);
+            return reallocated.then_some(s);
+        }
+        let cell = self.candidates.cell(instr)?;
+        let s = cell.0;
+        let misused = state.unallocated.contains(&s)
+            || (instr.op.as_deref() == Some("load") && !state.initialized.contains(&cell));
+        match instr.op.as_deref() {
+            Some("store") => {
+                state.initialized.insert(cell);
+            }
+            Some("free") => {
+                state.unallocated.insert(s);
+                state.allocated.remove(&s);
+            }
+            _ => {}
+        }
+        misused.then_some(s)
+    }
+}
+
+impl Analysis for Lifetimes<'_> {
+    type Fact = Option<State>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Option<State> {
+        Some(State {
+            unallocated: self.candidates.sites.keys().copied().collect(),
+            allocated: BTreeSet::new(),
+            initialized: BTreeSet::new(),
+        })
+    }
+
+    fn initial(&self) -> Option<State> {
+        None
+    }
+
+    fn merge(&self, into: &mut Option<State>, other: &Option<State>) {
+        let other = match other {
+            Some(other) => other,
+            None => return,
+        };
+        match into {
+            Some(into) => {
+                into.unallocated.extend(&other.unallocated);
+                into.allocated.extend(&other.allocated);
+                into.initialized
+                    .retain(|cell| other.initialized.contains(cell));
+            }
+            None => *into = Some(other.clone()),
+        }
+    }
+
+    fn transfer(&self, block: &Block, fact: &Option<State>) -> Option<State> {
+        let mut state = fact.clone()?;
+        for instr in &block.instrs {
+            self.step(&mut state, instr);
+        }
+        Some(state)
+    }
+}
+
+// Memory-to-register promotion: replaces an allocation with a variable per
+// cell when its address stays in the function and every pointer into it is
+// a constant offset, in bounds, assigned once. Loads become copies of the
+// variable of their cell and stores assignments to it, and the `alloc`, the
+// `free` and the pointer arithmetic go; the variables are assigned once per
+// store, so `to-ssa` after it puts them in SSA form.
+//
+// Only allocations used without an error the interpreter would report are
+// promoted, see `Lifetimes`. Functions using speculation keep their memory,
+// as aborting rolls the variables back but not what is stored.
+pub fn promote_memory(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    seed: u64,
+) -> bool {
+    if function.instrs.iter().any(Instruction::is_speculation_op) {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let mut cfg = take_control_flow_graph(function);
+    let aliases = AliasAnalysis::new(&function.args, &cfg);
+    let mut candidates = Candidates::new(function, &cfg, &aliases);
+    if candidates.sites.is_empty() {
+        function.instrs = cfg.into_instrs();
+        return false;
+    }
+
+    let lifetimes = Lifetimes {
+        candidates: &candidates,
+    };
+    let solution = solve(&cfg, &lifetimes);
+    let mut rejected = BTreeSet::new();
+    for (b, block) in cfg.iter() {
+        let mut state = match solution.before[b].clone() {
+            Some(state) => state,
+            None => continue,
+        };
+        for instr in &block.instrs {
+            rejected.extend(lifetimes.step(&mut state, instr));
+        }
+        // The allocations still held as the function returns are leaked.
+        if block.next_blocks.is_empty() {
+            rejected.extend(&state.allocated);
+        }
+    }
+    for (s, &(b, i)) in aliases.sites.iter().enumerate() {
+        if candidates.sites.contains_key(&s)
+            && (rejected.contains(&s)
+                || !bisect.should_run("mem2reg", &format!("promote `{}`", cfg.blocks[b].instrs[i])))
+        {
+            candidates.sites.remove(&s);
+        }
+    }
+    let sites = &candidates.sites;
+    candidates
+        .pointers
+        .retain(|_, (s, _)| sites.contains_key(s));
+    if candidates.sites.is_empty() {
+        function.instrs = cfg.into_instrs();
+        return false;
+    }
+
+    // A variable for each cell, named after the allocation.
+    let mut variables: HashMap<Cell, String> = HashMap::new();
+    for (s, &(b, i)) in aliases.sites.iter().enumerate() {
+        if let Some(&(size, _)) = candidates.sites.get(&s) {
+            let alloc = &cfg.blocks[b].instrs[i];
+            let base = alloc.dest.as_deref().unwrap();
+            for offset in 0..size {
+                variables.insert((s, offset), names.fresh(base));
+            }
+            remarks.enter(&function.name, b);
+            remarks.passed(
+                "mem2reg",
+                "Promoted",
+                alloc,
+                format!("replaced `{}` with {} variables", alloc, size),
+            );
+        }
+    }
+    for block in cfg.blocks.iter_mut() {
+        block.instrs.retain_mut(|instr| {
+            let is_pointer = |var: &Option<String>| {
+                var.as_ref()
+                    .is_some_and(|var| candidates.pointers.contains_key(var))
+            };
+            if is_pointer(&instr.dest) {
+                // The `alloc` and the pointer arithmetic.
+                return false;
+            }
+            let cell = match candidates.cell(instr) {
+                Some(cell) => cell,
+                None => return true,
+            };
+            let variable = variables[&cell].clone();
+            match instr.op.as_deref() {
+                Some("load") => instr.args = vec![variable],
+                Some("store") => {
+                    instr.dest = Some(variable);
+                    instr.type_ = Some(candidates.sites[&cell.0].1.clone());
+                    instr.args = vec![instr.args[1].clone()];
+                }
+                _ => return false,
+            }
+            instr.op = Some(String::from("id"));
+            instr.mark_transformed("mem2reg");
+            true
+        });
+    }
+    function.instrs = cfg.into_instrs();
+    true
+}
@@ -0,0 +1,182 @@
+use crate::Program;
+use serde::Serialize;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Default, Serialize)]
+pub struct PassMetrics {
+    pub pass: String,
+    // How many times the pass ran over a function.
+    pub runs: usize,
+    // How many of those runs changed the function.
+    pub changed: usize,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SizeMetrics {
+    pub function: String,
+    pub before: usize,
+    pub after: usize,
+    pub delta: i64,
+}
+
+// Measurements of one run of the driver, written with `--metrics` so that
+// dashboards can follow how an experiment changes over time.
+#[derive(Debug, Default, Serialize)]
+pub struct Metrics {
+    // Seconds since the Unix epoch when the driver started.
+    pub timestamp: u64,
+    pub passes: Vec<PassMetrics>,
+    pub functions: Vec<SizeMetrics>,
+}
+
+// Instructions, not counting labels.
+fn code_size(instrs: &[crate::Instruction]) -> usize {
+    instrs.iter().filter(|instr| !instr.is_label()).count()
+}
+
+fn header(out: &mut String, name: &str, help: &str) {
+    writeln!(out, "# HELP bril_opt_{} {}", name, help).unwrap();
+    writeln!(out, "# TYPE bril_opt_{} gauge", name).unwrap();
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Metrics {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_pass(&mut self, pass: &str, elapsed: Duration, changed: bool) {
+        let index = match self.passes.iter().position(|p| p.pass == pass) {
+            Some(index) => index,
+            None => {
+                self.passes.push(PassMetrics {
+                    pass: pass.to_string(),
+                    ..Default::default()
+                });
+                self.passes.len() - 1
+            }
+        };
+        let metrics = &mut self.passes[index];
+        metrics.runs += 1;
+        metrics.changed += changed as usize;
+        metrics.seconds += elapsed.as_secs_f64();
+    }
+
+    // Adds the runs of the passes `other` measured, as those of a worker
+    // running passes alongside this one; the seconds add up to more than the
+    // run took.
+    pub fn merge(&mut self, other: Metrics) {
+        for pass in other.passes {
+            match self.passes.iter_mut().find(|p| p.pass == pass.pass) {
+                Some(metrics) => {
+                    metrics.runs += pass.runs;
+                    metrics.changed += pass.changed;
+                    metrics.seconds += pass.seconds;
+                }
+                None => self.passes.push(pass),
+            }
+        }
+    }
+
+    // A function deleted by the passes is down to nothing.
+    pub fn record_sizes(&mut self, original: &Program, optimized: &Program) {
+        for before in &original.functions {
+            let after = optimized.functions.iter().find(|f| f.name == before.name);
+            let before_size = code_size(&before.instrs);
+            let after_size = after.map_or(0, |after| code_size(&after.instrs));
+            self.functions.push(SizeMetrics {
+                function: before.name.clone(),
+                before: before_size,
+                after: after_size,
+                delta: after_size as i64 - before_size as i64,
+            });
+        }
+        // Functions the passes added, like specialized clones, were nothing
+        // before.
+        for after in &optimized.functions {
+            if original.functions.iter().all(|f| f.name != after.name) {
+                let after_size = code_size(&after.instrs);
+                self.functions.push(SizeMetrics {
+                    function: after.name.clone(),
+                    before: 0,
+                    after: after_size,
+                    delta: after_size as i64,
+                });
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize metrics")
+    }
+
+    // The Prometheus text exposition format, for a node exporter's textfile
+    // collector or a push gateway.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        header(&mut out, "timestamp_seconds", "When the driver ran.");
+        writeln!(out, "bril_opt_timestamp_seconds {}", self.timestamp).unwrap();
+
+        // JSON strings are escaped the way Prometheus label values are.
+        let passes: Vec<_> = self
+            .passes
+            .iter()
+            .map(|p| (serde_json::json!(p.pass), p))
+            .collect();
+        header(&mut out, "pass_runs", "Runs of the pass over a function.");
+        for (label, pass) in &passes {
+            writeln!(out, "bril_opt_pass_runs{{pass={}}} {}", label, pass.runs).unwrap();
+        }
+        header(
+            &mut out,
+            "pass_changed",
+            "Runs of the pass that changed the function.",
+        );
+        for (label, pass) in &passes {
+            writeln!(
+                out,
+                "bril_opt_pass_changed{{pass={}}} {}",
+                label, pass.changed
+            )
+            .unwrap();
+        }
+        header(&mut out, "pass_seconds", "Time spent in the pass.");
+        for (label, pass) in &passes {
+            writeln!(
+                out,
+                "bril_opt_pass_seconds{{pass={}}} {}",
+                label, pass.seconds
+            )
+            .unwrap();
+        }
+
+        header(
+            &mut out,
+            "instructions",
+            "Instructions in a function before and after optimizing.",
+        );
+        for size in &self.functions {
+            let function = serde_json::json!(size.function);
+            writeln!(
+                out,
+                "bril_opt_instructions{{function={},stage=\"before\"}} {}",
+                function, size.before
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "bril_opt_instructions{{function={},stage=\"after\"}} {}",
+                function, size.after
+            )
+            .unwrap();
+        }
+        out
+    }
+}
@@ -0,0 +1,39 @@
+use crate::Function;
+use std::collections::{HashMap, HashSet};
+
+// Hands out names not used anywhere in the function yet, numbered from a
+// seed, which `--seed` gives, so that a run always makes the same names.
+pub struct Names {
+    used: HashSet<String>,
+    seed: u64,
+    counters: HashMap<String, u64>,
+}
+
+impl Names {
+    pub fn new(function: &Function, seed: u64) -> Self {
+        let mut used: HashSet<String> = function.args.iter().map(|a| a.name.clone()).collect();
+        for instr in &function.instrs {
+            used.extend(instr.dest.iter().cloned());
+            used.extend(instr.label.iter().cloned());
+            used.extend(instr.args.iter().cloned());
+        }
+        Names {
+            used,
+            seed,
+            counters: HashMap::new(),
+        }
+    }
+
+    // `base.0`, `base.1` and so on from the seed, skipping names that are
+    // taken.
+    pub fn fresh(&mut self, base: &str) -> String {
+        let counter = self.counters.entry(base.to_string()).or_insert(self.seed);
+        loop {
+            let name = format!("{}.{}", base, counter);
+            *counter += 1;
+            if self.used.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+}
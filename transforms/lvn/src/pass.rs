@@ -0,0 +1,319 @@
+// Optimizations over one function as the driver runs them. A pass is
+// anything implementing `Pass`, closures over a function and the analysis
+// manager included, and is known to the driver by the name it is registered
+// with in `Registry::builtin`: a new one only needs to be written against the
+// trait and listed there, and `--passes`, pipeline files, metrics and RUN
+// directives pick it up.
+//
+//     registry.register("my-pass", || Box::new(MyPass::default()));
+//
+// A pass gets what runs of the passes share through the `AnalysisManager`:
+// remarks, bisection, the trapping policy and the analyses of the function it
+// runs on. Those are computed the first time a pass asks for them and kept
+// until a pass reports a change to the function.
+use crate::bisect::OptBisect;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Function};
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::loops::LoopForest;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Changed {
+    No,
+    Yes,
+}
+
+impl From<bool> for Changed {
+    fn from(changed: bool) -> Self {
+        if changed {
+            Changed::Yes
+        } else {
+            Changed::No
+        }
+    }
+}
+
+impl Changed {
+    pub fn is_yes(self) -> bool {
+        self == Changed::Yes
+    }
+}
+
+pub trait Pass {
+    fn run(&mut self, func: &mut Function, analyses: &mut AnalysisManager) -> Changed;
+}
+
+impl<F> Pass for F
+where
+    F: FnMut(&mut Function, &mut AnalysisManager) -> Changed,
+{
+    fn run(&mut self, func: &mut Function, analyses: &mut AnalysisManager) -> Changed {
+        self(func, analyses)
+    }
+}
+
+// The analyses of one function computed so far.
+#[derive(Default)]
+struct Cached {
+    cfg: Option<Rc<ControlFlowGraph>>,
+    dominators: Option<Rc<DominatorTree>>,
+    loops: Option<Rc<LoopForest>>,
+}
+
+pub struct AnalysisManager {
+    pub remarks: Remarks,
+    pub bisect: OptBisect,
+    pub div_by_zero: DivByZero,
+    pub unroll_factor: usize,
+    // Where the names the passes make are numbered from.
+    pub seed: u64,
+    cache: HashMap<String, Cached>,
+}
+
+impl AnalysisManager {
+    pub fn new(
+        remarks: Remarks,
+        bisect: OptBisect,
+        div_by_zero: DivByZero,
+        unroll_factor: usize,
+        seed: u64,
+    ) -> Self {
+        AnalysisManager {
+            remarks,
+            bisect,
+            div_by_zero,
+            unroll_factor,
+            seed,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn cached(&mut self, func: &Function) -> &mut Cached {
+        self.cache.entry(func.name.clone()).or_default()
+    }
+
+    pub fn cfg(&mut self, func: &Function) -> Rc<ControlFlowGraph> {
+        let cached = self.cached(func);
+        cached
+            .cfg
+            .get_or_insert_with(|| Rc::new(construct_control_flow_graph(func)))
+            .clone()
+    }
+
+    pub fn dominators(&mut self, func: &Function) -> Rc<DominatorTree> {
+        if let Some(tree) = &self.cached(func).dominators {
+            return tree.clone();
+        }
+        let tree = Rc::new(DominatorTree::new(&self.cfg(func)));
+        self.cached(func).dominators = Some(tree.clone());
+        tree
+    }
+
+    pub fn loops(&mut self, func: &Function) -> Rc<LoopForest> {
+        if let Some(forest) = &self.cached(func).loops {
+            return forest.clone();
+        }
+        let cfg = self.cfg(func);
+        let tree = self.dominators(func);
+        let forest = Rc::new(LoopForest::with_dominators(&cfg, &tree));
+        self.cached(func).loops = Some(forest.clone());
+        forest
+    }
+
+    // Drops what is known about `func`, which a pass changed.
+    pub fn invalidate(&mut self, func: &Function) {
+        self.cache.remove(&func.name);
+    }
+
+    // Drops what is known about every function, after a pass over the whole
+    // program.
+    pub fn invalidate_all(&mut self) {
+        self.cache.clear();
+    }
+}
+
+// Whether `func` has any loops, for the loop passes to skip those that have
+// none without each building the analyses again.
+fn has_loops(func: &Function, analyses: &mut AnalysisManager) -> bool {
+    !analyses.loops(func).loops.is_empty()
+}
+
+pub type Factory = fn() -> Box<dyn Pass>;
+
+#[derive(Clone)]
+pub struct Registry {
+    passes: Vec<(&'static str, Factory)>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry { passes: Vec::new() }
+    }
+
+    pub fn register(&mut self, name: &'static str, factory: Factory) {
+        if self.contains(name) {
+            panic!("Pass registered twice: {}", name);
+        }
+        self.passes.push((name, factory));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.passes.iter().any(|(pass, _)| *pass == name)
+    }
+
+    // A new instance of the pass registered as `name`.
+    pub fn create(&self, name: &str) -> Option<Box<dyn Pass>> {
+        let (_, factory) = self.passes.iter().find(|(pass, _)| *pass == name)?;
+        Some(factory())
+    }
+
+    // The names of the registered passes, in the order they were registered.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().map(|(name, _)| *name)
+    }
+
+    // The passes of this crate.
+    pub fn builtin() -> Self {
+        use crate::*;
+
+        let mut registry = Registry::new();
+        registry.register("lvn", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                let mut cfg = construct_control_flow_graph(f);
+                let changed = local_value_numbering(
+                    f,
+                    &mut cfg,
+                    &mut a.remarks,
+                    &mut a.bisect,
+                    a.div_by_zero,
+                    a.seed,
+                );
+                f.instrs = cfg.into_instrs();
+                changed.into()
+            })
+        });
+        registry.register("svn", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                let mut cfg = take_control_flow_graph(f);
+                let changed = superlocal::run_superlocal_value_numbering(
+                    &f.name,
+                    &mut cfg,
+                    &mut a.remarks,
+                    &mut a.bisect,
+                    a.div_by_zero,
+                );
+                f.instrs = cfg.into_instrs();
+                changed.into()
+            })
+        });
+        registry.register("gvn", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                gvn::global_value_numbering(f, &mut a.remarks, &mut a.bisect).into()
+            })
+        });
+        registry.register("dce", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                let mut cfg = take_control_flow_graph(f);
+                let changed = dce::eliminate_dead_code(
+                    &f.name,
+                    &mut cfg,
+                    &mut a.remarks,
+                    &mut a.bisect,
+                    a.div_by_zero,
+                );
+                f.instrs = cfg.into_instrs();
+                changed.into()
+            })
+        });
+        registry.register("dse", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                dse::eliminate_dead_stores(f, &mut a.remarks, &mut a.bisect).into()
+            })
+        });
+        registry.register("rle", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                rle::eliminate_redundant_loads(f, &mut a.remarks, &mut a.bisect).into()
+            })
+        });
+        registry.register("mem2reg", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                mem2reg::promote_memory(f, &mut a.remarks, &mut a.bisect, a.seed).into()
+            })
+        });
+        registry.register("constprop", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                constprop::propagate_constants(f, &mut a.remarks, &mut a.bisect, a.div_by_zero)
+                    .into()
+            })
+        });
+        registry.register("licm", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                if !has_loops(f, a) {
+                    return Changed::No;
+                }
+                licm::hoist_invariants(f, &mut a.remarks, &mut a.bisect, a.div_by_zero, a.seed)
+                    .into()
+            })
+        });
+        registry.register("unroll", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                if !has_loops(f, a) {
+                    return Changed::No;
+                }
+                let factor = a.unroll_factor;
+                unroll::unroll_loops(
+                    f,
+                    &mut a.remarks,
+                    &mut a.bisect,
+                    a.div_by_zero,
+                    factor,
+                    a.seed,
+                )
+                .into()
+            })
+        });
+        registry.register("unswitch", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                if !has_loops(f, a) {
+                    return Changed::No;
+                }
+                unswitch::unswitch_loops(f, &mut a.remarks, &mut a.bisect, a.seed).into()
+            })
+        });
+        registry.register("tce", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                tce::eliminate_tail_calls(f, &mut a.remarks, &mut a.bisect, a.seed).into()
+            })
+        });
+        registry.register("simplify-cfg", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                simplify::simplify_cfg(f, &mut a.remarks, &mut a.bisect).into()
+            })
+        });
+        registry.register("jump-threading", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                threading::thread_jumps(f, &mut a.remarks, &mut a.bisect, a.div_by_zero, a.seed)
+                    .into()
+            })
+        });
+        registry.register("split-critical-edges", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                split::split_edges(f, &mut a.remarks, &mut a.bisect, a.seed).into()
+            })
+        });
+        registry.register("to-ssa", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                ssa::to_ssa(f, &mut a.remarks, &mut a.bisect, a.seed).into()
+            })
+        });
+        registry.register("from-ssa", || {
+            Box::new(|f: &mut Function, a: &mut AnalysisManager| {
+                ssa::from_ssa(f, &mut a.remarks, &mut a.bisect, a.seed).into()
+            })
+        });
+        registry
+    }
+}
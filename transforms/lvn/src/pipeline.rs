@@ -0,0 +1,160 @@
+// Pipelines written down in a file for `--pipeline`, so an experiment with
+// the passes can be described once and run again the same way. The file is
+// TOML, or JSON when its name ends in `.json`, with the steps under `passes`:
+//
+//     passes = [
+//         "tce",
+//         { pass = "unroll", unroll-factor = 8 },
+//         { repeat = ["constprop", "simplify-cfg", "dce"], max-iterations = 5 },
+//         { pass = "dce", fold-div-by-zero = "poison" },
+//     ]
+//
+// A step is the name of a pass, or a table naming it with options for that
+// step alone: `unroll-factor` and `fold-div-by-zero`, which stand in for the
+// flags of the same name. A `repeat` step runs its steps, groups among them,
+// over and over until a round goes by in which none changes anything or
+// `max-iterations` rounds have run, 10 unless given, like `--fixpoint` does
+// for a whole pipeline.
+use crate::config::{self, Value};
+use crate::trapping::DivByZero;
+use bril_ir::error::BrilError;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub div_by_zero: Option<DivByZero>,
+    pub unroll_factor: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    Pass(String, Options),
+    Repeat(Vec<Step>, usize),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Pass(pass, _) => write!(f, "{}", pass),
+            Step::Repeat(steps, _) => {
+                let steps: Vec<String> = steps.iter().map(Step::to_string).collect();
+                write!(f, "repeat[{}]", steps.join(", "))
+            }
+        }
+    }
+}
+
+// The same value as read from TOML, which JSON only adds floats and `null`
+// to.
+fn from_json(value: &serde_json::Value) -> Result<Value, String> {
+    match value {
+        serde_json::Value::String(s) => Ok(Value::String(s.clone())),
+        serde_json::Value::Bool(b) => Ok(Value::Boolean(*b)),
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .map(Value::Integer)
+            .ok_or_else(|| format!("expected an integer, found {}", n)),
+        serde_json::Value::Array(values) => values
+            .iter()
+            .map(from_json)
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        serde_json::Value::Object(fields) => {
+            let mut table = BTreeMap::new();
+            for (key, value) in fields {
+                table.insert(key.clone(), from_json(value)?);
+            }
+            Ok(Value::Table(table))
+        }
+        serde_json::Value::Null => Err(String::from("unexpected null")),
+    }
+}
+
+fn positive(table: &BTreeMap<String, Value>, key: &str) -> Result<Option<usize>, String> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Integer(i)) if *i > 0 => Ok(Some(*i as usize)),
+        Some(_) => Err(format!("`{}` must be a positive integer", key)),
+    }
+}
+
+fn step(value: &Value, known: &[&str]) -> Result<Step, String> {
+    let pass = |name: &str, options| {
+        if known.contains(&name) {
+            Ok(Step::Pass(name.to_string(), options))
+        } else {
+            Err(format!("unknown pass `{}`", name))
+        }
+    };
+    let table = match value {
+        Value::String(name) => return pass(name, Options::default()),
+        Value::Table(table) => table,
+        _ => return Err(String::from("a step must be a pass name or a table")),
+    };
+    let allowed: &[&str] = if table.contains_key("repeat") {
+        &["repeat", "max-iterations"]
+    } else {
+        &["pass", "unroll-factor", "fold-div-by-zero"]
+    };
+    if let Some(key) = table.keys().find(|key| !allowed.contains(&key.as_str())) {
+        return Err(format!("unexpected `{}` in a step", key));
+    }
+    if let Some(steps) = table.get("repeat") {
+        let max_iterations = positive(table, "max-iterations")?.unwrap_or(10);
+        return Ok(Step::Repeat(steps_of(steps, known)?, max_iterations));
+    }
+    let div_by_zero = match table.get("fold-div-by-zero") {
+        None => None,
+        Some(value) => Some(
+            value
+                .as_str()
+                .and_then(DivByZero::parse)
+                .ok_or("`fold-div-by-zero` must be \"preserve\", \"poison\" or \"zero\"")?,
+        ),
+    };
+    let options = Options {
+        div_by_zero,
+        unroll_factor: positive(table, "unroll-factor")?,
+    };
+    match table.get("pass").and_then(Value::as_str) {
+        Some(name) => pass(name, options),
+        None => Err(String::from("a step needs a `pass` or a `repeat`")),
+    }
+}
+
+fn steps_of(value: &Value, known: &[&str]) -> Result<Vec<Step>, String> {
+    match value {
+        Value::Array(values) => values.iter().map(|value| step(value, known)).collect(),
+        _ => Err(String::from("expected a list of steps")),
+    }
+}
+
+// The steps of a pipeline description, each pass among `known`.
+pub fn parse(description: &Value, known: &[&str]) -> Result<Vec<Step>, String> {
+    if let Value::Table(table) = description {
+        if let Some(key) = table.keys().find(|key| *key != "passes") {
+            return Err(format!("unexpected `{}` in a pipeline", key));
+        }
+    }
+    match description.get("passes") {
+        Some(steps) => steps_of(steps, known),
+        None => Err(String::from("a pipeline needs `passes`")),
+    }
+}
+
+pub fn load(path: &Path, known: &[&str]) -> Result<Vec<Step>, BrilError> {
+    let name = path.display().to_string();
+    let text = std::fs::read_to_string(path).map_err(|e| BrilError::io(&name, e))?;
+    let description = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|json| from_json(&json))
+    } else {
+        config::parse(&text)
+    };
+    description
+        .and_then(|description| parse(&description, known))
+        .map_err(|e| BrilError::config(name, e))
+}
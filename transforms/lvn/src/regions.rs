@@ -0,0 +1,155 @@
+use crate::analysis::compute_postdominators;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Program};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// A single-entry single-exit region: control enters only through `entry` and
+// leaves only to `exit`, which lies outside of it. Regions without an exit
+// run until the function returns.
+#[derive(Debug)]
+pub struct Region {
+    pub entry: usize,
+    pub exit: Option<usize>,
+    pub blocks: BTreeSet<usize>,
+    // Maximal regions nested directly inside this one.
+    pub children: Vec<Region>,
+}
+
+// Blocks reachable from `entry` without passing through `exit`.
+fn region_body(cfg: &ControlFlowGraph, entry: usize, exit: Option<usize>) -> BTreeSet<usize> {
+    let mut body = BTreeSet::from([entry]);
+    let mut stack = vec![entry];
+    while let Some(b) = stack.pop() {
+        for &next in &cfg.blocks[b].next_blocks {
+            if Some(next) != exit && body.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    body
+}
+
+fn is_region(
+    cfg: &ControlFlowGraph,
+    entry: usize,
+    exit: Option<usize>,
+    body: &BTreeSet<usize>,
+) -> bool {
+    body.iter().all(|&b| {
+        let single_entry = b == entry || cfg.blocks[b].prev_blocks.iter().all(|p| body.contains(p));
+        let next_blocks = &cfg.blocks[b].next_blocks;
+        let single_exit = match exit {
+            // Returning from inside would be a second way out.
+            Some(_) => !next_blocks.is_empty(),
+            None => true,
+        };
+        single_entry && single_exit
+    })
+}
+
+// The smallest region starting at `entry`, exiting at the nearest
+// postdominator that closes one off. Regions of a single block are trivial
+// and left out.
+fn canonical_region(
+    cfg: &ControlFlowGraph,
+    postdominators: &[BTreeSet<usize>],
+    entry: usize,
+) -> Option<Region> {
+    // A nearer postdominator is itself postdominated by the farther ones.
+    let mut exits: Vec<usize> = postdominators[entry]
+        .iter()
+        .copied()
+        .filter(|&x| x != entry)
+        .collect();
+    exits.sort_by_key(|&x| std::cmp::Reverse(postdominators[x].len()));
+    let candidates = exits.into_iter().map(Some).chain(std::iter::once(None));
+    for exit in candidates {
+        let body = region_body(cfg, entry, exit);
+        if is_region(cfg, entry, exit, &body) {
+            if body.len() < 2 {
+                return None;
+            }
+            return Some(Region {
+                entry,
+                exit,
+                blocks: body,
+                children: Vec::new(),
+            });
+        }
+    }
+    None
+}
+
+fn insert(parent: &mut Region, region: Region) {
+    match parent
+        .children
+        .iter_mut()
+        .find(|child| region.blocks.is_subset(&child.blocks))
+    {
+        Some(child) => insert(child, region),
+        None => parent.children.push(region),
+    }
+}
+
+// Builds the program structure tree: the whole function at the root, with
+// every canonical region below the smallest region containing it.
+pub fn program_structure_tree(cfg: &ControlFlowGraph) -> Region {
+    let postdominators = compute_postdominators(cfg);
+    let mut regions: Vec<Region> = (0..cfg.blocks.len())
+        .filter_map(|entry| canonical_region(cfg, &postdominators, entry))
+        .filter(|region| region.blocks.len() < cfg.blocks.len())
+        .collect();
+    // Outer regions first, so that each one is in place before its children.
+    regions.sort_by_key(|region| std::cmp::Reverse(region.blocks.len()));
+
+    let mut root = Region {
+        entry: 0,
+        exit: None,
+        blocks: (0..cfg.blocks.len()).collect(),
+        children: Vec::new(),
+    };
+    for region in regions {
+        insert(&mut root, region);
+    }
+    root
+}
+
+fn block_name(cfg: &ControlFlowGraph, b: usize) -> String {
+    match &cfg.blocks[b].instrs[0].label {
+        Some(label) => format!(".{}", label),
+        None => format!("<block {}>", b),
+    }
+}
+
+fn write_region(cfg: &ControlFlowGraph, region: &Region, depth: usize, out: &mut String) {
+    let exit = match region.exit {
+        Some(exit) => block_name(cfg, exit),
+        None => String::from("return"),
+    };
+    let blocks: Vec<String> = region.blocks.iter().map(|&b| block_name(cfg, b)).collect();
+    writeln!(
+        out,
+        "{}{} -> {}: {}",
+        "  ".repeat(depth),
+        block_name(cfg, region.entry),
+        exit,
+        blocks.join(" ")
+    )
+    .unwrap();
+    for child in &region.children {
+        write_region(cfg, child, depth + 1, out);
+    }
+}
+
+// Prints each function's program structure tree, one region per line.
+pub fn program_regions(program: &Program) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        writeln!(out, "@{}", function.name).unwrap();
+        let cfg = construct_control_flow_graph(function);
+        if !cfg.blocks.is_empty() {
+            write_region(&cfg, &program_structure_tree(&cfg), 1, &mut out);
+        }
+    }
+    out
+}
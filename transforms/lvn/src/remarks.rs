@@ -0,0 +1,127 @@
+use crate::{construct_control_flow_graph, Function, Instruction};
+use serde::Serialize;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemarkKind {
+    // An optimization was applied.
+    Passed,
+    // An optimization was considered but not applied.
+    Missed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Remark {
+    pub kind: RemarkKind,
+    pub pass: String,
+    pub name: String,
+    pub function: String,
+    pub block: usize,
+    // The ID of the instruction the remark is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instr: Option<String>,
+    pub message: String,
+}
+
+// Collects remarks from passes. Passes report against the function and block
+// set by the driver through `enter`, so they don't need to know where they
+// are running.
+#[derive(Debug, Default)]
+pub struct Remarks {
+    enabled: bool,
+    function: String,
+    block: usize,
+    records: Vec<Remark>,
+}
+
+impl Remarks {
+    pub fn new(enabled: bool) -> Self {
+        Remarks {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Adds the remarks `other` collected after these.
+    pub fn append(&mut self, other: Remarks) {
+        self.records.extend(other.records);
+    }
+
+    pub fn enter(&mut self, function: &str, block: usize) {
+        self.function = function.to_string();
+        self.block = block;
+    }
+
+    // Enters the block instruction `i` of `function` is in, for passes
+    // working on the instructions rather than the blocks.
+    pub fn enter_at(&mut self, function: &Function, i: usize) {
+        let cfg = construct_control_flow_graph(function);
+        let mut end = 0;
+        let block = cfg.iter().position(|(_, block)| {
+            end += block.instrs.len();
+            i < end
+        });
+        self.enter(&function.name, block.unwrap_or(0));
+    }
+
+    fn emit(
+        &mut self,
+        kind: RemarkKind,
+        pass: &str,
+        name: &str,
+        instr: &Instruction,
+        message: String,
+    ) {
+        if self.enabled {
+            self.records.push(Remark {
+                kind,
+                pass: pass.to_string(),
+                name: name.to_string(),
+                function: self.function.clone(),
+                block: self.block,
+                instr: instr.id.clone(),
+                message,
+            });
+        }
+    }
+
+    pub fn passed(&mut self, pass: &str, name: &str, instr: &Instruction, message: String) {
+        self.emit(RemarkKind::Passed, pass, name, instr, message);
+    }
+
+    pub fn missed(&mut self, pass: &str, name: &str, instr: &Instruction, message: String) {
+        self.emit(RemarkKind::Missed, pass, name, instr, message);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.records).expect("Failed to serialize remarks")
+    }
+
+    // One YAML document per remark, in the style of LLVM's -fsave-optimization-record.
+    pub fn to_yaml(&self) -> String {
+        let mut out = String::new();
+        for remark in &self.records {
+            let kind = match remark.kind {
+                RemarkKind::Passed => "Passed",
+                RemarkKind::Missed => "Missed",
+            };
+            writeln!(out, "--- !{}", kind).unwrap();
+            writeln!(out, "pass: {}", remark.pass).unwrap();
+            writeln!(out, "name: {}", remark.name).unwrap();
+            // JSON strings are valid double-quoted YAML scalars.
+            writeln!(out, "function: {}", serde_json::json!(remark.function)).unwrap();
+            writeln!(out, "block: {}", remark.block).unwrap();
+            if let Some(instr) = &remark.instr {
+                writeln!(out, "instr: {}", serde_json::json!(instr)).unwrap();
+            }
+            writeln!(out, "message: {}", serde_json::json!(remark.message)).unwrap();
+            writeln!(out, "...").unwrap();
+        }
+        out
+    }
+}
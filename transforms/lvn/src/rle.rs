@@ -0,0 +1,107 @@
+use crate::bisect::OptBisect;
+use crate::ebb::{Event, ExtendedBasicBlocks};
+use crate::remarks::Remarks;
+use crate::{take_control_flow_graph, Function, Instruction};
+use bril_cfg::alias::AliasAnalysis;
+use std::collections::BTreeMap;
+
+// The memory known at a point: for a pointer variable, the variable holding
+// what it points to, as the last load or store through it left it.
+type Known = BTreeMap<String, String>;
+
+// Forgets what `var` pointed to and what it held, once it is assigned.
+fn forget_var(known: &mut Known, var: &str) {
+    known.retain(|pointer, value| pointer != var && value != var);
+}
+
+// Forgets the memory `pointer` may point into, once it is written or freed.
+fn forget_memory(known: &mut Known, aliases: &AliasAnalysis, pointer: &str) {
+    known.retain(|other, _| !aliases.may_alias(other, pointer));
+}
+
+// Updates `known` past `instr`, replacing it with a copy first when it loads
+// what is already known.
+fn step(
+    instr: &mut Instruction,
+    known: &mut Known,
+    aliases: &AliasAnalysis,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+) -> bool {
+    let mut changed = false;
+    match instr.op.as_deref() {
+        Some("load") => {
+            let pointer = instr.args[0].clone();
+            if let Some(value) = known.get(&pointer) {
+                if bisect.should_run("rle", &format!("reuse `{}`", instr)) {
+                    let original = instr.to_string();
+                    instr.op = Some(String::from("id"));
+                    instr.args = vec![value.clone()];
+                    instr.mark_transformed("rle");
+                    remarks.passed(
+                        "rle",
+                        "RedundantLoad",
+                        instr,
+                        format!("replaced `{}` with `{}`", original, instr),
+                    );
+                    changed = true;
+                }
+            }
+            let dest = instr.dest.clone().unwrap();
+            forget_var(known, &dest);
+            if !changed && dest != pointer {
+                known.insert(pointer, dest);
+            }
+        }
+        Some("store") => {
+            let (pointer, value) = (instr.args[0].clone(), instr.args[1].clone());
+            forget_memory(known, aliases, &pointer);
+            known.insert(pointer, value);
+        }
+        Some("free") => forget_memory(known, aliases, &instr.args[0]),
+        _ => {
+            if instr.writes_memory() {
+                known.clear();
+            }
+            if let Some(dest) = &instr.dest {
+                forget_var(known, dest);
+            }
+        }
+    }
+    changed
+}
+
+// Redundant load elimination: turns a `load` into a copy of the value an
+// earlier load or store through the same pointer variable left in memory,
+// when nothing since may have written that memory, as the alias analysis
+// tells, or freed it. What is known is carried down each extended basic
+// block like superlocal value numbering does, so a block sees the loads and
+// stores of the blocks that are its only way in.
+//
+// A reused load cannot trap, since the load or store it reuses went to the
+// same memory.
+pub fn eliminate_redundant_loads(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+) -> bool {
+    let mut cfg = take_control_flow_graph(function);
+    let aliases = AliasAnalysis::new(&function.args, &cfg);
+    let mut known = Known::new();
+    let mut saved = Vec::new();
+    let mut changed = false;
+    for event in ExtendedBasicBlocks::new(&cfg).events() {
+        match event {
+            Event::Push(b) => {
+                saved.push(known.clone());
+                remarks.enter(&function.name, b);
+                for instr in cfg.blocks[b].instrs.iter_mut() {
+                    changed |= step(instr, &mut known, &aliases, remarks, bisect);
+                }
+            }
+            Event::Pop(_) => known = saved.pop().unwrap(),
+        }
+    }
+    function.instrs = cfg.into_instrs();
+    changed
+}
@@ -0,0 +1,252 @@
+use crate::bisect::OptBisect;
+use crate::cloning::jmp;
+use crate::remarks::Remarks;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Function, Instruction};
+use std::collections::{BTreeSet, HashSet};
+
+const PASS: &str = "simplify-cfg";
+
+fn block_name(cfg: &ControlFlowGraph, b: usize) -> String {
+    match cfg.blocks[b].label() {
+        Some(label) => format!(".{}", label),
+        None => format!("<block {}>", b),
+    }
+}
+
+fn falls_through(instrs: &[Instruction]) -> bool {
+    !instrs.last().is_some_and(Instruction::is_terminator)
+}
+
+struct Simplifier<'a> {
+    function: &'a str,
+    remarks: &'a mut Remarks,
+    bisect: &'a mut OptBisect,
+    // The changes bisection turned down, so they are not asked about again
+    // each time around.
+    declined: HashSet<String>,
+}
+
+impl Simplifier<'_> {
+    // Whether to make the change to block `b` described by `what`, reported
+    // as `name` with `message` if so.
+    fn should(
+        &mut self,
+        cfg: &ControlFlowGraph,
+        b: usize,
+        name: &str,
+        what: String,
+        message: String,
+    ) -> bool {
+        if self.declined.contains(&what) {
+            return false;
+        }
+        if !self.bisect.should_run(PASS, &what) {
+            self.declined.insert(what);
+            return false;
+        }
+        self.remarks.enter(self.function, b);
+        let at = cfg.blocks[b].instrs.first().cloned().unwrap_or_default();
+        self.remarks.passed(PASS, name, &at, message);
+        true
+    }
+
+    // Deletes the blocks no path from the entry reaches, all at once, as they
+    // may jump to each other.
+    fn remove_unreachable(&mut self, cfg: &mut ControlFlowGraph) -> bool {
+        let reached: BTreeSet<usize> = cfg.reverse_postorder().into_iter().collect();
+        let unreached: Vec<usize> = (0..cfg.len()).filter(|b| !reached.contains(b)).collect();
+        if unreached.is_empty()
+            || !self
+                .bisect
+                .should_run(PASS, "delete the unreachable blocks")
+        {
+            return false;
+        }
+        for b in unreached {
+            let name = block_name(cfg, b);
+            self.remarks.enter(self.function, b);
+            let at = cfg.blocks[b].instrs[0].clone();
+            self.remarks.passed(
+                PASS,
+                "Unreachable",
+                &at,
+                format!("deleted {}, which no path from the entry reaches", name),
+            );
+            cfg.blocks[b].instrs.clear();
+        }
+        true
+    }
+
+    // Turns a `br` to the same label twice into a `jmp`, and drops a `jmp`
+    // to the block right after.
+    fn drop_redundant_jumps(&mut self, cfg: &mut ControlFlowGraph) -> bool {
+        let mut changed = false;
+        for b in 0..cfg.len() {
+            let instr = match cfg.blocks[b].instrs.last() {
+                Some(instr) => instr.clone(),
+                None => continue,
+            };
+            match (instr.op.as_deref(), &instr.labels[..]) {
+                (Some("br"), [t, f]) if t == f => {
+                    let what = format!("replace `{}` with a jump", instr);
+                    let message = format!(
+                        "replaced `{}`, going to .{} either way, with a jump",
+                        instr, t
+                    );
+                    if self.should(cfg, b, "Redundant", what, message) {
+                        let mut jump = jmp(t.clone());
+                        jump.id = instr.id.clone();
+                        jump.mark_transformed(PASS);
+                        *cfg.blocks[b].instrs.last_mut().unwrap() = jump;
+                        changed = true;
+                    }
+                }
+                (Some("jmp"), [t])
+                    if cfg.blocks.get(b + 1).and_then(|next| next.label()) == Some(t.as_str()) =>
+                {
+                    let what = format!("delete `{}`", instr);
+                    let message = format!("deleted `{}` to the block right after", instr);
+                    if self.should(cfg, b, "Redundant", what, message) {
+                        cfg.blocks[b].instrs.pop();
+                        changed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+
+    // Sends the jumps to a block that does nothing but go on to another
+    // straight on to that one, and deletes it.
+    fn forward_jumps(&mut self, cfg: &mut ControlFlowGraph) -> bool {
+        for b in 1..cfg.len() {
+            let block = &cfg.blocks[b];
+            let label = match block.label() {
+                Some(label) => label.to_string(),
+                None => continue,
+            };
+            let only_jumps = match &block.instrs[..] {
+                [_] => true,
+                [_, instr] => instr.op.as_deref() == Some("jmp"),
+                _ => false,
+            };
+            let t = match block.next_blocks[..] {
+                [t] if only_jumps && t != b => t,
+                _ => continue,
+            };
+            let target = match cfg.blocks[t].label() {
+                Some(target) => target.to_string(),
+                None => continue,
+            };
+            let what = format!("forward .{} to .{}", label, target);
+            let message = format!("sent the jumps to .{} straight on to .{}", label, target);
+            if !self.should(cfg, b, "Forwarded", what, message) {
+                continue;
+            }
+            let preds: BTreeSet<usize> = cfg.blocks[b].prev_blocks.iter().copied().collect();
+            for p in preds {
+                if falls_through(&cfg.blocks[p].instrs) {
+                    // Deleting the block leaves `p` falling through into the
+                    // one after it.
+                    if t != b + 1 {
+                        let mut jump = jmp(target.clone());
+                        jump.mark_transformed(PASS);
+                        cfg.blocks[p].instrs.push(jump);
+                    }
+                    continue;
+                }
+                let instr = cfg.blocks[p].instrs.last_mut().unwrap();
+                for l in instr.labels.iter_mut().filter(|l| **l == label) {
+                    *l = target.clone();
+                }
+                instr.mark_transformed(PASS);
+            }
+            cfg.blocks[b].instrs.clear();
+            return true;
+        }
+        false
+    }
+
+    // Merges a block into its one predecessor when that goes nowhere else.
+    fn merge_blocks(&mut self, cfg: &mut ControlFlowGraph) -> bool {
+        for a in 0..cfg.len() {
+            let b = match cfg.blocks[a].next_blocks[..] {
+                [b] if b != a && b != 0 && cfg.blocks[b].prev_blocks == [a] => b,
+                _ => continue,
+            };
+            let what = format!("merge {} into {}", block_name(cfg, b), block_name(cfg, a));
+            let message = format!(
+                "merged {} into {}, its only predecessor",
+                block_name(cfg, b),
+                block_name(cfg, a)
+            );
+            if !self.should(cfg, b, "Merged", what, message) {
+                continue;
+            }
+            let mut instrs = std::mem::take(&mut cfg.blocks[a].instrs);
+            if !falls_through(&instrs) {
+                instrs.pop();
+            }
+            let moved = std::mem::take(&mut cfg.blocks[b].instrs);
+            let falls = falls_through(&moved);
+            instrs.extend(moved.into_iter().filter(|instr| !instr.is_label()));
+            // What `b` fell through into is somewhere else now.
+            if falls && b != a + 1 {
+                let mut end = match cfg.blocks.get(b + 1).and_then(|next| next.label()) {
+                    Some(next) => jmp(next.to_string()),
+                    None => Instruction {
+                        op: Some(String::from("ret")),
+                        ..Default::default()
+                    },
+                };
+                end.mark_transformed(PASS);
+                instrs.push(end);
+            }
+            cfg.blocks[a].instrs = instrs;
+            return true;
+        }
+        false
+    }
+}
+
+// CFG simplification: deletes the blocks no path from the entry reaches,
+// drops jumps to the block right after, sends jumps to blocks that only jump
+// on straight to where those go, and merges each block with one predecessor
+// going nowhere else into it, until none of that is left to do. Functions in
+// SSA form are left alone, as their `phi`s name the blocks they come from,
+// and so are functions using speculation, whose blocks also end at each
+// `speculate` and `guard`.
+pub fn simplify_cfg(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+) -> bool {
+    if function
+        .instrs
+        .iter()
+        .any(|instr| instr.is_ssa_op() || instr.is_speculation_op())
+    {
+        return false;
+    }
+    let name = function.name.clone();
+    let mut simplifier = Simplifier {
+        function: &name,
+        remarks,
+        bisect,
+        declined: HashSet::new(),
+    };
+    let mut changed = false;
+    loop {
+        let mut cfg = construct_control_flow_graph(function);
+        let simplified = simplifier.remove_unreachable(&mut cfg)
+            || simplifier.drop_redundant_jumps(&mut cfg)
+            || simplifier.forward_jumps(&mut cfg)
+            || simplifier.merge_blocks(&mut cfg);
+        if !simplified {
+            return changed;
+        }
+        function.instrs = cfg.into_instrs();
+        changed = true;
+    }
+}
@@ -0,0 +1,52 @@
+use crate::bisect::OptBisect;
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::{construct_control_flow_graph, Function, Instruction};
+use bril_cfg::split::split_critical_edges;
+
+// Splits the critical edges of `function`, each new block named after the
+// labels of the blocks its edge went between, for tools putting code on the
+// edges and for looking at what `bril_cfg::split` does. The edges leaving a
+// `speculate` or `guard` have no branch to send to a new block, so functions
+// using speculation are left alone.
+pub fn split_edges(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    seed: u64,
+) -> bool {
+    let mut cfg = construct_control_flow_graph(function);
+    if function.instrs.iter().any(Instruction::is_speculation_op)
+        || bril_cfg::split::critical_edges(&cfg).is_empty()
+        || !bisect.should_run(
+            "split-critical-edges",
+            &format!("split the critical edges of @{}", function.name),
+        )
+    {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let blocks = split_critical_edges(&mut cfg, |from, to| {
+        names.fresh(&format!("{}.{}", from.unwrap_or("entry"), to))
+    });
+    for &b in &blocks {
+        let block = &mut cfg.blocks[b];
+        for instr in &mut block.instrs {
+            instr.mark_transformed("split-critical-edges");
+        }
+        let jump = block.instrs.last().unwrap();
+        remarks.enter(&function.name, b);
+        remarks.passed(
+            "split-critical-edges",
+            "Split",
+            jump,
+            format!(
+                "put .{} on the edge to .{}",
+                block.label().unwrap(),
+                jump.labels[0]
+            ),
+        );
+    }
+    function.instrs = cfg.into_instrs();
+    true
+}
@@ -0,0 +1,383 @@
+use crate::bisect::OptBisect;
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::{take_control_flow_graph, ControlFlowGraph, Function, Instruction, Type};
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::liveness::Liveness;
+use bril_cfg::split::{critical_edges, split_edges};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// Stands for a variable that is not defined on the path a `phi` came in
+// from, as in `examples/to_ssa.py`.
+pub const UNDEFINED: &str = "__undefined";
+
+// Whether every variable is assigned once and arguments are never assigned.
+pub fn is_ssa(function: &Function) -> bool {
+    let mut defined: HashSet<&str> = function.args.iter().map(|a| a.name.as_str()).collect();
+    function
+        .instrs
+        .iter()
+        .filter_map(|instr| instr.dest.as_deref())
+        .all(|dest| defined.insert(dest))
+}
+
+fn label(name: String) -> Instruction {
+    Instruction {
+        label: Some(name),
+        ..Default::default()
+    }
+}
+
+// Gives every block a label, so that `phi` nodes can name it, and adds an
+// entry block when jumps go back to the first one, so that the entry has no
+// predecessors.
+fn label_blocks(function: &mut Function, names: &mut Names) {
+    let mut instrs = Vec::new();
+    let targeted: HashSet<&String> = function
+        .instrs
+        .iter()
+        .flat_map(|i| i.jump_targets())
+        .collect();
+    match function.instrs.first().and_then(|i| i.label.as_ref()) {
+        Some(first) if !targeted.contains(first) => {}
+        _ => instrs.push(label(names.fresh("entry"))),
+    }
+    let mut block_start = false;
+    for instr in &function.instrs {
+        if block_start && !instr.is_label() {
+            instrs.push(label(names.fresh("b")));
+        }
+        block_start = instr.ends_block();
+        instrs.push(instr.clone());
+    }
+    function.instrs = instrs;
+}
+
+struct Renamer<'a> {
+    cfg: &'a mut ControlFlowGraph,
+    tree: &'a DominatorTree,
+    // The variable each `phi` at the top of a block is for, in order.
+    phi_vars: Vec<Vec<String>>,
+    stacks: HashMap<String, Vec<String>>,
+    names: &'a mut Names,
+    function: &'a mut Function,
+}
+
+impl Renamer<'_> {
+    fn push_fresh(&mut self, var: &str, pushed: &mut Vec<String>) -> String {
+        let fresh = self.names.fresh(var);
+        self.function.debug.rename(var, &fresh);
+        self.stacks
+            .entry(var.to_string())
+            .or_default()
+            .push(fresh.clone());
+        pushed.push(var.to_string());
+        fresh
+    }
+
+    fn current(&self, var: &str) -> Option<&String> {
+        self.stacks.get(var).and_then(|stack| stack.last())
+    }
+
+    fn rename(&mut self, b: usize) {
+        let mut pushed = Vec::new();
+        let num_phis = self.phi_vars[b].len();
+        for i in 0..num_phis {
+            let var = self.phi_vars[b][i].clone();
+            let fresh = self.push_fresh(&var, &mut pushed);
+            // The label comes first.
+            self.cfg.blocks[b].instrs[1 + i].dest = Some(fresh);
+        }
+
+        for i in 1 + num_phis..self.cfg.blocks[b].instrs.len() {
+            let mut instr = std::mem::take(&mut self.cfg.blocks[b].instrs[i]);
+            let mut renamed = false;
+            for arg in instr.local_args_mut() {
+                if let Some(current) = self.current(arg) {
+                    renamed |= current != arg;
+                    *arg = current.clone();
+                }
+            }
+            if let Some(dest) = &instr.dest {
+                let dest = dest.clone();
+                instr.dest = Some(self.push_fresh(&dest, &mut pushed));
+                renamed = true;
+            }
+            if renamed {
+                instr.mark_transformed("to-ssa");
+            }
+            self.cfg.blocks[b].instrs[i] = instr;
+        }
+
+        let from = self.cfg.phi_label(b).unwrap().to_string();
+        let mut next_blocks = self.cfg.blocks[b].next_blocks.clone();
+        next_blocks.dedup();
+        for next in next_blocks {
+            for i in 0..self.phi_vars[next].len() {
+                let arg = match self.current(&self.phi_vars[next][i]) {
+                    Some(current) => current.clone(),
+                    None => String::from(UNDEFINED),
+                };
+                self.cfg.blocks[next].instrs[1 + i].add_phi_source(&from, &arg);
+            }
+        }
+
+        for &child in self.tree.children(b) {
+            self.rename(child);
+        }
+
+        for var in pushed {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+}
+
+// Converts a function to SSA form with `phi` nodes, after Cytron et al.:
+// `phi` nodes go on the iterated dominance frontiers of each variable's
+// definitions, and only where the variable is live, then every definition
+// gets a fresh name like `x.0` and every use the name of the definition that
+// reaches it, in a walk of the dominator tree. Arguments keep their names.
+//
+// Functions already in SSA form are left alone, which includes any using
+// `phi`, `get` or `set`. So are functions using speculation: the copies
+// `from_ssa` puts at the end of a block would come after its `speculate` or
+// `guard`.
+pub fn to_ssa(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    seed: u64,
+) -> bool {
+    let uses_ssa_ops = function.instrs.iter().any(Instruction::is_ssa_op);
+    let speculates = function.instrs.iter().any(Instruction::is_speculation_op);
+    if function.instrs.is_empty() || uses_ssa_ops || speculates || is_ssa(function) {
+        return false;
+    }
+    if !bisect.should_run("to-ssa", &format!("convert @{} to SSA", function.name)) {
+        return false;
+    }
+
+    let mut names = Names::new(function, seed);
+    label_blocks(function, &mut names);
+    let mut cfg = take_control_flow_graph(function);
+    let tree = DominatorTree::new(&cfg);
+    let frontiers = tree.frontiers(&cfg);
+    let liveness = Liveness::new(&cfg);
+
+    let mut types: HashMap<String, Type> = HashMap::new();
+    let mut def_blocks: HashMap<String, BTreeSet<usize>> = HashMap::new();
+    for arg in &function.args {
+        types.insert(arg.name.clone(), arg.type_.clone());
+        def_blocks.entry(arg.name.clone()).or_default().insert(0);
+    }
+    for (b, block) in cfg.iter() {
+        for instr in &block.instrs {
+            if let (Some(dest), Some(type_)) = (&instr.dest, &instr.type_) {
+                types.insert(dest.clone(), type_.clone());
+                def_blocks.entry(dest.clone()).or_default().insert(b);
+            }
+        }
+    }
+
+    let mut phi_vars = vec![Vec::new(); cfg.len()];
+    for var in def_blocks.keys() {
+        let mut worklist: Vec<usize> = def_blocks[var].iter().copied().collect();
+        let mut has_phi = HashSet::new();
+        while let Some(d) = worklist.pop() {
+            for &b in &frontiers[d] {
+                if liveness.live_in[b].contains(var) && has_phi.insert(b) {
+                    phi_vars[b].push(var.clone());
+                    if !def_blocks[var].contains(&b) {
+                        worklist.push(b);
+                    }
+                }
+            }
+        }
+    }
+    for (b, vars) in phi_vars.iter_mut().enumerate() {
+        // Sorted, so the `phi` nodes of a block come out the same on every run.
+        vars.sort();
+        let phis = vars.iter().map(|var| Instruction {
+            op: Some(String::from("phi")),
+            dest: Some(var.clone()),
+            type_: Some(types[var].clone()),
+            ..Default::default()
+        });
+        cfg.blocks[b].instrs.splice(1..1, phis.collect::<Vec<_>>());
+    }
+
+    let mut stacks = HashMap::new();
+    for arg in &function.args {
+        stacks.insert(arg.name.clone(), vec![arg.name.clone()]);
+    }
+    let mut renamer = Renamer {
+        cfg: &mut cfg,
+        tree: &tree,
+        phi_vars,
+        stacks,
+        names: &mut names,
+        function,
+    };
+    renamer.rename(0);
+
+    for (b, block) in cfg.blocks.iter_mut().enumerate() {
+        remarks.enter(&function.name, b);
+        for instr in block.instrs.iter_mut() {
+            if instr.is_phi() {
+                instr.mark_transformed("to-ssa");
+                remarks.passed("to-ssa", "Phi", instr, format!("inserted `{}`", instr));
+            }
+        }
+    }
+    function.instrs = cfg.into_instrs();
+    true
+}
+
+fn copy(dest: &str, src: &str, type_: &Type) -> Instruction {
+    let mut instr = Instruction {
+        op: Some(String::from("id")),
+        dest: Some(dest.to_string()),
+        type_: Some(type_.clone()),
+        args: vec![src.to_string()],
+        ..Default::default()
+    };
+    instr.mark_transformed("from-ssa");
+    instr
+}
+
+// Orders the copies `(dest, src, type)` of one edge, which all read their
+// sources before any of them writes, so that they can run one after another.
+// A copy goes once no other copy still needs the old value of its
+// destination. When only cycles are left, like the swap `a, b = b, a`, one
+// destination is saved in a temporary first.
+fn sequentialize(mut pending: Vec<(String, String, Type)>, names: &mut Names) -> Vec<Instruction> {
+    pending.retain(|(dest, src, _)| dest != src);
+    let mut copies = Vec::new();
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .position(|(dest, _, _)| pending.iter().all(|(_, src, _)| src != dest));
+        match ready {
+            Some(i) => {
+                let (dest, src, type_) = pending.remove(i);
+                copies.push(copy(&dest, &src, &type_));
+            }
+            None => {
+                let (dest, _, type_) = pending[0].clone();
+                let temp = names.fresh(&dest);
+                copies.push(copy(&temp, &dest, &type_));
+                for (_, src, _) in pending.iter_mut() {
+                    if *src == dest {
+                        *src = temp.clone();
+                    }
+                }
+            }
+        }
+    }
+    copies
+}
+
+// Takes a function out of SSA form by replacing each `phi` with copies at
+// the end of its predecessors. A critical edge, from a block that branches
+// elsewhere too, gets a block of its own for the copies, or they would
+// clobber values still used on the other edges (the lost-copy problem). The copies on an
+// edge happen all at once as far as the `phi` nodes are concerned, so they
+// are ordered to keep one from overwriting what another still reads (the
+// swap problem). Functions using speculation, which `to_ssa` leaves alone,
+// are left alone here too.
+pub fn from_ssa(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    seed: u64,
+) -> bool {
+    let instrs = &function.instrs;
+    if !instrs.iter().any(Instruction::is_phi) || instrs.iter().any(Instruction::is_speculation_op)
+    {
+        return false;
+    }
+    if !bisect.should_run(
+        "from-ssa",
+        &format!("convert @{} out of SSA", function.name),
+    ) {
+        return false;
+    }
+
+    let mut names = Names::new(function, seed);
+    let mut cfg = take_control_flow_graph(function);
+    let critical: HashSet<(usize, usize)> = critical_edges(&cfg).into_iter().collect();
+    let n = cfg.len();
+    // Copies for the end of each block, going before its terminator.
+    let mut copies_at_end = vec![Vec::new(); n];
+    // The critical edges with copies, which get blocks of their own for them.
+    let mut edges = Vec::new();
+    let mut edge_copies = Vec::new();
+    for b in 0..n {
+        let (phis, rest): (Vec<Instruction>, Vec<Instruction>) = cfg.blocks[b]
+            .instrs
+            .drain(..)
+            .partition(Instruction::is_phi);
+        cfg.blocks[b].instrs = rest;
+        if phis.is_empty() {
+            continue;
+        }
+        remarks.enter(&function.name, b);
+        for phi in &phis {
+            remarks.passed(
+                "from-ssa",
+                "Phi",
+                phi,
+                format!("replaced `{}` with copies", phi),
+            );
+        }
+
+        let mut prev_blocks = cfg.blocks[b].prev_blocks.clone();
+        prev_blocks.sort_unstable();
+        prev_blocks.dedup();
+        for p in prev_blocks {
+            // A `phi` can only name a predecessor with a label.
+            let from = match cfg.phi_label(p) {
+                Some(label) => label.to_string(),
+                None => continue,
+            };
+            let parallel: Vec<(String, String, Type)> = phis
+                .iter()
+                .filter_map(|phi| {
+                    let src = phi.phi_source(&from)?;
+                    if src == UNDEFINED {
+                        return None;
+                    }
+                    Some((phi.dest.clone()?, src.to_string(), phi.type_.clone()?))
+                })
+                .collect();
+            let copies = sequentialize(parallel, &mut names);
+            if copies.is_empty() {
+                continue;
+            }
+            if critical.contains(&(p, b)) {
+                edges.push((p, b));
+                edge_copies.push(copies);
+            } else {
+                copies_at_end[p].extend(copies);
+            }
+        }
+    }
+
+    for (block, copies) in cfg.blocks.iter_mut().zip(copies_at_end) {
+        let at = match block.instrs.last() {
+            Some(last) if last.is_terminator() => block.instrs.len() - 1,
+            _ => block.instrs.len(),
+        };
+        block.instrs.splice(at..at, copies);
+    }
+    let split = split_edges(&mut cfg, &edges, |from, to| {
+        names.fresh(&format!("{}.{}", from.unwrap(), to))
+    });
+    for (e, copies) in split.into_iter().zip(edge_copies) {
+        let instrs = &mut cfg.blocks[e].instrs;
+        instrs.last_mut().unwrap().mark_transformed("from-ssa");
+        instrs.splice(1..1, copies);
+    }
+    function.instrs = cfg.into_instrs();
+    true
+}
@@ -0,0 +1,262 @@
+use crate::{construct_control_flow_graph, ControlFlowGraph, Program};
+use bril_cfg::dominators::DominatorTree;
+use std::fmt::Write;
+
+// Structured control flow recovered from a CFG. Blocks and loops are named
+// after the CFG block that follows or heads them, which is also the target
+// of the `Break` or `Continue` leaving them.
+#[derive(Debug)]
+pub enum Node {
+    // The instructions of a block other than its label and terminator.
+    Code(usize),
+    Seq(Vec<Node>),
+    If {
+        cond: String,
+        then_branch: Box<Node>,
+        else_branch: Box<Node>,
+    },
+    // Breaking out of the block continues right after it, at `label`.
+    Block {
+        label: usize,
+        body: Box<Node>,
+    },
+    // Repeats its body, which never falls off its end.
+    Loop {
+        label: usize,
+        body: Box<Node>,
+    },
+    Break(usize),
+    Continue(usize),
+    Return(Option<String>),
+    // The Relooper's fallback for irreducible control flow: a loop over a
+    // label variable, running the arm for the block the label names. Arms
+    // end by returning or by jumping to another arm with `Goto`.
+    Dispatch {
+        entry: usize,
+        arms: Vec<(usize, Node)>,
+    },
+    Goto(usize),
+}
+
+// Translates how control leaves `b`, with `branch` giving the code for each
+// edge.
+fn terminator(cfg: &ControlFlowGraph, b: usize, branch: impl Fn(usize, usize) -> Node) -> Node {
+    let block = &cfg.blocks[b];
+    let last = block.instrs.last().unwrap();
+    match last.op.as_deref() {
+        Some("ret") => Node::Return(last.args.first().cloned()),
+        Some("br") => Node::If {
+            cond: last.args[0].clone(),
+            then_branch: Box::new(branch(b, block.next_blocks[0])),
+            else_branch: Box::new(branch(b, block.next_blocks[1])),
+        },
+        _ => match block.next_blocks.first() {
+            // A `jmp`, or falling through to the next block.
+            Some(&next) => branch(b, next),
+            None => Node::Return(None),
+        },
+    }
+}
+
+fn dispatch(cfg: &ControlFlowGraph) -> Node {
+    let arms = cfg
+        .reverse_postorder()
+        .into_iter()
+        .map(|b| {
+            let exit = terminator(cfg, b, |_, to| Node::Goto(to));
+            (b, Node::Seq(vec![Node::Code(b), exit]))
+        })
+        .collect();
+    Node::Dispatch { entry: 0, arms }
+}
+
+struct Structurizer<'a> {
+    cfg: &'a ControlFlowGraph,
+    // Position in reverse postorder, for reachable blocks only.
+    rpo: Vec<Option<usize>>,
+    // Children in the dominator tree.
+    children: Vec<Vec<usize>>,
+    is_merge: Vec<bool>,
+    is_header: Vec<bool>,
+}
+
+impl<'a> Structurizer<'a> {
+    // Returns `None` when the CFG is irreducible.
+    fn new(cfg: &'a ControlFlowGraph) -> Option<Self> {
+        let n = cfg.blocks.len();
+        let mut rpo = vec![None; n];
+        for (i, b) in cfg.reverse_postorder().into_iter().enumerate() {
+            rpo[b] = Some(i);
+        }
+        let dominators = DominatorTree::new(cfg);
+        let children = (0..n).map(|b| dominators.children(b).to_vec()).collect();
+
+        let mut is_merge = vec![false; n];
+        let mut is_header = vec![false; n];
+        for b in (0..n).filter(|&b| rpo[b].is_some()) {
+            let reachable_preds = cfg.blocks[b]
+                .prev_blocks
+                .iter()
+                .filter(|&&p| rpo[p].is_some());
+            let mut forward = 0;
+            for &p in reachable_preds {
+                if rpo[p] < rpo[b] {
+                    forward += 1;
+                } else if dominators.dominates(b, p) {
+                    is_header[b] = true;
+                } else {
+                    // A retreating edge into the middle of a loop.
+                    return None;
+                }
+            }
+            is_merge[b] = forward >= 2;
+        }
+
+        Some(Structurizer {
+            cfg,
+            rpo,
+            children,
+            is_merge,
+            is_header,
+        })
+    }
+
+    fn do_tree(&self, b: usize) -> Node {
+        let mut merges: Vec<usize> = self.children[b]
+            .iter()
+            .copied()
+            .filter(|&c| self.is_merge[c])
+            .collect();
+        // The merge node that comes last is the one the outermost block
+        // breaks out to.
+        merges.sort_by_key(|&c| std::cmp::Reverse(self.rpo[c]));
+        let body = self.node_within(b, &merges);
+        if self.is_header[b] {
+            Node::Loop {
+                label: b,
+                body: Box::new(body),
+            }
+        } else {
+            body
+        }
+    }
+
+    fn node_within(&self, b: usize, merges: &[usize]) -> Node {
+        match merges.split_first() {
+            Some((&follow, rest)) => Node::Seq(vec![
+                Node::Block {
+                    label: follow,
+                    body: Box::new(self.node_within(b, rest)),
+                },
+                self.do_tree(follow),
+            ]),
+            None => {
+                let exit = terminator(self.cfg, b, |from, to| self.do_branch(from, to));
+                Node::Seq(vec![Node::Code(b), exit])
+            }
+        }
+    }
+
+    fn do_branch(&self, from: usize, to: usize) -> Node {
+        if self.rpo[to] <= self.rpo[from] {
+            Node::Continue(to)
+        } else if self.is_merge[to] {
+            Node::Break(to)
+        } else {
+            self.do_tree(to)
+        }
+    }
+}
+
+// Recovers loops and conditionals from a reducible CFG along the lines of
+// Ramsey's "Beyond Relooper", falling back to a dispatch loop otherwise.
+pub fn structure(cfg: &ControlFlowGraph) -> Node {
+    match Structurizer::new(cfg) {
+        Some(structurizer) => structurizer.do_tree(0),
+        None => dispatch(cfg),
+    }
+}
+
+pub fn block_name(cfg: &ControlFlowGraph, b: usize) -> String {
+    match &cfg.blocks[b].instrs[0].label {
+        Some(label) => label.clone(),
+        None => format!("b{}", b),
+    }
+}
+
+fn line(out: &mut String, depth: usize, text: &str) {
+    writeln!(out, "{}{}", "  ".repeat(depth), text).unwrap();
+}
+
+fn write_node(cfg: &ControlFlowGraph, node: &Node, depth: usize, out: &mut String) {
+    match node {
+        Node::Code(b) => {
+            for instr in &cfg.blocks[*b].instrs {
+                if !instr.is_label() && !instr.is_terminator() {
+                    line(out, depth, &instr.to_string());
+                }
+            }
+        }
+        Node::Seq(nodes) => {
+            for node in nodes {
+                write_node(cfg, node, depth, out);
+            }
+        }
+        Node::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            line(out, depth, &format!("if {} {{", cond));
+            write_node(cfg, then_branch, depth + 1, out);
+            line(out, depth, "} else {");
+            write_node(cfg, else_branch, depth + 1, out);
+            line(out, depth, "}");
+        }
+        Node::Block { label, body } => {
+            line(out, depth, &format!("block {} {{", block_name(cfg, *label)));
+            write_node(cfg, body, depth + 1, out);
+            line(out, depth, "}");
+        }
+        Node::Loop { label, body } => {
+            line(out, depth, &format!("loop {} {{", block_name(cfg, *label)));
+            write_node(cfg, body, depth + 1, out);
+            line(out, depth, "}");
+        }
+        Node::Break(label) => line(out, depth, &format!("break {};", block_name(cfg, *label))),
+        Node::Continue(label) => line(
+            out,
+            depth,
+            &format!("continue {};", block_name(cfg, *label)),
+        ),
+        Node::Return(Some(value)) => line(out, depth, &format!("return {};", value)),
+        Node::Return(None) => line(out, depth, "return;"),
+        Node::Dispatch { entry, arms } => {
+            line(
+                out,
+                depth,
+                &format!("dispatch {} {{", block_name(cfg, *entry)),
+            );
+            for (b, arm) in arms {
+                line(out, depth + 1, &format!("{}:", block_name(cfg, *b)));
+                write_node(cfg, arm, depth + 2, out);
+            }
+            line(out, depth, "}");
+        }
+        Node::Goto(label) => line(out, depth, &format!("goto {};", block_name(cfg, *label))),
+    }
+}
+
+// Prints each function as structured pseudocode.
+pub fn program_structured(program: &Program) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        writeln!(out, "@{} {{", function.name).unwrap();
+        let cfg = construct_control_flow_graph(function);
+        if !cfg.blocks.is_empty() {
+            write_node(&cfg, &structure(&cfg), 1, &mut out);
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    out
+}
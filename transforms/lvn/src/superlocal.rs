@@ -0,0 +1,241 @@
+use crate::bisect::OptBisect;
+use crate::ebb::{Event, ExtendedBasicBlocks};
+use crate::fold::fold;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{ControlFlowGraph, Expression, Instruction, Literal, Type};
+use bril_ir::PURE_OPS;
+use fxhash::FxHashMap;
+
+pub type Key = (Option<Type>, Expression);
+
+enum Undo {
+    Var(String, Option<usize>),
+    Canonical(usize, Option<String>),
+    Expression(Key, Option<usize>),
+}
+
+// Value numbering state that can be rolled back to any earlier point, so a
+// block's facts stay visible in its subtree but not in its siblings'.
+#[derive(Default)]
+struct Table {
+    var_to_number: FxHashMap<String, usize>,
+    // The variable that currently holds each value, if any still does.
+    canonical: FxHashMap<usize, String>,
+    expression_to_number: FxHashMap<Key, usize>,
+    // The value of each number known to be a constant. Numbers are never
+    // handed out twice, so this needs no rolling back.
+    constants: FxHashMap<usize, Literal>,
+    next_number: usize,
+    log: Vec<Undo>,
+}
+
+impl Table {
+    fn fresh(&mut self) -> usize {
+        self.next_number += 1;
+        self.next_number - 1
+    }
+
+    // Variables defined outside the extended block are opaque inputs.
+    fn number_of(&mut self, var: &str) -> usize {
+        if let Some(&number) = self.var_to_number.get(var) {
+            return number;
+        }
+        let number = self.fresh();
+        self.set_var(var, number);
+        number
+    }
+
+    fn set_var(&mut self, var: &str, number: usize) {
+        let previous = self.var_to_number.insert(var.to_string(), number);
+        self.log.push(Undo::Var(var.to_string(), previous));
+        // The variable no longer holds its old value.
+        if let Some(old) = previous {
+            if self.canonical.get(&old).map(String::as_str) == Some(var) {
+                self.set_canonical(old, None);
+            }
+        }
+        if !self.canonical.contains_key(&number) {
+            self.set_canonical(number, Some(var.to_string()));
+        }
+    }
+
+    fn set_canonical(&mut self, number: usize, var: Option<String>) {
+        let previous = match var {
+            Some(var) => self.canonical.insert(number, var),
+            None => self.canonical.remove(&number),
+        };
+        self.log.push(Undo::Canonical(number, previous));
+    }
+
+    fn set_expression(&mut self, key: Key, number: usize) {
+        let previous = self.expression_to_number.insert(key.clone(), number);
+        self.log.push(Undo::Expression(key, previous));
+    }
+
+    fn rollback(&mut self, mark: usize) {
+        while self.log.len() > mark {
+            match self.log.pop().unwrap() {
+                Undo::Var(var, Some(number)) => {
+                    self.var_to_number.insert(var, number);
+                }
+                Undo::Var(var, None) => {
+                    self.var_to_number.remove(&var);
+                }
+                Undo::Canonical(number, Some(var)) => {
+                    self.canonical.insert(number, var);
+                }
+                Undo::Canonical(number, None) => {
+                    self.canonical.remove(&number);
+                }
+                Undo::Expression(key, Some(number)) => {
+                    self.expression_to_number.insert(key, number);
+                }
+                Undo::Expression(key, None) => {
+                    self.expression_to_number.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+fn number_instr(
+    instr: &mut Instruction,
+    table: &mut Table,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let mut changed = false;
+    let args: Vec<usize> = instr
+        .local_args()
+        .iter()
+        .map(|arg| table.number_of(arg))
+        .collect();
+    for (arg, number) in instr.local_args_mut().iter_mut().zip(&args) {
+        if let Some(canonical) = table.canonical.get(number) {
+            if arg != canonical {
+                *arg = canonical.clone();
+                changed = true;
+            }
+        }
+    }
+    if changed {
+        instr.mark_transformed("svn");
+    }
+
+    let dest = match &instr.dest {
+        Some(dest) => dest.clone(),
+        None => return changed,
+    };
+    if let Some(value) = fold_constants(instr, &args, table, div_by_zero) {
+        if bisect.should_run("svn", &format!("fold `{}`", instr)) {
+            let original = instr.to_string();
+            instr.op = Some(String::from("const"));
+            instr.args.clear();
+            instr.value = Some(value);
+            instr.mark_transformed("svn");
+            remarks.passed(
+                "svn",
+                "Folded",
+                instr,
+                format!("folded `{}` to `{}`", original, instr),
+            );
+            changed = true;
+        }
+    }
+    let op = instr.op.clone().expect("No op found");
+    let key = match op.as_str() {
+        "id" => {
+            table.set_var(&dest, args[0]);
+            return changed;
+        }
+        "const" => Expression::Const(instr.literal().unwrap()),
+        _ if PURE_OPS.contains(&op.as_str()) => Expression::pure_op(op, args),
+        _ => {
+            let number = table.fresh();
+            table.set_var(&dest, number);
+            return changed;
+        }
+    };
+    let key = (instr.type_.clone(), key);
+
+    if let Some(&number) = table.expression_to_number.get(&key) {
+        if let Some(canonical) = table.canonical.get(&number).cloned() {
+            if bisect.should_run("svn", &format!("reuse value for `{}`", instr)) {
+                let original = instr.to_string();
+                instr.op = Some(String::from("id"));
+                instr.args = vec![canonical.clone()];
+                instr.value = None;
+                instr.mark_transformed("svn");
+                remarks.passed(
+                    "svn",
+                    "Redundant",
+                    instr,
+                    format!("replaced `{}` with a copy of `{}`", original, canonical),
+                );
+                table.set_var(&dest, number);
+                return true;
+            }
+        }
+    }
+    let number = table.fresh();
+    if let Expression::Const(value) = key.1 {
+        table.constants.insert(number, value);
+    }
+    table.set_expression(key, number);
+    table.set_var(&dest, number);
+    changed
+}
+
+// The constant `instr` computes when every argument is one, which may be
+// known from any block up the tree.
+fn fold_constants(
+    instr: &Instruction,
+    args: &[usize],
+    table: &Table,
+    div_by_zero: DivByZero,
+) -> Option<Literal> {
+    let op = instr.op.as_deref()?;
+    if op == "id" || op == "const" {
+        return None;
+    }
+    let values = args
+        .iter()
+        .map(|number| table.constants.get(number).copied())
+        .collect::<Option<Vec<Literal>>>()?;
+    fold(op, &values, div_by_zero)
+}
+
+// Superlocal value numbering: numbers each extended basic block as a whole.
+// A block in such a tree sees everything its ancestors computed, so the
+// table is carried down the tree and rolled back when leaving a subtree.
+//
+// Redundant computations are turned into copies of the variable that already
+// holds the value, and those of constants into constants; deleting what they
+// were computed from is left to dead code elimination since it may still be
+// used outside the extended block.
+pub fn run_superlocal_value_numbering(
+    function: &str,
+    cfg: &mut ControlFlowGraph,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+) -> bool {
+    let mut table = Table::default();
+    let mut marks = Vec::new();
+    let mut changed = false;
+    for event in ExtendedBasicBlocks::new(cfg).events() {
+        match event {
+            Event::Push(b) => {
+                marks.push(table.log.len());
+                remarks.enter(function, b);
+                for instr in cfg.blocks[b].instrs.iter_mut() {
+                    changed |= number_instr(instr, &mut table, remarks, bisect, div_by_zero);
+                }
+            }
+            Event::Pop(_) => table.rollback(marks.pop().unwrap()),
+        }
+    }
+    changed
+}
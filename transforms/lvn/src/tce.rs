@@ -0,0 +1,128 @@
+use crate::bisect::OptBisect;
+use crate::cloning::{jmp, label};
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::{Function, Instruction};
+use bril_ir::NamedArg;
+
+// The index of the `ret` a call at `i` goes straight on to, past any labels,
+// if that returns what the call does.
+fn tail_return(function: &Function, i: usize) -> Option<usize> {
+    let call = &function.instrs[i];
+    let k = (i + 1..function.instrs.len()).find(|&k| !function.instrs[k].is_label())?;
+    let ret = &function.instrs[k];
+    let returned: Vec<&str> = call.dest.iter().map(String::as_str).collect();
+    let same = ret.args.iter().map(String::as_str).eq(returned);
+    (ret.op.as_deref() == Some("ret") && same).then_some(k)
+}
+
+// Tail call elimination: a call of a function to itself whose result it
+// returns right away, or that it returns right after when nothing comes back,
+// becomes copies of the arguments into the parameters and a jump to the
+// entry, so the recursion runs as a loop in a single frame. Functions in SSA
+// form are left alone, as the parameters would be assigned more than once,
+// and so are those speculating, where the call is an error.
+pub fn eliminate_tail_calls(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    seed: u64,
+) -> bool {
+    if function
+        .instrs
+        .iter()
+        .any(|instr| instr.is_ssa_op() || instr.is_speculation_op())
+    {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let mut entry: Option<String> = None;
+    let mut changed = false;
+    let mut i = 0;
+    while i < function.instrs.len() {
+        let call = &function.instrs[i];
+        let recursive = call.op.as_deref() == Some("call")
+            && call.funcs == [function.name.clone()]
+            && call.args.len() == function.args.len();
+        let mut k = match recursive.then(|| tail_return(function, i)).flatten() {
+            Some(k) => k,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        if !bisect.should_run("tce", &format!("turn `{}` into a jump", call)) {
+            i += 1;
+            continue;
+        }
+        let original = call.clone();
+        let target = match &entry {
+            Some(target) => target.clone(),
+            None => match function.instrs.first() {
+                Some(first) if first.is_label() => first.label.clone().unwrap(),
+                _ => {
+                    let target = names.fresh(&function.name);
+                    let mut instr = label(target.clone());
+                    instr.mark_transformed("tce");
+                    function.instrs.insert(0, instr);
+                    i += 1;
+                    k += 1;
+                    target
+                }
+            },
+        };
+        entry = Some(target.clone());
+
+        let id = |dest: String, arg: String, param: &NamedArg| {
+            let mut instr = Instruction {
+                op: Some(String::from("id")),
+                dest: Some(dest),
+                type_: Some(param.type_.clone()),
+                args: vec![arg],
+                ..Default::default()
+            };
+            instr.mark_transformed("tce");
+            instr
+        };
+        // An argument naming a parameter goes through a fresh variable, as
+        // the copies may overwrite the parameter before it is read.
+        let mut temps = Vec::new();
+        let mut copies = Vec::new();
+        for (arg, param) in original.args.iter().zip(&function.args) {
+            if *arg == param.name {
+                continue;
+            }
+            if function.args.iter().any(|other| other.name == *arg) {
+                let temp = names.fresh(&param.name);
+                temps.push(id(temp.clone(), arg.clone(), param));
+                copies.push(id(param.name.clone(), temp, param));
+            } else {
+                copies.push(id(param.name.clone(), arg.clone(), param));
+            }
+        }
+        let mut jump = jmp(target.clone());
+        jump.id = original.id.clone();
+        jump.mark_transformed("tce");
+        let mut replacement = temps;
+        replacement.extend(copies);
+        replacement.push(jump);
+
+        remarks.enter_at(function, i);
+        remarks.passed(
+            "tce",
+            "Eliminated",
+            &original,
+            format!(
+                "turned `{}` into a jump back to the entry of @{}",
+                original, function.name
+            ),
+        );
+        // The `ret` goes too when nothing else can get to it.
+        let end = if k == i + 1 { k + 1 } else { i + 1 };
+        let len = replacement.len();
+        function.instrs.splice(i..end, replacement);
+        i += len;
+        changed = true;
+    }
+    changed
+}
@@ -0,0 +1,228 @@
+use crate::bisect::OptBisect;
+use crate::cloning::{jmp, label};
+use crate::constprop::constants_after;
+use crate::fold::step;
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Function, Instruction, Literal};
+use bril_cfg::dominators::DominatorTree;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const PASS: &str = "jump-threading";
+// The most instructions a block may have besides its branch to be copied.
+const THREAD_BUDGET: usize = 16;
+// The most jumps threaded in one function. Threading past loop headers is
+// not done, so it only goes around cycles of irreducible control flow, but
+// those could keep it going forever.
+const THREAD_LIMIT: usize = 256;
+
+// A jump from `pred` into `block` that lands on `target`, as the branch `block`
+// ends with always goes there when control comes from `pred`.
+struct Thread {
+    pred: usize,
+    block: usize,
+    target: usize,
+}
+
+fn block_name(cfg: &ControlFlowGraph, b: usize) -> String {
+    match cfg.blocks[b].label() {
+        Some(label) => format!(".{}", label),
+        None => format!("<block {}>", b),
+    }
+}
+
+fn describe(cfg: &ControlFlowGraph, thread: &Thread) -> String {
+    format!(
+        "thread {} past {} to {}",
+        block_name(cfg, thread.pred),
+        block_name(cfg, thread.block),
+        block_name(cfg, thread.target)
+    )
+}
+
+// The first jump worth threading not in `declined`. What a variable holds
+// coming from a predecessor is what constant propagation knows after it, and
+// when the predecessor itself branches on the variable, the way it took.
+fn find(
+    function: &Function,
+    cfg: &ControlFlowGraph,
+    declined: &HashSet<String>,
+    div_by_zero: DivByZero,
+) -> Option<Thread> {
+    let tree = DominatorTree::new(cfg);
+    let constants = constants_after(function, cfg, div_by_zero);
+    for (b, block) in cfg.iter() {
+        let branch = match block.instrs.last() {
+            Some(instr) if instr.op.as_deref() == Some("br") => instr,
+            _ => continue,
+        };
+        let (t, f) = match block.next_blocks[..] {
+            [t, f] if t != f => (t, f),
+            _ => continue,
+        };
+        // Loop headers are left alone, as threading past one would give the
+        // loop a second entry.
+        let preds: BTreeSet<usize> = block.prev_blocks.iter().copied().collect();
+        if preds
+            .iter()
+            .any(|&p| tree.is_reachable(p) && tree.dominates(b, p))
+        {
+            continue;
+        }
+        let body = &block.instrs[..block.instrs.len() - 1];
+        if body.iter().filter(|instr| !instr.is_label()).count() > THREAD_BUDGET {
+            continue;
+        }
+        for &p in &preds {
+            let mut values: HashMap<&str, Option<Literal>> = match &constants[p] {
+                Some(after) => after
+                    .iter()
+                    .map(|(var, value)| (var.as_str(), Some(*value)))
+                    .collect(),
+                None => continue,
+            };
+            if let Some(taken) = cfg.blocks[p].instrs.last() {
+                if taken.op.as_deref() == Some("br") {
+                    if let [on_true, on_false] = cfg.blocks[p].next_blocks[..] {
+                        if on_true != on_false {
+                            let value = Literal::Bool(on_true == b);
+                            values.insert(&taken.args[0], Some(value));
+                        }
+                    }
+                }
+            }
+            for instr in body {
+                step(&mut values, instr, div_by_zero);
+            }
+            let target = match values.get(branch.args[0].as_str()) {
+                Some(Some(Literal::Bool(true))) => t,
+                Some(Some(Literal::Bool(false))) => f,
+                _ => continue,
+            };
+            let thread = Thread {
+                pred: p,
+                block: b,
+                target,
+            };
+            if !declined.contains(&describe(cfg, &thread)) {
+                return Some(thread);
+            }
+        }
+    }
+    None
+}
+
+// Sends `thread.pred` through a copy of its block without the branch, which
+// jumps straight to the target. A predecessor going nowhere else gets the
+// copy at its end; one that branches elsewhere too gets a new block for it
+// after the others.
+fn apply(function: &mut Function, cfg: &ControlFlowGraph, thread: &Thread, names: &mut Names) {
+    let block = &cfg.blocks[thread.block];
+    let from = block
+        .label()
+        .expect("a block with predecessors has a label");
+    let mut end = jmp(cfg.blocks[thread.target].label().unwrap().to_string());
+    end.mark_transformed(PASS);
+    let mut copy: Vec<Instruction> = block.instrs[..block.instrs.len() - 1]
+        .iter()
+        .filter(|instr| !instr.is_label())
+        .cloned()
+        .collect();
+    for instr in &mut copy {
+        instr.mark_transformed(PASS);
+    }
+    copy.push(end);
+
+    let mut blocks: Vec<Vec<Instruction>> = cfg
+        .blocks
+        .iter()
+        .map(|block| block.instrs.clone())
+        .collect();
+    let pred = &mut blocks[thread.pred];
+    match pred.last() {
+        Some(instr) if instr.op.as_deref() == Some("br") => {
+            let name = names.fresh(from);
+            let instr = pred.last_mut().unwrap();
+            for l in instr.labels.iter_mut().filter(|l| *l == from) {
+                *l = name.clone();
+            }
+            instr.mark_transformed(PASS);
+            // Falling off the end of the function returns, which the new
+            // block would get in the way of.
+            let last = blocks.last_mut().unwrap();
+            if !last.last().is_some_and(Instruction::is_terminator) {
+                let mut ret = Instruction {
+                    op: Some(String::from("ret")),
+                    ..Default::default()
+                };
+                ret.mark_transformed(PASS);
+                last.push(ret);
+            }
+            let mut head = label(name);
+            head.mark_transformed(PASS);
+            blocks.push(std::iter::once(head).chain(copy).collect());
+        }
+        Some(instr) if instr.is_terminator() => {
+            pred.pop();
+            pred.extend(copy);
+        }
+        _ => pred.extend(copy),
+    }
+    function.instrs = blocks.into_iter().flatten().collect();
+}
+
+// Jump threading: when the branch a block ends with is decided by where
+// control comes from, because constant propagation knows its condition there
+// or the predecessor branched on it as well, that predecessor goes through a
+// copy of the block ending in a jump to where the branch would take it. The
+// block is left for the other predecessors, and for simplify-cfg once none
+// are left. Blocks of over `THREAD_BUDGET` instructions are not copied, and
+// functions in SSA form or using speculation are left alone.
+pub fn thread_jumps(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+    seed: u64,
+) -> bool {
+    if function
+        .instrs
+        .iter()
+        .any(|instr| instr.is_ssa_op() || instr.is_speculation_op())
+    {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let mut declined = HashSet::new();
+    let mut changed = false;
+    for _ in 0..THREAD_LIMIT {
+        let cfg = construct_control_flow_graph(function);
+        let thread = match find(function, &cfg, &declined, div_by_zero) {
+            Some(thread) => thread,
+            None => break,
+        };
+        let what = describe(&cfg, &thread);
+        if !bisect.should_run(PASS, &what) {
+            declined.insert(what);
+            continue;
+        }
+        let branch = cfg.blocks[thread.block].instrs.last().unwrap();
+        remarks.enter(&function.name, thread.pred);
+        remarks.passed(
+            PASS,
+            "Threaded",
+            branch,
+            format!(
+                "sent {} past {} to {}, where `{}` always goes from it",
+                block_name(&cfg, thread.pred),
+                block_name(&cfg, thread.block),
+                block_name(&cfg, thread.target),
+                branch
+            ),
+        );
+        apply(function, &cfg, &thread, &mut names);
+        changed = true;
+    }
+    changed
+}
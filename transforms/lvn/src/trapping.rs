@@ -0,0 +1,50 @@
+// How the passes treat operations that can trap at run time, chosen with
+// `--fold-div-by-zero`:
+//
+// - `preserve`: a trap is behavior like any other, so a `div` or `load` is
+//   never deleted even when its result is unused, and a division by a
+//   constant zero is left to trap when it runs. This is what the
+//   interpreters do.
+// - `poison`: a trapping operation gives an undefined result instead, so an
+//   unused one may be deleted.
+// - `zero`: like `poison`, and a division by zero is defined to be zero, so a
+//   division by a constant zero folds to `0`.
+//
+// Reusing an earlier identical `div` is allowed under every policy, since the
+// earlier one already trapped if the reused one would.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivByZero {
+    #[default]
+    Preserve,
+    Poison,
+    Zero,
+}
+
+// Operations that trap on some arguments.
+const TRAPPING_OPS: [&str; 2] = ["div", "load"];
+
+impl DivByZero {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "preserve" => Some(DivByZero::Preserve),
+            "poison" => Some(DivByZero::Poison),
+            "zero" => Some(DivByZero::Zero),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DivByZero::Preserve => "preserve",
+            DivByZero::Poison => "poison",
+            DivByZero::Zero => "zero",
+        }
+    }
+
+    // Whether an instruction running `op` has to stay even when its result
+    // is never used.
+    pub fn keeps_unused(self, op: &str) -> bool {
+        self == DivByZero::Preserve && TRAPPING_OPS.contains(&op)
+    }
+}
@@ -0,0 +1,327 @@
+use crate::bisect::OptBisect;
+use crate::cloning::{End, Region};
+use crate::constprop::constants_after;
+use crate::fold::step;
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::trapping::DivByZero;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Function, Instruction, Literal};
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::liveness::Liveness;
+use bril_cfg::loops::{Loop, LoopForest};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// The most instructions a loop may grow to by unrolling.
+const UNROLL_BUDGET: usize = 256;
+
+enum Shape {
+    // This many copies of the loop one after the other, the back edges of
+    // each leading to the header of the next and those of the last back to
+    // the first.
+    Partial(usize),
+    // A copy for each time the body runs, in which the header goes straight
+    // into the body, and then the header once more, going straight out.
+    Full(usize),
+}
+
+// The constants the variables hold whenever control enters `l` from
+// outside.
+fn entry_constants(
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    constants: &[Option<BTreeMap<String, Literal>>],
+) -> BTreeMap<String, Literal> {
+    if cfg.entry() == Some(l.header) {
+        return BTreeMap::new();
+    }
+    let mut entry: Option<BTreeMap<String, Literal>> = None;
+    for &p in &cfg.blocks[l.header].prev_blocks {
+        let after = match &constants[p] {
+            Some(after) if !l.contains(p) => after,
+            _ => continue,
+        };
+        entry = Some(match entry {
+            None => after.clone(),
+            Some(mut entry) => {
+                entry.retain(|var, value| after.get(var) == Some(value));
+                entry
+            }
+        });
+    }
+    entry.unwrap_or_default()
+}
+
+// How many times the header of `l` sends control into the body before it
+// leaves the loop, if that follows from the constants on entry and is below
+// `limit`. Other exits may leave sooner; the count only says what the header
+// does when it gets to decide.
+//
+// The loop has to be innermost with one latch, so that the blocks dominating
+// the latch run exactly once each time around. Those are simulated, and a
+// variable assigned in any other block is taken to hold anything once any of
+// them has run, since that block may have run since.
+fn trip_count(
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    tree: &DominatorTree,
+    entry: &BTreeMap<String, Literal>,
+    limit: usize,
+    div_by_zero: DivByZero,
+) -> Option<usize> {
+    let header = &cfg.blocks[l.header];
+    let branch = header
+        .instrs
+        .last()
+        .filter(|instr| instr.op.as_deref() == Some("br"))?;
+    let stay = match header.next_blocks[..] {
+        [t, f] if l.contains(t) && !l.contains(f) => true,
+        [t, f] if !l.contains(t) && l.contains(f) => false,
+        _ => return None,
+    };
+    let latch = match l.latches[..] {
+        [latch] => latch,
+        _ => return None,
+    };
+    // The header comes first, as it dominates the rest.
+    let chain: Vec<usize> = cfg
+        .reverse_postorder()
+        .into_iter()
+        .filter(|&b| l.contains(b) && tree.dominates(b, latch))
+        .collect();
+
+    let off_chain: Vec<&str> = l
+        .body
+        .iter()
+        .filter(|b| !chain.contains(b))
+        .flat_map(|&b| cfg.blocks[b].instrs.iter())
+        .filter_map(|instr| instr.dest.as_deref())
+        .collect();
+
+    let mut values: HashMap<&str, Option<Literal>> = entry
+        .iter()
+        .map(|(var, value)| (var.as_str(), Some(*value)))
+        .collect();
+    for trips in 0..limit {
+        for (i, &b) in chain.iter().enumerate() {
+            for instr in &cfg.blocks[b].instrs {
+                step(&mut values, instr, div_by_zero);
+            }
+            if i == 0 {
+                match values.get(branch.args[0].as_str()) {
+                    Some(Some(Literal::Bool(taken))) if *taken == stay => {}
+                    Some(Some(Literal::Bool(_))) => return Some(trips),
+                    _ => return None,
+                }
+            }
+            // The blocks off the chain may run before the next one on it,
+            // whatever the chain has just assigned.
+            for var in &off_chain {
+                values.insert(var, None);
+            }
+        }
+    }
+    None
+}
+
+// Replaces the blocks of `l` with the copies `shape` asks for. The first copy
+// is the loop as it was, and the others follow its last block.
+//
+// A variable the loop assigns that is not live into its header or any of its
+// exits only carries values within one trip around, so each copy gets its
+// own name for it.
+fn unroll(
+    function: &mut Function,
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    liveness: &Liveness,
+    names: &mut Names,
+    shape: Shape,
+) {
+    let header = l.header;
+    let (copies, trips) = match shape {
+        Shape::Partial(factor) => (factor, None),
+        Shape::Full(trips) => (trips + 1, Some(trips)),
+    };
+    let blocks_of = |j: usize| -> Vec<usize> {
+        if trips == Some(j) {
+            vec![header]
+        } else {
+            l.body.iter().copied().collect()
+        }
+    };
+    let header_label = cfg.blocks[header].label().unwrap().to_string();
+    let region = Region::new(cfg, &l.body, names, "unroll", &header_label);
+    let mut labels: Vec<HashMap<usize, String>> = vec![HashMap::new(); copies];
+    labels[0].insert(header, header_label.clone());
+    for (j, copy) in labels.iter_mut().enumerate().skip(1) {
+        *copy = region.fresh_labels(blocks_of(j), names, &header_label);
+    }
+
+    let mut locals: BTreeSet<&str> = BTreeSet::new();
+    for &b in &l.body {
+        for dest in cfg.blocks[b]
+            .instrs
+            .iter()
+            .filter_map(|i| i.dest.as_deref())
+        {
+            let live = |b: usize| liveness.live_in[b].contains(dest);
+            if !live(header) && !l.exits.iter().any(|&e| live(e)) {
+                locals.insert(dest);
+            }
+        }
+    }
+    let mut renames: Vec<HashMap<String, String>> = vec![HashMap::new(); copies];
+    for (j, renames) in renames.iter_mut().enumerate().skip(1) {
+        for b in blocks_of(j) {
+            for dest in cfg.blocks[b]
+                .instrs
+                .iter()
+                .filter_map(|i| i.dest.as_deref())
+            {
+                if locals.contains(dest) && !renames.contains_key(dest) {
+                    let fresh = names.fresh(dest);
+                    function.debug.rename(dest, &fresh);
+                    renames.insert(dest.to_string(), fresh);
+                }
+            }
+        }
+    }
+
+    // The label to jump to from copy `j` to get to block `t`; back edges
+    // go on to the next copy.
+    let target = |t: usize, j: usize| -> String {
+        if t == header {
+            labels[(j + 1) % copies][&header].clone()
+        } else if l.contains(t) {
+            labels[j][&t].clone()
+        } else {
+            region.label_of(t)
+        }
+    };
+    // A full unrolling knows which way the header of each copy goes.
+    let end = |b: usize, j: usize| match (trips, &cfg.blocks[b].next_blocks[..]) {
+        (Some(trips), &[t, f]) if b == header => {
+            if l.contains(t) == (j < trips) {
+                End::Jump(t)
+            } else {
+                End::Jump(f)
+            }
+        }
+        _ => End::Same,
+    };
+    let mut instrs = Vec::new();
+    for (j, renames) in renames.iter().enumerate().skip(1) {
+        for b in blocks_of(j) {
+            instrs.extend(region.copy(b, &labels[j][&b], renames, end(b, j), |t| target(t, j)));
+        }
+    }
+    let replace = |b: usize| {
+        let back = |t: usize| (t == header).then(|| target(t, 0));
+        if !l.contains(b) {
+            None
+        } else if blocks_of(0).contains(&b) {
+            Some(region.keep(b, end(b, 0), back))
+        } else {
+            Some(Vec::new())
+        }
+    };
+    function.instrs = region.layout(replace, instrs);
+}
+
+// Loop unrolling. An innermost loop whose header is known to send control
+// into the body only a few times is unrolled fully: the body is copied once
+// for each of those times, and the loop is gone. Any other innermost loop is
+// unrolled `factor` times, so the copies run one after another and control
+// only goes back to the header with every `factor`th trip. Either way the
+// result has to stay within `UNROLL_BUDGET` instructions; a `factor` of 1
+// leaves those other loops alone.
+//
+// Loops with `phi`, `get` or `set` instructions are left alone, as their
+// copies would need new SSA names, and so are the loops of functions using
+// speculation, which can enter them on the side when they roll back.
+pub fn unroll_loops(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    div_by_zero: DivByZero,
+    factor: usize,
+    seed: u64,
+) -> bool {
+    if function.instrs.iter().any(Instruction::is_speculation_op) {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let cfg = construct_control_flow_graph(function);
+    let forest = LoopForest::new(&cfg);
+    // The loops are found again after each one is unrolled, by the labels of
+    // their headers, since the blocks move.
+    let headers: Vec<String> = forest
+        .loops
+        .iter()
+        .filter(|l| l.children.is_empty())
+        .filter_map(|l| cfg.blocks[l.header].label().map(String::from))
+        .collect();
+    let mut changed = false;
+    for header in headers {
+        let cfg = construct_control_flow_graph(function);
+        let tree = DominatorTree::new(&cfg);
+        let forest = LoopForest::with_dominators(&cfg, &tree);
+        let l = match forest
+            .loops
+            .iter()
+            .find(|l| cfg.blocks[l.header].label() == Some(header.as_str()))
+        {
+            Some(l) => l,
+            None => continue,
+        };
+        let instrs = || l.body.iter().flat_map(|&b| cfg.blocks[b].instrs.iter());
+        if instrs().any(Instruction::is_ssa_op) {
+            continue;
+        }
+        let size = instrs().count();
+        let constants = constants_after(function, &cfg, div_by_zero);
+        let entry = entry_constants(&cfg, l, &constants);
+        let limit = UNROLL_BUDGET / size.max(1);
+
+        remarks.enter(&function.name, l.header);
+        let at = &cfg.blocks[l.header].instrs[0];
+        let (shape, message) = match trip_count(&cfg, l, &tree, &entry, limit, div_by_zero) {
+            Some(trips) => (
+                Shape::Full(trips),
+                format!(
+                    "unrolled the loop at .{} fully, its body running {} times",
+                    header, trips
+                ),
+            ),
+            None if factor < 2 => continue,
+            None if size * factor > UNROLL_BUDGET => {
+                remarks.missed(
+                    "unroll",
+                    "TooLarge",
+                    at,
+                    format!(
+                        "kept the loop at .{}, as {} copies of its {} instructions would be too many",
+                        header, factor, size
+                    ),
+                );
+                continue;
+            }
+            None => (
+                Shape::Partial(factor),
+                format!("unrolled the loop at .{} {} times", header, factor),
+            ),
+        };
+        if !bisect.should_run("unroll", &format!("unroll the loop at .{}", header)) {
+            continue;
+        }
+        let name = match shape {
+            Shape::Full(_) => "FullyUnrolled",
+            Shape::Partial(_) => "Unrolled",
+        };
+        remarks.passed("unroll", name, at, message);
+        let liveness = Liveness::new(&cfg);
+        unroll(function, &cfg, l, &liveness, &mut names, shape);
+        changed = true;
+    }
+    changed
+}
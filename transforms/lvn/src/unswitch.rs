@@ -0,0 +1,176 @@
+use crate::bisect::OptBisect;
+use crate::cloning::{End, Region};
+use crate::names::Names;
+use crate::remarks::Remarks;
+use crate::{construct_control_flow_graph, ControlFlowGraph, Function, Instruction};
+use bril_cfg::dominators::DominatorTree;
+use bril_cfg::loops::{Loop, LoopForest};
+use std::collections::{HashMap, HashSet};
+
+// The most instructions a loop may have to be copied.
+const UNSWITCH_BUDGET: usize = 128;
+
+// A block of `l` branching on a variable the loop never assigns, which holds
+// a value on every path to the preheader `p`, so the preheader can branch on
+// it instead.
+fn invariant_branch(
+    function: &Function,
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    tree: &DominatorTree,
+    p: usize,
+) -> Option<usize> {
+    let assigned: HashSet<&str> = l
+        .body
+        .iter()
+        .flat_map(|&b| cfg.blocks[b].instrs.iter())
+        .filter_map(|instr| instr.dest.as_deref())
+        .collect();
+    let defined = |var: &str| {
+        function.args.iter().any(|arg| arg.name == var)
+            || cfg.iter().any(|(d, block)| {
+                tree.dominates(d, p) && block.instrs.iter().any(|i| i.dest.as_deref() == Some(var))
+            })
+    };
+    l.body.iter().copied().find(|&b| {
+        let block = &cfg.blocks[b];
+        match (block.instrs.last(), &block.next_blocks[..]) {
+            (Some(branch), &[t, f]) if branch.op.as_deref() == Some("br") && t != f => {
+                let var = branch.args[0].as_str();
+                !assigned.contains(var) && defined(var)
+            }
+            _ => false,
+        }
+    })
+}
+
+// Loop unswitching: a branch in a loop on a condition the loop never
+// assigns goes the same way every time around, so the preheader can make the
+// decision once, going to one of two copies of the loop, each with the
+// branches on the condition replaced by jumps the way it goes. The loop is
+// copied with its nested loops, so outer loops go first and branches move
+// out as far as they can. Loops without a preheader are left alone, as are
+// loops with `phi`, `get` or `set` instructions, loops over
+// `UNSWITCH_BUDGET` instructions and the loops of functions using
+// speculation.
+pub fn unswitch_loops(
+    function: &mut Function,
+    remarks: &mut Remarks,
+    bisect: &mut OptBisect,
+    seed: u64,
+) -> bool {
+    if function.instrs.iter().any(Instruction::is_speculation_op) {
+        return false;
+    }
+    let mut names = Names::new(function, seed);
+    let cfg = construct_control_flow_graph(function);
+    let forest = LoopForest::new(&cfg);
+    // The loops are found again after each one is unswitched, by the labels
+    // of their headers, since the blocks move.
+    let headers: Vec<String> = forest
+        .loops
+        .iter()
+        .filter_map(|l| cfg.blocks[l.header].label().map(String::from))
+        .collect();
+    let mut changed = false;
+    for header in headers {
+        let cfg = construct_control_flow_graph(function);
+        let tree = DominatorTree::new(&cfg);
+        let forest = LoopForest::with_dominators(&cfg, &tree);
+        let l = match forest
+            .loops
+            .iter()
+            .find(|l| cfg.blocks[l.header].label() == Some(header.as_str()))
+        {
+            Some(l) => l,
+            None => continue,
+        };
+        let instrs = || l.body.iter().flat_map(|&b| cfg.blocks[b].instrs.iter());
+        if instrs().any(Instruction::is_ssa_op) {
+            continue;
+        }
+        let p = match l.preheader {
+            Some(p) => p,
+            None => continue,
+        };
+        let x = match invariant_branch(function, &cfg, l, &tree, p) {
+            Some(x) => x,
+            None => continue,
+        };
+        let branch = cfg.blocks[x].instrs.last().unwrap();
+        let var = branch.args[0].clone();
+        remarks.enter(&function.name, x);
+        let size = instrs().count();
+        if size > UNSWITCH_BUDGET {
+            remarks.missed(
+                "unswitch",
+                "TooLarge",
+                branch,
+                format!(
+                    "kept `{}` in the loop at .{}, whose {} instructions are too many to copy",
+                    branch, header, size
+                ),
+            );
+            continue;
+        }
+        if !bisect.should_run("unswitch", &format!("unswitch `{}`", branch)) {
+            continue;
+        }
+
+        let region = Region::new(&cfg, &l.body, &mut names, "unswitch", &header);
+        let labels = region.fresh_labels(l.body.iter().copied(), &mut names, &header);
+        remarks.passed(
+            "unswitch",
+            "Unswitched",
+            branch,
+            format!(
+                "moved `{}` out of the loop at .{}, going to .{} when `{}` is false",
+                branch, header, labels[&l.header], var
+            ),
+        );
+        // Every branch on the condition goes the way of its copy.
+        let end = |b: usize, taken: bool| {
+            let block = &cfg.blocks[b];
+            match (block.instrs.last(), &block.next_blocks[..]) {
+                (Some(instr), &[t, f])
+                    if instr.op.as_deref() == Some("br") && instr.args[0] == var =>
+                {
+                    End::Jump(if taken { t } else { f })
+                }
+                _ => End::Same,
+            }
+        };
+        let mut copy = Vec::new();
+        for &b in &l.body {
+            let target = |t: usize| match labels.get(&t) {
+                Some(name) => name.clone(),
+                None => region.label_of(t),
+            };
+            copy.extend(region.copy(b, &labels[&b], &HashMap::new(), end(b, false), target));
+        }
+        let replace = |b: usize| {
+            if b == p {
+                let mut instrs = cfg.blocks[p].instrs.clone();
+                if instrs.last().is_some_and(Instruction::is_terminator) {
+                    instrs.pop();
+                }
+                let mut instr = Instruction {
+                    op: Some(String::from("br")),
+                    args: vec![var.clone()],
+                    labels: vec![header.clone(), labels[&l.header].clone()],
+                    ..Default::default()
+                };
+                instr.mark_transformed("unswitch");
+                instrs.push(instr);
+                Some(instrs)
+            } else if l.contains(b) {
+                Some(region.keep(b, end(b, true), |_| None))
+            } else {
+                None
+            }
+        };
+        function.instrs = region.layout(replace, copy);
+        changed = true;
+    }
+    changed
+}
@@ -0,0 +1,24 @@
+// Checks that the C and JavaScript backends reject the char extension,
+// which the text format cannot write, with an error rather than a panic.
+mod common;
+
+use common::run_lvn;
+
+const CHAR: &str = r#"{"functions":[{"name":"main","instrs":[
+  {"op":"const","dest":"c","type":"char","value":"h"},
+  {"op":"print","args":["c"]}
+]}]}"#;
+
+#[test]
+fn backends_reject_char() {
+    for (emit, backend) in [("c", "C"), ("js", "JavaScript")] {
+        let error = run_lvn(CHAR, &["--emit", emit]).unwrap_err();
+        let message = format!("the {} backend does not support the type char", backend);
+        assert!(error.contains(&message), "{}", error);
+        assert!(
+            error.contains("in @main, at `c: char = const 'h';`"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,69 @@
+// Checks that `--opt-bisect-limit` makes only the first transformations and
+// logs each decision, and that lvn-bisect finds the one that breaks a test.
+mod common;
+
+use common::{lvn_output, to_json};
+use std::process::{Command, Output};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  z: int = mul x y;
+  w: int = mul x y;
+  print z w;
+}
+"#;
+
+fn bisect_limit(limit: usize) -> Output {
+    lvn_output(
+        &to_json(PROGRAM),
+        &["--opt-bisect-limit", &limit.to_string()],
+    )
+}
+
+#[test]
+fn opt_bisect_limit_stops_early() {
+    let output = bisect_limit(1);
+    let log = String::from_utf8(output.stderr).unwrap();
+    let expected = "BISECT: running decision (1) lvn: reuse value for `y: int = add a b;`\n\
+        BISECT: NOT running decision (2) lvn: reuse value for `w: int = mul x y;`\n";
+    assert_eq!(log, expected);
+    let program = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        program.contains(r#""dest":"z","type":"int","args":["x","x"]"#),
+        "{}",
+        program
+    );
+    assert!(program.contains(r#""dest":"w""#), "{}", program);
+
+    let program = String::from_utf8(bisect_limit(2).stdout).unwrap();
+    assert!(!program.contains(r#""dest":"w""#), "{}", program);
+}
+
+#[test]
+fn lvn_bisect_finds_decision() {
+    let dir = std::env::temp_dir().join(format!("lvn-bisect-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("program.json");
+    std::fs::write(&path, to_json(PROGRAM)).unwrap();
+
+    // The test fails once LVN reuses `z` for `w`.
+    let output = Command::new(env!("CARGO_BIN_EXE_lvn-bisect"))
+        .arg(&path)
+        .args(["--", "grep", "-q", r#""dest":"w""#])
+        .output()
+        .expect("Failed to run lvn-bisect");
+    assert!(output.status.success());
+    let found = String::from_utf8(output.stdout).unwrap();
+    let expected = "First bad decision is 2 of 2:\n\
+        decision (2) lvn: reuse value for `w: int = mul x y;`\n";
+    assert_eq!(found, expected);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lvn-bisect"))
+        .arg(&path)
+        .output()
+        .expect("Failed to run lvn-bisect");
+    assert_eq!(output.status.code(), Some(2));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,92 @@
+// Checks what bril-opt summarizes about a program file: the instruction
+// counts, the comparison of runs under `--interp`, the verdict of `--check`,
+// and the changes `--watch` marks after an edit.
+mod common;
+
+use common::to_json;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  print y;
+}
+"#;
+
+// An interpreter that prints the same for every program and counts its
+// instructions as the dynamic ones.
+const INTERP: &str = r#"
+n=$(grep -o '"op"' | wc -l)
+echo out
+echo "total_dyn_inst: $n" >&2
+"#;
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bril-opt-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn bril_opt(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_bril-opt"))
+        .args(args)
+        .output()
+        .expect("Failed to run bril-opt");
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn bril_opt_summarizes() {
+    let dir = dir("summary");
+    let path = dir.join("program.json");
+    std::fs::write(&path, to_json(PROGRAM)).unwrap();
+    let interp = dir.join("interp.sh");
+    std::fs::write(&interp, INTERP).unwrap();
+    let (path, interp) = (path.to_str().unwrap(), format!("sh {}", interp.display()));
+
+    let args = [
+        path, "-p", "lvn,dce", "--interp", &interp, "--check", "true",
+    ];
+    let expected = "@main: 3 -> 2 instructions\n\
+        output: same (dynamic instructions: 3 -> 2)\n\
+        check: passed\n";
+    assert_eq!(bril_opt(&args), (Some(0), String::from(expected)));
+
+    let (code, summary) = bril_opt(&[path, "--check", "false"]);
+    assert_eq!(code, Some(0));
+    assert!(summary.contains("check: FAILED"), "{}", summary);
+
+    let (code, _) = bril_opt(&["--interp", &interp]);
+    assert_eq!(code, Some(2));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bril_opt_watches() {
+    let dir = dir("watch");
+    let path = dir.join("program.json");
+    std::fs::write(&path, to_json(PROGRAM)).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bril-opt"))
+        .arg("--watch")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run bril-opt");
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "@main: 3 -> 2 instructions");
+
+    let edited = PROGRAM.replace("print y;", "z: int = mul x y;\n  print z;");
+    std::fs::write(&path, to_json(&edited)).unwrap();
+    let lines: Vec<String> = lines.take(3).map(Result::unwrap).collect();
+    let header = format!("== {} (run 2)", path.display());
+    assert_eq!(lines, ["", &header, "* @main: 4 -> 3 instructions"]);
+    let _ = child.kill();
+    let _ = child.wait();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,51 @@
+// What the integration tests share: turning programs into JSON and back and
+// running them through lvn. Each test uses some of it.
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+pub fn to_json(source: &str) -> String {
+    let program =
+        bril2json::parse_abstract_program_from_read(source.as_bytes(), false, false, None);
+    serde_json::to_string(&program).expect("Failed to serialize program")
+}
+
+pub fn to_text(program: &str) -> String {
+    bril_rs::load_program_from_read(program.as_bytes()).to_string()
+}
+
+// The program optimized by running lvn with `passes`, or what lvn said when
+// it failed.
+pub fn optimize(program: &str, passes: &str) -> Result<String, String> {
+    run_lvn(program, &["--passes", passes])
+}
+
+// What lvn writes given `program` and `args`, or what it said when it failed.
+pub fn run_lvn(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = lvn_output(program, args);
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout).unwrap())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+// Everything lvn does given `program` and `args`, for tests that look at
+// what it logs as well.
+pub fn lvn_output(program: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lvn"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run lvn");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(program.as_bytes())
+        .expect("Failed to write to lvn");
+    child.wait_with_output().expect("Failed to run lvn")
+}
@@ -0,0 +1,96 @@
+// Checks that the `[driver]` table of `bril.toml` gives the defaults of the
+// flags, whether lvn finds the file itself or is given it with `--config`,
+// that flags still win, and that bad settings are errors in the file.
+mod common;
+
+use common::{run_lvn, to_json, to_text};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  print y;
+}
+"#;
+
+const OPTIMIZED: &str = "@main(a: int, b: int) {\n  x: int = add a b;\n  print x;\n}\n";
+
+// What lvn writes when run from `dir` with `args`.
+fn run_lvn_in(dir: &Path, args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lvn"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run lvn");
+    let program = to_json(PROGRAM);
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(program.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("Failed to run lvn");
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn config_gives_defaults() {
+    let dir = std::env::temp_dir().join(format!("lvn-config-{}", std::process::id()));
+    let nested = dir.join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    let config = dir.join("bril.toml");
+    std::fs::write(
+        &config,
+        "[driver]\npasses = [\"lvn\", \"dce\"]\nemit = \"liveness\"\n",
+    )
+    .unwrap();
+
+    // The nearest `bril.toml` is found from a directory inside the project.
+    let found = run_lvn_in(&nested, &[]);
+    assert_eq!(found, "@main\n  <block 0>: in: a b | out: -\n");
+    assert_eq!(
+        to_text(&run_lvn_in(&nested, &["--emit", "json"])),
+        OPTIMIZED
+    );
+
+    let given = run_lvn(&to_json(PROGRAM), &["--config", config.to_str().unwrap()]).unwrap();
+    assert_eq!(given, found);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn config_errors_name_file() {
+    let dir = std::env::temp_dir().join(format!("lvn-config-errors-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let settings = [
+        (
+            "passes = 3",
+            "passes must be a string or an array of strings",
+        ),
+        ("seed = -1", "seed must be a non-negative integer"),
+        ("emit = \"pdf\"", "unknown output format: pdf"),
+        ("fold-div-by-zero = \"trap\"", "invalid fold-div-by-zero"),
+        ("passes = [\"lvn\"", "line 3: expected `,` or `]`"),
+    ];
+    for (i, (setting, message)) in settings.iter().enumerate() {
+        let config = dir.join(format!("{}.toml", i));
+        std::fs::write(&config, format!("[driver]\n{}\n", setting)).unwrap();
+        let config = config.to_str().unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_lvn"))
+            .args(["--config", config])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run lvn");
+        assert_eq!(output.status.code(), Some(6), "{}", setting);
+        let error = String::from_utf8(output.stderr).unwrap();
+        let expected = format!("lvn: {}: {}", config, message);
+        assert!(error.starts_with(&expected), "{}", error);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
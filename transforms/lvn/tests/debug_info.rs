@@ -0,0 +1,36 @@
+// Checks that `--debug-info` keeps the table from the variables of the
+// optimized program to the source variables they hold, as LVN and SSA rename
+// and merge them, and that lvn drops the table otherwise.
+mod common;
+
+use common::{optimize, run_lvn, to_json};
+use serde_json::{json, Value};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  x: int = const 1;
+  print y x;
+}
+"#;
+
+fn vars(passes: &str) -> Value {
+    let output = run_lvn(&to_json(PROGRAM), &["--passes", passes, "--debug-info"]).unwrap();
+    let program: Value = serde_json::from_str(&output).unwrap();
+    program["functions"][0]["debug"]["vars"].clone()
+}
+
+#[test]
+fn debug_info_follows_renames() {
+    // LVN renames the first `x`, which it overwrites, and makes it stand in
+    // for `y` too.
+    assert_eq!(vars("lvn,dce"), json!({"x.0": ["x", "y"]}));
+    assert_eq!(
+        vars("to-ssa"),
+        json!({"x.0": ["x"], "x.1": ["x"], "y.0": ["y"]})
+    );
+
+    let output = optimize(&to_json(PROGRAM), "lvn,dce").unwrap();
+    assert!(!output.contains("\"debug\""), "{}", output);
+}
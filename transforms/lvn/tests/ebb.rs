@@ -0,0 +1,57 @@
+// Checks the extended basic blocks of a CFG: which blocks start a tree, and
+// the order in which a walk enters and leaves the blocks of each tree.
+mod common;
+
+use bril_cfg::construct_control_flow_graph;
+use bril_ir::Program;
+use common::to_json;
+use lvn::ebb::{Event, ExtendedBasicBlocks};
+
+// Blocks b0, .then, .inner, .else and .done. Only .done, with two
+// predecessors, starts a tree of its own.
+const PROGRAM: &str = r#"
+@main(c: bool) {
+  br c .then .else;
+.then:
+  br c .inner .done;
+.inner:
+  ret;
+.else:
+  jmp .done;
+.done:
+  ret;
+}
+"#;
+
+#[test]
+fn ebbs_walk_trees() {
+    let program: Program = serde_json::from_str(&to_json(PROGRAM)).unwrap();
+    let cfg = construct_control_flow_graph(&program.functions[0]);
+    let events: Vec<Event> = ExtendedBasicBlocks::new(&cfg).events().collect();
+    use Event::{Pop, Push};
+    let expected = [
+        Push(0),
+        Push(1),
+        Push(2),
+        Pop(2),
+        Pop(1),
+        Push(3),
+        Pop(3),
+        Pop(0),
+        Push(4),
+        Pop(4),
+    ];
+    assert_eq!(events, expected);
+}
+
+// An unreachable cycle of single-predecessor blocks is cut open at its first
+// block.
+#[test]
+fn ebbs_cut_unreachable_cycles() {
+    let source = "@main {\n  ret;\n.a:\n  jmp .b;\n.b:\n  jmp .a;\n}\n";
+    let program: Program = serde_json::from_str(&to_json(source)).unwrap();
+    let cfg = construct_control_flow_graph(&program.functions[0]);
+    let events: Vec<Event> = ExtendedBasicBlocks::new(&cfg).events().collect();
+    use Event::{Pop, Push};
+    assert_eq!(events, [Push(0), Pop(0), Push(1), Push(2), Pop(2), Pop(1)]);
+}
@@ -0,0 +1,33 @@
+// Checks that lvn reads its program from `--input` or a FILE argument and
+// writes it to `--output` just as it does through stdin and stdout.
+mod common;
+
+use common::{run_lvn, to_json};
+use std::path::Path;
+
+#[test]
+fn files_match_pipes() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("lvn-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source =
+        std::fs::read_to_string(root.join("../../benchmarks/core/fizz-buzz.bril")).unwrap();
+    let program = to_json(&source);
+    let input = dir.join("input.json");
+    std::fs::write(&input, &program).unwrap();
+    let (input, output) = (input.to_str().unwrap(), dir.join("output.json"));
+    let output = output.to_str().unwrap();
+
+    let piped = run_lvn(&program, &["--passes", "lvn,dce"]).unwrap();
+    let runs: [&[&str]; 2] = [
+        &["--passes", "lvn,dce", "--input", input, "--output", output],
+        &["--passes", "lvn,dce", input, "-o", output],
+    ];
+    for args in runs {
+        assert_eq!(run_lvn("", args).unwrap(), "");
+        assert_eq!(std::fs::read_to_string(output).unwrap(), piped);
+    }
+    let error = run_lvn("", &["--input", input, input]).unwrap_err();
+    assert!(error.contains("cannot be used with"), "{}", error);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,38 @@
+// Checks that `--fixpoint` repeats the passes until they change nothing,
+// logging each iteration, and that `--max-iterations` stops it earlier.
+mod common;
+
+use common::{lvn_output, to_json, to_text};
+
+const PROGRAM: &str = r#"
+@main {
+  a: int = const 1;
+  b: int = add a a;
+  c: int = add b b;
+  print a;
+}
+"#;
+
+#[test]
+fn fixpoint_converges() {
+    let output = lvn_output(&to_json(PROGRAM), &["--passes", "dce", "--fixpoint"]);
+    assert!(output.status.success());
+    let log = String::from_utf8(output.stderr).unwrap();
+    let expected = "fixpoint: iteration 1: 4 -> 2 instructions, changed by [dce]\n\
+        fixpoint: iteration 2: 2 -> 2 instructions, changed by []\n\
+        fixpoint: converged after 2 iterations\n";
+    assert_eq!(log, expected);
+    let program = to_text(&String::from_utf8(output.stdout).unwrap());
+    assert_eq!(program, "@main {\n  a: int = const 1;\n  print a;\n}\n");
+
+    let output = lvn_output(
+        &to_json(PROGRAM),
+        &["--passes", "dce", "--max-iterations", "1"],
+    );
+    let log = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        log.ends_with("fixpoint: stopped after 1 iterations without converging\n"),
+        "{}",
+        log
+    );
+}
@@ -0,0 +1,97 @@
+// Checks the page `--emit html` draws: a graph per function with a box per
+// block, the overlays of the analyses, and the analyses themselves as JSON
+// for the page's script.
+mod common;
+
+use common::{run_lvn, to_json};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  c: bool = lt a b;
+  br c .then .else;
+.then:
+  p: ptr<int> = alloc a;
+  free p;
+  ret;
+.else:
+  print x;
+}
+"#;
+
+// The analyses the page's script reads, parsed from the line that sets them.
+fn analyses(page: &str) -> serde_json::Value {
+    let line = page
+        .lines()
+        .find_map(|line| line.strip_prefix("<script>const ANALYSES = "))
+        .expect("no analyses in the page");
+    serde_json::from_str(line.trim_end_matches(';')).unwrap()
+}
+
+#[test]
+fn html_draws_blocks() {
+    let page = run_lvn(&to_json(PROGRAM), &["--emit", "html"]).unwrap();
+    assert!(
+        page.contains(r#"<svg class="cfg" data-function="main""#),
+        "{}",
+        page
+    );
+    for (block, name) in ["b0", ".then", ".else"].iter().enumerate() {
+        assert!(
+            page.contains(&format!(r#"data-block="{}""#, block)),
+            "{}",
+            page
+        );
+        assert!(page.contains(&format!(">{}</text>", name)), "{}", page);
+    }
+    assert!(page.contains(r#"data-id="main.0""#), "{}", page);
+    assert!(page.contains("p: ptr&lt;int&gt; = alloc a;"), "{}", page);
+    assert!(page.contains(r#"class="overlay-liveness""#), "{}", page);
+    assert!(page.contains(r#"id="toggle-liveness""#), "{}", page);
+    assert!(page.contains(r#"id="toggle-dominators""#), "{}", page);
+
+    let blocks = &analyses(&page)["main"];
+    assert_eq!(blocks[0]["live_in"], serde_json::json!(["a", "b"]));
+    assert_eq!(blocks[0]["live_out"], serde_json::json!(["a", "x"]));
+    assert_eq!(blocks[1]["name"], ".then");
+    assert_eq!(blocks[1]["dominators"], serde_json::json!([0, 1]));
+}
+
+// `--emit diff-html` draws the function before and after the passes side by
+// side, marking what they removed and what they added.
+#[test]
+fn diff_html_marks_changes() {
+    let program = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  print y;
+}
+"#;
+    let page = run_lvn(
+        &to_json(program),
+        &["--passes", "lvn,dce", "--emit", "diff-html"],
+    )
+    .unwrap();
+    assert!(
+        page.contains(r#"data-function="main (before)""#),
+        "{}",
+        page
+    );
+    assert!(page.contains(r#"data-function="main (after)""#), "{}", page);
+    assert!(
+        page.contains(r#"class="removed" data-id="main.1" x="8" y="48">y: int = add a b;"#),
+        "{}",
+        page
+    );
+    assert!(
+        page.contains(r#"class="added" data-id="" x="8" y="48">print x;"#),
+        "{}",
+        page
+    );
+    assert!(
+        !page.contains(r#"class="removed" data-id="main.0""#),
+        "{}",
+        page
+    );
+}
@@ -0,0 +1,64 @@
+// Checks that lvn reads programs using the import extension, keeps their
+// imports, and resolves them for the passes over the whole program.
+mod common;
+
+use common::{run_lvn, to_json, to_text};
+
+const LIBRARY: &str = r#"
+from "../main.json" import @log, @note;
+@twice(x: int): int {
+  two: int = const 2;
+  y: int = mul x two;
+  call @log y;
+  call @note;
+  ret y;
+}
+"#;
+
+const MAIN: &str = r#"
+from "lib.json" import @twice;
+@main {
+  v: int = const 3;
+  d: int = call @twice v;
+  five: int = const 5;
+  call @log five;
+  print d;
+}
+@log(x: int) {
+  print x;
+}
+@note {
+  ret;
+}
+@dead {
+  ret;
+}
+"#;
+
+#[test]
+fn imports_resolve() {
+    let dir = std::env::temp_dir().join(format!("lvn-imports-{}", std::process::id()));
+    let libs = dir.join("lib");
+    std::fs::create_dir_all(&libs).unwrap();
+    std::fs::write(libs.join("lib.json"), to_json(LIBRARY)).unwrap();
+    let main = dir.join("main.json");
+    std::fs::write(&main, to_json(MAIN)).unwrap();
+    let (main, libs) = (main.to_str().unwrap(), libs.to_str().unwrap());
+
+    // The library calls @log and @note back, so ipcp leaves @log as it is
+    // and dfe keeps @note.
+    let output = run_lvn("", &["--passes", "ipcp,dfe", main, "--libs", libs]).unwrap();
+    let imports = r#""imports":[{"path":"lib.json","functions":[{"name":"twice"}]}]"#;
+    assert!(output.contains(imports), "{}", output);
+    let text = to_text(&output);
+    assert!(text.contains("@log(x: int) {\n  print x;\n}"), "{}", text);
+    assert!(text.contains("@note"), "{}", text);
+    assert!(!text.contains("@dead"), "{}", text);
+
+    let error = run_lvn("", &["--passes", "dfe", main]).unwrap_err();
+    assert!(error.contains("cannot find lib.json"), "{}", error);
+    let missing = to_json(&MAIN.replace("@twice;", "@twice, @thrice;"));
+    let error = run_lvn(&missing, &["--passes", "dfe", "--libs", libs]).unwrap_err();
+    assert!(error.contains("has no function @thrice"), "{}", error);
+    let _ = std::fs::remove_dir_all(&dir);
+}
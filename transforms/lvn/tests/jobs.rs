@@ -0,0 +1,45 @@
+// Checks that optimizing several functions at once with `--jobs` gives the
+// program and the remarks one function at a time does, in the same order.
+// The programs are those of `test/semantics` and the core benchmarks.
+mod common;
+
+use common::{run_lvn, to_json};
+use std::path::Path;
+
+const PIPELINE: &str = "tce,lvn,svn,to-ssa,gvn,from-ssa,constprop,licm,unroll,dce,ipcp,dfe";
+
+#[test]
+fn jobs_keep_output() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("lvn-jobs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut failures = Vec::new();
+    for corpus in ["test/semantics", "../../benchmarks/core"] {
+        let mut paths: Vec<_> = std::fs::read_dir(root.join(corpus))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bril"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let program = to_json(&std::fs::read_to_string(&path).unwrap());
+            let run = |jobs: &str| {
+                let remarks = dir.join(format!("remarks-{}.json", jobs));
+                let remarks = remarks.to_str().unwrap();
+                let args = ["--passes", PIPELINE, "--jobs", jobs, "--remarks", remarks];
+                let output = run_lvn(&program, &args);
+                (output, std::fs::read_to_string(remarks).ok())
+            };
+            let serial = run("1");
+            if run("4") != serial {
+                failures.push(path.display().to_string());
+            }
+        }
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(
+        failures.is_empty(),
+        "--jobs 4 changed the output for: {}",
+        failures.join(", ")
+    );
+}
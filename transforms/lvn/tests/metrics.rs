@@ -0,0 +1,65 @@
+// Checks the metrics `--metrics` writes about a run: how often each pass ran
+// and changed something, and the instructions of each function before and
+// after, as JSON or as Prometheus text depending on the extension.
+mod common;
+
+use common::{run_lvn, to_json};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  print y;
+}
+"#;
+
+// The metrics lvn writes to a file named `name`.
+fn metrics(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("lvn-metrics-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let args = ["--passes", "lvn,dce", "--metrics", path.to_str().unwrap()];
+    run_lvn(&to_json(PROGRAM), &args).unwrap();
+    std::fs::read_to_string(&path).unwrap()
+}
+
+#[test]
+fn metrics_as_json() {
+    let metrics: serde_json::Value = serde_json::from_str(&metrics("metrics.json")).unwrap();
+    assert!(metrics["timestamp"].as_u64().is_some(), "{}", metrics);
+    let passes = metrics["passes"].as_array().unwrap();
+    let counts: Vec<_> = passes
+        .iter()
+        .map(|pass| (&pass["pass"], &pass["runs"], &pass["changed"]))
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            (&json!("lvn"), &json!(1), &json!(1)),
+            (&json!("dce"), &json!(1), &json!(0))
+        ]
+    );
+    assert!(passes.iter().all(|pass| pass["seconds"].as_f64().is_some()));
+    let function = json!({"function": "main", "before": 3, "after": 2, "delta": -1});
+    assert_eq!(metrics["functions"], json!([function]));
+}
+
+#[test]
+fn metrics_as_prometheus() {
+    let metrics = metrics("metrics.prom");
+    for line in [
+        "# TYPE bril_opt_pass_runs gauge",
+        "bril_opt_pass_runs{pass=\"lvn\"} 1",
+        "bril_opt_pass_changed{pass=\"dce\"} 0",
+        "bril_opt_instructions{function=\"main\",stage=\"before\"} 3",
+        "bril_opt_instructions{function=\"main\",stage=\"after\"} 2",
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "{}", metrics);
+    }
+    assert!(
+        metrics.contains("\nbril_opt_pass_seconds{pass=\"lvn\"} "),
+        "{}",
+        metrics
+    );
+}
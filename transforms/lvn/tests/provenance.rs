@@ -0,0 +1,38 @@
+// Checks that `--provenance` keeps the IDs of instructions, the passes that
+// rewrote them and the instructions merged into them, and that lvn drops all
+// of it otherwise.
+mod common;
+
+use common::{optimize, run_lvn, to_json};
+use serde_json::{json, Value};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  print y;
+}
+"#;
+
+fn instructions(output: &str) -> Value {
+    let program: Value = serde_json::from_str(output).unwrap();
+    program["functions"][0]["instrs"].clone()
+}
+
+#[test]
+fn provenance_records_passes() {
+    let output = run_lvn(&to_json(PROGRAM), &["--passes", "lvn,dce", "--provenance"]).unwrap();
+    let instrs = instructions(&output);
+    assert_eq!(instrs[0]["id"], "main.0");
+    assert_eq!(instrs[0]["merged_from"], json!(["main.1"]));
+    assert_eq!(instrs[0].get("transformed_by"), None);
+    assert_eq!(instrs[1]["id"], "main.2");
+    assert_eq!(instrs[1]["transformed_by"], json!(["lvn"]));
+
+    let output = optimize(&to_json(PROGRAM), "lvn,dce").unwrap();
+    for instr in instructions(&output).as_array().unwrap() {
+        for key in ["id", "merged_from", "transformed_by"] {
+            assert_eq!(instr.get(key), None, "{}", output);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Checks that `--remarks` writes what the passes did to a file, as JSON or as
+// YAML depending on its extension.
+mod common;
+
+use common::{run_lvn, to_json};
+
+const PROGRAM: &str = r#"
+@main(a: int, b: int) {
+  x: int = add a b;
+  y: int = add a b;
+  print y;
+}
+"#;
+
+// The remarks lvn writes to a file named `name` after running LVN.
+fn remarks(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("lvn-remarks-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let args = ["--passes", "lvn,dce", "--remarks", path.to_str().unwrap()];
+    run_lvn(&to_json(PROGRAM), &args).unwrap();
+    std::fs::read_to_string(&path).unwrap()
+}
+
+#[test]
+fn remarks_as_json() {
+    let remarks: serde_json::Value = serde_json::from_str(&remarks("remarks.json")).unwrap();
+    let remark = &remarks[0];
+    assert_eq!(remark["kind"], "passed");
+    assert_eq!(remark["pass"], "lvn");
+    assert_eq!(remark["name"], "Redundant");
+    assert_eq!(remark["function"], "main");
+    assert_eq!(remark["instr"], "main.1");
+    assert_eq!(
+        remark["message"],
+        "removed `y: int = add a b;`, reusing `x`"
+    );
+}
+
+#[test]
+fn remarks_as_yaml() {
+    let remarks = remarks("remarks.yaml");
+    let expected = "--- !Passed\npass: lvn\nname: Redundant\nfunction: \"main\"\nblock: 0\n\
+        instr: \"main.1\"\nmessage: \"removed `y: int = add a b;`, reusing `x`\"\n...\n";
+    assert!(remarks.contains(expected), "{}", remarks);
+}
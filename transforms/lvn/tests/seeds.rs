@@ -0,0 +1,47 @@
+// Checks that `--seed` numbers the names the passes make, the temporaries
+// of LVN, the versions of SSA and the labels of split edges, and that a seed
+// always gives the same ones.
+mod common;
+
+use common::{run_lvn, to_json, to_text};
+
+const PROGRAM: &str = r#"
+@main(n: int) {
+  d: int = add n n;
+  d: int = const 5;
+  e: int = add n n;
+  i: int = const 0;
+.top:
+  go: bool = lt i n;
+  br go .body .done;
+.body:
+  i: int = add i d;
+  br go .top .done;
+.done:
+  print d e i;
+}
+"#;
+
+fn optimize(seed: &str) -> String {
+    let args = [
+        "--passes",
+        "lvn,split-critical-edges,to-ssa",
+        "--seed",
+        seed,
+    ];
+    to_text(&run_lvn(&to_json(PROGRAM), &args).unwrap())
+}
+
+#[test]
+fn seeds_number_names() {
+    let (zero, seven) = (optimize("0"), optimize("7"));
+    assert_eq!(optimize("0"), zero);
+    assert_eq!(optimize("7"), seven);
+    assert_ne!(zero, seven);
+    for name in ["d.0.0: int = add n n", ".body.top.0:", "i.1: int = phi"] {
+        assert!(zero.contains(name), "{}", zero);
+    }
+    for name in ["d.7.7: int = add n n", ".body.top.7:", "i.8: int = phi"] {
+        assert!(seven.contains(name), "{}", seven);
+    }
+}
@@ -0,0 +1,156 @@
+// Checks that the passes keep the meaning of programs. Each program of the
+// corpus runs in brilirs before and after each pass, and after a few
+// pipelines of them, on the arguments of each of its `ARGS:` lines, and its
+// output and whether it fails, with what error, have to stay the same.
+//
+// The corpus is `test/semantics` and the core, float and mem benchmarks, or
+// the directories listed in `SEMANTICS_CORPUS` as in `PATH`:
+//
+//     SEMANTICS_CORPUS=../../benchmarks/long cargo test --test semantics
+mod common;
+
+use common::{optimize, to_json};
+use std::path::{Path, PathBuf};
+
+// Every pass lvn knows of.
+const PASSES: [&str; 19] = [
+    "lvn",
+    "svn",
+    "gvn",
+    "dce",
+    "dse",
+    "rle",
+    "mem2reg",
+    "constprop",
+    "licm",
+    "unroll",
+    "unswitch",
+    "tce",
+    "simplify-cfg",
+    "jump-threading",
+    "split-critical-edges",
+    "to-ssa",
+    "from-ssa",
+    "dfe",
+    "ipcp",
+];
+
+// Pipelines running passes on what others made of a program.
+const PIPELINES: [&str; 4] = [
+    "lvn,dce",
+    "to-ssa,from-ssa",
+    "mem2reg,to-ssa,gvn,from-ssa,dce",
+    "tce,ipcp,constprop,simplify-cfg,licm,unswitch,unroll,jump-threading,gvn,svn,rle,dse,dce,dfe",
+];
+
+fn corpus() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dirs: Vec<PathBuf> = match std::env::var_os("SEMANTICS_CORPUS") {
+        Some(dirs) => std::env::split_paths(&dirs).collect(),
+        None => vec![
+            root.join("test/semantics"),
+            root.join("../../benchmarks/core"),
+            root.join("../../benchmarks/float"),
+            root.join("../../benchmarks/mem"),
+        ],
+    };
+    let mut programs = Vec::new();
+    for dir in dirs {
+        let entries = std::fs::read_dir(&dir)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", dir.display(), e));
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "bril") {
+                programs.push(path);
+            }
+        }
+    }
+    programs.sort();
+    programs
+}
+
+// The arguments on each `ARGS:` line, as turnt reads them but for `-p`,
+// or none at all when there are no such lines.
+fn inputs(source: &str) -> Vec<Vec<String>> {
+    let inputs: Vec<Vec<String>> = source
+        .lines()
+        .filter_map(|line| line.split_once("ARGS:"))
+        .map(|(_, args)| {
+            args.split_whitespace()
+                .filter(|arg| *arg != "-p")
+                .map(String::from)
+                .collect()
+        })
+        .collect();
+    if inputs.is_empty() {
+        vec![Vec::new()]
+    } else {
+        inputs
+    }
+}
+
+// What the program prints given `args`, followed by the error it stops with
+// if it does. The interpreter recurses as the program does, so it gets more
+// stack than a test thread has.
+fn interpret(program: &str, args: &[String]) -> String {
+    let (program, args) = (program.to_string(), args.to_vec());
+    let interpreter = std::thread::Builder::new().stack_size(256 << 20);
+    let run = interpreter.spawn(move || {
+        let mut out = Vec::new();
+        let result = brilirs::run_input(
+            program.as_bytes(),
+            &mut out,
+            &args,
+            std::io::sink(),
+            brilirs::RunOptions::default(),
+        );
+        let mut behavior = String::from_utf8_lossy(&out).into_owned();
+        if let Err(e) = result {
+            behavior.push_str(&format!("error: {}\n", e));
+        }
+        behavior
+    });
+    run.unwrap().join().expect("The interpreter panicked")
+}
+
+#[test]
+fn passes_preserve_semantics() {
+    let mut failures = Vec::new();
+    for path in corpus() {
+        let source = std::fs::read_to_string(&path).unwrap();
+        let original = to_json(&source);
+        let inputs = inputs(&source);
+        let expected: Vec<String> = inputs
+            .iter()
+            .map(|args| interpret(&original, args))
+            .collect();
+        for passes in PASSES.iter().chain(&PIPELINES) {
+            let optimized = match optimize(&original, passes) {
+                Ok(optimized) => optimized,
+                Err(e) => {
+                    failures.push(format!(
+                        "{} with {}: lvn failed: {}",
+                        path.display(),
+                        passes,
+                        e
+                    ));
+                    continue;
+                }
+            };
+            for (args, expected) in inputs.iter().zip(&expected) {
+                let got = interpret(&optimized, args);
+                if got != *expected {
+                    failures.push(format!(
+                        "{} with {} on [{}]:\nexpected:\n{}got:\n{}",
+                        path.display(),
+                        passes,
+                        args.join(" "),
+                        expected,
+                        got
+                    ));
+                }
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
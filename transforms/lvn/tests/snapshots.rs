@@ -0,0 +1,117 @@
+// Snapshots of what lvn makes of the programs in `test/snapshots`: the
+// program after `lvn`, after `dce` and after both, and its CFG. Each is kept
+// next to the program as `NAME.KIND.snap`, so a change to how a pass rewrites
+// code shows up in review as a change to a snapshot.
+//
+// When an output differs from its snapshot, or there is no snapshot yet, the
+// test fails and writes the output to `NAME.KIND.snap.new` beside it, to be
+// looked over and moved in place. With `UPDATE_SNAPSHOTS=1` the snapshots
+// are overwritten instead:
+//
+//     UPDATE_SNAPSHOTS=1 cargo test --test snapshots
+mod common;
+
+use bril_cfg::{construct_control_flow_graph, ControlFlowGraph};
+use bril_ir::Program;
+use common::{optimize, to_json, to_text};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+// What to take a snapshot of, by the name of its snapshot and the passes to
+// run, or `None` for the CFG.
+const KINDS: [(&str, Option<&str>); 4] = [
+    ("lvn", Some("lvn")),
+    ("dce", Some("dce")),
+    ("lvn-dce", Some("lvn,dce")),
+    ("cfg", None),
+];
+
+fn block_name(cfg: &ControlFlowGraph, b: usize) -> String {
+    match cfg.blocks[b].label() {
+        Some(label) => format!(".{}", label),
+        None => format!("<block {}>", b),
+    }
+}
+
+// The blocks of each function with their instructions and the blocks they
+// come from and go to.
+fn cfg_text(program: &str) -> String {
+    let program: Program = serde_json::from_str(program).expect("Failed to parse program IR");
+    let mut text = String::new();
+    for function in &program.functions {
+        let cfg = construct_control_flow_graph(function);
+        writeln!(text, "@{}", function.name).unwrap();
+        for (b, block) in cfg.iter() {
+            let names = |blocks: &[usize]| -> String {
+                if blocks.is_empty() {
+                    return String::from("none");
+                }
+                let names: Vec<String> = blocks.iter().map(|&b| block_name(&cfg, b)).collect();
+                names.join(", ")
+            };
+            writeln!(text, "  {}", block_name(&cfg, b)).unwrap();
+            writeln!(text, "    from: {}", names(&block.prev_blocks)).unwrap();
+            for instr in block.instrs.iter().filter(|instr| !instr.is_label()) {
+                writeln!(text, "    {}", instr).unwrap();
+            }
+            writeln!(text, "    to: {}", names(&block.next_blocks)).unwrap();
+        }
+    }
+    text
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test/snapshots");
+    let mut programs: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bril"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+#[test]
+fn outputs_match_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value == "1");
+    let mut failures = Vec::new();
+    for path in programs() {
+        let program = to_json(&std::fs::read_to_string(&path).unwrap());
+        for (kind, passes) in KINDS {
+            let output = match passes {
+                Some(passes) => match optimize(&program, passes) {
+                    Ok(optimized) => to_text(&optimized),
+                    Err(e) => format!("lvn failed: {}", e),
+                },
+                None => cfg_text(&program),
+            };
+            let snapshot = path.with_extension(format!("{}.snap", kind));
+            let new = path.with_extension(format!("{}.snap.new", kind));
+            if update {
+                std::fs::write(&snapshot, &output).unwrap();
+                let _ = std::fs::remove_file(&new);
+                continue;
+            }
+            match std::fs::read_to_string(&snapshot) {
+                Ok(expected) if expected == output => {
+                    let _ = std::fs::remove_file(&new);
+                }
+                Ok(expected) => {
+                    std::fs::write(&new, &output).unwrap();
+                    failures.push(format!(
+                        "{} changed, see {}:\nexpected:\n{}got:\n{}",
+                        snapshot.display(),
+                        new.display(),
+                        expected,
+                        output
+                    ));
+                }
+                Err(_) => {
+                    std::fs::write(&new, &output).unwrap();
+                    failures.push(format!("no {}, see {}", snapshot.display(), new.display()));
+                }
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
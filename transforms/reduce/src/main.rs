@@ -0,0 +1,305 @@
+// Shrinks a Bril program while it keeps triggering a failure.
+//
+//     bril-reduce program.json --test "./still-fails.sh"
+//     bril-reduce program.json --pass "lvn" [--interp "brili 5"] [--timeout 5]
+//
+// With `--test`, the command receives a candidate program on stdin and must
+// exit successfully while the failure is still present. With `--pass`, a
+// candidate fails when the interpreter runs it fine but gives a different
+// result (or no result at all) after the pass has been applied.
+//
+// The reducer greedily deletes functions, then turns branches into jumps to
+// one of their targets, then deletes labeled blocks, then instructions in
+// shrinking chunks, and starts over until nothing more can be removed. The
+// smallest failing program is printed to stdout.
+//
+// Deleting instructions easily leaves a loop that never ends, so each run of
+// a command is stopped after `--timeout` seconds. A candidate the test or
+// the interpreter takes that long on does not fail, while one the pass or
+// its optimized version takes that long on does.
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+enum Predicate {
+    Test(Vec<String>),
+    Differential {
+        pass: Vec<String>,
+        interp: Vec<String>,
+    },
+}
+
+fn split_command(command: &str) -> Vec<String> {
+    let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+    if words.is_empty() {
+        panic!("Empty command");
+    }
+    words
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+// Runs `command` on `input`, or gives `None` when it takes over `timeout`.
+fn run(command: &[String], input: &[u8], timeout: Duration) -> Option<Output> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Failed to run {}: {}", command[0], e));
+    // The command may not read all of its input, so ignore broken pipes.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    // The output is read while the command runs so that it never waits for
+    // room in a pipe.
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .unwrap_or_else(|e| panic!("Failed to run {}: {}", command[0], e))
+        {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    };
+    Some(Output {
+        status,
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    })
+}
+
+impl Predicate {
+    fn fails(&self, program: &Value, timeout: Duration) -> bool {
+        let json = program.to_string();
+        match self {
+            Predicate::Test(test) => {
+                run(test, json.as_bytes(), timeout).is_some_and(|output| output.status.success())
+            }
+            Predicate::Differential { pass, interp } => {
+                // Candidates the interpreter rejects are not valid programs.
+                let expected = match run(interp, json.as_bytes(), timeout) {
+                    Some(expected) if expected.status.success() => expected,
+                    _ => return false,
+                };
+                let optimized = match run(pass, json.as_bytes(), timeout) {
+                    Some(optimized) if optimized.status.success() => optimized,
+                    _ => return true,
+                };
+                match run(interp, &optimized.stdout, timeout) {
+                    Some(actual) => !actual.status.success() || actual.stdout != expected.stdout,
+                    None => true,
+                }
+            }
+        }
+    }
+}
+
+// A predicate together with how long each of its runs may take.
+struct Check {
+    predicate: Predicate,
+    timeout: Duration,
+}
+
+impl Check {
+    fn fails(&self, program: &Value) -> bool {
+        self.predicate.fails(program, self.timeout)
+    }
+}
+
+fn instrs_mut(program: &mut Value, function: usize) -> &mut Vec<Value> {
+    program["functions"][function]["instrs"]
+        .as_array_mut()
+        .expect("Function without instrs")
+}
+
+fn num_functions(program: &Value) -> usize {
+    program["functions"]
+        .as_array()
+        .expect("Program without functions")
+        .len()
+}
+
+// Tries deleting each non-main function in turn.
+fn reduce_functions(program: &mut Value, check: &Check) -> bool {
+    let mut changed = false;
+    let mut i = 0;
+    while i < num_functions(program) {
+        if program["functions"][i]["name"] == "main" {
+            i += 1;
+            continue;
+        }
+        let mut candidate = program.clone();
+        candidate["functions"].as_array_mut().unwrap().remove(i);
+        if check.fails(&candidate) {
+            *program = candidate;
+            changed = true;
+        } else {
+            i += 1;
+        }
+    }
+    changed
+}
+
+// Tries replacing each branch with a jump to one of its targets, which leaves
+// the other target for `reduce_blocks` to delete when nothing else jumps to
+// it.
+fn reduce_branches(program: &mut Value, check: &Check) -> bool {
+    let mut changed = false;
+    for f in 0..num_functions(program) {
+        for i in 0..instrs_mut(program, f).len() {
+            let instr = &instrs_mut(program, f)[i];
+            if instr["op"] != "br" {
+                continue;
+            }
+            let targets = instr["labels"].as_array().cloned().unwrap_or_default();
+            for target in targets {
+                let mut candidate = program.clone();
+                instrs_mut(&mut candidate, f)[i] = json!({"op": "jmp", "labels": [target]});
+                if check.fails(&candidate) {
+                    *program = candidate;
+                    changed = true;
+                    break;
+                }
+            }
+        }
+    }
+    changed
+}
+
+// Tries deleting each label together with the instructions up to the next one.
+fn reduce_blocks(program: &mut Value, check: &Check) -> bool {
+    let mut changed = false;
+    for f in 0..num_functions(program) {
+        let mut start = 0;
+        loop {
+            let instrs = instrs_mut(program, f);
+            let begin = match (start..instrs.len()).find(|i| instrs[*i].get("label").is_some()) {
+                Some(begin) => begin,
+                None => break,
+            };
+            let end = (begin + 1..instrs.len())
+                .find(|i| instrs[*i].get("label").is_some())
+                .unwrap_or(instrs.len());
+
+            let mut candidate = program.clone();
+            instrs_mut(&mut candidate, f).drain(begin..end);
+            if check.fails(&candidate) {
+                *program = candidate;
+                changed = true;
+                start = begin;
+            } else {
+                start = begin + 1;
+            }
+        }
+    }
+    changed
+}
+
+// Tries deleting chunks of instructions, halving the chunk size down to one.
+fn reduce_instrs(program: &mut Value, check: &Check) -> bool {
+    let mut changed = false;
+    for f in 0..num_functions(program) {
+        let mut chunk = instrs_mut(program, f).len().max(1);
+        loop {
+            let mut start = 0;
+            while start < instrs_mut(program, f).len() {
+                let end = (start + chunk).min(instrs_mut(program, f).len());
+                let mut candidate = program.clone();
+                instrs_mut(&mut candidate, f).drain(start..end);
+                if check.fails(&candidate) {
+                    *program = candidate;
+                    changed = true;
+                } else {
+                    start = end;
+                }
+            }
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+    }
+    changed
+}
+
+fn main() {
+    let usage =
+        "Usage: bril-reduce program.json (--test CMD | --pass CMD [--interp CMD]) [--timeout SECONDS]";
+    let mut path = None;
+    let mut test = None;
+    let mut pass = None;
+    let mut interp = String::from("brili");
+    let mut timeout = 5;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--test" => test = Some(args.next().expect(usage)),
+            "--pass" => pass = Some(args.next().expect(usage)),
+            "--interp" => interp = args.next().expect(usage),
+            "--timeout" => timeout = args.next().and_then(|t| t.parse().ok()).expect(usage),
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("{}", usage),
+        }
+    }
+    let predicate = match (test, pass) {
+        (Some(test), None) => Predicate::Test(split_command(&test)),
+        (None, Some(pass)) => Predicate::Differential {
+            pass: split_command(&pass),
+            interp: split_command(&interp),
+        },
+        _ => panic!("{}", usage),
+    };
+    let check = Check {
+        predicate,
+        timeout: Duration::from_secs(timeout),
+    };
+
+    let source = std::fs::read_to_string(path.expect(usage)).expect("Failed to read program");
+    let mut program: Value = serde_json::from_str(&source).expect("Failed to parse program");
+    if !check.fails(&program) {
+        eprintln!("The original program does not fail, nothing to reduce.");
+        std::process::exit(1);
+    }
+
+    let count = |program: &Value| -> usize {
+        (0..num_functions(program))
+            .map(|f| {
+                program["functions"][f]["instrs"]
+                    .as_array()
+                    .map_or(0, Vec::len)
+            })
+            .sum()
+    };
+    eprintln!("Starting from {} instructions", count(&program));
+    loop {
+        let mut changed = reduce_functions(&mut program, &check);
+        changed |= reduce_branches(&mut program, &check);
+        changed |= reduce_blocks(&mut program, &check);
+        changed |= reduce_instrs(&mut program, &check);
+        eprintln!("Reduced to {} instructions", count(&program));
+        if !changed {
+            break;
+        }
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string(&program).expect("Failed to serialize program")
+    );
+}
@@ -0,0 +1,55 @@
+// Checks that bril-reduce shrinks a program to what its test still fails on,
+// and that it refuses a program that does not fail to begin with.
+use serde_json::{json, Value};
+use std::process::{Command, Output};
+
+fn program() -> Value {
+    json!({"functions": [
+        {"name": "main", "instrs": [
+            {"op": "const", "dest": "a", "type": "int", "value": 4},
+            {"op": "const", "dest": "b", "type": "int", "value": 2},
+            {"op": "const", "dest": "c", "type": "bool", "value": true},
+            {"op": "br", "args": ["c"], "labels": ["then", "else"]},
+            {"label": "then"},
+            {"op": "mul", "dest": "x", "type": "int", "args": ["a", "b"]},
+            {"op": "print", "args": ["x"]},
+            {"label": "else"},
+            {"op": "div", "dest": "y", "type": "int", "args": ["a", "b"]},
+            {"op": "print", "args": ["y"]}
+        ]},
+        {"name": "helper", "instrs": [{"op": "ret"}]}
+    ]})
+}
+
+fn reduce(name: &str, test: &str) -> Output {
+    let dir = std::env::temp_dir().join(format!("bril-reduce-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, program().to_string()).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_bril-reduce"))
+        .arg(&path)
+        .args(["--test", test])
+        .output()
+        .expect("Failed to run bril-reduce")
+}
+
+#[test]
+fn reduce_keeps_failure() {
+    let output = reduce("div.json", "grep -q div");
+    assert!(output.status.success());
+    let reduced: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let expected = json!({"functions": [
+        {"name": "main", "instrs": [
+            {"op": "div", "dest": "y", "type": "int", "args": ["a", "b"]}
+        ]}
+    ]});
+    assert_eq!(reduced, expected);
+}
+
+#[test]
+fn reduce_needs_failure() {
+    let output = reduce("none.json", "grep -q sub");
+    assert_eq!(output.status.code(), Some(1));
+    let message = String::from_utf8(output.stderr).unwrap();
+    assert!(message.contains("does not fail"), "{}", message);
+}